@@ -0,0 +1,76 @@
+use diffsol::ColoringAlgorithm;
+
+const ALGORITHMS: [ColoringAlgorithm; 6] = [
+    ColoringAlgorithm::Greedy,
+    ColoringAlgorithm::LargestFirst,
+    ColoringAlgorithm::SmallestLast,
+    ColoringAlgorithm::IncidenceDegree,
+    ColoringAlgorithm::Dsatur,
+    ColoringAlgorithm::Star,
+];
+
+/// The non-zeros of a 2D 5-point (or 9-point if `diagonals` is true) stencil on an `m` x `m` grid, in column-major order
+fn grid_non_zeros(m: usize, diagonals: bool) -> Vec<(usize, usize)> {
+    let mut non_zeros = Vec::new();
+    for k in 0..m * m {
+        let (i, j) = ((k / m) as i64, (k % m) as i64);
+        for di in -1..=1i64 {
+            for dj in -1..=1i64 {
+                if !diagonals && di != 0 && dj != 0 {
+                    continue;
+                }
+                let (ii, jj) = (i + di, j + dj);
+                if ii < 0 || jj < 0 || ii >= m as i64 || jj >= m as i64 {
+                    continue;
+                }
+                non_zeros.push(((ii * m as i64 + jj) as usize, k));
+            }
+        }
+    }
+    non_zeros
+}
+
+fn max_row_non_zeros(non_zeros: &[(usize, usize)], nrows: usize) -> usize {
+    let mut counts = vec![0; nrows];
+    for &(i, _j) in non_zeros {
+        counts[i] += 1;
+    }
+    counts.into_iter().max().unwrap_or(0)
+}
+
+fn main() {
+    for (name, diagonals) in [("5-point", false), ("9-point", true)] {
+        for m in [10, 50] {
+            let n = m * m;
+            let non_zeros = grid_non_zeros(m, diagonals);
+            println!(
+                "{} stencil, {}x{} grid: lower bound {} colours",
+                name,
+                m,
+                m,
+                max_row_non_zeros(&non_zeros, n)
+            );
+            for algorithm in ALGORITHMS {
+                let colors = algorithm.color(&non_zeros, n);
+                println!(
+                    "    {:?}: {} colours",
+                    algorithm,
+                    colors.iter().max().unwrap()
+                );
+            }
+        }
+    }
+    divan::main();
+}
+
+#[divan::bench(args = ALGORITHMS)]
+fn grid_5_point(algorithm: ColoringAlgorithm) {
+    let non_zeros = grid_non_zeros(50, false);
+    let _colors = algorithm.color(divan::black_box(&non_zeros), 50 * 50);
+}
+
+#[divan::bench(args = ALGORITHMS)]
+fn grid_9_point(algorithm: ColoringAlgorithm) {
+    let non_zeros = grid_non_zeros(50, true);
+    let _colors = algorithm.color(divan::black_box(&non_zeros), 50 * 50);
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use num_traits::{abs, One, Pow, Zero};
+
+use crate::{
+    op::{LinearOp, NonLinearOp},
+    Matrix, Scalar, Vector,
+};
+
+use super::{find_non_zeros_linear, find_non_zeros_nonlinear};
+
+/// A single entry `(row, col)` of a jacobian (or matrix) compared by [check_jacobian] or [check_linear_op].
+#[derive(Debug, Clone)]
+pub struct JacobianCheckEntry<T: Scalar> {
+    pub row: usize,
+    pub col: usize,
+    /// The reference value (central finite differences for [check_jacobian], the operator itself for [check_linear_op])
+    pub expected: T,
+    /// The value given by `jac_mul_inplace` (or `gemv_inplace` for a linear operator)
+    pub action: T,
+    /// The value given by `jacobian_inplace` (or `matrix_inplace` for a linear operator)
+    pub matrix: T,
+    /// The largest error of `action` and `matrix`, relative to `max(|expected|, 1)`
+    pub relative_error: T,
+}
+
+/// The result of [check_jacobian] or [check_linear_op].
+#[derive(Debug, Clone)]
+pub struct JacobianCheck<T: Scalar> {
+    /// All entries that are non-zero in the reference, the jacobian action, the jacobian matrix, or the detected sparsity pattern
+    pub entries: Vec<JacobianCheckEntry<T>>,
+    pub max_relative_error: T,
+    /// Entries that are non-zero in the reference but were not found by sparsity detection
+    /// (i.e. [find_non_zeros_nonlinear] or [find_non_zeros_linear]), these would be dropped if the jacobian is calculated using colouring
+    pub missing_non_zeros: Vec<(usize, usize)>,
+}
+
+impl<T: Scalar> JacobianCheck<T> {
+    /// Returns true if all the entries agree to within `tol` and no non-zero entries are missing from the sparsity pattern
+    pub fn is_ok(&self, tol: T) -> bool {
+        self.max_relative_error <= tol && self.missing_non_zeros.is_empty()
+    }
+
+    /// The entries with a relative error larger than `tol`
+    pub fn errors(&self, tol: T) -> Vec<&JacobianCheckEntry<T>> {
+        self.entries
+            .iter()
+            .filter(|e| e.relative_error > tol)
+            .collect()
+    }
+
+    fn new(
+        expected: &[Vec<T>],
+        action: &[Vec<T>],
+        matrix: &[Vec<T>],
+        non_zeros: Vec<(usize, usize)>,
+    ) -> Self {
+        let non_zeros: HashSet<(usize, usize)> = HashSet::from_iter(non_zeros);
+        let mut entries = Vec::new();
+        let mut missing_non_zeros = Vec::new();
+        let mut max_relative_error = T::zero();
+        for j in 0..expected.len() {
+            for i in 0..expected[j].len() {
+                let (e, a, m) = (expected[j][i], action[j][i], matrix[j][i]);
+                let is_non_zero = e != T::zero() || a != T::zero() || m != T::zero();
+                let is_detected = non_zeros.contains(&(i, j));
+                if !is_non_zero && !is_detected {
+                    continue;
+                }
+                let scale = if abs(e) > T::one() { abs(e) } else { T::one() };
+                let err_a = abs(a - e) / scale;
+                let err_m = abs(m - e) / scale;
+                let relative_error = if err_a > err_m { err_a } else { err_m };
+                if relative_error > max_relative_error || relative_error.is_nan() {
+                    max_relative_error = relative_error;
+                }
+                if e != T::zero() && !is_detected {
+                    missing_non_zeros.push((i, j));
+                }
+                entries.push(JacobianCheckEntry {
+                    row: i,
+                    col: j,
+                    expected: e,
+                    action: a,
+                    matrix: m,
+                    relative_error,
+                });
+            }
+        }
+        Self {
+            entries,
+            max_relative_error,
+            missing_non_zeros,
+        }
+    }
+}
+
+fn to_column<V: Vector>(v: &V) -> Vec<V::T> {
+    (0..v.len()).map(|i| v[i]).collect()
+}
+
+fn matrix_columns<M: Matrix>(m: &M, ncols: usize) -> Vec<Vec<M::T>> {
+    let mut v = M::V::zeros(ncols);
+    let mut col = M::V::zeros(m.nrows());
+    (0..ncols)
+        .map(|j| {
+            v[j] = M::T::one();
+            m.gemv(M::T::one(), &v, M::T::zero(), &mut col);
+            v[j] = M::T::zero();
+            to_column(&col)
+        })
+        .collect()
+}
+
+/// Check the jacobian of a [NonLinearOp] at `(x, t)`. Each column of the jacobian is calculated using central finite differences
+/// of [NonLinearOp::call_inplace], and compared against [NonLinearOp::jac_mul_inplace] and [NonLinearOp::jacobian_inplace].
+/// The sparsity pattern detected by [find_non_zeros_nonlinear] is also checked against the finite difference jacobian.
+pub fn check_jacobian<F: NonLinearOp + ?Sized>(op: &F, x: &F::V, t: F::T) -> JacobianCheck<F::T> {
+    let nstates = op.nstates();
+    let nout = op.nout();
+    let eps: f64 = F::T::EPSILON.into();
+    let cbrt_eps = F::T::from(eps.pow(1.0 / 3.0));
+
+    let mut expected = Vec::with_capacity(nstates);
+    let mut action = Vec::with_capacity(nstates);
+    let mut x_pert = x.clone();
+    let mut v = F::V::zeros(nstates);
+    let mut f_plus = F::V::zeros(nout);
+    let mut f_minus = F::V::zeros(nout);
+    let mut col = F::V::zeros(nout);
+    for j in 0..nstates {
+        let h = if abs(x[j]) > F::T::one() {
+            cbrt_eps * abs(x[j])
+        } else {
+            cbrt_eps
+        };
+        x_pert[j] = x[j] + h;
+        op.call_inplace(&x_pert, t, &mut f_plus);
+        x_pert[j] = x[j] - h;
+        op.call_inplace(&x_pert, t, &mut f_minus);
+        x_pert[j] = x[j];
+        f_plus -= &f_minus;
+        f_plus *= crate::scale(F::T::one() / (h + h));
+        expected.push(to_column(&f_plus));
+
+        v[j] = F::T::one();
+        op.jac_mul_inplace(x, t, &v, &mut col);
+        v[j] = F::T::zero();
+        action.push(to_column(&col));
+    }
+
+    let mut jac = F::M::new_from_sparsity(nout, nstates, op.sparsity());
+    op.jacobian_inplace(x, t, &mut jac);
+    let matrix = matrix_columns(&jac, nstates);
+
+    let non_zeros = find_non_zeros_nonlinear(op, x, t);
+    JacobianCheck::new(&expected, &action, &matrix, non_zeros)
+}
+
+/// Check a [LinearOp] at time `t`. Each column of the operator is calculated by applying [LinearOp::call_inplace] to a unit vector,
+/// and compared against [LinearOp::gemv_inplace] (with a non-zero `beta`) and [LinearOp::matrix_inplace].
+/// The sparsity pattern detected by [find_non_zeros_linear] is also checked against the operator.
+pub fn check_linear_op<F: LinearOp + ?Sized>(op: &F, t: F::T) -> JacobianCheck<F::T> {
+    let nstates = op.nstates();
+    let nout = op.nout();
+    let beta = F::T::from(2.0);
+    let offset = F::V::from_element(nout, F::T::one());
+
+    let mut expected = Vec::with_capacity(nstates);
+    let mut action = Vec::with_capacity(nstates);
+    let mut v = F::V::zeros(nstates);
+    let mut col = F::V::zeros(nout);
+    for j in 0..nstates {
+        v[j] = F::T::one();
+        op.call_inplace(&v, t, &mut col);
+        expected.push(to_column(&col));
+
+        // y = A v + beta * offset, so (y - beta * offset) should equal the j-th column of A
+        col.copy_from(&offset);
+        op.gemv_inplace(&v, t, beta, &mut col);
+        col.axpy(-beta, &offset, F::T::one());
+        action.push(to_column(&col));
+        v[j] = F::T::zero();
+    }
+
+    let mut mat = F::M::new_from_sparsity(nout, nstates, op.sparsity());
+    op.matrix_inplace(t, &mut mat);
+    let matrix = matrix_columns(&mat, nstates);
+
+    let non_zeros = find_non_zeros_linear(op, t);
+    JacobianCheck::new(&expected, &action, &matrix, non_zeros)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use nalgebra::{DMatrix, DVector};
+
+    use super::{check_jacobian, check_linear_op};
+    use crate::{
+        ode_solver::test_models::{
+            exponential_decay::exponential_decay_problem, robertson::robertson,
+            robertson_ode::robertson_ode_autodiff,
+        },
+        op::closure::Closure,
+        OdeEquations,
+    };
+
+    type M = DMatrix<f64>;
+
+    #[test]
+    fn check_test_models() {
+        let (problem, _soln) = robertson::<M>(false);
+        let x = DVector::from_vec(vec![0.9, 3.0e-5, 0.1]);
+        let check = check_jacobian(problem.eqn.rhs().as_ref(), &x, 0.0);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+        let check = check_linear_op(problem.eqn.mass().as_ref(), 0.0);
+        assert!(check.is_ok(1e-12), "{:?}", check.errors(1e-12));
+        assert_eq!(check.entries.len(), 2);
+
+        let (problem, _soln) = robertson::<M>(true);
+        let check = check_jacobian(problem.eqn.rhs().as_ref(), &x, 0.0);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+
+        let (problem, _soln) = robertson_ode_autodiff::<M>(true);
+        let check = check_jacobian(problem.eqn.rhs().as_ref(), &x, 0.0);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+
+        let (problem, _soln) = exponential_decay_problem::<M>(false);
+        let x = problem.eqn.init(0.0);
+        let check = check_jacobian(problem.eqn.rhs().as_ref(), &x, 0.0);
+        assert!(check.is_ok(1e-8), "{:?}", check.errors(1e-8));
+    }
+
+    #[test]
+    fn check_incorrect_jacobian() {
+        // y = x0 * x1, but the jacobian action is missing the x0 * v1 term
+        let op = Closure::<M, _, _>::new(
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+                y[0] = x[0] * x[1];
+            },
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, v: &DVector<f64>, y: &mut DVector<f64>| {
+                y[0] = v[0] * x[1];
+            },
+            2,
+            1,
+            Rc::new(DVector::zeros(0)),
+        );
+        let x = DVector::from_vec(vec![1.0, 2.0]);
+        let check = check_jacobian(&op, &x, 0.0);
+        assert!(!check.is_ok(1e-6));
+        assert_eq!(check.missing_non_zeros, vec![(0, 1)]);
+        let errors = check.errors(1e-6);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].row, errors[0].col), (0, 1));
+        assert!((errors[0].expected - 1.0).abs() < 1e-8);
+        assert_eq!(errors[0].action, 0.0);
+        assert_eq!(errors[0].matrix, 0.0);
+        assert!(check.max_relative_error > 0.99);
+    }
+}
@@ -1 +1,17 @@
 pub type Graph = petgraph::graph::Graph<(), (), petgraph::Directed>;
+
+/// Returns the (undirected) neighbours of each vertex in the graph, sorted and without duplicates.
+pub fn adjacency_lists(graph: &Graph) -> Vec<Vec<usize>> {
+    (0..graph.node_count())
+        .map(|v| {
+            let mut neighbors = graph
+                .neighbors_undirected(petgraph::graph::NodeIndex::new(v))
+                .map(|w| w.index())
+                .filter(|&w| w != v)
+                .collect::<Vec<_>>();
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            neighbors
+        })
+        .collect()
+}
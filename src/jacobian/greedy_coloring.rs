@@ -1,5 +1,7 @@
 // Translated from https://github.com/JuliaDiff/SparseDiffTools.jl under an MIT license
 
+use std::collections::{BTreeSet, HashSet};
+
 use petgraph::graph::NodeIndex;
 
 use super::graph::Graph;
@@ -30,3 +32,124 @@ pub fn color_graph_greedy(graph: &Graph) -> Vec<usize> {
     }
     result
 }
+
+/// Colour the vertices greedily in the given order, giving each vertex the smallest colour
+/// not used by any of its neighbours. `adjacency` gives the neighbours of each vertex (see [super::graph::adjacency_lists]).
+pub fn color_graph_greedy_ordered(adjacency: &[Vec<usize>], order: &[usize]) -> Vec<usize> {
+    let n = adjacency.len();
+    let mut result = vec![0; n];
+    // forbidden[c] == v + 1 if colour c is used by a neighbour of v
+    let mut forbidden = vec![0; n + 1];
+    for &v in order {
+        for &w in adjacency[v].iter() {
+            forbidden[result[w]] = v + 1;
+        }
+        result[v] = (1..=n).find(|&c| forbidden[c] != v + 1).unwrap_or(1);
+    }
+    result
+}
+
+/// Order the vertices by decreasing degree (largest-first ordering).
+pub fn largest_first_ordering(adjacency: &[Vec<usize>]) -> Vec<usize> {
+    let mut order = (0..adjacency.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&v| std::cmp::Reverse(adjacency[v].len()));
+    order
+}
+
+/// Smallest-last ordering: repeatedly remove a vertex of minimum degree from the graph,
+/// the vertices are then coloured in the reverse order of removal.
+pub fn smallest_last_ordering(adjacency: &[Vec<usize>]) -> Vec<usize> {
+    let n = adjacency.len();
+    let mut degree = adjacency.iter().map(|a| a.len()).collect::<Vec<_>>();
+    let max_degree = degree.iter().max().copied().unwrap_or(0);
+    let mut buckets = vec![Vec::new(); max_degree + 1];
+    for v in (0..n).rev() {
+        buckets[degree[v]].push(v);
+    }
+    let mut removed = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut min_degree = 0;
+    while order.len() < n {
+        // buckets may contain stale entries for vertices whose degree has since decreased
+        let Some(v) = buckets[min_degree].pop() else {
+            min_degree += 1;
+            continue;
+        };
+        if removed[v] || degree[v] != min_degree {
+            continue;
+        }
+        removed[v] = true;
+        order.push(v);
+        for &w in adjacency[v].iter() {
+            if !removed[w] {
+                degree[w] -= 1;
+                buckets[degree[w]].push(w);
+            }
+        }
+        min_degree = min_degree.saturating_sub(1);
+    }
+    order.reverse();
+    order
+}
+
+/// Incidence-degree ordering: repeatedly choose the vertex with the most neighbours that have already been ordered.
+pub fn incidence_degree_ordering(adjacency: &[Vec<usize>]) -> Vec<usize> {
+    let n = adjacency.len();
+    let mut incidence = vec![0; n];
+    let max_degree = adjacency.iter().map(|a| a.len()).max().unwrap_or(0);
+    let mut buckets = vec![Vec::new(); max_degree + 1];
+    // start from the vertex with the largest degree (ties broken by index)
+    for v in largest_first_ordering(adjacency).into_iter().rev() {
+        buckets[0].push(v);
+    }
+    let mut ordered = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut max_incidence = 0;
+    while order.len() < n {
+        let Some(v) = buckets[max_incidence].pop() else {
+            max_incidence -= 1;
+            continue;
+        };
+        if ordered[v] || incidence[v] != max_incidence {
+            continue;
+        }
+        ordered[v] = true;
+        order.push(v);
+        for &w in adjacency[v].iter() {
+            if !ordered[w] {
+                incidence[w] += 1;
+                buckets[incidence[w]].push(w);
+                max_incidence = max_incidence.max(incidence[w]);
+            }
+        }
+    }
+    order
+}
+
+/// DSATUR colouring: repeatedly colour the uncoloured vertex with the largest number of distinct colours
+/// in its neighbourhood (saturation degree), breaking ties by the largest degree.
+pub fn color_graph_dsatur(adjacency: &[Vec<usize>]) -> Vec<usize> {
+    let n = adjacency.len();
+    let mut result = vec![0; n];
+    let mut neighbor_colors: Vec<HashSet<usize>> = vec![HashSet::new(); n];
+    // (saturation, degree, reversed index) so that the last element is the next vertex to colour
+    let key = |v: usize, saturation: usize| (saturation, adjacency[v].len(), n - v);
+    let mut queue = (0..n).map(|v| key(v, 0)).collect::<BTreeSet<_>>();
+    let mut forbidden = vec![0; n + 1];
+    while let Some((_, _, rev_v)) = queue.pop_last() {
+        let v = n - rev_v;
+        for &w in adjacency[v].iter() {
+            forbidden[result[w]] = v + 1;
+        }
+        let c = (1..=n).find(|&c| forbidden[c] != v + 1).unwrap_or(1);
+        result[v] = c;
+        for &w in adjacency[v].iter() {
+            if result[w] == 0 && !neighbor_colors[w].contains(&c) {
+                queue.remove(&key(w, neighbor_colors[w].len()));
+                neighbor_colors[w].insert(c);
+                queue.insert(key(w, neighbor_colors[w].len()));
+            }
+        }
+    }
+    result
+}
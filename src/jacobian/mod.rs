@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
 use crate::op::{LinearOp, Op};
 use crate::vector::Vector;
@@ -6,11 +6,21 @@ use crate::Scalar;
 use crate::{op::NonLinearOp, Matrix, MatrixSparsity, VectorIndex};
 use num_traits::{One, Zero};
 
-use self::{coloring::nonzeros2graph, greedy_coloring::color_graph_greedy};
+use self::{
+    coloring::nonzeros2graph,
+    graph::adjacency_lists,
+    greedy_coloring::{
+        color_graph_dsatur, color_graph_greedy, color_graph_greedy_ordered,
+        incidence_degree_ordering, largest_first_ordering, smallest_last_ordering,
+    },
+    star_coloring::color_graph_star,
+};
 
+pub mod check;
 pub mod coloring;
 pub mod graph;
 pub mod greedy_coloring;
+pub mod star_coloring;
 
 /// Find the non-zero entries of the Jacobian matrix of a non-linear operator.
 /// This is used as the default `find_non_zeros` function for the `NonLinearOp` and `LinearOp` traits.
@@ -37,6 +47,94 @@ pub fn find_non_zeros_nonlinear<F: NonLinearOp + ?Sized>(
     triplets
 }
 
+/// Find the non-zero entries of the Jacobian matrix of a non-linear operator using only evaluations of the operator,
+/// by setting each input in turn to NaN and checking which outputs become NaN.
+/// This is useful for operators that approximate the action of the Jacobian (e.g. by finite differences),
+/// where NaN inputs to [NonLinearOp::jac_mul_inplace] would not propagate correctly.
+pub fn find_non_zeros_nonlinear_from_call<F: NonLinearOp + ?Sized>(
+    op: &F,
+    x: &F::V,
+    t: F::T,
+) -> Vec<(usize, usize)> {
+    let mut xj = x.clone();
+    let mut col = F::V::zeros(op.nout());
+    let mut triplets = Vec::with_capacity(op.nstates());
+    for j in 0..op.nstates() {
+        xj[j] = F::T::NAN;
+        op.call_inplace(&xj, t, &mut col);
+        for i in 0..op.nout() {
+            if col[i].is_nan() {
+                triplets.push((i, j));
+            }
+            col[i] = F::T::zero();
+        }
+        xj[j] = x[j];
+    }
+    triplets
+}
+
+/// Take the union of the non-zero entries found by `find` at the state `x` and at `nprobes` perturbed states.
+/// This reduces the chance of missing entries that are masked at a single state, for example by branches or by multiplication by a state that is zero.
+/// The perturbations are pseudo-random but deterministic, so the detected pattern is reproducible. Each perturbation only increases the magnitude
+/// of a state and keeps its sign, and the entries of any output of `op` that is not finite at a perturbed state are skipped, so probing
+/// does not add spurious rows for functions that are only defined for states of one sign (e.g. `sqrt` or `ln`).
+pub fn find_non_zeros_with_probes<F, G>(
+    op: &F,
+    x: &F::V,
+    t: F::T,
+    nprobes: usize,
+    mut find: G,
+) -> Vec<(usize, usize)>
+where
+    F: NonLinearOp + ?Sized,
+    G: FnMut(&F::V) -> Vec<(usize, usize)>,
+{
+    // store as (col, row) so that the result is in column-major order
+    let mut non_zeros: BTreeSet<(usize, usize)> =
+        find(x).into_iter().map(|(i, j)| (j, i)).collect();
+    let mut rng = ProbeRng::default();
+    let mut xk = x.clone();
+    let mut fk = F::V::zeros(op.nout());
+    for _ in 0..nprobes {
+        for i in 0..x.len() {
+            let scale = F::T::one() + num_traits::abs(x[i]);
+            let dx = F::T::from(rng.next_f64().abs()) * scale;
+            xk[i] = if x[i] < F::T::zero() {
+                x[i] - dx
+            } else {
+                x[i] + dx
+            };
+        }
+        op.call_inplace(&xk, t, &mut fk);
+        non_zeros.extend(
+            find(&xk)
+                .into_iter()
+                .filter(|&(i, _)| num_traits::abs(fk[i]) < F::T::INFINITY)
+                .map(|(i, j)| (j, i)),
+        );
+    }
+    non_zeros.into_iter().map(|(j, i)| (i, j)).collect()
+}
+
+/// xorshift64* generator giving perturbations in the range [-1, 1)
+struct ProbeRng(u64);
+
+impl Default for ProbeRng {
+    fn default() -> Self {
+        Self(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+impl ProbeRng {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        2.0 * (bits as f64 / (1u64 << 53) as f64) - 1.0
+    }
+}
+
 /// Find the non-zero entries of the matrix of a linear operator.
 /// This is used as the default `find_non_zeros` function for the `NonLinearOp` and `LinearOp` traits.
 /// Users can override this function with a more efficient and reliable implementation if desired.
@@ -58,6 +156,78 @@ pub fn find_non_zeros_linear<F: LinearOp + ?Sized>(op: &F, t: F::T) -> Vec<(usiz
     triplets
 }
 
+/// The algorithm used to colour the columns of the jacobian. Columns with the same colour are calculated using a single
+/// jacobian-vector product, so fewer colours means fewer calls to [NonLinearOp::jac_mul_inplace]. The number of colours is
+/// bounded below by the maximum number of non-zeros in any row (except for [ColoringAlgorithm::Star]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColoringAlgorithm {
+    /// Greedy colouring of the columns in their natural order
+    #[default]
+    Greedy,
+    /// Greedy colouring of the columns ordered by decreasing degree
+    LargestFirst,
+    /// Greedy colouring of the columns in smallest-last order
+    SmallestLast,
+    /// Greedy colouring of the columns in incidence-degree order
+    IncidenceDegree,
+    /// DSATUR colouring, which chooses the next column by the number of distinct colours of its neighbours
+    Dsatur,
+    /// Star colouring of the adjacency graph of the jacobian. This only applies to jacobians that are symmetric
+    /// (both in sparsity pattern and values), and can use fewer colours than the other algorithms.
+    Star,
+}
+
+impl ColoringAlgorithm {
+    /// Colour the `ncols` columns of a jacobian with non-zero entries `non_zeros`, returning the colour of each column (starting from 1).
+    ///
+    /// Panics if the algorithm is [ColoringAlgorithm::Star] and the sparsity pattern is not symmetric.
+    pub fn color(&self, non_zeros: &[(usize, usize)], ncols: usize) -> Vec<usize> {
+        if ncols == 0 {
+            return Vec::new();
+        }
+        if let ColoringAlgorithm::Star = self {
+            assert!(
+                is_symmetric(non_zeros),
+                "star colouring requires a symmetric sparsity pattern"
+            );
+            let mut adjacency = vec![Vec::new(); ncols];
+            for &(i, j) in non_zeros.iter().filter(|(i, j)| i != j) {
+                adjacency[j].push(i);
+            }
+            adjacency.iter_mut().for_each(|a| {
+                a.sort_unstable();
+                a.dedup();
+            });
+            let order = smallest_last_ordering(&adjacency);
+            return color_graph_star(&adjacency, &order);
+        }
+        let graph = nonzeros2graph(non_zeros, ncols);
+        if let ColoringAlgorithm::Greedy = self {
+            return color_graph_greedy(&graph);
+        }
+        let adjacency = adjacency_lists(&graph);
+        match self {
+            ColoringAlgorithm::LargestFirst => {
+                color_graph_greedy_ordered(&adjacency, &largest_first_ordering(&adjacency))
+            }
+            ColoringAlgorithm::SmallestLast => {
+                color_graph_greedy_ordered(&adjacency, &smallest_last_ordering(&adjacency))
+            }
+            ColoringAlgorithm::IncidenceDegree => {
+                color_graph_greedy_ordered(&adjacency, &incidence_degree_ordering(&adjacency))
+            }
+            ColoringAlgorithm::Dsatur => color_graph_dsatur(&adjacency),
+            ColoringAlgorithm::Greedy | ColoringAlgorithm::Star => unreachable!(),
+        }
+    }
+}
+
+/// Returns true if the sparsity pattern given by `non_zeros` is symmetric
+pub fn is_symmetric(non_zeros: &[(usize, usize)]) -> bool {
+    let set: HashSet<(usize, usize)> = non_zeros.iter().copied().collect();
+    set.iter().all(|(i, j)| set.contains(&(*j, *i)))
+}
+
 pub struct JacobianColoring<M: Matrix> {
     dst_indices_per_color: Vec<<M::Sparsity as MatrixSparsity>::Index>,
     src_indices_per_color: Vec<<M::V as Vector>::Index>,
@@ -65,29 +235,69 @@ pub struct JacobianColoring<M: Matrix> {
 }
 
 impl<M: Matrix> JacobianColoring<M> {
-    pub fn new_from_non_zeros<F: Op<M = M>>(op: &F, non_zeros: Vec<(usize, usize)>) -> Self {
+    /// Create a colouring of the columns of the jacobian of `op` with non-zero entries `non_zeros`, using the given colouring algorithm.
+    ///
+    /// Panics if the sparsity of `op` is not defined, or if `algorithm` is [ColoringAlgorithm::Star] and the sparsity pattern is not symmetric.
+    pub fn new_from_non_zeros<F: Op<M = M>>(
+        op: &F,
+        non_zeros: Vec<(usize, usize)>,
+        algorithm: ColoringAlgorithm,
+    ) -> Self {
         let sparsity = op
             .sparsity()
             .expect("Jacobian sparsity not defined, cannot use coloring");
         let ncols = op.nstates();
-        let graph = nonzeros2graph(non_zeros.as_slice(), ncols);
-        let coloring = color_graph_greedy(&graph);
+        let coloring = algorithm.color(non_zeros.as_slice(), ncols);
         let max_color = coloring.iter().max().copied().unwrap_or(0);
+
+        // for each non-zero (i, j), find the colour c and the row of the compressed column c it is read from
+        let src = if let ColoringAlgorithm::Star = algorithm {
+            let mut rows_with_color: HashSet<(usize, usize)> = HashSet::new();
+            let mut conflicts: HashSet<(usize, usize)> = HashSet::new();
+            for &(i, j) in non_zeros.iter() {
+                if !rows_with_color.insert((i, coloring[j])) {
+                    conflicts.insert((i, coloring[j]));
+                }
+            }
+            non_zeros
+                .iter()
+                .map(|&(i, j)| {
+                    if conflicts.contains(&(i, coloring[j])) {
+                        // use symmetry, a_ij = a_ji is unique in row j of the colour of column i
+                        (coloring[i], j)
+                    } else {
+                        (coloring[j], i)
+                    }
+                })
+                .collect::<Vec<_>>()
+        } else {
+            non_zeros
+                .iter()
+                .map(|&(i, j)| (coloring[j], i))
+                .collect::<Vec<_>>()
+        };
+
         let mut dst_indices_per_color = Vec::new();
         let mut src_indices_per_color = Vec::new();
         let mut input_indices_per_color = Vec::new();
         for c in 1..=max_color {
             let mut rows = Vec::new();
             let mut cols = Vec::new();
-            for (i, j) in non_zeros.iter() {
-                if coloring[*j] == c {
+            let mut src_rows = Vec::new();
+            for ((i, j), (src_color, src_row)) in non_zeros.iter().zip(src.iter()) {
+                if *src_color == c {
                     rows.push(*i);
                     cols.push(*j);
+                    src_rows.push(*src_row);
                 }
             }
             let dst_indices = sparsity.get_index(rows.as_slice(), cols.as_slice());
-            let src_indices = <M::V as Vector>::Index::from_slice(rows.as_slice());
-            let unique_cols: HashSet<_> = HashSet::from_iter(cols.iter().cloned());
+            let src_indices = <M::V as Vector>::Index::from_slice(src_rows.as_slice());
+            let unique_cols: HashSet<_> = non_zeros
+                .iter()
+                .map(|(_i, j)| *j)
+                .filter(|j| coloring[*j] == c)
+                .collect();
             let unique_cols = unique_cols.into_iter().collect::<Vec<_>>();
             let input_indices = <M::V as Vector>::Index::from_slice(unique_cols.as_slice());
             dst_indices_per_color.push(dst_indices);
@@ -155,16 +365,23 @@ impl<M: Matrix> JacobianColoring<M> {
 mod tests {
     use std::rc::Rc;
 
-    use crate::jacobian::{find_non_zeros_linear, find_non_zeros_nonlinear, JacobianColoring};
+    use crate::jacobian::{
+        find_non_zeros_linear, find_non_zeros_nonlinear, find_non_zeros_nonlinear_from_call,
+        find_non_zeros_with_probes, ColoringAlgorithm, JacobianColoring,
+    };
     use crate::matrix::Matrix;
+    use crate::op::closure_autodiff::ClosureAutodiff;
+    use crate::op::closure_no_jac::ClosureNoJac;
     use crate::op::linear_closure::LinearClosure;
     use crate::op::{LinearOp, Op};
     use crate::vector::Vector;
-    use crate::NonLinearOp;
     use crate::{
         jacobian::{coloring::nonzeros2graph, greedy_coloring::color_graph_greedy},
         op::closure::Closure,
     };
+    use crate::{
+        AutodiffFunction, AutodiffScalar, MatrixSparsity, NonLinearOp, OdeBuilder, OdeEquations,
+    };
     use nalgebra::{DMatrix, DVector};
     use std::ops::MulAssign;
 
@@ -195,7 +412,7 @@ mod tests {
         );
         let y0 = DVector::zeros(nstates);
         let t0 = 0.0;
-        ret.calculate_sparsity(&y0, t0);
+        ret.calculate_sparsity(&y0, t0).unwrap();
         ret
     }
 
@@ -281,7 +498,8 @@ mod tests {
             let y0 = V::zeros(n);
             let t0 = 0.0;
             let non_zeros = find_non_zeros_nonlinear(&op, &y0, t0);
-            let coloring = JacobianColoring::new_from_non_zeros(&op, non_zeros);
+            let coloring =
+                JacobianColoring::new_from_non_zeros(&op, non_zeros, ColoringAlgorithm::default());
             let mut jac = M::zeros(3, 3);
             coloring.jacobian_inplace(&op, &y0, t0, &mut jac);
             let mut gemv1 = V::zeros(n);
@@ -297,7 +515,8 @@ mod tests {
             let op = helper_triplets2op_linear(triplets.as_slice(), n, n);
             let t0 = 0.0;
             let non_zeros = find_non_zeros_linear(&op, t0);
-            let coloring = JacobianColoring::new_from_non_zeros(&op, non_zeros);
+            let coloring =
+                JacobianColoring::new_from_non_zeros(&op, non_zeros, ColoringAlgorithm::default());
             let mut jac = M::zeros(3, 3);
             coloring.matrix_inplace(&op, t0, &mut jac);
             let mut gemv1 = V::zeros(n);
@@ -308,4 +527,237 @@ mod tests {
             gemv1.assert_eq_st(&gemv2, 1e-10);
         }
     }
+
+    #[test]
+    fn finite_difference_coloring() {
+        let test_triplets = [
+            vec![(0, 0, 1.0), (1, 1, 1.0), (2, 2, 1.0)],
+            vec![(0, 0, 1.0), (1, 1, 1.0)],
+            vec![(0, 0, 0.9), (1, 0, 2.0), (1, 1, 1.1), (2, 2, 1.4)],
+        ];
+        type V = DVector<f64>;
+        type M = DMatrix<f64>;
+        let n = 3;
+
+        for triplets in test_triplets.iter() {
+            let mut op = ClosureNoJac::<M, _>::new(
+                move |x: &V, _p: &V, _t, y: &mut V| {
+                    y.fill(0.0);
+                    for (i, j, v) in triplets {
+                        y[*i] += x[*j] * x[*j] * v;
+                    }
+                },
+                n,
+                n,
+                Rc::new(V::zeros(0)),
+            );
+            let y0 = V::from_element(n, 2.0);
+            let t0 = 0.0;
+            let non_zeros = find_non_zeros_nonlinear_from_call(&op, &y0, t0);
+            let expect = triplets
+                .iter()
+                .map(|(i, j, _v)| (*i, *j))
+                .collect::<Vec<_>>();
+            assert_eq!(non_zeros, expect);
+
+            op.calculate_sparsity(&y0, t0).unwrap();
+            let before = op.statistics();
+            let jac = op.jacobian(&y0, t0);
+            // f(y0) is evaluated once and shared by the finite differences of all the colours
+            let after = op.statistics();
+            assert_eq!(
+                after.number_of_calls - before.number_of_calls,
+                after.number_of_jac_muls - before.number_of_jac_muls + 1
+            );
+            let mut expect_jac = M::zeros(n, n);
+            for (i, j, v) in triplets {
+                expect_jac[(*i, *j)] = 4.0 * v;
+            }
+            for j in 0..n {
+                jac.column(j)
+                    .into_owned()
+                    .assert_eq_st(&expect_jac.column(j).into_owned(), 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn find_non_zeros_probes() {
+        // y0 = x0 * x1 only if x0 > 0, so (0, 1) is not found at x0 = 0
+        let op = Closure::<DMatrix<f64>, _, _>::new(
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+                y[0] = if x[0] > 0.0 { x[0] * x[1] } else { x[0] };
+                y[1] = x[1];
+            },
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, v: &DVector<f64>, y: &mut DVector<f64>| {
+                y[0] = if x[0] > 0.0 {
+                    v[0] * x[1] + x[0] * v[1]
+                } else {
+                    v[0]
+                };
+                y[1] = v[1];
+            },
+            2,
+            2,
+            Rc::new(DVector::zeros(0)),
+        );
+        let x = DVector::zeros(2);
+        let find = |x: &DVector<f64>| find_non_zeros_nonlinear(&op, x, 0.0);
+        assert_eq!(
+            find_non_zeros_with_probes(&op, &x, 0.0, 0, find),
+            vec![(0, 0), (1, 1)]
+        );
+        assert_eq!(
+            find_non_zeros_with_probes(&op, &x, 0.0, 4, find),
+            vec![(0, 0), (0, 1), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn find_non_zeros_probes_keep_sign() {
+        // y0 = sqrt(x0) is only defined for x0 >= 0, and y1 = ln(-x1) for x1 < 0
+        let op = ClosureNoJac::<DMatrix<f64>, _>::new(
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+                y[0] = x[0].sqrt();
+                y[1] = (-x[1]).ln() * x[0];
+            },
+            2,
+            2,
+            Rc::new(DVector::zeros(0)),
+        );
+        let x = DVector::from_vec(vec![1.0, -1.0]);
+        let find = |x: &DVector<f64>| find_non_zeros_nonlinear_from_call(&op, x, 0.0);
+        assert_eq!(
+            find_non_zeros_with_probes(&op, &x, 0.0, 8, find),
+            vec![(0, 0), (1, 0), (1, 1)]
+        );
+    }
+
+    struct Masked;
+
+    impl AutodiffFunction<f64> for Masked {
+        fn call<S: AutodiffScalar<f64>>(&self, x: &[S], _p: &[f64], _t: f64, y: &mut [S]) {
+            y[0] = x[0] * x[1] * 0.0;
+            y[1] = x[2].powi(0) + x[1];
+            y[2] = x[2] * x[0];
+        }
+    }
+
+    #[test]
+    fn find_non_zeros_traced() {
+        let op = ClosureAutodiff::<DMatrix<f64>, _>::new(Masked, 3, 3, Rc::new(DVector::zeros(0)));
+        let x = DVector::zeros(3);
+        let non_zeros = op.find_non_zeros_traced(&x, 0.0);
+        assert_eq!(non_zeros, vec![(0, 0), (2, 0), (0, 1), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn user_jacobian_sparsity() {
+        type M = DMatrix<f64>;
+        let problem = OdeBuilder::new()
+            .jacobian_sparsity([(1, 1), (0, 0), (2, 2), (0, 2), (2, 0), (0, 1), (1, 1)])
+            .build_ode_autodiff::<M, _, _>(Masked, |_p, _t| DVector::from_element(3, 1.0))
+            .unwrap();
+        let rhs = problem.eqn.rhs();
+        let sparsity = rhs.sparsity().unwrap();
+        assert_eq!(sparsity.nrows(), 3);
+        let x = DVector::from_vec(vec![1.0, 2.0, 3.0]);
+        let jac = rhs.jacobian(&x, 0.0);
+        let expect = M::from_row_slice(3, 3, &[0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 3.0, 0.0, 1.0]);
+        for j in 0..3 {
+            jac.column(j)
+                .into_owned()
+                .assert_eq_st(&expect.column(j).into_owned(), 1e-14);
+        }
+
+        let problem = OdeBuilder::new()
+            .jacobian_sparsity([(0, 3)])
+            .build_ode_autodiff::<M, _, _>(Masked, |_p, _t| DVector::from_element(3, 1.0));
+        assert!(problem.is_err());
+    }
+
+    #[test]
+    fn star_coloring_asymmetric_sparsity() {
+        type M = DMatrix<f64>;
+        // the detected pattern of Masked is not symmetric, so it cannot be star coloured
+        let problem = OdeBuilder::new()
+            .use_coloring(true)
+            .coloring_algorithm(ColoringAlgorithm::Star)
+            .build_ode_autodiff::<M, _, _>(Masked, |_p, _t| DVector::from_element(3, 1.0));
+        assert!(problem.is_err());
+    }
+
+    fn laplacian_2d_triplets(m: usize) -> Vec<(usize, usize, f64)> {
+        let mut triplets = Vec::new();
+        for k in 0..m * m {
+            let (i, j) = (k / m, k % m);
+            if i > 0 {
+                triplets.push((k - m, k, 1.0));
+            }
+            if j > 0 {
+                triplets.push((k - 1, k, 1.0));
+            }
+            triplets.push((k, k, -4.0 - k as f64));
+            if j < m - 1 {
+                triplets.push((k + 1, k, 1.0));
+            }
+            if i < m - 1 {
+                triplets.push((k + m, k, 1.0));
+            }
+        }
+        triplets
+    }
+
+    #[test]
+    fn coloring_algorithms() {
+        type V = DVector<f64>;
+        type M = DMatrix<f64>;
+        let algorithms = [
+            ColoringAlgorithm::Greedy,
+            ColoringAlgorithm::LargestFirst,
+            ColoringAlgorithm::SmallestLast,
+            ColoringAlgorithm::IncidenceDegree,
+            ColoringAlgorithm::Dsatur,
+            ColoringAlgorithm::Star,
+        ];
+        let m = 6;
+        let n = m * m;
+        let triplets = laplacian_2d_triplets(m);
+        let op = helper_triplets2op_nonlinear(triplets.as_slice(), n, n);
+        let y0 = V::zeros(n);
+        let non_zeros = find_non_zeros_nonlinear(&op, &y0, 0.0);
+        let max_row_non_zeros = 5;
+        let mut expect = M::zeros(n, n);
+        for (i, j, v) in triplets.iter() {
+            expect[(*i, *j)] = *v;
+        }
+        for algorithm in algorithms {
+            let colors = algorithm.color(&non_zeros, n);
+            let ncolors = colors.iter().max().copied().unwrap();
+            if algorithm != ColoringAlgorithm::Star {
+                // no two columns with the same colour have a non-zero in the same row
+                assert!(ncolors >= max_row_non_zeros);
+                for (i, j) in non_zeros.iter() {
+                    for (k, l) in non_zeros.iter() {
+                        assert!(!(i == k && j != l && colors[*j] == colors[*l]));
+                    }
+                }
+            }
+
+            let coloring = JacobianColoring::new_from_non_zeros(&op, non_zeros.clone(), algorithm);
+            let mut jac = M::zeros(n, n);
+            coloring.jacobian_inplace(&op, &y0, 0.0, &mut jac);
+            for j in 0..n {
+                jac.column(j)
+                    .into_owned()
+                    .assert_eq_st(&expect.column(j).into_owned(), 1e-14);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "symmetric")]
+    fn star_coloring_not_symmetric() {
+        ColoringAlgorithm::Star.color(&[(0, 0), (1, 0), (1, 1)], 2);
+    }
 }
@@ -0,0 +1,107 @@
+/// Find a star colouring of the adjacency graph of a symmetric matrix, i.e. a distance-1 colouring where every
+/// path on four vertices uses at least three colours. Each non-zero `a_ij` of a symmetric matrix can then be recovered
+/// directly from the compressed columns, either from row `i` of the colour of column `j`, or from row `j` of the colour of column `i`.
+///
+/// `adjacency` gives the neighbours of each vertex (excluding the vertex itself), vertices are coloured in the given order.
+/// See Algorithm 4.1 in Gebremedhin, A. H., Manne, F., & Pothen, A. (2005). What color is your Jacobian? Graph coloring for computing derivatives. SIAM review, 47(4), 629-705.
+pub fn color_graph_star(adjacency: &[Vec<usize>], order: &[usize]) -> Vec<usize> {
+    let n = adjacency.len();
+    let mut result = vec![0; n];
+    // forbidden[c] == v + 1 if colour c cannot be used for v
+    let mut forbidden = vec![0; n + 1];
+    for &v in order {
+        for &w in adjacency[v].iter() {
+            if result[w] != 0 {
+                forbidden[result[w]] = v + 1;
+            }
+            for &x in adjacency[w].iter() {
+                if x == v || result[x] == 0 {
+                    continue;
+                }
+                if result[w] == 0 {
+                    // v and x are distance-2 neighbours through an uncoloured vertex
+                    forbidden[result[x]] = v + 1;
+                } else if adjacency[x]
+                    .iter()
+                    .any(|&y| y != w && result[y] == result[w])
+                {
+                    // colouring v with the colour of x would make v-w-x-y a two-coloured path
+                    forbidden[result[x]] = v + 1;
+                }
+            }
+        }
+        result[v] = (1..=n).find(|&c| forbidden[c] != v + 1).unwrap_or(1);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::color_graph_star;
+
+    /// check that every path on four vertices uses at least three colours
+    fn is_star_coloring(adjacency: &[Vec<usize>], colors: &[usize]) -> bool {
+        for v in 0..adjacency.len() {
+            for &w in adjacency[v].iter() {
+                if colors[v] == colors[w] {
+                    return false;
+                }
+                for &x in adjacency[w].iter().filter(|&&x| x != v) {
+                    for &y in adjacency[x].iter().filter(|&&y| y != w && y != v) {
+                        if colors[v] == colors[x] && colors[w] == colors[y] {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn star_coloring_path_and_grid() {
+        // path graph 0-1-2-...-9
+        let n = 10;
+        let adjacency = (0..n)
+            .map(|i| {
+                let mut a = Vec::new();
+                if i > 0 {
+                    a.push(i - 1);
+                }
+                if i < n - 1 {
+                    a.push(i + 1);
+                }
+                a
+            })
+            .collect::<Vec<_>>();
+        let order = (0..n).collect::<Vec<_>>();
+        let colors = color_graph_star(&adjacency, &order);
+        assert!(is_star_coloring(&adjacency, &colors));
+        assert_eq!(colors.iter().max(), Some(&3));
+
+        // 5x5 grid
+        let m = 5;
+        let adjacency = (0..m * m)
+            .map(|k| {
+                let (i, j) = (k / m, k % m);
+                let mut a = Vec::new();
+                if i > 0 {
+                    a.push(k - m);
+                }
+                if j > 0 {
+                    a.push(k - 1);
+                }
+                if j < m - 1 {
+                    a.push(k + 1);
+                }
+                if i < m - 1 {
+                    a.push(k + m);
+                }
+                a
+            })
+            .collect::<Vec<_>>();
+        let order = (0..m * m).collect::<Vec<_>>();
+        let colors = color_graph_star(&adjacency, &order);
+        assert!(is_star_coloring(&adjacency, &colors));
+    }
+}
@@ -1,12 +1,16 @@
 //! # DiffSol
 //!
-//! DiffSol is a library for solving differential equations. It provides a simple interface to solve ODEs and semi-explicit DAEs.
+//! DiffSol is a library for solving differential equations. It provides a simple interface to solve ODEs, semi-explicit DAEs and fully implicit DAEs.
 //!
 //! ## Solving ODEs
 //!
 //! To create a new problem, use the [OdeBuilder] struct. You can set the initial time, initial step size, relative tolerance, absolute tolerance, and parameters,
 //! or leave them at their default values. Then, call the [OdeBuilder::build_ode] method with the ODE equations, or the [OdeBuilder::build_ode_with_mass] method
-//! with the ODE equations and the mass matrix equations.
+//! with the ODE equations and the mass matrix equations. A mass matrix that depends on the state (i.e. `M(t, y) dy/dt = F(t, y)`) can be given
+//! using the [OdeBuilder::build_ode_with_state_mass] method (not supported by [SundialsIda]).
+//! Fully implicit DAEs of the form `F(t, y, y') = 0` can be given as a residual and its jacobians using the [OdeBuilder::build_implicit_dae] method,
+//! or by implementing the [ImplicitDaeEquations] trait and wrapping it in an [ImplicitDae]. These are solved directly (without rewriting them
+//! in the semi-explicit form) by the [Bdf] and [SundialsIda] solvers.
 //!
 //! You will also need to choose a matrix type to use. DiffSol can use the [nalgebra](https://nalgebra.org) `DMatrix` type, or any other type that implements the
 //! [Matrix] trait. You can also use the [sundials](https://computation.llnl.gov/projects/sundials) library for the matrix and vector types (see [SundialsMatrix]).
@@ -15,8 +19,11 @@
 //! - A Backwards Difference Formulae [Bdf] solver, suitable for stiff problems and singular mass matrices.
 //! - A Singly Diagonally Implicit Runge-Kutta (SDIRK or ESDIRK) solver [Sdirk]. You can use your own butcher tableau using [Tableau] or use one of the provided ([Tableau::tr_bdf2], [Tableau::esdirk34]).
 //! - A BDF solver that wraps the IDA solver solver from the sundials library ([SundialsIda], requires the `sundials` feature).
+//! - An explicit Runge-Kutta-Chebyshev solver [Rkc] for large, mildly stiff problems with a jacobian spectrum close to the negative real axis (e.g. diffusion),
+//!   which needs no linear solves and chooses the number of stages each step from an estimate of the spectral radius of the jacobian.
 //!
 //! See the [OdeSolverMethod] trait for a more detailed description of the available methods on each solver.
+//! All solvers report the same set of statistics (number of steps, nonlinear and linear solver work, operator evaluations and timings) via [OdeSolverMethod::statistics].
 //!
 //! ```rust
 //! use diffsol::{OdeBuilder, Bdf, OdeSolverState, OdeSolverMethod};
@@ -43,13 +50,81 @@
 //! let mut solver = Bdf::default();
 //! let t = 0.4;
 //! let state = OdeSolverState::new(&problem);
-//! solver.set_problem(state, &problem);
+//! solver.set_problem(state, &problem).unwrap();
 //! while solver.state().unwrap().t <= t {
 //!     solver.step().unwrap();
 //! }
 //! let y = solver.interpolate(t);
 //! ```
 //!
+//! ## Steady states
+//!
+//! To find the steady state (equilibrium) of an ODE or DAE problem rather than a trajectory, use the [steady_state] function.
+//! This uses pseudo-transient continuation, i.e. implicit Euler steps with a growing pseudo time step followed by Newton's method once close to the steady state,
+//! which converges from initial conditions where Newton's method alone fails. The algorithm is controlled using [SteadyStateOptions], including whether to fall back to
+//! integrating the problem with the [Bdf] solver if pseudo-transient continuation fails.
+//!
+//! Branches of steady states can be followed as one of the parameters of the problem is varied using the [continuation] function (pseudo-arclength continuation).
+//! This returns a [ContinuationBranch] containing the steady states and their stability, which can be plotted, along with the fold and Hopf bifurcations
+//! found along the branch (see [BifurcationKind]).
+//!
+//! Periodic orbits (limit cycles), such as those born at a Hopf bifurcation, can be found using the [periodic_orbit] function, which uses single shooting
+//! with any of the ODE solvers to find a point on the orbit and its period. The returned [PeriodicOrbit] includes the Floquet multipliers that determine
+//! the stability of the orbit.
+//!
+//! ## Boundary value problems
+//!
+//! Two-point boundary value problems `y' = f(t, y)` on `[a, b]` with boundary conditions `g(y(a), y(b)) = 0` can be solved using the [bvp_collocation] function
+//! (fourth order collocation with mesh refinement, similar to scipy's `solve_bvp`), or the [bvp_shooting] function (multiple shooting using any of the ODE solvers).
+//! The right-hand side is given by an [OdeSolverProblem], and the boundary conditions by a [NonLinearOp] (e.g. a [Closure]) with `2n` states `(y(a), y(b))`
+//! and `n` outputs. Both return a [BvpSolution] containing the solution at the mesh points, which can be interpolated between them.
+//!
+//! ## Delay differential equations
+//!
+//! Delay differential equations with constant delays, `y'(t) = f(t, y(t), y(t - tau_1), ..., y(t - tau_m))` with a history function giving the solution before the
+//! initial time, can be specified using the [OdeBuilder::build_dde] method, and solved using a [DdeSolver] wrapping any of the ODE solvers.
+//! The delayed states are interpolated from a [DdeHistory] of the solution, and the solver steps exactly to the discontinuities propagated from the initial time.
+//!
+//! ## Stochastic differential equations
+//!
+//! Stochastic differential equations `dy = f(t, y) dt + g(t, y) dW` are specified using an [SdeProblem], with the drift given by the right-hand side of an [OdeSolverProblem]
+//! and a diffusion operator with diagonal or general noise ([SdeNoise]). A sample path can be found using the [sde_solve] function with the fixed step Euler-Maruyama
+//! or Milstein schemes, or the adaptive SRIW1 scheme (see [SdeMethod]), where the Wiener increments are sampled using a seeded generator [SdeRng]. The mean and variance of the
+//! solution over an ensemble of sample paths can be found using the [sde_ensemble] function.
+//!
+//! ## Second order and Hamiltonian systems
+//!
+//! Second order systems `q'' = f(t, q, q')` (optionally with a mass matrix `M q'' = g(t, q, q')`) and separable Hamiltonian systems `H(q, p) = T(p) + V(q)` can be
+//! specified using the [OdeBuilder::build_second_order], [OdeBuilder::build_second_order_with_mass] and [OdeBuilder::build_separable_hamiltonian] methods, which
+//! write them as first order systems `y = (q, v)` or `y = (q, p)`. These can be solved with any of the ODE solvers, or with the fixed step solvers for long time
+//! integration: [Symplectic] (velocity Verlet and the fourth and sixth order Yoshida compositions) and [ImplicitMidpoint] for Hamiltonian systems, and
+//! [GeneralisedAlpha] (including the Newmark methods) for structural dynamics problems with mass and damping.
+//!
+//! ## Exponential integrators
+//!
+//! Stiff semilinear problems `y' = L y + N(t, y)` can be solved using the [ExponentialIntegrator] solver (the fixed step ETDRK4 method or the adaptive EXPRB32 method),
+//! which integrates the stiff linear part exactly. The actions of the phi-functions of the jacobian are approximated in a Krylov subspace using only the jacobian-vector
+//! product of the right-hand side (see [krylov_phi]), so the jacobian is never factorised. The matrix exponential of a small dense matrix is available using
+//! [DenseMatrix::expm].
+//!
+//! ## Linear ODEs
+//!
+//! Linear ODEs with constant coefficients `y' = A y + b(t)`, such as compartmental pharmacokinetic models, can be specified using the [OdeBuilder::build_linear_ode]
+//! method and solved exactly using the [LinearOdeSolver] solver, which uses the matrix exponential of `A` rather than a time-stepping scheme. The solution is exact
+//! (to rounding error) at any output time for piecewise-constant inputs `b(t)`, as long as the times at which the input changes are given as breakpoints.
+//!
+//! ## Multirate problems
+//!
+//! Problems with components that evolve on very different time scales can be solved with the multirate [MriGark] solver, which splits the right-hand side
+//! into a fast and a slow part. The fast part can be given as any [NonLinearOp], or as the equations of a subset of the states using [FastStatesCallable].
+//! The slow part is integrated with large explicit steps, and the fast part is sub-cycled over each stage with an inner solver (e.g. [Bdf]).
+//!
+//! ## Parallel-in-time
+//!
+//! Problems over long time intervals can be solved in parallel over time slices using the [parareal] function, which iterates a cheap coarse solver
+//! (e.g. [Sdirk]) and an accurate fine solver (e.g. [Bdf]) until the solution at the slice boundaries converges, with the fine solves running on multiple threads.
+//! The number of iterations and the speedup over the serial fine solve are reported in the [PararealSolution].
+//!
 //! ## DiffSL
 //!
 //! DiffSL is a domain-specific language for specifying differential equations <https://github.com/martinjrobins/diffsl>. It uses the LLVM compiler framwork
@@ -71,19 +146,38 @@
 //! The provided linear solvers are:
 //! - [NalgebraLU]: a direct solver that uses the LU decomposition implemented in the [nalgebra](https://nalgebra.org) library.
 //! - [FaerLU]: a direct solver that uses the LU decomposition implemented in the [faer](https://github.com/sarah-ek/faer-rs) library.
+//! - [Gmres]: a matrix-free iterative solver (restarted GMRES) that only uses the action of the jacobian on a vector, with an optional preconditioner.
 //! - [SundialsLinearSolver]: a linear solver that uses the [sundials](https://computation.llnl.gov/projects/sundials) library (requires the `sundials` feature).
 //!
 //! The provided nonlinear solvers are:
-//! - [NewtonNonlinearSolver]: a nonlinear solver that uses the Newton method.
+//! - [NewtonNonlinearSolver]: a nonlinear solver that uses the Newton method. By default it takes full steps, but a backtracking line search or a dogleg trust region
+//!   can be selected using [NewtonNonlinearSolver::set_globalisation] (see [NewtonGlobalisation]) to make the iteration converge from poor initial guesses, for example
+//!   when making the initial state of a DAE consistent. The iterates can be restricted to lie within bounds, and if the iteration fails the returned error contains a
+//!   [NewtonError] with the diagnostics for each iteration.
+//! - [BroydenNonlinearSolver]: a quasi-Newton solver that updates the inverse of the jacobian using Broyden's "good" update, so the jacobian is factorised less often.
+//! - [AndersonNonlinearSolver]: an Anderson-accelerated fixed point solver that does not require a jacobian, suitable for non-stiff problems.
+//! - [NewtonKrylovNonlinearSolver]: a jacobian-free Newton-Krylov solver that solves each Newton step inexactly using [Gmres], with the tolerance chosen by an
+//!   Eisenstat-Walker forcing term, for large systems where the jacobian cannot be assembled. A preconditioner (e.g. [NalgebraLU] on the lagged jacobian) can be
+//!   given to the [Gmres] solver.
+//!
+//! Any of these can be used as the nonlinear solver of the [Bdf] solver by creating it with [Bdf::new].
 //!
 //! ## Jacobian and Mass matrix calculation
 //!
 //! Via [OdeEquations], the user provides the action of the jacobian on a vector `J(x) v`. By default DiffSol uses this to generate a jacobian matrix for the ODE solver.
+//! Alternatively, the [OdeBuilder::build_ode_autodiff] method takes a right-hand side function that is generic over the scalar type (see [AutodiffFunction]),
+//! and calculates `J(x) v` exactly using forward-mode automatic differentiation with dual numbers ([Dual]).
 //! Generally this requires `n` evaluations of the jacobian action for a system of size `n`, so it is often more efficient if the user can provide the jacobian matrix directly
 //! by implementing the [NonLinearOp::jacobian_inplace] and the [LinearOp::matrix_inplace] (if applicable) functions.
 //!
+//! To check a user-supplied jacobian, [jacobian::check::check_jacobian] compares the jacobian action and matrix of an operator against central finite differences,
+//! and [jacobian::check::check_linear_op] does the same for a linear operator such as the mass matrix.
+//!
 //! DiffSol also provides an experimental feature to calculate sparse jacobians more efficiently by automatically detecting the sparsity pattern of the jacobian and using
 //! colouring \[1\] to reduce the number of jacobian evaluations. You can enable this feature by enabling [OdeBuilder::use_coloring()] option when building the ODE problem.
+//! The algorithm used to colour the jacobian can be chosen using [OdeBuilder::coloring_algorithm()] (see [ColoringAlgorithm]).
+//! The sparsity pattern can be detected more robustly by probing several states ([OdeBuilder::sparsity_probes()]), or can be supplied directly using [OdeBuilder::jacobian_sparsity()].
+//! For problems built with [OdeBuilder::build_ode_autodiff], the pattern is found by tracing the dependencies of each output ([scalar::tracer::Tracer]).
 //!
 //! \[1\] Gebremedhin, A. H., Manne, F., & Pothen, A. (2005). What color is your Jacobian? Graph coloring for computing derivatives. SIAM review, 47(4), 629-705.
 //!
@@ -138,8 +232,12 @@ pub mod scalar;
 pub mod solver;
 pub mod vector;
 
+pub use jacobian::ColoringAlgorithm;
 use linear_solver::LinearSolver;
-pub use linear_solver::{FaerLU, NalgebraLU};
+pub use linear_solver::{
+    gmres::{Gmres, IdentityPreconditioner},
+    FaerLU, NalgebraLU,
+};
 
 #[cfg(feature = "sundials")]
 pub use matrix::sundials::SundialsMatrix;
@@ -154,17 +252,38 @@ pub use linear_solver::sundials::SundialsLinearSolver;
 pub use ode_solver::sundials::SundialsIda;
 
 use matrix::{DenseMatrix, Matrix, MatrixCommon, MatrixSparsity, MatrixView, MatrixViewMut};
-pub use nonlinear_solver::newton::NewtonNonlinearSolver;
+pub use nonlinear_solver::anderson::AndersonNonlinearSolver;
+pub use nonlinear_solver::broyden::BroydenNonlinearSolver;
+pub use nonlinear_solver::newton::{
+    NewtonError, NewtonFailure, NewtonGlobalisation, NewtonIteration, NewtonNonlinearSolver,
+};
+pub use nonlinear_solver::newton_krylov::NewtonKrylovNonlinearSolver;
 use nonlinear_solver::{root::RootFinder, NonLinearSolver};
 pub use ode_solver::{
-    bdf::Bdf, builder::OdeBuilder, equations::OdeEquations, method::OdeSolverMethod,
-    method::OdeSolverState, method::OdeSolverStopReason, problem::OdeSolverProblem, sdirk::Sdirk,
-    tableau::Tableau,
+    bdf::Bdf, builder::OdeBuilder, bvp::bvp_collocation, bvp::bvp_shooting, bvp::BvpOptions,
+    bvp::BvpSolution, continuation::continuation, continuation::Bifurcation,
+    continuation::BifurcationKind, continuation::ContinuationBranch,
+    continuation::ContinuationOptions, continuation::ContinuationPoint, dde::DdeHistory,
+    dde::DdeProblem, dde::DdeSolver, equations::OdeEquations, equations::OdeEquationsStatistics,
+    exponential::krylov_phi, exponential::ExponentialIntegrator,
+    generalised_alpha::GeneralisedAlpha, implicit_equations::ImplicitDae,
+    implicit_equations::ImplicitDaeEquations, linear::LinearOdeSolver, method::OdeSolverMethod,
+    method::OdeSolverState, method::OdeSolverStatistics, method::OdeSolverStopReason,
+    method::OdeSolverTimings, multirate::MriFastEquations, multirate::MriGark, parareal::parareal,
+    parareal::PararealOptions, parareal::PararealSolution, periodic_orbit::periodic_orbit,
+    periodic_orbit::PeriodicOrbit, problem::OdeSolverProblem, rkc::Rkc, sde::sde_ensemble,
+    sde::sde_solve, sde::SdeEnsemble, sde::SdeMethod, sde::SdeNoise, sde::SdeOptions,
+    sde::SdeProblem, sde::SdeRng, sde::SdeSolution, sdirk::Sdirk, steady_state::steady_state,
+    steady_state::SteadyStateOptions, steady_state::SteadyStateSolution,
+    symplectic::ImplicitMidpoint, symplectic::Symplectic, tableau::Tableau,
 };
+pub use op::closure_autodiff::AutodiffFunction;
+pub use op::multirate::FastStatesCallable;
 use op::{
-    closure::Closure, closure_no_jac::ClosureNoJac, linear_closure::LinearClosure,
-    unit::UnitCallable, LinearOp, NonLinearOp, Op,
+    closure::Closure, closure_autodiff::ClosureAutodiff, closure_no_jac::ClosureNoJac,
+    linear_closure::LinearClosure, unit::UnitCallable, LinearOp, NonLinearOp, Op,
 };
+pub use scalar::dual::{AutodiffScalar, Dual};
 use scalar::{IndexType, Scalar, Scale};
 use solver::SolverProblem;
 use vector::{Vector, VectorCommon, VectorIndex, VectorRef, VectorView, VectorViewMut};
@@ -211,7 +330,7 @@ mod tests {
         let y = solver.solve(&problem, t).unwrap();
 
         let state = OdeSolverState::new(&problem);
-        solver.set_problem(state, &problem);
+        solver.set_problem(state, &problem).unwrap();
         while solver.state().unwrap().t <= t {
             solver.step().unwrap();
         }
@@ -252,7 +371,7 @@ mod tests {
         let y = solver.solve(&problem, t).unwrap();
 
         let state = OdeSolverState::new(&problem);
-        solver.set_problem(state, &problem);
+        solver.set_problem(state, &problem).unwrap();
         while solver.state().unwrap().t <= t {
             solver.step().unwrap();
         }
@@ -0,0 +1,303 @@
+use std::cell::Cell;
+
+use anyhow::{anyhow, Result};
+use num_traits::{abs, One, Pow, Zero};
+
+use crate::{
+    op::{NonLinearOp, Op},
+    scalar::scale,
+    LinearSolver, Scalar, SolverProblem, Vector,
+};
+
+/// A preconditioner that does nothing, used by [Gmres] when no preconditioner is given.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityPreconditioner;
+
+impl<C: Op> LinearSolver<C> for IdentityPreconditioner {
+    fn set_problem(&mut self, _problem: &SolverProblem<C>) {}
+    fn set_linearisation(&mut self, _x: &C::V, _t: C::T) {}
+    fn solve_in_place(&self, _b: &mut C::V) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A matrix-free linear solver using the restarted GMRES method \[1\].
+///
+/// The linear operator is the jacobian of the nonlinear operator `C` at the linearisation point, and is only used through the
+/// jacobian-vector product [NonLinearOp::jac_mul_inplace], so the jacobian is never assembled. The iteration stops when the norm of
+/// the residual is less than `rtol` times the norm of the right-hand side (see [Self::set_tolerance]).
+///
+/// An optional preconditioner `P` (any [LinearSolver], e.g. an LU factorisation of an approximate or lagged jacobian) is applied
+/// from the right. The preconditioner is only set up in [LinearSolver::set_linearisation], [Self::set_linearisation_point] just
+/// moves the point at which the jacobian-vector products are evaluated, so the preconditioner can be lagged.
+///
+/// \[1\] Saad, Y., & Schultz, M. H. (1986). GMRES: A generalized minimal residual algorithm for solving nonsymmetric linear systems. SIAM Journal on scientific and statistical computing, 7(3), 856-869.
+pub struct Gmres<C: NonLinearOp, P: LinearSolver<C> = IdentityPreconditioner> {
+    problem: Option<SolverProblem<C>>,
+    preconditioner: P,
+    x: Option<C::V>,
+    t: C::T,
+    restart: usize,
+    max_iter: usize,
+    rtol: C::T,
+    niter: Cell<usize>,
+}
+
+impl<C: NonLinearOp> Gmres<C, IdentityPreconditioner> {
+    pub fn new() -> Self {
+        Self::with_preconditioner(IdentityPreconditioner)
+    }
+}
+
+impl<C: NonLinearOp> Default for Gmres<C, IdentityPreconditioner> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: NonLinearOp, P: LinearSolver<C>> Gmres<C, P> {
+    /// Create a new solver with the given (right) preconditioner, with a restart length of 30, a maximum of 300 iterations and `rtol = 1e-6`.
+    pub fn with_preconditioner(preconditioner: P) -> Self {
+        Self {
+            problem: None,
+            preconditioner,
+            x: None,
+            t: C::T::zero(),
+            restart: 30,
+            max_iter: 300,
+            rtol: C::T::from(1e-6),
+            niter: Cell::new(0),
+        }
+    }
+    pub fn preconditioner(&self) -> &P {
+        &self.preconditioner
+    }
+    pub fn set_restart(&mut self, restart: usize) {
+        assert!(restart > 0, "restart must be greater than zero");
+        self.restart = restart;
+    }
+    pub fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    /// Set the relative tolerance `rtol`, the iteration stops when `||b - J x|| <= rtol ||b||`.
+    pub fn set_tolerance(&mut self, rtol: C::T) {
+        self.rtol = rtol;
+    }
+    pub fn tolerance(&self) -> C::T {
+        self.rtol
+    }
+    /// The number of iterations taken by the last call to [LinearSolver::solve_in_place].
+    pub fn niter(&self) -> usize {
+        self.niter.get()
+    }
+
+    /// Set the point at which the jacobian-vector products are evaluated, without setting up the preconditioner.
+    pub fn set_linearisation_point(&mut self, x: &C::V, t: C::T) {
+        match self.x.as_mut() {
+            Some(self_x) => self_x.copy_from(x),
+            None => self.x = Some(x.clone()),
+        }
+        self.t = t;
+    }
+
+    fn jac_mul(&self, v: &C::V, out: &mut C::V) {
+        let x = self.x.as_ref().expect("Gmres: linearisation not set");
+        self.problem
+            .as_ref()
+            .unwrap()
+            .f
+            .jac_mul_inplace(x, self.t, v, out);
+    }
+}
+
+impl<C: NonLinearOp, P: LinearSolver<C>> LinearSolver<C> for Gmres<C, P> {
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        self.preconditioner.set_problem(problem);
+        self.x = None;
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.set_linearisation_point(x, t);
+        self.preconditioner.set_linearisation(x, t);
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<()> {
+        if self.problem.is_none() {
+            panic!("Gmres::solve() called before set_problem");
+        }
+        let dot = |a: &C::V, b: &C::V| a.binary_fold(b, C::T::zero(), |acc, x, y, _| acc + x * y);
+        let n = b.len();
+        let m = self.restart;
+        self.niter.set(0);
+        let b_norm = b.norm();
+        if b_norm == C::T::zero() {
+            return Ok(());
+        }
+        let tol = self.rtol * b_norm;
+
+        // start from x = 0, so the initial residual is b
+        let mut x = C::V::zeros(n);
+        let mut r = b.clone();
+        let mut w = C::V::zeros(n);
+        let mut niter = 0;
+        loop {
+            let beta = r.norm();
+            if beta <= tol {
+                break;
+            }
+
+            // arnoldi process with modified gram-schmidt, the least squares problem is solved using givens rotations
+            let mut v = vec![r.clone() * scale(C::T::one() / beta)];
+            let mut h = vec![vec![C::T::zero(); m]; m + 1];
+            let mut cs = vec![C::T::zero(); m];
+            let mut sn = vec![C::T::zero(); m];
+            let mut g = vec![C::T::zero(); m + 1];
+            g[0] = beta;
+            let mut k = 0;
+            for j in 0..m {
+                let mut z = v[j].clone();
+                self.preconditioner.solve_in_place(&mut z)?;
+                self.jac_mul(&z, &mut w);
+                for (i, v_i) in v.iter().enumerate() {
+                    h[i][j] = dot(&w, v_i);
+                    w.axpy(-h[i][j], v_i, C::T::one());
+                }
+                h[j + 1][j] = w.norm();
+
+                for i in 0..j {
+                    let temp = cs[i] * h[i][j] + sn[i] * h[i + 1][j];
+                    h[i + 1][j] = -sn[i] * h[i][j] + cs[i] * h[i + 1][j];
+                    h[i][j] = temp;
+                }
+                let denom = (h[j][j] * h[j][j] + h[j + 1][j] * h[j + 1][j]).pow(C::T::from(0.5));
+                k = j + 1;
+                niter += 1;
+                if denom == C::T::zero() {
+                    // the krylov space is invariant, and the solution is in the current space
+                    break;
+                }
+                cs[j] = h[j][j] / denom;
+                sn[j] = h[j + 1][j] / denom;
+                let h_next = h[j + 1][j];
+                h[j][j] = denom;
+                h[j + 1][j] = C::T::zero();
+                g[j + 1] = -sn[j] * g[j];
+                g[j] = cs[j] * g[j];
+
+                // a subdiagonal entry that is zero to within rounding error means the krylov space is invariant
+                let breakdown = h_next <= C::T::EPSILON * denom;
+                if abs(g[j + 1]) <= tol || niter >= self.max_iter || breakdown {
+                    break;
+                }
+                v.push(w.clone() * scale(C::T::one() / h_next));
+            }
+
+            // solve the upper triangular system H y = g, and update x += P^{-1} V y
+            let mut y = vec![C::T::zero(); k];
+            for i in (0..k).rev() {
+                let sum = ((i + 1)..k).fold(g[i], |acc, l| acc - h[i][l] * y[l]);
+                y[i] = if h[i][i] == C::T::zero() {
+                    C::T::zero()
+                } else {
+                    sum / h[i][i]
+                };
+            }
+            let mut u = C::V::zeros(n);
+            for (y_i, v_i) in y.iter().zip(v.iter()) {
+                u.axpy(*y_i, v_i, C::T::one());
+            }
+            self.preconditioner.solve_in_place(&mut u)?;
+            x += &u;
+
+            // true residual r = b - J x
+            self.jac_mul(&x, &mut r);
+            r.axpy(C::T::one(), b, -C::T::one());
+            if niter >= self.max_iter {
+                if r.norm() <= tol {
+                    break;
+                }
+                self.niter.set(niter);
+                return Err(anyhow!(
+                    "GMRES did not converge in {} iterations, residual norm {}",
+                    niter,
+                    r.norm()
+                ));
+            }
+        }
+        self.niter.set(niter);
+        b.copy_from(&x);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{
+        linear_solver::{
+            tests::{linear_problem, test_linear_solver},
+            LinearSolveSolution,
+        },
+        op::closure::Closure,
+        LinearSolver, NalgebraLU, SolverProblem, Vector,
+    };
+
+    use super::Gmres;
+
+    type MCpu = nalgebra::DMatrix<f64>;
+    type VCpu = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_gmres() {
+        let (p, solns) = linear_problem::<MCpu>();
+        test_linear_solver(Gmres::new(), p, solns);
+    }
+
+    #[test]
+    fn test_gmres_nonsymmetric_restarted() {
+        // a nonsymmetric tridiagonal matrix
+        let n = 20;
+        let a = MCpu::from_fn(n, n, |i, j| {
+            if i == j {
+                4.0
+            } else if j == i + 1 {
+                -1.0
+            } else if i == j + 1 {
+                -2.0
+            } else {
+                0.0
+            }
+        });
+        let x = VCpu::from_fn(n, |i, _| (i + 1) as f64);
+        let b = &a * &x;
+        let a1 = a.clone();
+        let op = Closure::<MCpu, _, _>::new(
+            move |x: &VCpu, _p: &VCpu, _t, y: &mut VCpu| y.gemv(1.0, &a, x, 0.0),
+            move |_x: &VCpu, _p: &VCpu, _t, v: &VCpu, y: &mut VCpu| y.gemv(1.0, &a1, v, 0.0),
+            n,
+            n,
+            Rc::new(VCpu::zeros(0)),
+        );
+        let problem = SolverProblem::new(Rc::new(op), Rc::new(VCpu::from_element(n, 1e-10)), 1e-10);
+
+        let mut s = Gmres::new();
+        s.set_restart(5);
+        s.set_tolerance(1e-10);
+        test_linear_solver(
+            s,
+            problem.clone(),
+            vec![LinearSolveSolution::new(b.clone(), x.clone())],
+        );
+
+        // with an exact preconditioner, gmres converges in a single iteration
+        let mut s = Gmres::with_preconditioner(NalgebraLU::default());
+        s.set_tolerance(1e-10);
+        s.set_problem(&problem);
+        s.set_linearisation(&x, 0.0);
+        let soln = s.solve(&b).unwrap();
+        soln.assert_eq_st(&x, 1e-8);
+        assert_eq!(s.niter(), 1);
+    }
+}
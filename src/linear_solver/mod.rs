@@ -10,6 +10,8 @@ pub mod faer;
 #[cfg(feature = "sundials")]
 pub mod sundials;
 
+pub mod gmres;
+
 pub use faer::lu::LU as FaerLU;
 pub use nalgebra::lu::LU as NalgebraLU;
 
@@ -59,7 +61,8 @@ pub mod tests {
 
     use super::LinearSolveSolution;
 
-    fn linear_problem<M: DenseMatrix + 'static>() -> (
+    #[allow(clippy::type_complexity)]
+    pub fn linear_problem<M: DenseMatrix + 'static>() -> (
         SolverProblem<impl NonLinearOp<M = M, V = M::V, T = M::T>>,
         Vec<LinearSolveSolution<M::V>>,
     ) {
@@ -1,4 +1,9 @@
-use crate::{LinearSolver, NonLinearOp};
+use std::rc::Rc;
+
+use anyhow::Result;
+use num_traits::{One, Zero};
+
+use crate::{op::closure::Closure, LinearSolver, NonLinearOp, SolverProblem, Vector};
 
 use super::Matrix;
 
@@ -7,4 +12,33 @@ pub trait DefaultSolver: Matrix {
     fn default_solver<C: NonLinearOp<M = Self, V = Self::V, T = Self::T>>() -> Self::LS<C> {
         Self::LS::default()
     }
+
+    /// Solve the linear system `self x = b` in place using the [Self::default_solver], this is intended for the small systems that
+    /// arise within the solvers (e.g. least squares problems or bordered systems) rather than the jacobian of the equations.
+    fn solve_in_place(&self, b: &mut Self::V) -> Result<()> {
+        self.solve_many_in_place(std::slice::from_mut(b))
+    }
+
+    /// Solve the linear systems `self x = b` in place for each of the right-hand sides `b` (see [Self::solve_in_place]), factorising the
+    /// matrix only once.
+    fn solve_many_in_place(&self, bs: &mut [Self::V]) -> Result<()> {
+        let n = self.nrows();
+        let a = self.clone();
+        let jac = self.clone();
+        let op = Rc::new(Closure::<Self, _, _>::new(
+            move |x, _p, _t, y| a.gemv(Self::T::one(), x, Self::T::zero(), y),
+            move |_x, _p, _t, v, y| jac.gemv(Self::T::one(), v, Self::T::zero(), y),
+            n,
+            n,
+            Rc::new(Self::V::zeros(0)),
+        ));
+        let problem = SolverProblem::new(op, Rc::new(Self::V::zeros(n)), Self::T::zero());
+        let mut solver = Self::default_solver();
+        solver.set_problem(&problem);
+        solver.set_linearisation(&Self::V::zeros(n), Self::T::zero());
+        for b in bs.iter_mut() {
+            solver.solve_in_place(b)?;
+        }
+        Ok(())
+    }
 }
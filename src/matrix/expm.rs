@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Result};
+use num_traits::{abs, One, Pow, Zero};
+
+use crate::{scale, IndexType, Scalar, Vector};
+
+use super::{default_solver::DefaultSolver, DenseMatrix};
+
+// coefficients of the degree 13 Pade approximant to exp, and the largest 1-norm for which it is accurate to double precision (Higham 2005)
+const PADE13: [f64; 14] = [
+    64764752532480000.0,
+    32382376266240000.0,
+    7771770303897600.0,
+    1187353796428800.0,
+    129060195264000.0,
+    10559470521600.0,
+    670442572800.0,
+    33522128640.0,
+    1323241920.0,
+    40840800.0,
+    960960.0,
+    16380.0,
+    182.0,
+    1.0,
+];
+const THETA13: f64 = 5.371920351148152;
+
+/// The matrix exponential `exp(a)` of a small dense square matrix, using the scaling and squaring algorithm with a degree 13 Pade approximant.
+///
+/// Returns an error if `a` has non-finite entries.
+///
+/// Higham, N. J. (2005). The scaling and squaring method for the matrix exponential revisited. SIAM Journal on Matrix Analysis and Applications, 26(4), 1179-1193.
+pub fn expm<M: DenseMatrix + DefaultSolver>(a: &M) -> Result<M> {
+    let n = a.nrows();
+    assert_eq!(n, a.ncols(), "expm requires a square matrix");
+    if n == 0 {
+        return Ok(a.clone());
+    }
+
+    // scale a by 2^{-s} so that its norm is less than theta13
+    let is_finite = (0..n).all(|j| (0..n).all(|i| abs(a[(i, j)]) < M::T::INFINITY));
+    if !is_finite {
+        return Err(anyhow!(
+            "Cannot compute the exponential of a non-finite matrix"
+        ));
+    }
+    let norm = norm1(a);
+    let theta = M::T::from(THETA13);
+    let s = if norm > theta {
+        let ratio: f64 = (norm / theta).into();
+        ratio.log2().ceil() as i32
+    } else {
+        0
+    };
+    let a = a.clone() * scale(M::T::from(2.0).pow(-s));
+
+    let b = PADE13.map(M::T::from);
+    let ident = identity::<M>(n);
+    let a2 = a.mat_mul(&a);
+    let a4 = a2.mat_mul(&a2);
+    let a6 = a4.mat_mul(&a2);
+    let mut u =
+        a6.clone() * scale(b[13]) + &(a4.clone() * scale(b[11])) + &(a2.clone() * scale(b[9]));
+    u = a6.mat_mul(&u)
+        + &(a6.clone() * scale(b[7]))
+        + &(a4.clone() * scale(b[5]))
+        + &(a2.clone() * scale(b[3]))
+        + &(ident.clone() * scale(b[1]));
+    let u = a.mat_mul(&u);
+    let mut v =
+        a6.clone() * scale(b[12]) + &(a4.clone() * scale(b[10])) + &(a2.clone() * scale(b[8]));
+    v = a6.mat_mul(&v)
+        + &(a6 * scale(b[6]))
+        + &(a4 * scale(b[4]))
+        + &(a2 * scale(b[2]))
+        + &(ident * scale(b[0]));
+
+    // r = (v - u)^{-1} (v + u), the denominator is well conditioned for norms below theta13
+    let denominator = v.clone() - &u;
+    let mut r = v + &u;
+    let mut columns = (0..n)
+        .map(|j| M::V::from_vec((0..n).map(|i| r[(i, j)]).collect()))
+        .collect::<Vec<_>>();
+    denominator.solve_many_in_place(&mut columns)?;
+    for (j, col) in columns.iter().enumerate() {
+        for i in 0..n {
+            r[(i, j)] = col[i];
+        }
+    }
+    for _ in 0..s {
+        r = r.mat_mul(&r);
+    }
+    Ok(r)
+}
+
+/// The actions `phi_k(a) e_1` of the phi-functions `phi_0(z) = exp(z)`, `phi_{k+1}(z) = (phi_k(z) - 1 / k!) / z` on the first unit vector,
+/// for `k = 0..=p`. These are found from the exponential of the augmented matrix `[[a, e_1, 0], [0, 0, I_{p-1}], [0, 0, 0]]` (Sidje 1998).
+///
+/// Sidje, R. B. (1998). Expokit: a software package for computing matrix exponentials. ACM Transactions on Mathematical Software, 24(1), 130-156.
+pub fn phi_e1<M: DenseMatrix + DefaultSolver>(a: &M, p: IndexType) -> Result<Vec<M::V>> {
+    let m = a.nrows();
+    let mut aug = M::zeros(m + p, m + p);
+    for j in 0..m {
+        for i in 0..m {
+            aug[(i, j)] = a[(i, j)];
+        }
+    }
+    if p > 0 {
+        aug[(0, m)] = M::T::one();
+    }
+    for k in 1..p {
+        aug[(m + k - 1, m + k)] = M::T::one();
+    }
+    let exp = expm(&aug)?;
+    Ok((0..=p)
+        .map(|k| {
+            let col = if k == 0 { 0 } else { m + k - 1 };
+            M::V::from_vec((0..m).map(|i| exp[(i, col)]).collect())
+        })
+        .collect())
+}
+
+/// The matrix exponential `exp(h a)` and its integral `int_0^h exp(s a) ds = h phi_1(h a)` of a small dense square matrix, found from the
+/// exponential of the augmented matrix `[[h a, h I], [0, 0]]`. These give the exact solution `y(t + h) = exp(h a) y(t) + (int_0^h exp(s a) ds) b`
+/// of the linear equations `y' = a y + b` with a constant input `b`.
+pub fn expm_integral<M: DenseMatrix + DefaultSolver>(a: &M, h: M::T) -> Result<(M, M)> {
+    let n = a.nrows();
+    let mut aug = M::zeros(2 * n, 2 * n);
+    for j in 0..n {
+        for i in 0..n {
+            aug[(i, j)] = h * a[(i, j)];
+        }
+        aug[(j, n + j)] = h;
+    }
+    let exp = expm(&aug)?;
+    let mut exp_ha = M::zeros(n, n);
+    let mut integral = M::zeros(n, n);
+    for j in 0..n {
+        for i in 0..n {
+            exp_ha[(i, j)] = exp[(i, j)];
+            integral[(i, j)] = exp[(i, n + j)];
+        }
+    }
+    Ok((exp_ha, integral))
+}
+
+// the maximum absolute column sum
+fn norm1<M: DenseMatrix>(a: &M) -> M::T {
+    (0..a.ncols()).fold(M::T::zero(), |acc, j| {
+        let sum = (0..a.nrows()).fold(M::T::zero(), |s, i| s + abs(a[(i, j)]));
+        if sum > acc {
+            sum
+        } else {
+            acc
+        }
+    })
+}
+
+fn identity<M: DenseMatrix>(n: IndexType) -> M {
+    M::from_diagonal(&M::V::from_element(n, M::T::one()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expm, expm_integral, phi_e1};
+    use crate::DenseMatrix;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn test_expm_rotation() {
+        // exp([[0, t], [-t, 0]]) is a rotation by t
+        for t in [0.1, 1.0, 10.0, 100.0] {
+            let a = M::from_vec(2, 2, vec![0.0, -t, t, 0.0]);
+            let e = expm(&a).unwrap();
+            let expect = M::from_vec(2, 2, vec![t.cos(), -t.sin(), t.sin(), t.cos()]);
+            for i in 0..2 {
+                for j in 0..2 {
+                    assert!((e[(i, j)] - expect[(i, j)]).abs() < 1e-12 * t.max(1.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_expm_triangular() {
+        // exp([[a, 1], [0, b]]) = [[e^a, (e^a - e^b) / (a - b)], [0, e^b]]
+        let (a, b) = (-1.0, -50.0f64);
+        let m = M::from_vec(2, 2, vec![a, 0.0, 1.0, b]);
+        let e = expm(&m).unwrap();
+        assert!((e[(0, 0)] - a.exp()).abs() < 1e-14);
+        assert!((e[(1, 1)] - b.exp()).abs() < 1e-14);
+        assert!((e[(0, 1)] - (a.exp() - b.exp()) / (a - b)).abs() < 1e-14);
+        assert_eq!(e[(1, 0)], 0.0);
+        assert_eq!(
+            DenseMatrix::expm(&M::zeros(3, 3)).unwrap(),
+            M::identity(3, 3)
+        );
+        assert!(expm(&M::from_element(2, 2, f64::NAN)).is_err());
+        assert!(expm(&M::from_element(2, 2, f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn test_phi_e1() {
+        // for a scalar z, phi_1 = (e^z - 1) / z, phi_2 = (e^z - 1 - z) / z^2, phi_3 = (e^z - 1 - z - z^2 / 2) / z^3
+        let z = -2.0f64;
+        let phi = phi_e1(&M::from_element(1, 1, z), 3).unwrap();
+        let expect = [
+            z.exp(),
+            (z.exp() - 1.0) / z,
+            (z.exp() - 1.0 - z) / z.powi(2),
+            (z.exp() - 1.0 - z - 0.5 * z * z) / z.powi(3),
+        ];
+        for (phi_k, expect_k) in phi.iter().zip(expect.iter()) {
+            assert_eq!(phi_k.len(), 1);
+            assert!((phi_k[0] - expect_k).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn test_expm_integral() {
+        // for a = [[-1, 0], [0, -2]], int_0^h exp(s a) ds = diag(1 - e^{-h}, (1 - e^{-2h}) / 2)
+        let a = M::from_vec(2, 2, vec![-1.0, 0.0, 0.0, -2.0]);
+        let h = 0.3f64;
+        let (e, integral) = expm_integral(&a, h).unwrap();
+        assert!((e[(0, 0)] - (-h).exp()).abs() < 1e-15);
+        assert!((e[(1, 1)] - (-2.0 * h).exp()).abs() < 1e-15);
+        assert!((integral[(0, 0)] - (1.0 - (-h).exp())).abs() < 1e-15);
+        assert!((integral[(1, 1)] - 0.5 * (1.0 - (-2.0 * h).exp())).abs() < 1e-15);
+        assert_eq!(integral[(0, 1)], 0.0);
+    }
+}
@@ -13,6 +13,7 @@ mod dense_nalgebra_serial;
 mod dense_faer_serial;
 
 pub mod default_solver;
+pub mod expm;
 mod sparse_serial;
 
 #[cfg(feature = "sundials")]
@@ -26,7 +27,7 @@ pub trait MatrixCommon: Sized + Debug {
     fn ncols(&self) -> IndexType;
 }
 
-impl<'a, M> MatrixCommon for &'a M
+impl<M> MatrixCommon for &M
 where
     M: MatrixCommon,
 {
@@ -41,7 +42,7 @@ where
     }
 }
 
-impl<'a, M> MatrixCommon for &'a mut M
+impl<M> MatrixCommon for &mut M
 where
     M: MatrixCommon,
 {
@@ -126,7 +127,12 @@ pub trait MatrixSparsity: Clone {
         indices: Vec<(IndexType, IndexType)>,
     ) -> Result<Self>;
     fn indices(&self) -> Vec<(IndexType, IndexType)>;
-    fn union(&self, other: &Self) -> Result<Self>;
+    fn union(&self, other: &Self) -> Result<Self> {
+        Ok(self.union_with_index(other)?.0)
+    }
+    /// Returns the union of the sparsity patterns of `self` and `other`, along with the position of each non-zero of `self`
+    /// and of `other` in the non-zeros of the union (both empty for dense matrices). See [Matrix::scale_add_and_assign_mapped].
+    fn union_with_index(&self, other: &Self) -> Result<(Self, Vec<IndexType>, Vec<IndexType>)>;
     fn new_diagonal(n: IndexType) -> Self;
     fn get_index(&self, rows: &[IndexType], cols: &[IndexType]) -> Self::Index;
 }
@@ -181,13 +187,13 @@ impl MatrixSparsity for Dense {
         Vec::new()
     }
 
-    fn union(&self, other: &Self) -> Result<Self> {
+    fn union_with_index(&self, other: &Self) -> Result<(Self, Vec<IndexType>, Vec<IndexType>)> {
         if self.nrows != other.nrows || self.ncols != other.ncols {
             return Err(anyhow::anyhow!(
                 "Cannot union matrices with different shapes"
             ));
         }
-        Ok(self.clone())
+        Ok((self.clone(), Vec::new(), Vec::new()))
     }
 
     fn new_diagonal(n: IndexType) -> Self {
@@ -241,6 +247,21 @@ pub trait Matrix:
     /// Panics if the sparsity of self, x, and y do not match (i.e. sparsity of self must be the union of the sparsity of x and y)
     fn scale_add_and_assign(&mut self, x: &Self, beta: Self::T, y: &Self);
 
+    /// Perform the assignment self = x + beta * y where x and y are matrices with different sparsity patterns and beta is a scalar.
+    /// The sparsity of self must be the union of the sparsity of x and y, and `x_index` and `y_index` give the position of each non-zero
+    /// of x and y in the non-zeros of self (see [MatrixSparsity::union_with_index]), so the assignment is a single pass over the non-zeros.
+    /// The default implementation ignores the indices and calls [Self::scale_add_and_assign].
+    fn scale_add_and_assign_mapped(
+        &mut self,
+        x: &Self,
+        _x_index: &[IndexType],
+        beta: Self::T,
+        y: &Self,
+        _y_index: &[IndexType],
+    ) {
+        self.scale_add_and_assign(x, beta, y);
+    }
+
     /// Create a new matrix from a vector of triplets (i, j, value) where i and j are the row and column indices of the value
     fn try_from_triplets(
         nrows: IndexType,
@@ -296,4 +317,12 @@ pub trait DenseMatrix:
         ret.gemm(Self::T::one(), self, b, Self::T::zero());
         ret
     }
+
+    /// The matrix exponential of a small square matrix, see [expm::expm]
+    fn expm(&self) -> Result<Self>
+    where
+        Self: default_solver::DefaultSolver,
+    {
+        expm::expm(self)
+    }
 }
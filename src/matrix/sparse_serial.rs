@@ -1,4 +1,4 @@
-use std::{collections::HashSet, ops::Mul};
+use std::ops::Mul;
 
 use anyhow::Result;
 use nalgebra::DVector;
@@ -27,6 +27,13 @@ impl<T: Scalar> Mul<Scale<T>> for CscMatrix<T> {
     }
 }
 
+impl<T: Scalar> Mul<Scale<T>> for &CscMatrix<T> {
+    type Output = CscMatrix<T>;
+    fn mul(self, rhs: Scale<T>) -> Self::Output {
+        self * rhs.value()
+    }
+}
+
 impl MatrixSparsity for SparsityPattern {
     type Index = Vec<IndexType>;
 
@@ -68,33 +75,49 @@ impl MatrixSparsity for SparsityPattern {
         indices
     }
 
-    fn union(&self, other: &Self) -> Result<Self> {
-        let max_nnz = self.nnz().max(other.nnz());
-        let min_nnz = self.nnz().min(other.nnz());
-        let mut minor_indices = Vec::with_capacity(self.nnz() + max_nnz - min_nnz);
-        let mut major_offsets = Vec::with_capacity(self.major_dim());
+    fn union_with_index(&self, other: &Self) -> Result<(Self, Vec<IndexType>, Vec<IndexType>)> {
+        if self.major_dim() != other.major_dim() || self.minor_dim() != other.minor_dim() {
+            return Err(anyhow::anyhow!(
+                "Cannot union matrices with different shapes"
+            ));
+        }
+        let mut minor_indices = Vec::with_capacity(self.nnz().max(other.nnz()));
+        let mut major_offsets = Vec::with_capacity(self.major_dim() + 1);
+        let mut self_index = Vec::with_capacity(self.nnz());
+        let mut other_index = Vec::with_capacity(other.nnz());
 
-        // loop through columns, calculate union of rows
-        let mut offset = 0;
+        // loop through columns, merging the (sorted) rows of each
         for j in 0..self.major_dim() {
-            let lane = self.lane(j);
-            let other_lane = other.lane(j);
-            let set: HashSet<usize> =
-                HashSet::from_iter(lane.iter().chain(other_lane.iter()).cloned());
-            let mut set = set.into_iter().collect::<Vec<_>>();
-
-            major_offsets.push(offset);
-            offset += set.len();
-
-            minor_indices.append(&mut set);
+            major_offsets.push(minor_indices.len());
+            let (lane, other_lane) = (self.lane(j), other.lane(j));
+            let (mut a, mut b) = (0, 0);
+            while a < lane.len() || b < other_lane.len() {
+                let i = match (lane.get(a), other_lane.get(b)) {
+                    (Some(&ia), Some(&ib)) => ia.min(ib),
+                    (Some(&ia), None) => ia,
+                    (None, Some(&ib)) => ib,
+                    (None, None) => unreachable!(),
+                };
+                if lane.get(a) == Some(&i) {
+                    self_index.push(minor_indices.len());
+                    a += 1;
+                }
+                if other_lane.get(b) == Some(&i) {
+                    other_index.push(minor_indices.len());
+                    b += 1;
+                }
+                minor_indices.push(i);
+            }
         }
-        SparsityPattern::try_from_offsets_and_indices(
+        major_offsets.push(minor_indices.len());
+        let union = SparsityPattern::try_from_offsets_and_indices(
             self.major_dim(),
             self.minor_dim(),
             major_offsets,
             minor_indices,
         )
-        .map_err(anyhow::Error::new)
+        .map_err(anyhow::Error::new)?;
+        Ok((union, self_index, other_index))
     }
 
     fn try_from_indices(
@@ -106,22 +129,25 @@ impl MatrixSparsity for SparsityPattern {
         let major_dim = ncols;
         let minor_dim = nrows;
 
-        // sort indices by major index
+        // sort indices by major index, then minor index
         let mut indices = indices;
-        indices.sort_unstable_by_key(|&(_, j)| j);
+        indices.sort_unstable_by_key(|&(i, j)| (j, i));
+        indices.dedup();
 
         // split into major offsets and minor indices
-        let mut curr_col = 0;
         let mut major_offsets = Vec::with_capacity(major_dim + 1);
         let mut minor_indices = Vec::with_capacity(indices.len());
-        for (i, j) in indices {
-            while curr_col < j {
-                major_offsets.push(minor_indices.len());
-                curr_col += 1;
+        let mut indices = indices.into_iter().peekable();
+        for j in 0..major_dim {
+            major_offsets.push(minor_indices.len());
+            while let Some((i, _j)) = indices.next_if(|&(_i, col)| col == j) {
+                minor_indices.push(i);
             }
-            minor_indices.push(i);
         }
         major_offsets.push(minor_indices.len());
+        if indices.next().is_some() {
+            return Err(anyhow::anyhow!("Index out of bounds"));
+        }
 
         SparsityPattern::try_from_offsets_and_indices(
             major_dim,
@@ -181,8 +207,7 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
         self.clone_from(other);
     }
     fn gemv(&self, alpha: Self::T, x: &Self::V, beta: Self::T, y: &mut Self::V) {
-        let mut tmp = self * x;
-        tmp *= alpha;
+        let tmp = self * x;
         y.axpy(alpha, &tmp, beta);
     }
 
@@ -217,6 +242,25 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
     fn scale_add_and_assign(&mut self, x: &Self, beta: Self::T, y: &Self) {
         *self = x + y * beta;
     }
+    fn scale_add_and_assign_mapped(
+        &mut self,
+        x: &Self,
+        x_index: &[IndexType],
+        beta: Self::T,
+        y: &Self,
+        y_index: &[IndexType],
+    ) {
+        assert_eq!(x_index.len(), x.nnz());
+        assert_eq!(y_index.len(), y.nnz());
+        let values = self.values_mut();
+        values.iter_mut().for_each(|v| *v = T::zero());
+        for (&i, &xv) in x_index.iter().zip(x.values()) {
+            values[i] += xv;
+        }
+        for (&i, &yv) in y_index.iter().zip(y.values()) {
+            values[i] += beta * yv;
+        }
+    }
     fn new_from_sparsity(
         nrows: IndexType,
         ncols: IndexType,
@@ -230,3 +274,60 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+    use nalgebra_sparse::{pattern::SparsityPattern, CscMatrix};
+
+    use crate::{Matrix, MatrixSparsity};
+
+    #[test]
+    fn test_union_and_scale_add_mapped() {
+        // x = |1 0 0|, y = |0 0 0|
+        //     |0 2 0|      |3 4 0|
+        //     |0 0 0|      |0 0 5|
+        let x = CscMatrix::try_from_triplets(3, 3, vec![(1, 1, 2.0), (0, 0, 1.0)]).unwrap();
+        let y = CscMatrix::try_from_triplets(3, 3, vec![(1, 0, 3.0), (1, 1, 4.0), (2, 2, 5.0)])
+            .unwrap();
+        let (union, x_index, y_index) = x.pattern().union_with_index(y.pattern()).unwrap();
+        assert_eq!(union.indices(), vec![(0, 0), (1, 0), (1, 1), (2, 2)]);
+        assert_eq!(x_index, vec![0, 2]);
+        assert_eq!(y_index, vec![1, 2, 3]);
+
+        let mut z = CscMatrix::new_from_sparsity(3, 3, Some(&union));
+        z.scale_add_and_assign_mapped(&x, &x_index, 2.0, &y, &y_index);
+        assert_eq!(z.values(), &[1.0, 6.0, 10.0, 10.0]);
+    }
+
+    #[test]
+    fn test_try_from_indices() {
+        // rows out of order within a column and duplicated indices give the sorted, deduplicated pattern
+        let pattern =
+            SparsityPattern::try_from_indices(3, 3, vec![(2, 2), (1, 1), (1, 0), (0, 0), (1, 1)])
+                .unwrap();
+        assert_eq!(pattern.indices(), vec![(0, 0), (1, 0), (1, 1), (2, 2)]);
+
+        // trailing empty columns
+        let pattern = SparsityPattern::try_from_indices(3, 3, vec![(1, 0), (0, 0)]).unwrap();
+        assert_eq!(pattern.indices(), vec![(0, 0), (1, 0)]);
+        assert_eq!(pattern.major_offsets(), &[0, 2, 2, 2]);
+
+        // out of bounds
+        assert!(SparsityPattern::try_from_indices(3, 3, vec![(0, 3)]).is_err());
+        assert!(SparsityPattern::try_from_indices(3, 3, vec![(3, 0)]).is_err());
+    }
+
+    #[test]
+    fn test_gemv() {
+        // a = |1 0|
+        //     |3 2|
+        let a = CscMatrix::try_from_triplets(2, 2, vec![(0, 0, 1.0), (1, 0, 3.0), (1, 1, 2.0)])
+            .unwrap();
+        let x = DVector::from_vec(vec![1.0, 2.0]);
+        let mut y = DVector::from_vec(vec![1.0, -1.0]);
+        // y = 2 a x + 3 y, with alpha applied once
+        a.gemv(2.0, &x, 3.0, &mut y);
+        assert_eq!(y, DVector::from_vec(vec![5.0, 11.0]));
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use crate::{
+    matrix::default_solver::DefaultSolver, op::NonLinearOp, scalar::scale, NonLinearSolver,
+    SolverProblem, Vector,
+};
+use anyhow::{anyhow, Result};
+use nalgebra::{DMatrix, DVector};
+use num_traits::{One, Zero};
+
+use super::{Convergence, ConvergenceStatus};
+
+/// A fixed-point solver with Anderson acceleration \[1\].
+///
+/// The problem `F(x) = 0` is solved as the fixed point of `G(x) = x - F(x)`, and at each iteration the new iterate is the combination
+/// of the last `memory` evaluations of `G` that minimises the (linearised) residual. No jacobian is required, so this is suitable for
+/// non-stiff problems where the fixed point iteration is contractive, such as the implicit stages of an ODE solver with a small step size.
+///
+/// \[1\] Walker, H. F., & Ni, P. (2011). Anderson acceleration for fixed-point iterations. SIAM Journal on Numerical Analysis, 49(4), 1715-1735.
+pub struct AndersonNonlinearSolver<C: NonLinearOp> {
+    convergence: Option<Convergence<C>>,
+    problem: Option<SolverProblem<C>>,
+    max_iter: usize,
+    niter: usize,
+    memory: usize,
+}
+
+impl<C: NonLinearOp> AndersonNonlinearSolver<C> {
+    /// Create a new solver that uses the last `memory` iterates for the acceleration (`memory = 0` gives the plain fixed point iteration).
+    pub fn new(memory: usize) -> Self {
+        Self {
+            problem: None,
+            convergence: None,
+            max_iter: 100,
+            niter: 0,
+            memory,
+        }
+    }
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+}
+
+impl<C: NonLinearOp> Default for AndersonNonlinearSolver<C> {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl<C: NonLinearOp> NonLinearSolver<C> for AndersonNonlinearSolver<C> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("AndersonNonlinearSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        let problem = self.problem.as_ref().unwrap();
+        self.convergence = Some(Convergence::new(problem, self.max_iter));
+    }
+
+    // no jacobian is used
+    fn reset_jacobian(&mut self, _x: &C::V, _t: C::T) {}
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<()> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("AndersonNonlinearSolver::solve() called before set_problem");
+        }
+        if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
+            panic!("AndersonNonlinearSolver::solve() called with state of wrong size, expected {}, got {}", self.problem.as_ref().unwrap().f.nstates(), xn.len());
+        }
+        let dot = |a: &C::V, b: &C::V| a.binary_fold(b, C::T::zero(), |acc, x, y, _| acc + x * y);
+        let convergence = self.convergence.as_mut().unwrap();
+        let problem = self.problem.as_ref().unwrap();
+        convergence.reset(xn);
+        self.niter = 0;
+
+        // the residual of the fixed point iteration is r = G(x) - x = -F(x)
+        let mut r = problem.f.call(xn, t);
+        r *= scale(-C::T::one());
+        let mut g = xn.clone() + &r;
+        let mut delta_r: VecDeque<C::V> = VecDeque::with_capacity(self.memory);
+        let mut delta_g: VecDeque<C::V> = VecDeque::with_capacity(self.memory);
+        loop {
+            self.niter += 1;
+
+            // x_{k+1} = g_k - sum_i gamma_i delta_g_i, where gamma minimises || r_k - sum_i gamma_i delta_r_i ||
+            let mut x_new = g.clone();
+            if !delta_r.is_empty() {
+                let m = delta_r.len();
+                let mut a = DMatrix::<C::T>::zeros(m, m);
+                let mut gamma = DVector::<C::T>::zeros(m);
+                for i in 0..m {
+                    for j in 0..=i {
+                        a[(i, j)] = dot(&delta_r[i], &delta_r[j]);
+                        a[(j, i)] = a[(i, j)];
+                    }
+                    gamma[i] = dot(&delta_r[i], &r);
+                }
+                // a small multiple of the identity regularises nearly dependent columns, if the normal equations are still singular
+                // then the acceleration is skipped for this iteration
+                let reg = C::T::from(1e-12) * a.trace() / C::T::from(m as f64);
+                for i in 0..m {
+                    a[(i, i)] += reg;
+                }
+                if a.solve_in_place(&mut gamma).is_ok() {
+                    for (gamma_i, delta_g_i) in gamma.iter().zip(delta_g.iter()) {
+                        x_new.axpy(-*gamma_i, delta_g_i, C::T::one());
+                    }
+                }
+            }
+
+            let mut step = x_new.clone() - &*xn;
+            xn.copy_from(&x_new);
+            let res = convergence.check_new_iteration(&mut step);
+            match res {
+                ConvergenceStatus::Continue => (),
+                ConvergenceStatus::Converged => return Ok(()),
+                ConvergenceStatus::Diverged => break,
+                ConvergenceStatus::MaximumIterations => break,
+            }
+
+            let mut r_new = problem.f.call(xn, t);
+            r_new *= scale(-C::T::one());
+            let g_new = xn.clone() + &r_new;
+            if self.memory > 0 {
+                if delta_r.len() == self.memory {
+                    delta_r.pop_front();
+                    delta_g.pop_front();
+                }
+                delta_r.push_back(r_new.clone() - &r);
+                delta_g.push_back(g_new.clone() - &g);
+            }
+            r = r_new;
+            g = g_new;
+        }
+        Err(anyhow!("Anderson iteration did not converge"))
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{op::NonLinearOp, scalar::scale, LinearSolver, NonLinearSolver, SolverProblem, Vector};
+use anyhow::{anyhow, Result};
+use num_traits::{One, Zero};
+
+use super::{Convergence, ConvergenceStatus};
+
+/// The maximum number of Broyden updates stored by [BroydenNonlinearSolver] before the jacobian is refactorised.
+pub const MAX_BROYDEN_UPDATES: usize = 40;
+
+/// A quasi-Newton solver using Broyden's "good" update of the jacobian.
+///
+/// The jacobian is only factorised when [NonLinearSolver::reset_jacobian] is called (e.g. by the ODE solver when the step size changes),
+/// and the inverse of the jacobian is updated after each iteration using the rank-one Broyden updates, which are stored as pairs of
+/// vectors and applied using the Sherman-Morrison formula (as in algorithm `brsol` of \[1\]). The updates are kept between solves until
+/// the jacobian is reset, and once the number of stored updates reaches [MAX_BROYDEN_UPDATES] the jacobian is refactorised at the start of the next solve.
+/// This reduces the number of jacobian evaluations and factorisations for problems where these are expensive.
+///
+/// \[1\] Kelley, C. T. (1995). Iterative methods for linear and nonlinear equations. Society for Industrial and Applied Mathematics.
+pub struct BroydenNonlinearSolver<C: NonLinearOp, Ls: LinearSolver<C>> {
+    convergence: Option<Convergence<C>>,
+    linear_solver: Ls,
+    problem: Option<SolverProblem<C>>,
+    max_iter: usize,
+    niter: usize,
+    is_jacobian_set: bool,
+    updates: Vec<(C::V, C::V)>,
+}
+
+impl<C: NonLinearOp, Ls: LinearSolver<C>> BroydenNonlinearSolver<C, Ls> {
+    pub fn new(linear_solver: Ls) -> Self {
+        Self {
+            problem: None,
+            convergence: None,
+            linear_solver,
+            max_iter: 100,
+            niter: 0,
+            is_jacobian_set: false,
+            updates: Vec::new(),
+        }
+    }
+    pub fn linear_solver(&self) -> &Ls {
+        &self.linear_solver
+    }
+
+    // applies the stored updates to y = J_0^{-1} b, giving y = H b where H = (I + u_m s_m^T) ... (I + u_0 s_0^T) J_0^{-1}
+    fn apply_updates(updates: &[(C::V, C::V)], y: &mut C::V) {
+        for (u, s) in updates {
+            let alpha = s.binary_fold(y, C::T::zero(), |acc, x, y, _| acc + x * y);
+            y.axpy(alpha, u, C::T::one());
+        }
+    }
+}
+
+impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for BroydenNonlinearSolver<C, Ls> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("BroydenNonlinearSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        self.linear_solver.set_problem(problem);
+        let problem = self.problem.as_ref().unwrap();
+        self.convergence = Some(Convergence::new(problem, self.max_iter));
+        self.is_jacobian_set = false;
+        self.updates.clear();
+    }
+
+    fn reset_jacobian(&mut self, x: &C::V, t: C::T) {
+        self.linear_solver.set_linearisation(x, t);
+        self.is_jacobian_set = true;
+        self.updates.clear();
+    }
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<()> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("BroydenNonlinearSolver::solve() called before set_problem");
+        }
+        if !self.is_jacobian_set || self.updates.len() >= MAX_BROYDEN_UPDATES {
+            self.reset_jacobian(xn, t);
+        }
+        if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
+            panic!("BroydenNonlinearSolver::solve() called with state of wrong size, expected {}, got {}", self.problem.as_ref().unwrap().f.nstates(), xn.len());
+        }
+        let dot = |a: &C::V, b: &C::V| a.binary_fold(b, C::T::zero(), |acc, x, y, _| acc + x * y);
+        let convergence = self.convergence.as_mut().unwrap();
+        let problem = self.problem.as_ref().unwrap();
+        convergence.reset(xn);
+        self.niter = 0;
+
+        // hf = H F(x), where H is the current approximation to the inverse of the jacobian, and the step is s = -H F(x)
+        let mut hf = problem.f.call(xn, t);
+        self.linear_solver.solve_in_place(&mut hf)?;
+        Self::apply_updates(&self.updates, &mut hf);
+        let mut step = hf.clone() * scale(-C::T::one());
+        loop {
+            self.niter += 1;
+            *xn += &step;
+            let s = step.clone();
+
+            let res = convergence.check_new_iteration(&mut step);
+            match res {
+                ConvergenceStatus::Continue => (),
+                ConvergenceStatus::Converged => return Ok(()),
+                ConvergenceStatus::Diverged => break,
+                ConvergenceStatus::MaximumIterations => break,
+            }
+
+            problem.f.call_inplace(xn, t, &mut hf);
+            self.linear_solver.solve_in_place(&mut hf)?;
+            Self::apply_updates(&self.updates, &mut hf);
+
+            // the broyden update of the inverse is H <- (I + u s^T) H with u = (s - H y) / (s^T H y), where y = F(x + s) - F(x).
+            // Since H F(x) = -s this gives u = -H F(x + s) / (s^T (H F(x + s) + s))
+            let s_hf = dot(&s, &hf);
+            let denom = s_hf + dot(&s, &s);
+            if denom != C::T::zero() && self.updates.len() < MAX_BROYDEN_UPDATES {
+                let u = hf.clone() * scale(-C::T::one() / denom);
+                hf.axpy(s_hf, &u, C::T::one());
+                self.updates.push((u, s));
+            }
+            step.copy_from(&hf);
+            step *= scale(-C::T::one());
+        }
+        Err(anyhow!("Broyden iteration did not converge"))
+    }
+}
@@ -94,6 +94,16 @@ impl<C: Op> Convergence<C> {
         self.iter = 0;
         self.old_norm = None;
     }
+    // returns true if the norm of `dy`, scaled by the tolerances at the initial point, is less than the convergence tolerance
+    fn is_small(&self, dy: &C::V) -> bool {
+        let mut dy = dy.clone();
+        dy.component_div_assign(
+            self.scale
+                .as_ref()
+                .expect("Convergence::is_small() called before Convergence::reset()"),
+        );
+        dy.norm() < self.tol
+    }
     fn check_new_iteration(&mut self, dy: &mut C::V) -> ConvergenceStatus {
         if self.scale.is_none() {
             panic!("Convergence::check_new_iteration() called before Convergence::reset()");
@@ -136,13 +146,20 @@ impl<C: Op> Convergence<C> {
     }
 }
 
+pub mod anderson;
+pub mod broyden;
 pub mod newton;
+pub mod newton_krylov;
 pub mod root;
 
 //tests
 #[cfg(test)]
 pub mod tests {
-    use self::newton::NewtonNonlinearSolver;
+    use self::anderson::AndersonNonlinearSolver;
+    use self::broyden::BroydenNonlinearSolver;
+    use self::newton::{NewtonError, NewtonGlobalisation, NewtonNonlinearSolver};
+    use self::newton_krylov::NewtonKrylovNonlinearSolver;
+    use crate::linear_solver::gmres::Gmres;
     use crate::{
         linear_solver::nalgebra::lu::LU,
         matrix::MatrixCommon,
@@ -153,6 +170,7 @@ pub mod tests {
     use super::*;
     use num_traits::Zero;
 
+    #[allow(clippy::type_complexity)]
     pub fn get_square_problem<M>() -> (
         SolverProblem<impl NonLinearOp<M = M, V = M::V, T = M::T>>,
         Vec<NonLinearSolveSolution<M::V>>,
@@ -206,6 +224,7 @@ pub mod tests {
     }
 
     type MCpu = nalgebra::DMatrix<f64>;
+    type VCpu = nalgebra::DVector<f64>;
 
     #[test]
     fn test_newton_cpu_square() {
@@ -214,4 +233,107 @@ pub mod tests {
         let s = NewtonNonlinearSolver::new(lu);
         test_nonlinear_solver(s, prob, soln);
     }
+
+    #[test]
+    fn test_broyden_cpu_square() {
+        let lu = LU::default();
+        let (prob, soln) = get_square_problem::<MCpu>();
+        let s = BroydenNonlinearSolver::new(lu);
+        test_nonlinear_solver(s, prob, soln);
+    }
+
+    #[test]
+    fn test_newton_krylov_cpu_square() {
+        let (prob, soln) = get_square_problem::<MCpu>();
+        let s = NewtonKrylovNonlinearSolver::new(Gmres::new());
+        test_nonlinear_solver(s, prob, soln);
+    }
+
+    #[test]
+    fn test_anderson_cpu_cos() {
+        // 0 = x - cos(x), the fixed point iteration x = cos(x) is contractive
+        let op = Closure::<MCpu, _, _>::new(
+            |x: &VCpu, _p: &VCpu, _t, y: &mut VCpu| y[0] = x[0] - x[0].cos(),
+            |x: &VCpu, _p: &VCpu, _t, v: &VCpu, y: &mut VCpu| y[0] = v[0] * (1.0 + x[0].sin()),
+            1,
+            1,
+            Rc::new(VCpu::zeros(0)),
+        );
+        let problem = SolverProblem::new(Rc::new(op), Rc::new(VCpu::from_vec(vec![1e-8])), 1e-8);
+        let solns = vec![NonLinearSolveSolution::new(
+            VCpu::from_vec(vec![1.0]),
+            VCpu::from_vec(vec![0.7390851332151607]),
+        )];
+        let mut plain = AndersonNonlinearSolver::new(0);
+        plain.set_problem(&problem);
+        plain.solve(&solns[0].x0, 0.0).unwrap();
+        let mut accelerated = AndersonNonlinearSolver::default();
+        accelerated.set_problem(&problem);
+        accelerated.solve(&solns[0].x0, 0.0).unwrap();
+        assert!(accelerated.niter() < plain.niter());
+        test_nonlinear_solver(accelerated, problem, solns);
+    }
+
+    // 0 = atan(x), full newton steps diverge for |x0| > 1.39
+    fn get_atan_problem() -> SolverProblem<impl NonLinearOp<M = MCpu, V = VCpu, T = f64>> {
+        let op = Closure::new(
+            |x: &VCpu, _p: &VCpu, _t, y: &mut VCpu| y[0] = x[0].atan(),
+            |x: &VCpu, _p: &VCpu, _t, v: &VCpu, y: &mut VCpu| y[0] = v[0] / (1.0 + x[0] * x[0]),
+            1,
+            1,
+            Rc::new(VCpu::zeros(0)),
+        );
+        SolverProblem::new(Rc::new(op), Rc::new(VCpu::from_vec(vec![1e-8])), 1e-8)
+    }
+
+    #[test]
+    fn test_newton_globalisation() {
+        let x0 = VCpu::from_vec(vec![3.0]);
+        let problem = get_atan_problem();
+
+        let mut s = NewtonNonlinearSolver::new(LU::default());
+        s.set_problem(&problem);
+        let err = s.solve(&x0, 0.0).unwrap_err();
+        let err = err.downcast_ref::<NewtonError<f64>>().unwrap();
+        assert!(err.iterations.is_empty());
+        assert_eq!(err.niter, s.niter());
+
+        for globalisation in [
+            NewtonGlobalisation::line_search(),
+            NewtonGlobalisation::trust_region(),
+        ] {
+            let mut s = NewtonNonlinearSolver::new(LU::default());
+            s.set_globalisation(globalisation);
+            s.set_problem(&problem);
+            let x = s.solve(&x0, 0.0).unwrap();
+            assert!(x[0].abs() < 1e-8, "x = {}", x[0]);
+            assert_eq!(s.iterations().len(), s.niter());
+            assert!(s.iterations()[0].step_length < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_newton_bounds() {
+        // 0 = ln(x) - 1, the first newton step from x0 = 10 is negative, so the bounds are needed to keep x > 0
+        let op = Closure::new(
+            |x: &VCpu, _p: &VCpu, _t, y: &mut VCpu| {
+                assert!(x[0] > 0.0);
+                y[0] = x[0].ln() - 1.0;
+            },
+            |x: &VCpu, _p: &VCpu, _t, v: &VCpu, y: &mut VCpu| y[0] = v[0] / x[0],
+            1,
+            1,
+            Rc::new(VCpu::zeros(0)),
+        );
+        let problem = SolverProblem::new(Rc::new(op), Rc::new(VCpu::from_vec(vec![1e-8])), 1e-8);
+        let mut s = NewtonNonlinearSolver::new(LU::default());
+        s.set_globalisation(NewtonGlobalisation::line_search());
+        s.set_bounds(
+            VCpu::from_vec(vec![1e-3]),
+            VCpu::from_vec(vec![f64::INFINITY]),
+        );
+        s.set_problem(&problem);
+        let x = s.solve(&VCpu::from_vec(vec![10.0]), 0.0).unwrap();
+        assert!((x[0] - 1.0f64.exp()).abs() < 1e-6, "x = {}", x[0]);
+    }
 }
@@ -1,9 +1,106 @@
-use crate::{op::NonLinearOp, LinearSolver, NonLinearSolver, SolverProblem, Vector};
-use anyhow::{anyhow, Result};
-use std::ops::SubAssign;
+use crate::{
+    op::NonLinearOp, scalar::scale, LinearSolver, NonLinearSolver, Scalar, SolverProblem, Vector,
+};
+use anyhow::Result;
+use num_traits::{One, Pow, Zero};
+use std::{fmt, ops::SubAssign};
 
 use super::{Convergence, ConvergenceStatus};
 
+/// The globalisation strategy used by [NewtonNonlinearSolver] to make the iteration converge from poor initial guesses.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NewtonGlobalisation<T: Scalar> {
+    /// Take full Newton steps, reusing the jacobian from the last call to [NonLinearSolver::reset_jacobian] (a modified Newton method).
+    /// This is the default, and is what the ODE solvers use for their implicit stages.
+    FullStep,
+
+    /// Backtracking line search on the merit function `phi(x) = 0.5 ||F(x)||^2`. The jacobian is updated every iteration, and a step
+    /// `x + alpha dx` is accepted if it satisfies the Armijo condition `phi(x + alpha dx) <= (1 - 2 armijo alpha) phi(x)`,
+    /// otherwise `alpha` is multiplied by `backtrack`. The iteration fails if `alpha` falls below `min_step`.
+    LineSearch {
+        armijo: T,
+        backtrack: T,
+        min_step: T,
+    },
+
+    /// Powell's dogleg trust region method on the merit function `phi(x) = 0.5 ||F(x)||^2`. The jacobian is updated every iteration.
+    /// The initial radius is `factor * ||x0||` (or `factor` if `x0` is zero), and a step is accepted if the ratio of the actual to
+    /// the predicted reduction in `phi` is greater than `eta`. Computing the steepest descent direction requires `n` jacobian-vector
+    /// products per iteration, so this is intended for small to moderate sized problems, such as the consistent initialisation of a DAE.
+    TrustRegion { factor: T, eta: T },
+}
+
+impl<T: Scalar> NewtonGlobalisation<T> {
+    /// A backtracking line search with the usual parameters (`armijo = 1e-4`, `backtrack = 0.5`, `min_step = 1e-8`).
+    pub fn line_search() -> Self {
+        Self::LineSearch {
+            armijo: T::from(1e-4),
+            backtrack: T::from(0.5),
+            min_step: T::from(1e-8),
+        }
+    }
+
+    /// A dogleg trust region with the usual parameters (`factor = 100`, `eta = 1e-4`).
+    pub fn trust_region() -> Self {
+        Self::TrustRegion {
+            factor: T::from(100.0),
+            eta: T::from(1e-4),
+        }
+    }
+}
+
+/// Diagnostics for a single iteration of [NewtonNonlinearSolver].
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewtonIteration<T: Scalar> {
+    /// the 2-norm of the residual `F(x)` at the start of the iteration
+    pub residual_norm: T,
+    /// the 2-norm of the step taken
+    pub step_norm: T,
+    /// the fraction of the Newton step that was taken (the line search `alpha`, or `||step|| / ||dx||` for the trust region)
+    pub step_length: T,
+}
+
+/// The reason [NewtonNonlinearSolver] failed to converge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NewtonFailure {
+    Diverged,
+    MaximumIterations,
+    LineSearchFailed,
+    TrustRegionTooSmall,
+}
+
+/// The error returned by [NewtonNonlinearSolver] if the iteration does not converge, this contains the diagnostics for each iteration
+/// and can be recovered from the [anyhow::Error] using `downcast_ref::<NewtonError<T>>()`.
+#[derive(Clone, Debug)]
+pub struct NewtonError<T: Scalar> {
+    pub reason: NewtonFailure,
+    pub niter: usize,
+    /// the diagnostics for each iteration, only recorded when a globalisation strategy is used
+    pub iterations: Vec<NewtonIteration<T>>,
+}
+
+impl<T: Scalar> fmt::Display for NewtonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            NewtonFailure::Diverged => "diverged",
+            NewtonFailure::MaximumIterations => "maximum number of iterations reached",
+            NewtonFailure::LineSearchFailed => "line search failed to reduce the residual",
+            NewtonFailure::TrustRegionTooSmall => "trust region radius became too small",
+        };
+        write!(
+            f,
+            "Newton iteration did not converge ({}) after {} iterations",
+            reason, self.niter
+        )?;
+        if let Some(last) = self.iterations.last() {
+            write!(f, ", last residual norm {}", last.residual_norm)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Scalar> std::error::Error for NewtonError<T> {}
+
 pub struct NewtonNonlinearSolver<C: NonLinearOp, Ls: LinearSolver<C>> {
     convergence: Option<Convergence<C>>,
     linear_solver: Ls,
@@ -11,6 +108,9 @@ pub struct NewtonNonlinearSolver<C: NonLinearOp, Ls: LinearSolver<C>> {
     max_iter: usize,
     niter: usize,
     is_jacobian_set: bool,
+    globalisation: NewtonGlobalisation<C::T>,
+    bounds: Option<(C::V, C::V)>,
+    iterations: Vec<NewtonIteration<C::T>>,
 }
 
 impl<C: NonLinearOp, Ls: LinearSolver<C>> NewtonNonlinearSolver<C, Ls> {
@@ -22,11 +122,249 @@ impl<C: NonLinearOp, Ls: LinearSolver<C>> NewtonNonlinearSolver<C, Ls> {
             max_iter: 100,
             niter: 0,
             is_jacobian_set: false,
+            globalisation: NewtonGlobalisation::FullStep,
+            bounds: None,
+            iterations: Vec::new(),
         }
     }
     pub fn linear_solver(&self) -> &Ls {
         &self.linear_solver
     }
+
+    /// Set the globalisation strategy, see [NewtonGlobalisation].
+    pub fn set_globalisation(&mut self, globalisation: NewtonGlobalisation<C::T>) {
+        self.globalisation = globalisation;
+    }
+    pub fn globalisation(&self) -> &NewtonGlobalisation<C::T> {
+        &self.globalisation
+    }
+
+    /// Restrict the iterates to `lower <= x <= upper` (componentwise), each trial point is projected onto these bounds.
+    /// Use [Scalar::INFINITY] for components that are unbounded.
+    pub fn set_bounds(&mut self, lower: C::V, upper: C::V) {
+        assert_eq!(lower.len(), upper.len());
+        self.bounds = Some((lower, upper));
+    }
+
+    /// The diagnostics for each iteration of the last call to [NonLinearSolver::solve]. These are only recorded when a globalisation
+    /// strategy is used, so that the full step iteration used by the ODE solvers does not pay for the extra residual norm.
+    pub fn iterations(&self) -> &[NewtonIteration<C::T>] {
+        &self.iterations
+    }
+
+    fn project(bounds: &Option<(C::V, C::V)>, x: &mut C::V) {
+        if let Some((lower, upper)) = bounds {
+            for i in 0..x.len() {
+                if x[i] < lower[i] {
+                    x[i] = lower[i];
+                } else if x[i] > upper[i] {
+                    x[i] = upper[i];
+                }
+            }
+        }
+    }
+
+    fn error(&self, reason: NewtonFailure) -> anyhow::Error {
+        anyhow::Error::new(NewtonError {
+            reason,
+            niter: self.niter,
+            iterations: self.iterations.clone(),
+        })
+    }
+
+    fn solve_full_step(&mut self, xn: &mut C::V, t: C::T) -> Result<()> {
+        let convergence = self.convergence.as_mut().unwrap();
+        let problem = self.problem.as_ref().unwrap();
+        let mut tmp = xn.clone();
+        loop {
+            self.niter += 1;
+            problem.f.call_inplace(xn, t, &mut tmp);
+            //tmp = f_at_n
+
+            self.linear_solver.solve_in_place(&mut tmp)?;
+            //tmp = -delta_n
+
+            let x_old = self.bounds.as_ref().map(|_| xn.clone());
+            xn.sub_assign(&tmp);
+            // xn = xn + delta_n
+            if let Some(x_old) = x_old {
+                Self::project(&self.bounds, xn);
+                tmp = x_old - &*xn;
+            }
+
+            let res = convergence.check_new_iteration(&mut tmp);
+            match res {
+                ConvergenceStatus::Continue => continue,
+                ConvergenceStatus::Converged => return Ok(()),
+                ConvergenceStatus::Diverged => return Err(self.error(NewtonFailure::Diverged)),
+                ConvergenceStatus::MaximumIterations => {
+                    return Err(self.error(NewtonFailure::MaximumIterations))
+                }
+            }
+        }
+    }
+
+    fn solve_damped(&mut self, xn: &mut C::V, t: C::T) -> Result<()> {
+        let half = C::T::from(0.5);
+        let dot = |a: &C::V, b: &C::V| a.binary_fold(b, C::T::zero(), |acc, x, y, _| acc + x * y);
+        let f = self.problem.as_ref().unwrap().f.clone();
+        let mut fx = f.call(xn, t);
+        let mut phi = half * dot(&fx, &fx);
+        let mut radius = match self.globalisation {
+            NewtonGlobalisation::TrustRegion { factor, .. } => {
+                let norm = xn.norm();
+                if norm > C::T::zero() {
+                    factor * norm
+                } else {
+                    factor
+                }
+            }
+            _ => C::T::zero(),
+        };
+        loop {
+            let residual_norm = fx.norm();
+            if residual_norm == C::T::zero() {
+                return Ok(());
+            }
+            if self.niter >= self.max_iter {
+                return Err(self.error(NewtonFailure::MaximumIterations));
+            }
+            self.niter += 1;
+
+            // newton step dx = -J^{-1} F, using the jacobian at the current iterate
+            self.reset_jacobian(xn, t);
+            let mut dx = fx.clone();
+            self.linear_solver.solve_in_place(&mut dx)?;
+            dx *= scale(-C::T::one());
+            let dx_norm = dx.norm();
+
+            // converged if the (scaled) newton step is small, in which case take the full step
+            if self.convergence.as_ref().unwrap().is_small(&dx) {
+                let mut x_new = xn.clone() + &dx;
+                Self::project(&self.bounds, &mut x_new);
+                self.iterations.push(NewtonIteration {
+                    residual_norm,
+                    step_norm: (x_new.clone() - &*xn).norm(),
+                    step_length: C::T::one(),
+                });
+                xn.copy_from(&x_new);
+                return Ok(());
+            }
+
+            let (x_new, f_new) = match self.globalisation {
+                NewtonGlobalisation::LineSearch {
+                    armijo,
+                    backtrack,
+                    min_step,
+                } => {
+                    let mut alpha = C::T::one();
+                    loop {
+                        let mut x_trial = xn.clone() + &(dx.clone() * scale(alpha));
+                        Self::project(&self.bounds, &mut x_trial);
+                        let f_trial = f.call(&x_trial, t);
+                        let phi_trial = half * dot(&f_trial, &f_trial);
+                        // the directional derivative of phi along the newton step is -2 phi
+                        if phi_trial <= (C::T::one() - C::T::from(2.0) * armijo * alpha) * phi {
+                            break (x_trial, f_trial);
+                        }
+                        alpha *= backtrack;
+                        if alpha < min_step {
+                            self.iterations.push(NewtonIteration {
+                                residual_norm,
+                                step_norm: C::T::zero(),
+                                step_length: C::T::zero(),
+                            });
+                            return Err(self.error(NewtonFailure::LineSearchFailed));
+                        }
+                    }
+                }
+                NewtonGlobalisation::TrustRegion { eta, .. } => {
+                    // steepest descent direction of phi is -g, where g = J^T F, and the cauchy point is -(|g|^2 / |J g|^2) g
+                    let n = xn.len();
+                    let mut g = C::V::zeros(n);
+                    let mut e = C::V::zeros(n);
+                    let mut col = C::V::zeros(n);
+                    for j in 0..n {
+                        e[j] = C::T::one();
+                        f.jac_mul_inplace(xn, t, &e, &mut col);
+                        g[j] = dot(&col, &fx);
+                        e[j] = C::T::zero();
+                    }
+                    let g_norm = g.norm();
+                    // g is only zero at a stationary point of phi, in which case fall back to the dogleg between x and the newton step
+                    let cauchy = if g_norm > C::T::zero() {
+                        let jg = f.jac_mul(xn, t, &g);
+                        let jg_norm2 = dot(&jg, &jg);
+                        g.clone() * scale(-g_norm * g_norm / jg_norm2)
+                    } else {
+                        C::V::zeros(n)
+                    };
+                    let cauchy_norm = cauchy.norm();
+                    loop {
+                        let p = if dx_norm <= radius {
+                            dx.clone()
+                        } else if g_norm > C::T::zero() && cauchy_norm >= radius {
+                            g.clone() * scale(-radius / g_norm)
+                        } else {
+                            // find tau such that ||cauchy + tau (dx - cauchy)|| = radius
+                            let d = dx.clone() - &cauchy;
+                            let a = dot(&d, &d);
+                            let b = C::T::from(2.0) * dot(&cauchy, &d);
+                            let c = cauchy_norm * cauchy_norm - radius * radius;
+                            let tau = (-b + (b * b - C::T::from(4.0) * a * c).pow(half))
+                                / (C::T::from(2.0) * a);
+                            cauchy.clone() + &(d * scale(tau))
+                        };
+
+                        // the predicted reduction from the linear model F + J p uses the step actually taken after projecting onto the bounds
+                        let mut x_trial = xn.clone() + &p;
+                        Self::project(&self.bounds, &mut x_trial);
+                        let p = x_trial.clone() - &*xn;
+                        let p_norm = p.norm();
+                        let mut model = f.jac_mul(xn, t, &p);
+                        model += &fx;
+                        let predicted = phi - half * dot(&model, &model);
+
+                        let f_trial = f.call(&x_trial, t);
+                        let phi_trial = half * dot(&f_trial, &f_trial);
+                        let rho = if predicted > C::T::zero() {
+                            (phi - phi_trial) / predicted
+                        } else {
+                            -C::T::one()
+                        };
+
+                        if rho < C::T::from(0.25) {
+                            radius = C::T::from(0.25) * p_norm;
+                        } else if rho > C::T::from(0.75) && p_norm >= C::T::from(0.99) * radius {
+                            radius *= C::T::from(2.0);
+                        }
+                        if rho > eta {
+                            break (x_trial, f_trial);
+                        }
+                        if radius <= C::T::EPSILON * (C::T::one() + xn.norm()) {
+                            self.iterations.push(NewtonIteration {
+                                residual_norm,
+                                step_norm: C::T::zero(),
+                                step_length: C::T::zero(),
+                            });
+                            return Err(self.error(NewtonFailure::TrustRegionTooSmall));
+                        }
+                    }
+                }
+                NewtonGlobalisation::FullStep => unreachable!(),
+            };
+
+            let step_norm = (x_new.clone() - &*xn).norm();
+            self.iterations.push(NewtonIteration {
+                residual_norm,
+                step_norm,
+                step_length: step_norm / dx_norm,
+            });
+            xn.copy_from(&x_new);
+            phi = half * dot(&f_new, &f_new);
+            fx = f_new;
+        }
+    }
 }
 
 impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for NewtonNonlinearSolver<C, Ls> {
@@ -67,30 +405,12 @@ impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for NewtonNonlinear
         if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
             panic!("NewtonNonlinearSolver::solve() called with state of wrong size, expected {}, got {}", self.problem.as_ref().unwrap().f.nstates(), xn.len());
         }
-        let convergence = self.convergence.as_mut().unwrap();
-        let problem = self.problem.as_ref().unwrap();
-        convergence.reset(xn);
-        let mut tmp = xn.clone();
+        self.convergence.as_mut().unwrap().reset(xn);
         self.niter = 0;
-        loop {
-            self.niter += 1;
-            problem.f.call_inplace(xn, t, &mut tmp);
-            //tmp = f_at_n
-
-            self.linear_solver.solve_in_place(&mut tmp)?;
-            //tmp = -delta_n
-
-            xn.sub_assign(&tmp);
-            // xn = xn + delta_n
-
-            let res = convergence.check_new_iteration(&mut tmp);
-            match res {
-                ConvergenceStatus::Continue => continue,
-                ConvergenceStatus::Converged => return Ok(()),
-                ConvergenceStatus::Diverged => break,
-                ConvergenceStatus::MaximumIterations => break,
-            }
+        self.iterations.clear();
+        match self.globalisation {
+            NewtonGlobalisation::FullStep => self.solve_full_step(xn, t),
+            _ => self.solve_damped(xn, t),
         }
-        Err(anyhow!("Newton iteration did not converge"))
     }
 }
@@ -0,0 +1,162 @@
+use crate::{
+    linear_solver::gmres::{Gmres, IdentityPreconditioner},
+    op::NonLinearOp,
+    LinearSolver, NonLinearSolver, SolverProblem, Vector,
+};
+use anyhow::{anyhow, Result};
+use num_traits::Pow;
+use std::ops::SubAssign;
+
+use super::{Convergence, ConvergenceStatus};
+
+/// A jacobian-free Newton-Krylov (JFNK) solver.
+///
+/// Each Newton step is found by solving the linear system `J dx = F(x)` inexactly using [Gmres], which only requires the action of the
+/// jacobian on a vector [NonLinearOp::jac_mul_inplace] at the current iterate, so the jacobian is never assembled. The relative tolerance of
+/// the linear solve (the forcing term `eta`) is chosen using choice 2 of Eisenstat and Walker \[1\], `eta_k = gamma (||F(x_k)|| / ||F(x_{k-1})||)^alpha`
+/// with `gamma = 0.9` and `alpha = 2`, safeguarded so that it does not decrease too quickly and bounded to lie in `[eta_min, eta_max]`.
+///
+/// The [Gmres] solver can be given a preconditioner, which is only set up when [NonLinearSolver::reset_jacobian] is called, so it is lagged
+/// in the same way as the jacobian of [crate::NewtonNonlinearSolver]. When used with [crate::Bdf], the number of linear solver setups reported
+/// in the statistics is then the number of preconditioner setups (zero if there is no preconditioner).
+///
+/// \[1\] Eisenstat, S. C., & Walker, H. F. (1996). Choosing the forcing terms in an inexact Newton method. SIAM Journal on Scientific Computing, 17(1), 16-32.
+pub struct NewtonKrylovNonlinearSolver<C: NonLinearOp, P: LinearSolver<C> = IdentityPreconditioner>
+{
+    convergence: Option<Convergence<C>>,
+    gmres: Gmres<C, P>,
+    problem: Option<SolverProblem<C>>,
+    max_iter: usize,
+    niter: usize,
+    number_of_linear_iterations: usize,
+    is_jacobian_set: bool,
+    eta_0: C::T,
+    eta_min: C::T,
+    eta_max: C::T,
+}
+
+impl<C: NonLinearOp, P: LinearSolver<C>> NewtonKrylovNonlinearSolver<C, P> {
+    pub fn new(gmres: Gmres<C, P>) -> Self {
+        Self {
+            problem: None,
+            convergence: None,
+            gmres,
+            max_iter: 100,
+            niter: 0,
+            number_of_linear_iterations: 0,
+            is_jacobian_set: false,
+            eta_0: C::T::from(0.1),
+            eta_min: C::T::from(1e-4),
+            eta_max: C::T::from(0.9),
+        }
+    }
+    pub fn gmres(&self) -> &Gmres<C, P> {
+        &self.gmres
+    }
+
+    /// Set the forcing term used for the first Newton iteration (default 0.1), and the lower (default 1e-4) and upper (default 0.9) bounds of the forcing term.
+    pub fn set_forcing_terms(&mut self, eta_0: C::T, eta_min: C::T, eta_max: C::T) {
+        assert!(eta_min <= eta_0 && eta_0 <= eta_max);
+        self.eta_0 = eta_0;
+        self.eta_min = eta_min;
+        self.eta_max = eta_max;
+    }
+
+    /// The total number of GMRES iterations over the lifetime of the solver.
+    pub fn number_of_linear_iterations(&self) -> usize {
+        self.number_of_linear_iterations
+    }
+}
+
+impl<C: NonLinearOp, P: LinearSolver<C>> NonLinearSolver<C> for NewtonKrylovNonlinearSolver<C, P> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("NewtonKrylovNonlinearSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        self.gmres.set_problem(problem);
+        let problem = self.problem.as_ref().unwrap();
+        self.convergence = Some(Convergence::new(problem, self.max_iter));
+        self.is_jacobian_set = false;
+    }
+
+    // sets up the preconditioner
+    fn reset_jacobian(&mut self, x: &C::V, t: C::T) {
+        self.gmres.set_linearisation(x, t);
+        self.is_jacobian_set = true;
+    }
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<()> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("NewtonKrylovNonlinearSolver::solve() called before set_problem");
+        }
+        if !self.is_jacobian_set {
+            self.reset_jacobian(xn, t);
+        }
+        if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
+            panic!("NewtonKrylovNonlinearSolver::solve() called with state of wrong size, expected {}, got {}", self.problem.as_ref().unwrap().f.nstates(), xn.len());
+        }
+        let convergence = self.convergence.as_mut().unwrap();
+        let problem = self.problem.as_ref().unwrap();
+        convergence.reset(xn);
+        self.niter = 0;
+        let gamma = C::T::from(0.9);
+        let alpha = C::T::from(2.0);
+
+        let mut f = problem.f.call(xn, t);
+        let mut f_norm = f.norm();
+        let mut eta = self.eta_0;
+        let mut tmp = f.clone();
+        loop {
+            self.niter += 1;
+
+            // inexact newton step, ||F(x) - J dx|| <= eta ||F(x)||
+            self.gmres.set_linearisation_point(xn, t);
+            self.gmres.set_tolerance(eta);
+            tmp.copy_from(&f);
+            let result = self.gmres.solve_in_place(&mut tmp);
+            self.number_of_linear_iterations += self.gmres.niter();
+            result?;
+
+            xn.sub_assign(&tmp);
+
+            let res = convergence.check_new_iteration(&mut tmp);
+            match res {
+                ConvergenceStatus::Continue => (),
+                ConvergenceStatus::Converged => return Ok(()),
+                ConvergenceStatus::Diverged => break,
+                ConvergenceStatus::MaximumIterations => break,
+            }
+
+            // eisenstat-walker choice 2, with the safeguard eta_k >= gamma eta_{k-1}^alpha if that is larger than 0.1
+            problem.f.call_inplace(xn, t, &mut f);
+            let f_norm_new = f.norm();
+            let eta_new = gamma * (f_norm_new / f_norm).pow(alpha);
+            let eta_safe = gamma * eta.pow(alpha);
+            eta = if eta_safe > C::T::from(0.1) && eta_safe > eta_new {
+                eta_safe
+            } else {
+                eta_new
+            };
+            if eta > self.eta_max {
+                eta = self.eta_max;
+            }
+            if eta < self.eta_min {
+                eta = self.eta_min;
+            }
+            f_norm = f_norm_new;
+        }
+        Err(anyhow!("Newton-Krylov iteration did not converge"))
+    }
+}
@@ -1,10 +1,10 @@
 use std::ops::AddAssign;
 use std::rc::Rc;
+use std::time::Instant;
 
 use anyhow::{anyhow, Result};
 
 use num_traits::{abs, One, Pow, Zero};
-use serde::Serialize;
 
 use crate::{
     matrix::{default_solver::DefaultSolver, Matrix, MatrixRef},
@@ -13,8 +13,8 @@ use crate::{
     scalar::scale,
     vector::DefaultDenseMatrix,
     DenseMatrix, IndexType, MatrixViewMut, NewtonNonlinearSolver, NonLinearOp, NonLinearSolver,
-    OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, Scalar,
-    SolverProblem, Vector, VectorRef, VectorView, VectorViewMut,
+    OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStatistics, OdeSolverStopReason,
+    Op, Scalar, SolverProblem, Vector, VectorRef, VectorView, VectorViewMut,
 };
 
 pub mod faer;
@@ -22,31 +22,6 @@ pub mod nalgebra;
 
 use super::equations::OdeEquations;
 
-#[derive(Clone, Debug, Serialize)]
-pub struct BdfStatistics<T: Scalar> {
-    pub number_of_linear_solver_setups: usize,
-    pub number_of_steps: usize,
-    pub number_of_error_test_failures: usize,
-    pub number_of_nonlinear_solver_iterations: usize,
-    pub number_of_nonlinear_solver_fails: usize,
-    pub initial_step_size: T,
-    pub final_step_size: T,
-}
-
-impl<T: Scalar> Default for BdfStatistics<T> {
-    fn default() -> Self {
-        Self {
-            number_of_linear_solver_setups: 0,
-            number_of_steps: 0,
-            number_of_error_test_failures: 0,
-            number_of_nonlinear_solver_iterations: 0,
-            number_of_nonlinear_solver_fails: 0,
-            initial_step_size: T::zero(),
-            final_step_size: T::zero(),
-        }
-    }
-}
-
 /// Implements a Backward Difference formula (BDF) implicit multistep integrator.
 /// The basic algorithm is derived in \[1\]. This
 /// particular implementation follows that implemented in the Matlab routine ode15s
@@ -77,7 +52,7 @@ pub struct Bdf<
     alpha: Vec<Eqn::T>,
     gamma: Vec<Eqn::T>,
     error_const: Vec<Eqn::T>,
-    statistics: BdfStatistics<Eqn::T>,
+    statistics: OdeSolverStatistics<Eqn::T>,
     state: Option<OdeSolverState<Eqn::V>>,
     tstop: Option<Eqn::T>,
     root_finder: Option<RootFinder<Eqn::V>>,
@@ -97,9 +72,24 @@ where
     for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
 {
     fn default() -> Self {
-        let n = 1;
         let linear_solver = Eqn::M::default_solver();
-        let mut nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
+        Self::new(NewtonNonlinearSolver::new(linear_solver))
+    }
+}
+
+impl<Eqn, Nls> Bdf<<Eqn::V as DefaultDenseMatrix>::M, Eqn, Nls>
+where
+    Eqn: OdeEquations,
+    Eqn::V: DefaultDenseMatrix,
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    /// Create a new BDF solver that uses the given nonlinear solver (e.g. [crate::BroydenNonlinearSolver] or [crate::AndersonNonlinearSolver])
+    /// to solve the implicit equation at each step. The maximum number of iterations of the nonlinear solver is set by the BDF solver.
+    pub fn new(nonlinear_solver: Nls) -> Self {
+        let n = 1;
+        let mut nonlinear_solver = nonlinear_solver;
         nonlinear_solver.set_max_iter(Self::NEWTON_MAXITER);
         type M<V> = <V as DefaultDenseMatrix>::M;
         Self {
@@ -113,7 +103,7 @@ where
             alpha: vec![Eqn::T::from(1.0); Self::MAX_ORDER + 1],
             error_const: vec![Eqn::T::from(1.0); Self::MAX_ORDER + 1],
             u: <M<Eqn::V> as Matrix>::zeros(Self::MAX_ORDER + 1, Self::MAX_ORDER + 1),
-            statistics: BdfStatistics::default(),
+            statistics: OdeSolverStatistics::default(),
             state: None,
             tstop: None,
             root_finder: None,
@@ -133,10 +123,6 @@ where
     const MAX_FACTOR: f64 = 10.0;
     const MIN_TIMESTEP: f64 = 1e-32;
 
-    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
-        &self.statistics
-    }
-
     fn nonlinear_problem_op(&self) -> &Rc<BdfCallable<Eqn>> {
         &self.nonlinear_solver.problem().f
     }
@@ -197,7 +183,9 @@ where
         // use any x and t as they won't be used
         let t = self.state.as_ref().unwrap().t;
         let x = &self.state.as_ref().unwrap().y;
+        let start = Instant::now();
         self.nonlinear_solver.reset_jacobian(x, t);
+        self.statistics.timings.linear_solver_setup += start.elapsed();
     }
 
     fn _update_differences(&mut self, d: &Eqn::V) {
@@ -255,156 +243,7 @@ where
         (y_predict, t_new)
     }
 
-    fn handle_tstop(&mut self, tstop: Eqn::T) -> Result<Option<OdeSolverStopReason<Eqn::T>>> {
-        // check if the we are at tstop
-        let state = self.state.as_ref().unwrap();
-        let troundoff = Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(state.t) + abs(state.h));
-        if abs(state.t - tstop) <= troundoff {
-            self.tstop = None;
-            return Ok(Some(OdeSolverStopReason::TstopReached));
-        } else if tstop < state.t - troundoff {
-            self.tstop = None;
-            return Err(anyhow!("tstop is before current time"));
-        }
-
-        // check if the next step will be beyond tstop, if so adjust the step size
-        if state.t + state.h > tstop + troundoff {
-            let factor = (tstop - state.t) / state.h;
-            self._update_step_size(factor);
-        }
-        Ok(None)
-    }
-}
-
-impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> OdeSolverMethod<Eqn>
-    for Bdf<M, Eqn, Nls>
-where
-    Nls: NonLinearSolver<BdfCallable<Eqn>>,
-    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
-    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
-{
-    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
-        //interpolate solution at time values t* where t-h < t* < t
-        //
-        //definition of the interpolating polynomial can be found on page 7 of [1]
-
-        // state must be set
-        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
-
-        // check that t is before the current time
-        if t > state.t {
-            return Err(anyhow!("Interpolation time is after current time"));
-        }
-
-        let mut time_factor = Eqn::T::from(1.0);
-        let mut order_summation = self.diff.column(0).into_owned();
-        for i in 0..self.order {
-            let i_t = Eqn::T::from(i as f64);
-            time_factor *= (t - (state.t - state.h * i_t)) / (state.h * (Eqn::T::one() + i_t));
-            order_summation += self.diff.column(i + 1) * scale(time_factor);
-        }
-        Ok(order_summation)
-    }
-
-    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
-        self.ode_problem.as_ref()
-    }
-
-    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
-        self.state.as_ref()
-    }
-
-    fn take_state(&mut self) -> Option<OdeSolverState<<Eqn>::V>> {
-        Option::take(&mut self.state)
-    }
-
-    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
-        let mut state = state;
-        self.ode_problem = Some(problem.clone());
-        let nstates = problem.eqn.rhs().nstates();
-        self.order = 1usize;
-        self.n_equal_steps = 0;
-        self.diff = M::zeros(nstates, Self::MAX_ORDER + 3);
-        self.diff_tmp = M::zeros(nstates, Self::MAX_ORDER + 3);
-        self.diff.column_mut(0).copy_from(&state.y);
-
-        // kappa values for difference orders, taken from Table 1 of [1]
-        let kappa = [
-            Eqn::T::from(0.0),
-            Eqn::T::from(-0.1850),
-            Eqn::T::from(-1.0) / Eqn::T::from(9.0),
-            Eqn::T::from(-0.0823),
-            Eqn::T::from(-0.0415),
-            Eqn::T::from(0.0),
-        ];
-        self.alpha = vec![Eqn::T::zero()];
-        self.gamma = vec![Eqn::T::zero()];
-        self.error_const = vec![Eqn::T::one()];
-
-        #[allow(clippy::needless_range_loop)]
-        for i in 1..=Self::MAX_ORDER {
-            let i_t = Eqn::T::from(i as f64);
-            let one_over_i = Eqn::T::one() / i_t;
-            let one_over_i_plus_one = Eqn::T::one() / (i_t + Eqn::T::one());
-            self.gamma.push(self.gamma[i - 1] + one_over_i);
-            self.alpha
-                .push(Eqn::T::one() / ((Eqn::T::one() - kappa[i]) * self.gamma[i]));
-            self.error_const
-                .push(kappa[i] * self.gamma[i] + one_over_i_plus_one);
-        }
-
-        // update initial step size based on function
-        let mut scale_factor = state.y.abs();
-        scale_factor *= scale(problem.rtol);
-        scale_factor += problem.atol.as_ref();
-
-        let f0 = problem.eqn.rhs().call(&state.y, state.t);
-        let hf0 = &f0 * scale(state.h);
-        let y1 = &state.y + &hf0;
-        let t1 = state.t + state.h;
-        let f1 = problem.eqn.rhs().call(&y1, t1);
-
-        // store f1 in diff[1] for use in step size control
-        self.diff.column_mut(1).copy_from(&hf0);
-
-        let mut df = f1 - f0;
-        df.component_div_assign(&scale_factor);
-        let d2 = df.norm();
-
-        let one_over_order_plus_one =
-            Eqn::T::one() / (Eqn::T::from(self.order as f64) + Eqn::T::one());
-        let mut new_h = state.h * d2.pow(-one_over_order_plus_one);
-        if new_h > Eqn::T::from(100.0) * state.h {
-            new_h = Eqn::T::from(100.0) * state.h;
-        }
-        state.h = new_h;
-
-        // setup linear solver for first step
-        let bdf_callable = Rc::new(BdfCallable::new(problem));
-        bdf_callable.set_c(state.h, self.alpha[self.order]);
-
-        let nonlinear_problem = SolverProblem::new_from_ode_problem(bdf_callable, problem);
-        self.nonlinear_solver.set_problem(&nonlinear_problem);
-
-        // setup U
-        self.u = Self::_compute_r(self.order, Eqn::T::one());
-
-        // update statistics
-        self.statistics.initial_step_size = state.h;
-
-        // store state
-        self.state = Some(state);
-        if let Some(root_fn) = problem.eqn.root() {
-            let state = self.state.as_ref().unwrap();
-            self.root_finder = Some(RootFinder::new(root_fn.nout()));
-            self.root_finder
-                .as_ref()
-                .unwrap()
-                .init(root_fn.as_ref(), &state.y, state.t);
-        }
-    }
-
-    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
         let mut d: Eqn::V;
         let mut safety: Eqn::T;
         let mut error_norm: Eqn::T;
@@ -421,9 +260,12 @@ where
             let mut y_new = y_predict.clone();
 
             // solve BDF equation using y0 as starting point
+            let start = Instant::now();
             let solver_result = self.nonlinear_solver.solve_in_place(&mut y_new, t_new);
             // update statistics
+            self.statistics.timings.nonlinear_solver += start.elapsed();
             self.statistics.number_of_nonlinear_solver_iterations += self.nonlinear_solver.niter();
+            self.statistics.number_of_linear_solves += self.nonlinear_solver.niter();
             match solver_result {
                 Ok(()) => {
                     // test error is within tolerance
@@ -488,8 +330,10 @@ where
                     } else {
                         // newton iteration did not converge, so update jacobian and try again
                         self.nonlinear_problem_op().set_jacobian_is_stale();
+                        let start = Instant::now();
                         self.nonlinear_solver
                             .reset_jacobian(&y_predict, self.state.as_ref().unwrap().t);
+                        self.statistics.timings.linear_solver_setup += start.elapsed();
                         updated_jacobian = true;
                         // same prediction as last time
                     }
@@ -512,6 +356,20 @@ where
 
         self._update_differences(&d);
 
+        // the time derivative of the interpolating polynomial at the new step is (1/h) sum_{j=1}^{k} (1/j) D^j y_n
+        {
+            let state = self.state.as_mut().unwrap();
+            state.dy.copy_from_view(&self.diff.column(1));
+            for j in 2..=self.order {
+                state.dy.axpy(
+                    Eqn::T::one() / Eqn::T::from(j as f64),
+                    &self.diff.column(j).into_owned(),
+                    Eqn::T::one(),
+                );
+            }
+            state.dy *= scale(Eqn::T::one() / state.h);
+        }
+
         // a change in order is only done after running at order k for k + 1 steps
         // (see page 83 of [2])
         self.n_equal_steps += 1;
@@ -568,12 +426,14 @@ where
 
         // check for root within accepted step
         if let Some(root_fn) = self.problem().as_ref().unwrap().eqn.root() {
+            let start = Instant::now();
             let ret = self.root_finder.as_ref().unwrap().check_root(
                 &|t| self.interpolate(t),
                 root_fn.as_ref(),
                 &self.state.as_ref().unwrap().y,
                 self.state.as_ref().unwrap().t,
             );
+            self.statistics.timings.root_finding += start.elapsed();
             if let Some(root) = ret {
                 return Ok(OdeSolverStopReason::RootFound(root));
             }
@@ -589,6 +449,199 @@ where
         Ok(OdeSolverStopReason::InternalTimestep)
     }
 
+    fn handle_tstop(&mut self, tstop: Eqn::T) -> Result<Option<OdeSolverStopReason<Eqn::T>>> {
+        // check if the we are at tstop
+        let state = self.state.as_ref().unwrap();
+        let troundoff = Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(state.t) + abs(state.h));
+        if abs(state.t - tstop) <= troundoff {
+            self.tstop = None;
+            return Ok(Some(OdeSolverStopReason::TstopReached));
+        } else if tstop < state.t - troundoff {
+            self.tstop = None;
+            return Err(anyhow!("tstop is before current time"));
+        }
+
+        // check if the next step will be beyond tstop, if so adjust the step size
+        if state.t + state.h > tstop + troundoff {
+            let factor = (tstop - state.t) / state.h;
+            self._update_step_size(factor);
+        }
+        Ok(None)
+    }
+}
+
+impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> OdeSolverMethod<Eqn>
+    for Bdf<M, Eqn, Nls>
+where
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        //interpolate solution at time values t* where t-h < t* < t
+        //
+        //definition of the interpolating polynomial can be found on page 7 of [1]
+
+        // state must be set
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+
+        // check that t is before the current time
+        if t > state.t {
+            return Err(anyhow!("Interpolation time is after current time"));
+        }
+
+        let mut time_factor = Eqn::T::from(1.0);
+        let mut order_summation = self.diff.column(0).into_owned();
+        for i in 0..self.order {
+            let i_t = Eqn::T::from(i as f64);
+            time_factor *= (t - (state.t - state.h * i_t)) / (state.h * (Eqn::T::one() + i_t));
+            order_summation += self.diff.column(i + 1) * scale(time_factor);
+        }
+        Ok(order_summation)
+    }
+
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.ode_problem.as_ref()
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.ode_problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<<Eqn>::V>> {
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(
+        &mut self,
+        state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        let mut state = state;
+        self.ode_problem = Some(problem.clone());
+        let nstates = problem.eqn.rhs().nstates();
+        self.order = 1usize;
+        self.n_equal_steps = 0;
+        self.diff = M::zeros(nstates, Self::MAX_ORDER + 3);
+        self.diff_tmp = M::zeros(nstates, Self::MAX_ORDER + 3);
+        self.diff.column_mut(0).copy_from(&state.y);
+
+        // kappa values for difference orders, taken from Table 1 of [1]
+        let kappa = [
+            Eqn::T::from(0.0),
+            Eqn::T::from(-0.1850),
+            Eqn::T::from(-1.0) / Eqn::T::from(9.0),
+            Eqn::T::from(-0.0823),
+            Eqn::T::from(-0.0415),
+            Eqn::T::from(0.0),
+        ];
+        self.alpha = vec![Eqn::T::zero()];
+        self.gamma = vec![Eqn::T::zero()];
+        self.error_const = vec![Eqn::T::one()];
+
+        #[allow(clippy::needless_range_loop)]
+        for i in 1..=Self::MAX_ORDER {
+            let i_t = Eqn::T::from(i as f64);
+            let one_over_i = Eqn::T::one() / i_t;
+            let one_over_i_plus_one = Eqn::T::one() / (i_t + Eqn::T::one());
+            self.gamma.push(self.gamma[i - 1] + one_over_i);
+            self.alpha
+                .push(Eqn::T::one() / ((Eqn::T::one() - kappa[i]) * self.gamma[i]));
+            self.error_const
+                .push(kappa[i] * self.gamma[i] + one_over_i_plus_one);
+        }
+
+        // update initial step size based on function
+        let mut scale_factor = state.y.abs();
+        scale_factor *= scale(problem.rtol);
+        scale_factor += problem.atol.as_ref();
+
+        if problem.eqn.is_implicit() {
+            // a fully implicit DAE has no rhs, so use the time derivative of the state and
+            // limit the step size using the size of the state relative to its derivative
+            // (see Hairer, Norsett, Wanner, Solving Ordinary Differential Equations I, Section II.4.2)
+            let y_dot0 = state.dy.clone();
+            let mut tmp = state.y.clone();
+            tmp.component_div_assign(&scale_factor);
+            let d0 = tmp.norm();
+            let mut tmp = y_dot0.clone();
+            tmp.component_div_assign(&scale_factor);
+            let d1 = tmp.norm();
+            if d0 > Eqn::T::from(1e-5) && d1 > Eqn::T::from(1e-5) {
+                let h0 = Eqn::T::from(0.01) * (d0 / d1);
+                if h0 < state.h {
+                    state.h = h0;
+                }
+            }
+
+            // store h * y'(t0) in diff[1] for use in step size control
+            let hf0 = y_dot0 * scale(state.h);
+            self.diff.column_mut(1).copy_from(&hf0);
+        } else {
+            let f0 = problem.eqn.rhs().call(&state.y, state.t);
+            let hf0 = &f0 * scale(state.h);
+            let y1 = &state.y + &hf0;
+            let t1 = state.t + state.h;
+            let f1 = problem.eqn.rhs().call(&y1, t1);
+
+            // store f1 in diff[1] for use in step size control
+            self.diff.column_mut(1).copy_from(&hf0);
+
+            let mut df = f1 - f0;
+            df.component_div_assign(&scale_factor);
+            let d2 = df.norm();
+
+            let one_over_order_plus_one =
+                Eqn::T::one() / (Eqn::T::from(self.order as f64) + Eqn::T::one());
+            let mut new_h = state.h * d2.pow(-one_over_order_plus_one);
+            if new_h > Eqn::T::from(100.0) * state.h {
+                new_h = Eqn::T::from(100.0) * state.h;
+            }
+            state.h = new_h;
+        }
+
+        // setup linear solver for first step
+        let bdf_callable = Rc::new(BdfCallable::new(problem));
+        bdf_callable.set_c(state.h, self.alpha[self.order]);
+
+        let nonlinear_problem = SolverProblem::new_from_ode_problem(bdf_callable, problem);
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // setup U
+        self.u = Self::_compute_r(self.order, Eqn::T::one());
+
+        // update statistics
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+
+        // store state
+        self.state = Some(state);
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t);
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
     fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<()> {
         self.tstop = Some(tstop);
         if let Some(OdeSolverStopReason::TstopReached) = self.handle_tstop(tstop)? {
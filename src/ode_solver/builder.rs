@@ -1,12 +1,23 @@
-use std::rc::Rc;
+use std::{cell::RefCell, rc::Rc};
 
 use crate::{
-    vector::DefaultDenseMatrix, Closure, ClosureNoJac, LinearClosure, Matrix, OdeEquations,
+    jacobian::ColoringAlgorithm,
+    op::{
+        delay_closure::DelayClosure, residual_closure::ResidualClosure,
+        state_mass_closure::StateMassClosure,
+    },
+    vector::DefaultDenseMatrix,
+    AutodiffFunction, Closure, ClosureAutodiff, ClosureNoJac, LinearClosure, Matrix, OdeEquations,
     OdeSolverProblem, Op, UnitCallable, Vector,
 };
 use anyhow::Result;
+use num_traits::{One, Zero};
 
-use super::equations::OdeSolverEquations;
+use super::{
+    dde::{DdeHistory, DdeProblem},
+    equations::OdeSolverEquations,
+    implicit_equations::{ImplicitDae, ImplicitDaeSolverEquations},
+};
 
 /// Builder for ODE problems. Use methods to set parameters and then call one of the build methods when done.
 pub struct OdeBuilder {
@@ -16,6 +27,9 @@ pub struct OdeBuilder {
     atol: Vec<f64>,
     p: Vec<f64>,
     use_coloring: bool,
+    sparsity_probes: usize,
+    coloring_algorithm: ColoringAlgorithm,
+    jacobian_sparsity: Option<Vec<(usize, usize)>>,
     constant_mass: bool,
 }
 
@@ -33,6 +47,9 @@ impl OdeBuilder {
     /// - atol = [1e-6]
     /// - p = []
     /// - use_coloring = false
+    /// - sparsity_probes = 0
+    /// - coloring_algorithm = ColoringAlgorithm::Greedy
+    /// - jacobian_sparsity = None
     /// - constant_mass = false
     pub fn new() -> Self {
         Self {
@@ -42,6 +59,9 @@ impl OdeBuilder {
             atol: vec![1e-6],
             p: vec![],
             use_coloring: false,
+            sparsity_probes: 0,
+            coloring_algorithm: ColoringAlgorithm::default(),
+            jacobian_sparsity: None,
             constant_mass: false,
         }
     }
@@ -93,12 +113,58 @@ impl OdeBuilder {
     /// Set whether to use coloring when computing the Jacobian.
     /// This can speed up the computation of the Jacobian for large sparse systems.
     /// However, it relys on the sparsity of the Jacobian being constant,
-    /// and for certain systems it may detect the wrong sparsity pattern.
+    /// and for certain systems it may detect the wrong sparsity pattern
+    /// (see [Self::sparsity_probes] and [Self::jacobian_sparsity]).
     pub fn use_coloring(mut self, use_coloring: bool) -> Self {
         self.use_coloring = use_coloring;
         self
     }
 
+    /// Set the number of perturbed states (in addition to the initial state) used to detect the sparsity pattern of the Jacobian
+    /// when [Self::use_coloring] is set. The detected pattern is the union of the patterns found at each state, which reduces the
+    /// chance of missing entries that happen to be zero at the initial state (e.g. due to branches or multiplication by a zero state).
+    pub fn sparsity_probes(mut self, nprobes: usize) -> Self {
+        self.sparsity_probes = nprobes;
+        self
+    }
+
+    /// Set the algorithm used to colour the columns of the Jacobian of the right-hand side when [Self::use_coloring] is set
+    /// or the sparsity pattern is provided using [Self::jacobian_sparsity]. Fewer colours means fewer evaluations of the
+    /// Jacobian-vector product. [ColoringAlgorithm::Star] can only be used if the Jacobian is symmetric.
+    pub fn coloring_algorithm(mut self, algorithm: ColoringAlgorithm) -> Self {
+        self.coloring_algorithm = algorithm;
+        self
+    }
+
+    /// Set the sparsity pattern of the Jacobian of the right-hand side as a list of non-zero entries `(row, col)`.
+    /// If set, the Jacobian is calculated using coloring with this pattern, and no automatic sparsity detection is performed
+    /// (this only applies to the closure-based build methods).
+    pub fn jacobian_sparsity<I>(mut self, non_zeros: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        self.jacobian_sparsity = Some(non_zeros.into_iter().collect());
+        self
+    }
+
+    fn build_jacobian_non_zeros(
+        non_zeros: Option<Vec<(usize, usize)>>,
+        nstates: usize,
+    ) -> Result<Option<Vec<(usize, usize)>>> {
+        let Some(mut non_zeros) = non_zeros else {
+            return Ok(None);
+        };
+        if non_zeros.iter().any(|&(i, j)| i >= nstates || j >= nstates) {
+            return Err(anyhow::anyhow!(
+                "jacobian sparsity pattern has entries outside of the jacobian"
+            ));
+        }
+        // sort in column-major order, which is the order used by the sparsity detection
+        non_zeros.sort_by_key(|&(i, j)| (j, i));
+        non_zeros.dedup();
+        Ok(Some(non_zeros))
+    }
+
     fn build_atol<V: Vector>(atol: Vec<f64>, nstates: usize) -> Result<V> {
         if atol.len() == 1 {
             Ok(V::from_element(nstates, V::T::from(atol[0])))
@@ -184,11 +250,17 @@ impl OdeBuilder {
         let y0 = init(&p, t0);
         let nstates = y0.len();
         let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
         let mut mass = LinearClosure::new(mass, nstates, nstates, p.clone());
-        if self.use_coloring {
-            rhs.calculate_sparsity(&y0, t0);
+        let jacobian_non_zeros = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)?;
+        if self.use_coloring || jacobian_non_zeros.is_some() {
             mass.calculate_sparsity(t0);
         }
+        if let Some(non_zeros) = jacobian_non_zeros {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
+        }
         let mass = Rc::new(mass);
         let rhs = Rc::new(rhs);
         let eqn = OdeSolverEquations::new(rhs, mass, None, init, p, self.constant_mass);
@@ -202,6 +274,93 @@ impl OdeBuilder {
         ))
     }
 
+    /// Build an ODE problem with a mass matrix that depends on the state, i.e. `M(t, y) dy/dt = F(t, y)`.
+    /// The jacobian of the nonlinear systems solved by the BDF and SDIRK methods includes the derivative of the mass matrix with respect
+    /// to the state, and is calculated column by column using jacobian-vector products (so is not sparse).
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(x: &V, p: &V, t: S, y: &mut V) that computes the right-hand side of the ODE.
+    /// - `rhs_jac`: Function of type Fn(x: &V, p: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Jacobian of the right-hand side with the vector v.
+    /// - `mass`: Function of type Fn(v: &V, x: &V, p: &V, t: S, beta: S, y: &mut V) that computes a gemv multiplication of the mass matrix at state x with the vector v (i.e. y = M(t, x) * v + beta * y).
+    /// - `mass_jac`: Function of type Fn(v: &V, x: &V, p: &V, t: S, w: &V, y: &mut V) that computes the multiplication of the Jacobian of `M(t, x) v` with respect to the state with the vector w (i.e. y = d(M(t, x) v)/dx * w).
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_ode::<M, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // (1 + y^2) dy/dt = -y
+    /// // y(0) = 0.1
+    /// let problem = OdeBuilder::new()
+    ///   .build_ode_with_state_mass::<M, _, _, _, _, _>(
+    ///       |x, _p, _t, y| y[0] = -x[0],
+    ///       |x, _p, _t, v, y| y[0] = -v[0],
+    ///       |v, x, _p, _t, beta, y| y[0] = (1.0 + x[0] * x[0]) * v[0] + beta * y[0],
+    ///       |v, x, _p, _t, w, y| y[0] = 2.0 * x[0] * v[0] * w[0],
+    ///       |p, _t| DVector::from_element(1, 0.1),
+    /// );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_with_state_mass<M, F, G, H, K, I>(
+        self,
+        rhs: F,
+        rhs_jac: G,
+        mass: H,
+        mass_jac: K,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<M, F, G>,
+                I,
+                UnitCallable<M>,
+                UnitCallable<M>,
+                StateMassClosure<M, H, K>,
+            >,
+        >,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+        H: Fn(&M::V, &M::V, &M::V, M::T, M::T, &mut M::V),
+        K: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::from(self.t0);
+        let y0 = init(&p, t0);
+        let nstates = y0.len();
+        let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
+        let mass = StateMassClosure::new(mass, mass_jac, nstates, p.clone());
+        if let Some(non_zeros) = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)? {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
+        }
+        let mass = Rc::new(mass);
+        let rhs = Rc::new(rhs);
+        let eqn = OdeSolverEquations::new_with_state_mass(rhs, mass, None, init, p);
+        let atol = Self::build_atol(self.atol, nstates)?;
+        Ok(OdeSolverProblem::new(
+            eqn,
+            M::T::from(self.rtol),
+            atol,
+            t0,
+            M::T::from(self.h0),
+        ))
+    }
+
     /// Build an ODE problem with a mass matrix that is the identity matrix.
     ///
     /// # Arguments
@@ -249,9 +408,12 @@ impl OdeBuilder {
         let y0 = init(&p, t0);
         let nstates = y0.len();
         let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
         let mass = Rc::new(UnitCallable::new(nstates));
-        if self.use_coloring {
-            rhs.calculate_sparsity(&y0, t0);
+        if let Some(non_zeros) = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)? {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
         }
         let rhs = Rc::new(rhs);
         let eqn = OdeSolverEquations::new(rhs, mass, None, init, p, self.use_coloring);
@@ -265,6 +427,666 @@ impl OdeBuilder {
         ))
     }
 
+    /// Build a second order ODE problem `q'' = f(t, q, q')`, written as the first order system `y = (q, v)`, `y' = (v, f(t, q, v))`,
+    /// which can be solved using any of the ODE solvers. The symplectic solvers [crate::Symplectic] require that `f` does not depend on `q'`.
+    ///
+    /// # Arguments
+    ///
+    /// - `acc`: Function of type Fn(q: &V, v: &V, p: &V, t: S, a: &mut V) that computes the acceleration `f(t, q, v)`.
+    /// - `acc_jac`: Function of type Fn(q: &V, v: &V, p: &V, t: S, dq: &V, dv: &V, y: &mut V) that computes the multiplication of the Jacobian
+    ///   of the acceleration with the vector `(dq, dv)`, i.e. `y = df/dq dq + df/dv dv`.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> (V, V) that computes the initial position `q` and velocity `v`.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_second_order::<M, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // q'' = -q
+    /// // q(0) = 1, q'(0) = 0
+    /// let problem = OdeBuilder::new()
+    ///    .build_second_order::<M, _, _, _>(
+    ///        |q, _v, _p, _t, a| a.copy_from(&(-q)),
+    ///        |_q, _v, _p, _t, dq, _dv, y| y.copy_from(&(-dq)),
+    ///        |_p, _t| (DVector::from_element(1, 1.0), DVector::from_element(1, 0.0)),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_second_order<M, F, G, I>(
+        self,
+        acc: F,
+        acc_jac: G,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<
+                    M,
+                    impl Fn(&M::V, &M::V, M::T, &mut M::V),
+                    impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+                >,
+                impl Fn(&M::V, M::T) -> M::V,
+            >,
+        >,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &M::V, &mut M::V),
+        I: Fn(&M::V, M::T) -> (M::V, M::V),
+    {
+        let n = init(&Self::build_p(self.p.clone()), M::T::from(self.t0))
+            .0
+            .len();
+        let (rhs, rhs_jac) = Self::second_order_rhs::<M, _, _>(n, acc, acc_jac);
+        self.build_ode(rhs, rhs_jac, Self::second_order_init(init))
+    }
+
+    /// Build a second order ODE problem with a mass matrix `M q'' = f(t, q, q')` (e.g. structural dynamics with `f(t, q, q') = f_ext(t) - C q' - K q`
+    /// for a damping matrix `C` and stiffness matrix `K`), written as the first order system `y = (q, v)` with mass matrix `diag(I, M)` and
+    /// right-hand side `(v, f(t, q, v))`. This can be solved using [crate::GeneralisedAlpha], or any of the ODE solvers that support a mass matrix.
+    ///
+    /// # Arguments
+    ///
+    /// - `force`: Function of type Fn(q: &V, v: &V, p: &V, t: S, y: &mut V) that computes `f(t, q, v)`.
+    /// - `force_jac`: Function of type Fn(q: &V, v: &V, p: &V, t: S, dq: &V, dv: &V, y: &mut V) that computes the multiplication of the Jacobian
+    ///   of `f` with the vector `(dq, dv)`, i.e. `y = df/dq dq + df/dv dv`.
+    /// - `mass`: Function of type Fn(v: &V, p: &V, t: S, beta: S, y: &mut V) that computes a gemv multiplication of the mass matrix `M` with the vector v (i.e. y = M * v + beta * y).
+    /// - `init`: Function of type Fn(p: &V, t: S) -> (V, V) that computes the initial position `q` and velocity `v`.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_second_order_with_mass::<M, _, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // 2 q'' = -0.1 q' - q
+    /// // q(0) = 1, q'(0) = 0
+    /// let problem = OdeBuilder::new()
+    ///    .build_second_order_with_mass::<M, _, _, _, _>(
+    ///        |q, v, _p, _t, y| y.copy_from(&(-q - v * 0.1)),
+    ///        |_q, _v, _p, _t, dq, dv, y| y.copy_from(&(-dq - dv * 0.1)),
+    ///        |v, _p, _t, beta, y| y.axpy(2.0, v, beta),
+    ///        |_p, _t| (DVector::from_element(1, 1.0), DVector::from_element(1, 0.0)),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_second_order_with_mass<M, F, G, H, I>(
+        self,
+        force: F,
+        force_jac: G,
+        mass: H,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<
+                    M,
+                    impl Fn(&M::V, &M::V, M::T, &mut M::V),
+                    impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+                >,
+                impl Fn(&M::V, M::T) -> M::V,
+                LinearClosure<M, impl Fn(&M::V, &M::V, M::T, M::T, &mut M::V)>,
+            >,
+        >,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &M::V, &mut M::V),
+        H: Fn(&M::V, &M::V, M::T, M::T, &mut M::V),
+        I: Fn(&M::V, M::T) -> (M::V, M::V),
+    {
+        let n = init(&Self::build_p(self.p.clone()), M::T::from(self.t0))
+            .0
+            .len();
+        let (rhs, rhs_jac) = Self::second_order_rhs::<M, _, _>(n, force, force_jac);
+
+        // diag(I, M)
+        let buffer = RefCell::new([M::V::zeros(n), M::V::zeros(n)]);
+        let mass = move |x: &M::V, p: &M::V, t: M::T, beta: M::T, y: &mut M::V| {
+            let mut buffer = buffer.borrow_mut();
+            let [x_v, y_v] = &mut *buffer;
+            for i in 0..n {
+                y[i] = x[i] + beta * y[i];
+                x_v[i] = x[n + i];
+                y_v[i] = y[n + i];
+            }
+            mass(x_v, p, t, beta, y_v);
+            for i in 0..n {
+                y[n + i] = y_v[i];
+            }
+        };
+        self.build_ode_with_mass(rhs, rhs_jac, mass, Self::second_order_init(init))
+    }
+
+    /// Build a Hamiltonian system with a separable Hamiltonian `H(q, p) = T(p) + V(q)`, i.e. `q' = dT/dp`, `p' = -dV/dq`, written as the first
+    /// order system `y = (q, p)`. This can be solved using the symplectic solvers [crate::Symplectic], or any of the ODE solvers.
+    ///
+    /// # Arguments
+    ///
+    /// - `kinetic_grad`: Function of type Fn(p: &V, params: &V, t: S, y: &mut V) that computes the gradient of the kinetic energy `dT/dp`.
+    /// - `kinetic_hess`: Function of type Fn(p: &V, params: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Hessian of the kinetic energy with the vector v.
+    /// - `potential_grad`: Function of type Fn(q: &V, params: &V, t: S, y: &mut V) that computes the gradient of the potential energy `dV/dq`.
+    /// - `potential_hess`: Function of type Fn(q: &V, params: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Hessian of the potential energy with the vector v.
+    /// - `init`: Function of type Fn(params: &V, t: S) -> (V, V) that computes the initial position `q` and momentum `p`.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_separable_hamiltonian::<M, _, _, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // H = p^2 / 2 + q^2 / 2
+    /// // q(0) = 1, p(0) = 0
+    /// let problem = OdeBuilder::new()
+    ///    .build_separable_hamiltonian::<M, _, _, _, _, _>(
+    ///        |p, _params, _t, y| y.copy_from(p),
+    ///        |_p, _params, _t, v, y| y.copy_from(v),
+    ///        |q, _params, _t, y| y.copy_from(q),
+    ///        |_q, _params, _t, v, y| y.copy_from(v),
+    ///        |_params, _t| (DVector::from_element(1, 1.0), DVector::from_element(1, 0.0)),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_separable_hamiltonian<M, F1, G1, F2, G2, I>(
+        self,
+        kinetic_grad: F1,
+        kinetic_hess: G1,
+        potential_grad: F2,
+        potential_hess: G2,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<
+                    M,
+                    impl Fn(&M::V, &M::V, M::T, &mut M::V),
+                    impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+                >,
+                impl Fn(&M::V, M::T) -> M::V,
+            >,
+        >,
+    >
+    where
+        M: Matrix,
+        F1: Fn(&M::V, &M::V, M::T, &mut M::V),
+        G1: Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+        F2: Fn(&M::V, &M::V, M::T, &mut M::V),
+        G2: Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+        I: Fn(&M::V, M::T) -> (M::V, M::V),
+    {
+        let n = init(&Self::build_p(self.p.clone()), M::T::from(self.t0))
+            .0
+            .len();
+
+        // (dT/dp, -dV/dq)
+        let buffer = RefCell::new([M::V::zeros(n), M::V::zeros(n), M::V::zeros(n)]);
+        let rhs = move |x: &M::V, params: &M::V, t: M::T, y: &mut M::V| {
+            let mut buffer = buffer.borrow_mut();
+            let [q, p, out] = &mut *buffer;
+            split(x, q, p);
+            kinetic_grad(p, params, t, out);
+            for i in 0..n {
+                y[i] = out[i];
+            }
+            potential_grad(q, params, t, out);
+            for i in 0..n {
+                y[n + i] = -out[i];
+            }
+        };
+        let buffer = RefCell::new([
+            M::V::zeros(n),
+            M::V::zeros(n),
+            M::V::zeros(n),
+            M::V::zeros(n),
+            M::V::zeros(n),
+        ]);
+        let rhs_jac = move |x: &M::V, params: &M::V, t: M::T, v: &M::V, y: &mut M::V| {
+            let mut buffer = buffer.borrow_mut();
+            let [q, p, dq, dp, out] = &mut *buffer;
+            split(x, q, p);
+            split(v, dq, dp);
+            kinetic_hess(p, params, t, dp, out);
+            for i in 0..n {
+                y[i] = out[i];
+            }
+            potential_hess(q, params, t, dq, out);
+            for i in 0..n {
+                y[n + i] = -out[i];
+            }
+        };
+        self.build_ode(rhs, rhs_jac, Self::second_order_init(init))
+    }
+
+    /// Build a linear ODE problem with constant coefficients `y' = A y + b(t)`, such as a compartment model with an input `b(t)`.
+    /// This can be solved exactly using the matrix exponential with [crate::LinearOdeSolver], or with any of the ODE solvers.
+    ///
+    /// # Arguments
+    ///
+    /// - `a`: Function of type Fn(x: &V, p: &V, t: S, beta: S, y: &mut V) that computes a gemv multiplication of the matrix `A` with the vector x (i.e. y = A * x + beta * y).
+    ///   `A` can depend on the parameters but not on the time.
+    /// - `b`: Function of type Fn(p: &V, t: S, y: &mut V) that computes the input `b(t)`.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_linear_ode::<M, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::{OdeBuilder, OdeSolverMethod, LinearOdeSolver};
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // a two compartment model with an infusion into the first compartment for t < 1
+    /// // y' = [[-1, 0], [1, -0.5]] y + b(t)
+    /// let problem = OdeBuilder::new()
+    ///    .build_linear_ode::<M, _, _, _>(
+    ///        |x, _p, _t, beta, y| {
+    ///            y[0] = -x[0] + beta * y[0];
+    ///            y[1] = x[0] - 0.5 * x[1] + beta * y[1];
+    ///        },
+    ///        |_p, t, y| {
+    ///            y[0] = if t < 1.0 { 1.0 } else { 0.0 };
+    ///            y[1] = 0.0;
+    ///        },
+    ///        |_p, _t| DVector::from_element(2, 0.0),
+    ///    ).unwrap();
+    /// let mut solver = LinearOdeSolver::<M, _>::default();
+    /// solver.set_breakpoints(vec![1.0]).unwrap();
+    /// let y = solver.solve(&problem, 2.0).unwrap();
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_linear_ode<M, F, G, I>(
+        self,
+        a: F,
+        b: G,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<
+                    M,
+                    impl Fn(&M::V, &M::V, M::T, &mut M::V),
+                    impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+                >,
+                I,
+            >,
+        >,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, M::T, M::T, &mut M::V),
+        G: Fn(&M::V, M::T, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let a = Rc::new(a);
+        let a_jac = a.clone();
+        let rhs = move |x: &M::V, p: &M::V, t: M::T, y: &mut M::V| {
+            b(p, t, y);
+            a(x, p, t, M::T::one(), y);
+        };
+        let rhs_jac = move |_x: &M::V, p: &M::V, t: M::T, v: &M::V, y: &mut M::V| {
+            a_jac(v, p, t, M::T::zero(), y);
+        };
+        self.build_ode(rhs, rhs_jac, init)
+    }
+
+    // the right-hand side (v, f(t, q, v)) and its jacobian for the second order system y = (q, v)
+    #[allow(clippy::type_complexity)]
+    fn second_order_rhs<M, F, G>(
+        n: usize,
+        acc: F,
+        acc_jac: G,
+    ) -> (
+        impl Fn(&M::V, &M::V, M::T, &mut M::V),
+        impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+    )
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &M::V, &mut M::V),
+    {
+        let buffer = RefCell::new([M::V::zeros(n), M::V::zeros(n), M::V::zeros(n)]);
+        let rhs = move |x: &M::V, p: &M::V, t: M::T, y: &mut M::V| {
+            let mut buffer = buffer.borrow_mut();
+            let [q, v, a] = &mut *buffer;
+            split(x, q, v);
+            acc(q, v, p, t, a);
+            for i in 0..n {
+                y[i] = v[i];
+                y[n + i] = a[i];
+            }
+        };
+        let buffer = RefCell::new([
+            M::V::zeros(n),
+            M::V::zeros(n),
+            M::V::zeros(n),
+            M::V::zeros(n),
+            M::V::zeros(n),
+        ]);
+        let rhs_jac = move |x: &M::V, p: &M::V, t: M::T, w: &M::V, y: &mut M::V| {
+            let mut buffer = buffer.borrow_mut();
+            let [q, v, dq, dv, out] = &mut *buffer;
+            split(x, q, v);
+            split(w, dq, dv);
+            acc_jac(q, v, p, t, dq, dv, out);
+            for i in 0..n {
+                y[i] = dv[i];
+                y[n + i] = out[i];
+            }
+        };
+        (rhs, rhs_jac)
+    }
+
+    // the initial state y = (q, v) for the second order system
+    fn second_order_init<V: Vector>(init: impl Fn(&V, V::T) -> (V, V)) -> impl Fn(&V, V::T) -> V {
+        move |p: &V, t: V::T| {
+            let (q, v) = init(p, t);
+            let n = q.len();
+            let mut y = V::zeros(2 * n);
+            for i in 0..n {
+                y[i] = q[i];
+                y[n + i] = v[i];
+            }
+            y
+        }
+    }
+
+    /// Build a delay differential equation problem `y'(t) = f(t, y(t), y(t - tau_1), ..., y(t - tau_m))` with constant delays `tau_i > 0`,
+    /// where the solution before the initial time is given by a history function `y(t) = phi(p, t)` for `t <= t0`. The problem should be
+    /// solved using a [crate::DdeSolver].
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(x: &V, delayed: &[V], p: &V, t: S, y: &mut V) that computes the right-hand side, where `delayed[i]` is the
+    ///   delayed state `y(t - tau_i)`.
+    /// - `rhs_jac`: Function of type Fn(x: &V, delayed: &[V], p: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Jacobian of the
+    ///   right-hand side with respect to the current state `x` with the vector v.
+    /// - `delays`: The delays `tau_i`.
+    /// - `history`: Function of type Fn(p: &V, t: S) -> V that computes the solution for `t <= t0` (so the initial state is `history(p, t0)`).
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_dde::<M, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::{Bdf, DdeSolver, OdeBuilder};
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt = -y(t - 1)
+    /// // y(t) = 1 for t <= 0
+    /// let problem = OdeBuilder::new()
+    ///    .build_dde::<M, _, _, _>(
+    ///        |_x, d, _p, _t, y| y.copy_from(&(-&d[0])),
+    ///        |_x, _d, _p, _t, _v, y| y.fill(0.0),
+    ///        vec![1.0],
+    ///        |_p, _t| DVector::from_element(1, 1.0),
+    ///    ).unwrap();
+    /// let mut solver = DdeSolver::new(Bdf::default());
+    /// let y = solver.solve(&problem, 2.0).unwrap();
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_dde<M, F, G, H>(
+        self,
+        rhs: F,
+        rhs_jac: G,
+        delays: Vec<f64>,
+        history: H,
+    ) -> Result<
+        DdeProblem<OdeSolverEquations<M, DelayClosure<M, F, G, H>, impl Fn(&M::V, M::T) -> M::V>>,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &[M::V], &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &[M::V], &M::V, M::T, &M::V, &mut M::V),
+        H: Fn(&M::V, M::T) -> M::V,
+    {
+        if delays.is_empty() {
+            return Err(anyhow::anyhow!("at least one delay is required"));
+        }
+        if delays.iter().any(|&tau| tau <= 0.0) {
+            return Err(anyhow::anyhow!("delays must be positive"));
+        }
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::from(self.t0);
+        let y0 = history(&p, t0);
+        let nstates = y0.len();
+        let delays = delays.into_iter().map(M::T::from).collect::<Vec<_>>();
+        let dde_history = Rc::new(RefCell::new(DdeHistory::new(t0)));
+        let history = Rc::new(history);
+        let rhs = DelayClosure::new(
+            rhs,
+            rhs_jac,
+            history.clone(),
+            dde_history.clone(),
+            delays.clone(),
+            nstates,
+            p.clone(),
+        );
+        let mass = Rc::new(UnitCallable::new(nstates));
+        let init = move |p: &M::V, t: M::T| history(p, t);
+        let eqn = OdeSolverEquations::new(Rc::new(rhs), mass, None, init, p, true);
+        let atol = Self::build_atol(self.atol, nstates)?;
+        let ode = OdeSolverProblem::new(eqn, M::T::from(self.rtol), atol, t0, M::T::from(self.h0));
+        Ok(DdeProblem {
+            ode,
+            delays,
+            history: dde_history,
+        })
+    }
+
+    /// Build an ODE problem with a mass matrix that is the identity matrix, where the action of the Jacobian of the right-hand side
+    /// is calculated exactly using forward-mode automatic differentiation. The right-hand side must be generic over the scalar type
+    /// of the states, so it is provided as a type implementing [AutodiffFunction] rather than a closure.
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Type implementing [AutodiffFunction] that computes the right-hand side of the ODE.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_ode_autodiff::<M, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::{AutodiffFunction, AutodiffScalar, OdeBuilder};
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt = -a y^2
+    /// struct Decay;
+    ///
+    /// impl AutodiffFunction<f64> for Decay {
+    ///     fn call<S: AutodiffScalar<f64>>(&self, x: &[S], p: &[f64], _t: f64, y: &mut [S]) {
+    ///         y[0] = -x[0] * x[0] * p[0];
+    ///     }
+    /// }
+    ///
+    /// // y(0) = 1
+    /// let problem = OdeBuilder::new()
+    ///    .p([0.1])
+    ///    .build_ode_autodiff::<M, _, _>(Decay, |p, _t| DVector::from_element(1, 1.0));
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_autodiff<M, F, I>(
+        self,
+        rhs: F,
+        init: I,
+    ) -> Result<OdeSolverProblem<OdeSolverEquations<M, ClosureAutodiff<M, F>, I>>>
+    where
+        M: Matrix,
+        F: AutodiffFunction<M::T>,
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::from(self.t0);
+        let y0 = init(&p, t0);
+        let nstates = y0.len();
+        let mut rhs = ClosureAutodiff::new(rhs, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
+        let mass = Rc::new(UnitCallable::new(nstates));
+        if let Some(non_zeros) = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)? {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
+        }
+        let rhs = Rc::new(rhs);
+        let eqn = OdeSolverEquations::new(rhs, mass, None, init, p, true);
+        let atol = Self::build_atol(self.atol, nstates)?;
+        Ok(OdeSolverProblem::new(
+            eqn,
+            M::T::from(self.rtol),
+            atol,
+            t0,
+            M::T::from(self.h0),
+        ))
+    }
+
+    /// Build an ODE problem with a mass matrix that is the identity matrix, where the Jacobian of the right-hand side is not provided.
+    /// The action of the Jacobian is approximated using directional finite differences, with a step size scaled by the magnitude of
+    /// the state, the solver tolerances and the machine epsilon. If [Self::use_coloring] is set, the full Jacobian is calculated using
+    /// colour-compressed finite differences.
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(x: &V, p: &V, t: S, y: &mut V) that computes the right-hand side of the ODE.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_ode_no_jac::<M, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt = -y
+    /// // y(0) = 1
+    /// let problem = OdeBuilder::new()
+    ///    .build_ode_no_jac::<M, _, _>(
+    ///        |x, _p, _t, y| y.copy_from(&(-x)),
+    ///        |p, _t| DVector::from_element(1, 1.0),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_no_jac<M, F, I>(
+        self,
+        rhs: F,
+        init: I,
+    ) -> Result<OdeSolverProblem<OdeSolverEquations<M, ClosureNoJac<M, F>, I>>>
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, M::T, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::from(self.t0);
+        let rtol = M::T::from(self.rtol);
+        let y0 = init(&p, t0);
+        let nstates = y0.len();
+        let atol = Self::build_atol(self.atol, nstates)?;
+        let mut rhs = ClosureNoJac::new(rhs, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
+        rhs.set_typical_x(&atol);
+        let mass = Rc::new(UnitCallable::new(nstates));
+        if let Some(non_zeros) = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)? {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
+        }
+        let rhs = Rc::new(rhs);
+        let eqn = OdeSolverEquations::new(rhs, mass, None, init, p, true);
+        Ok(OdeSolverProblem::new(
+            eqn,
+            rtol,
+            atol,
+            t0,
+            M::T::from(self.h0),
+        ))
+    }
+
+    /// Build an ODE problem with a mass matrix, where the Jacobian of the right-hand side is not provided (see [Self::build_ode_no_jac]).
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(x: &V, p: &V, t: S, y: &mut V) that computes the right-hand side of the ODE.
+    /// - `mass`: Function of type Fn(v: &V, p: &V, t: S, beta: S, y: &mut V) that computes a gemv multiplication of the mass matrix with the vector v (i.e. y = M * v + beta * y).
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_with_mass_no_jac<M, F, H, I>(
+        self,
+        rhs: F,
+        mass: H,
+        init: I,
+    ) -> Result<OdeSolverProblem<OdeSolverEquations<M, ClosureNoJac<M, F>, I, LinearClosure<M, H>>>>
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, M::T, &mut M::V),
+        H: Fn(&M::V, &M::V, M::T, M::T, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::from(self.t0);
+        let rtol = M::T::from(self.rtol);
+        let y0 = init(&p, t0);
+        let nstates = y0.len();
+        let atol = Self::build_atol(self.atol, nstates)?;
+        let mut rhs = ClosureNoJac::new(rhs, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
+        rhs.set_typical_x(&atol);
+        let mut mass = LinearClosure::new(mass, nstates, nstates, p.clone());
+        let jacobian_non_zeros = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)?;
+        if self.use_coloring || jacobian_non_zeros.is_some() {
+            mass.calculate_sparsity(t0);
+        }
+        if let Some(non_zeros) = jacobian_non_zeros {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
+        }
+        let mass = Rc::new(mass);
+        let rhs = Rc::new(rhs);
+        let eqn = OdeSolverEquations::new(rhs, mass, None, init, p, self.constant_mass);
+        Ok(OdeSolverProblem::new(
+            eqn,
+            rtol,
+            atol,
+            t0,
+            M::T::from(self.h0),
+        ))
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn build_ode_with_root<M, F, G, I, H>(
         self,
@@ -290,10 +1112,13 @@ impl OdeBuilder {
         let y0 = init(&p, t0);
         let nstates = y0.len();
         let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
+        rhs.set_coloring_algorithm(self.coloring_algorithm);
         let mass = Rc::new(UnitCallable::new(nstates));
         let root = Rc::new(ClosureNoJac::new(root, nstates, nroots, p.clone()));
-        if self.use_coloring {
-            rhs.calculate_sparsity(&y0, t0);
+        if let Some(non_zeros) = Self::build_jacobian_non_zeros(self.jacobian_sparsity, nstates)? {
+            rhs.set_sparsity(non_zeros)?;
+        } else if self.use_coloring {
+            rhs.calculate_sparsity_with_probes(&y0, t0, self.sparsity_probes)?;
         }
         let rhs = Rc::new(rhs);
         let eqn = OdeSolverEquations::new(rhs, mass, Some(root), init, p, self.use_coloring);
@@ -307,6 +1132,99 @@ impl OdeBuilder {
         ))
     }
 
+    /// Build a fully implicit DAE problem `F(t, y, y') = 0`, given as a residual (see [crate::ImplicitDaeEquations]).
+    /// The problem can be solved using [crate::Bdf] or [crate::SundialsIda], but not [crate::Sdirk].
+    ///
+    /// # Arguments
+    ///
+    /// - `residual`: Function of type Fn(x: &V, x_dot: &V, p: &V, t: S, y: &mut V) that computes the residual `F(t, x, x_dot)`.
+    /// - `residual_jac`: Function of type Fn(x: &V, x_dot: &V, p: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Jacobian of the residual with respect to the state `x` with the vector v.
+    /// - `residual_jac_dot`: Function of type Fn(x: &V, x_dot: &V, p: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Jacobian of the residual with respect to the time derivative `x_dot` with the vector v.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    /// - `init_dot`: Function of type Fn(p: &V, t: S) -> V that computes the initial time derivative of the state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_implicit_dae::<M, _, _, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt + y = 0
+    /// // z - y = 0
+    /// // y(0) = 0.1, z(0) = 0.1
+    /// let problem = OdeBuilder::new()
+    ///   .build_implicit_dae::<M, _, _, _, _, _>(
+    ///       |x, x_dot, _p, _t, y| {
+    ///           y[0] = x_dot[0] + x[0];
+    ///           y[1] = x[1] - x[0];
+    ///       },
+    ///       |_x, _x_dot, _p, _t, v, y| {
+    ///           y[0] = v[0];
+    ///           y[1] = v[1] - v[0];
+    ///       },
+    ///       |_x, _x_dot, _p, _t, v, y| {
+    ///           y[0] = v[0];
+    ///           y[1] = 0.0;
+    ///       },
+    ///       |_p, _t| DVector::from_element(2, 0.1),
+    ///       |_p, _t| DVector::from_element(2, -0.1),
+    /// );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_implicit_dae<M, F, G, H, I, J>(
+        self,
+        residual: F,
+        residual_jac: G,
+        residual_jac_dot: H,
+        init: I,
+        init_dot: J,
+    ) -> Result<
+        OdeSolverProblem<
+            ImplicitDae<ImplicitDaeSolverEquations<M, ResidualClosure<M, F, G, H>, I, J>>,
+        >,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+        H: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+        J: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::from(self.t0);
+        let y0 = init(&p, t0);
+        let nstates = y0.len();
+        if init_dot(&p, t0).len() != nstates {
+            return Err(anyhow::anyhow!(
+                "init and init_dot must have the same number of states"
+            ));
+        }
+        let residual = Rc::new(ResidualClosure::new(
+            residual,
+            residual_jac,
+            residual_jac_dot,
+            nstates,
+            p.clone(),
+        ));
+        let eqn = ImplicitDae::new(ImplicitDaeSolverEquations::new(
+            residual, None, init, init_dot, p,
+        ));
+        let atol = Self::build_atol(self.atol, nstates)?;
+        Ok(OdeSolverProblem::new(
+            eqn,
+            M::T::from(self.rtol),
+            atol,
+            t0,
+            M::T::from(self.h0),
+        ))
+    }
+
     /// Build an ODE problem using the default dense matrix (see [Self::build_ode]).
     #[allow(clippy::type_complexity)]
     pub fn build_ode_dense<V, F, G, I>(
@@ -346,3 +1264,12 @@ impl OdeBuilder {
         ))
     }
 }
+
+// split x = (a, b) into its two halves
+fn split<V: Vector>(x: &V, a: &mut V, b: &mut V) {
+    let n = a.len();
+    for i in 0..n {
+        a[i] = x[i];
+        b[i] = x[n + i];
+    }
+}
@@ -0,0 +1,519 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use num_traits::{One, Pow, Zero};
+
+use crate::{
+    matrix::default_solver::DefaultSolver,
+    op::bvp::{CollocationCallable, MultipleShootingCallable},
+    NewtonError, NewtonFailure, NewtonGlobalisation, NewtonNonlinearSolver, NonLinearOp,
+    NonLinearSolver, OdeEquations, OdeSolverMethod, OdeSolverProblem, Op, Scalar, SolverProblem,
+    Vector,
+};
+
+use super::fixed_step::{hermite_derivative, hermite_interpolate};
+
+/// Options for [bvp_collocation] and [bvp_shooting].
+#[derive(Clone, Debug)]
+pub struct BvpOptions<T: Scalar> {
+    /// The mesh used by [bvp_collocation] is refined until the relative rms residual on each segment (see [BvpSolution::rms_residuals]) is
+    /// less than this (default `1e-3`).
+    pub tolerance: T,
+    /// The maximum number of mesh points used by [bvp_collocation] (default `1000`).
+    pub max_nodes: usize,
+    /// The maximum number of Newton iterations for each solve of the nonlinear system (default `20`).
+    pub max_iter: usize,
+}
+
+impl<T: Scalar> Default for BvpOptions<T> {
+    fn default() -> Self {
+        Self {
+            tolerance: T::from(1e-3),
+            max_nodes: 1000,
+            max_iter: 20,
+        }
+    }
+}
+
+/// The solution of a boundary value problem found by [bvp_collocation] or [bvp_shooting].
+#[derive(Clone, Debug)]
+pub struct BvpSolution<V: Vector> {
+    /// The mesh points.
+    pub t: Vec<V::T>,
+    /// The solution at the mesh points.
+    pub y: Vec<V>,
+    /// The time derivative `f(t, y)` of the solution at the mesh points.
+    pub dy: Vec<V>,
+    /// The relative rms residual `||(S' - f(t, S)) / (1 + |f(t, S)|)||` on each segment, where `S` is the cubic interpolant used by
+    /// [BvpSolution::interpolate], estimated using Lobatto quadrature.
+    pub rms_residuals: Vec<V::T>,
+    /// The total number of Newton iterations taken.
+    pub number_of_newton_iterations: usize,
+}
+
+impl<V: Vector> BvpSolution<V> {
+    /// Evaluate the solution at time `t` using cubic Hermite interpolation between the mesh points (for [bvp_collocation] this is the
+    /// collocation polynomial). Panics if `t` is outside the mesh.
+    pub fn interpolate(&self, t: V::T) -> V {
+        let n = self.t.len();
+        assert!(
+            t >= self.t[0] && t <= self.t[n - 1],
+            "time is outside the mesh"
+        );
+        let k = (self.t.partition_point(|&t_k| t_k <= t) - 1).min(n - 2);
+        hermite_interpolate(
+            self.t[k],
+            self.t[k + 1],
+            &self.y[k],
+            &self.y[k + 1],
+            &self.dy[k],
+            &self.dy[k + 1],
+            t,
+        )
+    }
+}
+
+fn check_mesh<V: Vector>(mesh: &[V::T], y: &[V], n: usize) -> Result<()> {
+    if mesh.len() < 2 {
+        return Err(anyhow!("the mesh should have at least two points"));
+    }
+    if mesh.windows(2).any(|w| w[1] <= w[0]) {
+        return Err(anyhow!("the mesh should be strictly increasing"));
+    }
+    if y.len() != mesh.len() {
+        return Err(anyhow!(
+            "the initial guess has {} points, but the mesh has {} points",
+            y.len(),
+            mesh.len()
+        ));
+    }
+    if y.iter().any(|y_k| y_k.len() != n) {
+        return Err(anyhow!("the initial guess should have {} states", n));
+    }
+    Ok(())
+}
+
+// the unknowns x = (y_0, ..., y_{N-1})
+fn pack<V: Vector>(y: &[V]) -> V {
+    let n = y[0].len();
+    let mut x = V::zeros(n * y.len());
+    for (k, y_k) in y.iter().enumerate() {
+        for i in 0..n {
+            x[k * n + i] = y_k[i];
+        }
+    }
+    x
+}
+
+// solve H(x) = 0 using Newton's method with a line search, returning the number of iterations
+fn newton_solve<Eqn, C>(
+    callable: Rc<C>,
+    problem: &OdeSolverProblem<Eqn>,
+    x: &mut Eqn::V,
+    t: Eqn::T,
+    max_iter: usize,
+) -> Result<usize>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    C: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    let n = problem.atol.len();
+    let nnodes = x.len() / n;
+    let atol = pack(&vec![problem.atol.as_ref().clone(); nnodes]);
+    let mut newton = NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+    newton.set_globalisation(NewtonGlobalisation::line_search());
+    newton.set_max_iter(max_iter);
+    newton.set_problem(&SolverProblem::new(
+        callable.clone(),
+        Rc::new(atol.clone()),
+        problem.rtol,
+    ));
+    match newton.solve_in_place(x, t) {
+        Ok(()) => Ok(newton.niter()),
+        Err(e) => {
+            // the residual cannot be reduced below the level of the rounding error (or the error of the ODE solves when shooting), so
+            // accept the solution if the line search fails once the residual is within the tolerances
+            let is_line_search_failure = e
+                .downcast_ref::<NewtonError<Eqn::T>>()
+                .is_some_and(|e| e.reason == NewtonFailure::LineSearchFailed);
+            let mut scale = x.abs() * crate::scale(problem.rtol);
+            scale += &atol;
+            let mut f = callable.call(x, t);
+            f.component_div_assign(&scale);
+            if is_line_search_failure && f.norm() <= Eqn::T::one() {
+                Ok(newton.niter())
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+// the solution at the mesh points, with the rms residual on each segment
+fn solution<Eqn: OdeEquations>(
+    problem: &OdeSolverProblem<Eqn>,
+    t: Vec<Eqn::T>,
+    x: &Eqn::V,
+    number_of_newton_iterations: usize,
+) -> BvpSolution<Eqn::V> {
+    let n = problem.eqn.rhs().nstates();
+    let rhs = problem.eqn.rhs();
+    let y = (0..t.len())
+        .map(|k| {
+            let mut y_k = Eqn::V::zeros(n);
+            for i in 0..n {
+                y_k[i] = x[k * n + i];
+            }
+            y_k
+        })
+        .collect::<Vec<_>>();
+    let dy = y
+        .iter()
+        .zip(t.iter())
+        .map(|(y_k, &t_k)| rhs.call(y_k, t_k))
+        .collect::<Vec<_>>();
+
+    // the residual is zero in the middle of each segment for the collocation solution, so the 5-point Lobatto quadrature of the squared
+    // residual only needs the values at the two interior points (1 -+ sqrt(3 / 7)) / 2
+    let offset = Eqn::T::from(21.0).pow(Eqn::T::from(0.5)) / Eqn::T::from(14.0);
+    let half = Eqn::T::from(0.5);
+    let weight = Eqn::T::from(49.0 / 180.0);
+    let rms_residuals = (0..t.len() - 1)
+        .map(|k| {
+            let h = t[k + 1] - t[k];
+            let sum = [half - offset, half + offset]
+                .iter()
+                .fold(Eqn::T::zero(), |acc, &s| {
+                    let t_s = t[k] + s * h;
+                    let (t0, t1) = (t[k], t[k + 1]);
+                    let y_s =
+                        hermite_interpolate(t0, t1, &y[k], &y[k + 1], &dy[k], &dy[k + 1], t_s);
+                    let dy_s =
+                        hermite_derivative(t0, t1, &y[k], &y[k + 1], &dy[k], &dy[k + 1], t_s);
+                    let f_s = rhs.call(&y_s, t_s);
+                    let r = dy_s.binary_fold(&f_s, Eqn::T::zero(), |acc, dy_i, f_i, _| {
+                        let r_i = (dy_i - f_i) / (Eqn::T::one() + num_traits::abs(f_i));
+                        acc + r_i * r_i
+                    });
+                    acc + r
+                });
+            (weight * sum).pow(half)
+        })
+        .collect();
+    BvpSolution {
+        t,
+        y,
+        dy,
+        rms_residuals,
+        number_of_newton_iterations,
+    }
+}
+
+/// Solve the two-point boundary value problem `y' = f(t, y)` on the interval `[a, b]`, with boundary conditions `g(y(a), y(b)) = 0`, using
+/// multiple shooting.
+///
+/// The `problem` gives the right-hand side `f` (it should be an ODE, i.e. without algebraic equations), the tolerances of the ODE solves and
+/// of the Newton iteration, and any parameters of the equations (its initial condition and time are not used). The boundary conditions `bc`
+/// are an operator (e.g. a [crate::Closure]) with `2n` states `(y(a), y(b))` and `n` outputs. The `mesh` `a = t_0 < ... < t_{N-1} = b` gives
+/// the start of each shooting segment, and `y` is the initial guess of the solution at the mesh points.
+///
+/// The states at the mesh points are found by solving the continuity conditions `y(t_{k+1}; t_k, y_k) = y_{k+1}` between the segments
+/// together with the boundary conditions (see [MultipleShootingCallable]) using [NewtonNonlinearSolver] with a line search, where the
+/// ODE on each segment is solved using the given `solver`. The jacobian of the solution on each segment is calculated using forward differences,
+/// so the ODE should be solved with a tolerance that is tight compared with the square root of the required accuracy. As the residual cannot
+/// be reduced below the error of the ODE solves, the solution is accepted if the line search fails once the residual is within the tolerances of
+/// the problem. Only [BvpOptions::max_iter] is used from the `options`, as the accuracy is determined by the tolerances of the ODE solver.
+pub fn bvp_shooting<Eqn, G, S>(
+    solver: &mut S,
+    problem: &OdeSolverProblem<Eqn>,
+    bc: &G,
+    mesh: &[Eqn::T],
+    y: &[Eqn::V],
+    options: &BvpOptions<Eqn::T>,
+) -> Result<BvpSolution<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    S: OdeSolverMethod<Eqn>,
+{
+    check_mesh(mesh, y, problem.eqn.rhs().nstates())?;
+    let callable = Rc::new(MultipleShootingCallable::new(
+        solver,
+        problem,
+        bc,
+        mesh.to_vec(),
+    ));
+    let mut x = pack(y);
+    let result = newton_solve(callable.clone(), problem, &mut x, mesh[0], options.max_iter);
+    if let Some(e) = callable.take_error() {
+        return Err(e);
+    }
+    let number_of_newton_iterations = result?;
+    Ok(solution(
+        problem,
+        mesh.to_vec(),
+        &x,
+        number_of_newton_iterations,
+    ))
+}
+
+/// Solve the two-point boundary value problem `y' = f(t, y)` on the interval `[a, b]`, with boundary conditions `g(y(a), y(b)) = 0`, using
+/// fourth order collocation with mesh refinement (similar to scipy's `solve_bvp`).
+///
+/// The `problem` gives the right-hand side `f` (it should be an ODE, i.e. without algebraic equations), the tolerances of the Newton iteration,
+/// and any parameters of the equations (its initial condition and time are not used). The boundary conditions `bc` are an operator (e.g. a
+/// [crate::Closure]) with `2n` states `(y(a), y(b))` and `n` outputs. The initial `mesh` `a = t_0 < ... < t_{N-1} = b` and the initial guess `y`
+/// of the solution at the mesh points are refined as required.
+///
+/// The solution is approximated by a cubic on each segment that satisfies the ODE at both ends and the middle of the segment (see
+/// [CollocationCallable]), and the resulting nonlinear system for the states at the mesh points is solved using [NewtonNonlinearSolver]
+/// with a line search, where the jacobian is calculated from its sparsity pattern using colouring. The relative rms residual of the cubic on each
+/// segment is then estimated, and one (or two, if the residual is more than 100 times [BvpOptions::tolerance]) mesh points are added to each
+/// segment where it exceeds [BvpOptions::tolerance], using the current solution as the initial guess. An error is returned if the number of mesh
+/// points would exceed [BvpOptions::max_nodes].
+pub fn bvp_collocation<Eqn, G>(
+    problem: &OdeSolverProblem<Eqn>,
+    bc: &G,
+    mesh: &[Eqn::T],
+    y: &[Eqn::V],
+    options: &BvpOptions<Eqn::T>,
+) -> Result<BvpSolution<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    check_mesh(mesh, y, problem.eqn.rhs().nstates())?;
+    let mut mesh = mesh.to_vec();
+    let mut y = y.to_vec();
+    let mut number_of_newton_iterations = 0;
+    loop {
+        let callable = Rc::new(CollocationCallable::new(problem, bc, mesh.clone()));
+        let mut x = pack(&y);
+        number_of_newton_iterations +=
+            newton_solve(callable, problem, &mut x, mesh[0], options.max_iter)?;
+        let solution = solution(problem, mesh, &x, number_of_newton_iterations);
+
+        // add one or two points to each segment where the residual is too large
+        let mut new_mesh = vec![solution.t[0]];
+        let mut new_y = vec![solution.y[0].clone()];
+        for (k, &rms) in solution.rms_residuals.iter().enumerate() {
+            let (t0, t1) = (solution.t[k], solution.t[k + 1]);
+            let fractions = if rms <= options.tolerance {
+                vec![]
+            } else if rms < Eqn::T::from(100.0) * options.tolerance {
+                vec![Eqn::T::from(0.5)]
+            } else {
+                vec![
+                    Eqn::T::one() / Eqn::T::from(3.0),
+                    Eqn::T::from(2.0) / Eqn::T::from(3.0),
+                ]
+            };
+            for s in fractions {
+                let t_s = t0 + s * (t1 - t0);
+                new_mesh.push(t_s);
+                new_y.push(solution.interpolate(t_s));
+            }
+            new_mesh.push(t1);
+            new_y.push(solution.y[k + 1].clone());
+        }
+        if new_mesh.len() == solution.t.len() {
+            return Ok(solution);
+        }
+        if new_mesh.len() > options.max_nodes {
+            let max_rms = solution
+                .rms_residuals
+                .iter()
+                .fold(Eqn::T::zero(), |acc, &r| if r > acc { r } else { acc });
+            return Err(anyhow!(
+                "the maximum number of mesh points ({}) was exceeded, with a maximum rms residual of {}",
+                options.max_nodes,
+                max_rms
+            ));
+        }
+        mesh = new_mesh;
+        y = new_y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{Bdf, Closure, OdeBuilder, OdeEquations, OdeSolverProblem};
+
+    use super::{bvp_collocation, bvp_shooting, BvpOptions};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+    type Bc = Closure<M, fn(&V, &V, f64, &mut V), fn(&V, &V, f64, &V, &mut V)>;
+
+    // y'' = y, with y(0) = 0 and y(1) = 1, which has solution y = sinh(t) / sinh(1)
+    fn linear_problem() -> (
+        OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>>,
+        Bc,
+    ) {
+        let problem = OdeBuilder::new()
+            .rtol(1e-10)
+            .atol([1e-10])
+            .build_ode::<M, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| {
+                    y[0] = x[1];
+                    y[1] = x[0];
+                },
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = v[1];
+                    y[1] = v[0];
+                },
+                |_p: &V, _t| V::zeros(2),
+            )
+            .unwrap();
+        let bc: Bc = Closure::new(
+            |x: &V, _p: &V, _t, y: &mut V| {
+                y[0] = x[0];
+                y[1] = x[2] - 1.0;
+            },
+            |_x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                y[0] = v[0];
+                y[1] = v[2];
+            },
+            4,
+            2,
+            Rc::new(V::zeros(0)),
+        );
+        (problem, bc)
+    }
+
+    // bratu's problem y'' + exp(y) = 0, with y(0) = y(1) = 0, the lower solution is
+    // y = -2 ln(cosh((t - 1/2) theta / 2) / cosh(theta / 4)), where theta = sqrt(2) cosh(theta / 4)
+    fn bratu_problem() -> (
+        OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>>,
+        Bc,
+        impl Fn(f64) -> f64,
+    ) {
+        let problem = OdeBuilder::new()
+            .rtol(1e-10)
+            .atol([1e-10])
+            .build_ode::<M, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| {
+                    y[0] = x[1];
+                    y[1] = -x[0].exp();
+                },
+                |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = v[1];
+                    y[1] = -x[0].exp() * v[0];
+                },
+                |_p: &V, _t| V::zeros(2),
+            )
+            .unwrap();
+        let bc: Bc = Closure::new(
+            |x: &V, _p: &V, _t, y: &mut V| {
+                y[0] = x[0];
+                y[1] = x[2];
+            },
+            |_x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                y[0] = v[0];
+                y[1] = v[2];
+            },
+            4,
+            2,
+            Rc::new(V::zeros(0)),
+        );
+        let theta = (0..100).fold(1.0f64, |theta, _| 2.0f64.sqrt() * (theta / 4.0).cosh());
+        let exact =
+            move |t: f64| -2.0 * (((t - 0.5) * theta / 2.0).cosh() / (theta / 4.0).cosh()).ln();
+        (problem, bc, exact)
+    }
+
+    fn mesh(n: usize) -> Vec<f64> {
+        (0..n).map(|i| i as f64 / (n - 1) as f64).collect()
+    }
+
+    #[test]
+    fn test_bvp_collocation_linear() {
+        let (problem, bc) = linear_problem();
+        let t = mesh(5);
+        let y = vec![V::zeros(2); t.len()];
+        let options = BvpOptions {
+            tolerance: 1e-6,
+            ..Default::default()
+        };
+        let solution = bvp_collocation(&problem, &bc, &t, &y, &options).unwrap();
+        assert!(solution.t.len() > t.len());
+        assert!(solution.rms_residuals.iter().all(|&r| r <= 1e-6));
+        for (t_k, y_k) in solution.t.iter().zip(solution.y.iter()) {
+            let exact = t_k.sinh() / 1.0f64.sinh();
+            assert!(
+                (y_k[0] - exact).abs() < 1e-6,
+                "t {} y {} exact {}",
+                t_k,
+                y_k[0],
+                exact
+            );
+        }
+        let y = solution.interpolate(0.3);
+        assert!((y[0] - 0.3f64.sinh() / 1.0f64.sinh()).abs() < 1e-6);
+        assert!((y[1] - 0.3f64.cosh() / 1.0f64.sinh()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bvp_shooting_linear() {
+        let (problem, bc) = linear_problem();
+        let mut solver = Bdf::default();
+        let t = mesh(4);
+        let y = vec![V::zeros(2); t.len()];
+        let solution =
+            bvp_shooting(&mut solver, &problem, &bc, &t, &y, &BvpOptions::default()).unwrap();
+        assert_eq!(solution.t, t);
+        for (t_k, y_k) in solution.t.iter().zip(solution.y.iter()) {
+            assert!((y_k[0] - t_k.sinh() / 1.0f64.sinh()).abs() < 1e-6);
+            assert!((y_k[1] - t_k.cosh() / 1.0f64.sinh()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_bvp_bratu() {
+        let (problem, bc, exact) = bratu_problem();
+        let y = vec![V::zeros(2); 3];
+
+        // refinement from a coarse mesh
+        let options = BvpOptions {
+            tolerance: 1e-6,
+            ..Default::default()
+        };
+        let solution = bvp_collocation(&problem, &bc, &mesh(3), &y, &options).unwrap();
+        assert!(solution.t.len() > 3);
+        for (t_k, y_k) in solution.t.iter().zip(solution.y.iter()) {
+            assert!(
+                (y_k[0] - exact(*t_k)).abs() < 1e-6,
+                "t {} y {} exact {}",
+                t_k,
+                y_k[0],
+                exact(*t_k)
+            );
+        }
+
+        let mut solver = Bdf::default();
+        let solution = bvp_shooting(&mut solver, &problem, &bc, &mesh(3), &y, &options).unwrap();
+        for (t_k, y_k) in solution.t.iter().zip(solution.y.iter()) {
+            assert!(
+                (y_k[0] - exact(*t_k)).abs() < 1e-6,
+                "t {} y {} exact {}",
+                t_k,
+                y_k[0],
+                exact(*t_k)
+            );
+        }
+
+        // too few mesh points
+        let options = BvpOptions {
+            tolerance: 1e-10,
+            max_nodes: 10,
+            ..Default::default()
+        };
+        let err = bvp_collocation(&problem, &bc, &mesh(3), &y, &options).unwrap_err();
+        assert!(err.to_string().contains("maximum number of mesh points"));
+    }
+}
@@ -0,0 +1,608 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use nalgebra::{DMatrix, DVector, Schur};
+use num_traits::{abs, One, Zero};
+
+use crate::{
+    matrix::{default_solver::DefaultSolver, MatrixRef},
+    op::arclength::ArclengthCallable,
+    vector::DefaultDenseMatrix,
+    NewtonGlobalisation, NewtonNonlinearSolver, NonLinearSolver, OdeEquations, OdeSolverProblem,
+    Scalar, SolverProblem, Vector, VectorRef,
+};
+
+use super::steady_state::{steady_state, SteadyStateOptions};
+
+/// Options for [continuation].
+#[derive(Clone, Debug)]
+pub struct ContinuationOptions<T: Scalar> {
+    /// The initial arclength step (default `1e-2`).
+    pub ds: T,
+    /// The minimum arclength step (default `1e-6`), continuation stops if the corrector fails with a smaller step. This is also the accuracy
+    /// (in arclength) to which the bifurcation points are located.
+    pub ds_min: T,
+    /// The maximum arclength step (default `0.1`).
+    pub ds_max: T,
+    /// The maximum number of points on the branch (default `1000`).
+    pub max_points: usize,
+    /// Continuation stops when the parameter is less than this (default `-inf`).
+    pub param_min: T,
+    /// Continuation stops when the parameter is greater than this (default `inf`).
+    pub param_max: T,
+    /// If true (the default), the branch is followed in the direction of increasing parameter from the initial point, otherwise decreasing.
+    pub increasing: bool,
+}
+
+impl<T: Scalar> Default for ContinuationOptions<T> {
+    fn default() -> Self {
+        Self {
+            ds: T::from(1e-2),
+            ds_min: T::from(1e-6),
+            ds_max: T::from(0.1),
+            max_points: 1000,
+            param_min: -T::INFINITY,
+            param_max: T::INFINITY,
+            increasing: true,
+        }
+    }
+}
+
+/// The type of a bifurcation found by [continuation].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BifurcationKind {
+    /// A fold (saddle-node, or limit point), where the branch turns back in the parameter and a real eigenvalue crosses zero.
+    Fold,
+    /// A branch point, where a real eigenvalue crosses zero but the branch does not turn back (e.g. a transcritical or pitchfork bifurcation).
+    BranchPoint,
+    /// A Hopf bifurcation, where a pair of complex conjugate eigenvalues crosses the imaginary axis.
+    Hopf,
+}
+
+/// A bifurcation found by [continuation].
+#[derive(Clone, Debug)]
+pub struct Bifurcation<V: Vector> {
+    pub kind: BifurcationKind,
+    /// The parameter value at the bifurcation.
+    pub param: V::T,
+    /// The state at the bifurcation.
+    pub y: V,
+    /// The bifurcation lies between the points `index - 1` and `index` of the branch.
+    pub index: usize,
+}
+
+/// A point on a branch of steady states computed by [continuation].
+#[derive(Clone, Debug)]
+pub struct ContinuationPoint<V: Vector> {
+    /// The parameter value.
+    pub param: V::T,
+    /// The steady state.
+    pub y: V,
+    /// The (finite) eigenvalues `(re, im)` of the linearisation of the equations `M v' = J v` about the steady state.
+    pub eigenvalues: Vec<(V::T, V::T)>,
+    /// True if the steady state is linearly stable, i.e. all the eigenvalues have a negative real part.
+    pub stable: bool,
+}
+
+/// A branch of steady states computed by [continuation], with the bifurcations found along it.
+#[derive(Clone, Debug)]
+pub struct ContinuationBranch<V: Vector> {
+    pub points: Vec<ContinuationPoint<V>>,
+    pub bifurcations: Vec<Bifurcation<V>>,
+}
+
+impl<V: Vector> ContinuationBranch<V> {
+    /// The parameter values of the points on the branch (e.g. for plotting).
+    pub fn params(&self) -> Vec<V::T> {
+        self.points.iter().map(|p| p.param).collect()
+    }
+
+    /// The values of the state component `i` of the points on the branch (e.g. for plotting).
+    pub fn component(&self, i: usize) -> Vec<V::T> {
+        self.points.iter().map(|p| p.y[i]).collect()
+    }
+}
+
+/// Follow a branch of steady states of an ODE or DAE as the parameter `params[param_index]` is varied, using pseudo-arclength continuation \[1\].
+///
+/// The parameters of the `problem` are set to `params`, and the initial steady state is found from the initial condition of the problem using
+/// [steady_state]. The branch is then followed using a tangent predictor and a Newton corrector (a [NewtonNonlinearSolver] with a line search) on the hyperplane
+/// orthogonal to the tangent (see [ArclengthCallable]), so that the branch can be followed around folds. The arclength step is halved if the corrector
+/// fails and increased if it converges quickly, and continuation stops when the parameter leaves `[param_min, param_max]`, the maximum number of points
+/// is reached, or the step becomes smaller than `ds_min`.
+///
+/// At each point the jacobian `J` of the equations and the mass matrix `M` (or the jacobians of the residual of a fully implicit DAE) are formed as dense
+/// matrices, and the stability of the steady state is found from the finite eigenvalues of `J v = lambda M v` (the infinite eigenvalues due to the algebraic
+/// equations of a DAE are ignored). Bifurcations are detected by monitoring the sign of the determinant of `J` (folds and branch points) and the number of
+/// complex eigenvalues with positive real part (Hopf bifurcations), and are located by bisection on the arclength step.
+/// As the matrices are dense, this is intended for small to medium sized systems.
+///
+/// \[1\] Keller, H. B. (1977). Numerical solution of bifurcation and nonlinear eigenvalue problems. Applications of Bifurcation Theory, 359-384.
+pub fn continuation<Eqn>(
+    mut problem: OdeSolverProblem<Eqn>,
+    params: Eqn::V,
+    param_index: usize,
+    options: &ContinuationOptions<Eqn::T>,
+) -> Result<ContinuationBranch<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    Eqn::V: DefaultDenseMatrix,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    problem.set_params(params.clone())?;
+    let y0 = steady_state(&problem, &SteadyStateOptions::default())?.y;
+    let p0 = params[param_index];
+    let n = y0.len();
+
+    // the parameter uses the smallest absolute tolerance of the states
+    let atol_param = (0..n).fold(Eqn::T::INFINITY, |acc, i| {
+        if problem.atol[i] < acc {
+            problem.atol[i]
+        } else {
+            acc
+        }
+    });
+    let rtol = problem.rtol;
+    let callable = Rc::new(ArclengthCallable::new(problem, params, param_index)?);
+    let atol = Rc::new(callable.pack(&callable.problem().atol, atol_param));
+    let mut corrector = NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+    corrector.set_max_iter(10);
+    corrector.set_globalisation(NewtonGlobalisation::line_search());
+    corrector.set_problem(&SolverProblem::new(callable.clone(), atol, rtol));
+    let mut cont = Continuation {
+        callable,
+        corrector,
+        n,
+    };
+
+    let mut u = cont.callable.pack(&y0, p0);
+    let mut e_p = Eqn::V::zeros(n + 1);
+    e_p[n] = if options.increasing {
+        Eqn::T::one()
+    } else {
+        -Eqn::T::one()
+    };
+    let mut analysis = cont.analyse(&u, &e_p)?;
+    let mut branch = ContinuationBranch {
+        points: vec![cont.point(&u, &analysis)],
+        bifurcations: Vec::new(),
+    };
+
+    let mut ds = options.ds;
+    while branch.points.len() < options.max_points {
+        let u_new = match cont.correct(&u, &analysis.tangent, ds) {
+            Some(u_new) => u_new,
+            None => {
+                ds *= Eqn::T::from(0.5);
+                if ds < options.ds_min {
+                    break;
+                }
+                continue;
+            }
+        };
+        let quick = cont.corrector.niter() <= 3;
+        let new_analysis = cont.analyse(&u_new, &analysis.tangent)?;
+
+        let index = branch.points.len();
+        if new_analysis.det_sign != analysis.det_sign {
+            let turned = analysis.tangent[n] * new_analysis.tangent[n] < Eqn::T::zero();
+            let kind = if turned {
+                BifurcationKind::Fold
+            } else {
+                BifurcationKind::BranchPoint
+            };
+            let y = cont.locate(&u, &analysis, &u_new, ds, options.ds_min, |a| {
+                a.det_sign == analysis.det_sign
+            })?;
+            branch.bifurcations.push(cont.bifurcation(kind, &y, index));
+        }
+        if new_analysis.unstable_complex != analysis.unstable_complex {
+            let y = cont.locate(&u, &analysis, &u_new, ds, options.ds_min, |a| {
+                a.unstable_complex == analysis.unstable_complex
+            })?;
+            branch
+                .bifurcations
+                .push(cont.bifurcation(BifurcationKind::Hopf, &y, index));
+        }
+
+        branch.points.push(cont.point(&u_new, &new_analysis));
+        u = u_new;
+        analysis = new_analysis;
+        if quick {
+            ds *= Eqn::T::from(1.5);
+            if ds > options.ds_max {
+                ds = options.ds_max;
+            }
+        }
+        if u[n] < options.param_min || u[n] > options.param_max {
+            break;
+        }
+    }
+    Ok(branch)
+}
+
+// the tangent, sign of the determinant of the jacobian and eigenvalues at a point on the branch
+struct PointAnalysis<V: Vector> {
+    tangent: V,
+    det_sign: i8,
+    eigenvalues: Vec<(V::T, V::T)>,
+    unstable_complex: usize,
+}
+
+struct Continuation<Eqn: OdeEquations>
+where
+    Eqn::M: DefaultSolver,
+{
+    callable: Rc<ArclengthCallable<Eqn>>,
+    corrector: NewtonNonlinearSolver<
+        ArclengthCallable<Eqn>,
+        <Eqn::M as DefaultSolver>::LS<ArclengthCallable<Eqn>>,
+    >,
+    n: usize,
+}
+
+impl<Eqn: OdeEquations> Continuation<Eqn>
+where
+    Eqn::M: DefaultSolver,
+{
+    // predict and correct the point a distance ds along the tangent from u, returns None if the corrector fails
+    fn correct(&mut self, u: &Eqn::V, tangent: &Eqn::V, ds: Eqn::T) -> Option<Eqn::V> {
+        self.callable.set_step(u, tangent, ds);
+        let mut x = u.clone();
+        x.axpy(ds, tangent, Eqn::T::one());
+        self.corrector.reset_jacobian(&x, Eqn::T::zero());
+        self.corrector
+            .solve_in_place(&mut x, Eqn::T::zero())
+            .ok()
+            .map(|_| x)
+    }
+
+    fn analyse(&self, u: &Eqn::V, prev_tangent: &Eqn::V) -> Result<PointAnalysis<Eqn::V>> {
+        let n = self.n;
+        let mut y = Eqn::V::zeros(n);
+        let p = self.callable.unpack(u, &mut y);
+
+        // dense jacobian dF/dy (with F = -f, so the linearisation is M v' = -dF/dy v) and mass matrix
+        let mut jac = DMatrix::<Eqn::T>::zeros(n, n);
+        let mut mass = DMatrix::<Eqn::T>::zeros(n, n);
+        let mut e_j = Eqn::V::zeros(n);
+        let mut col = Eqn::V::zeros(n);
+        for j in 0..n {
+            e_j[j] = Eqn::T::one();
+            self.callable.state_jac_mul_inplace(&y, p, &e_j, &mut col);
+            for i in 0..n {
+                jac[(i, j)] = col[i];
+            }
+            col = Eqn::V::zeros(n);
+            self.callable.mass_mul_inplace(&y, p, &e_j, &mut col);
+            for i in 0..n {
+                mass[(i, j)] = col[i];
+            }
+            e_j[j] = Eqn::T::zero();
+        }
+        let mut f_p = Eqn::V::zeros(n);
+        self.callable.param_derivative_inplace(&y, p, &mut f_p);
+
+        // tangent from the bordered system [dF/dy dF/dp; prev_tangent^T] tangent = e_{n+1}, so tangent . prev_tangent > 0
+        let mut bordered = DMatrix::<Eqn::T>::zeros(n + 1, n + 1);
+        bordered.view_mut((0, 0), (n, n)).copy_from(&jac);
+        for i in 0..n {
+            bordered[(i, n)] = f_p[i];
+        }
+        for j in 0..=n {
+            bordered[(n, j)] = prev_tangent[j];
+        }
+        let mut rhs = DVector::<Eqn::T>::zeros(n + 1);
+        rhs[n] = Eqn::T::one();
+        bordered
+            .solve_in_place(&mut rhs)
+            .map_err(|_| anyhow!("Singular jacobian of the continuation problem"))?;
+        let mut tangent = Eqn::V::zeros(n + 1);
+        for i in 0..=n {
+            tangent[i] = rhs[i];
+        }
+        let norm = tangent.norm();
+        tangent *= crate::scale(Eqn::T::one() / norm);
+
+        let det_sign = det_sign(jac.clone());
+        let eigenvalues = generalised_eigenvalues(-jac, &mass)?;
+        let unstable_complex = eigenvalues
+            .iter()
+            .filter(|(re, im)| *re > Eqn::T::zero() && *im != Eqn::T::zero())
+            .count();
+        Ok(PointAnalysis {
+            tangent,
+            det_sign,
+            eigenvalues,
+            unstable_complex,
+        })
+    }
+
+    fn point(&self, u: &Eqn::V, analysis: &PointAnalysis<Eqn::V>) -> ContinuationPoint<Eqn::V> {
+        let mut y = Eqn::V::zeros(self.n);
+        let param = self.callable.unpack(u, &mut y);
+        ContinuationPoint {
+            param,
+            y,
+            stable: analysis
+                .eigenvalues
+                .iter()
+                .all(|(re, _)| *re < Eqn::T::zero()),
+            eigenvalues: analysis.eigenvalues.clone(),
+        }
+    }
+
+    fn bifurcation(&self, kind: BifurcationKind, u: &Eqn::V, index: usize) -> Bifurcation<Eqn::V> {
+        let mut y = Eqn::V::zeros(self.n);
+        let param = self.callable.unpack(u, &mut y);
+        Bifurcation {
+            kind,
+            param,
+            y,
+            index,
+        }
+    }
+
+    // locates a change in the analysis between u (where `is_before` is true) and u_new = correct(u, tangent, ds) using bisection
+    // on the arclength step, returns the first point found after the change
+    fn locate(
+        &mut self,
+        u: &Eqn::V,
+        analysis: &PointAnalysis<Eqn::V>,
+        u_new: &Eqn::V,
+        ds: Eqn::T,
+        ds_min: Eqn::T,
+        is_before: impl Fn(&PointAnalysis<Eqn::V>) -> bool,
+    ) -> Result<Eqn::V> {
+        let mut lo = Eqn::T::zero();
+        let mut hi = ds;
+        let mut after = u_new.clone();
+        while hi - lo > ds_min {
+            let mid = (lo + hi) * Eqn::T::from(0.5);
+            let u_mid = match self.correct(u, &analysis.tangent, mid) {
+                Some(u_mid) => u_mid,
+                None => break,
+            };
+            if is_before(&self.analyse(&u_mid, &analysis.tangent)?) {
+                lo = mid;
+            } else {
+                hi = mid;
+                after = u_mid;
+            }
+        }
+        Ok(after)
+    }
+}
+
+// the sign of the determinant of a small dense matrix from its LU factorisation, zero if the matrix is singular
+fn det_sign<T: Scalar>(a: DMatrix<T>) -> i8 {
+    let lu = a.lu();
+    if !lu.is_invertible() {
+        return 0;
+    }
+    let sign = if lu.p().determinant::<T>() < T::zero() {
+        -1
+    } else {
+        1
+    };
+    lu.u().diagonal().iter().fold(
+        sign,
+        |sign, u_ii| if *u_ii < T::zero() { -sign } else { sign },
+    )
+}
+
+// the finite eigenvalues (re, im) of the generalised eigenvalue problem a v = lambda b v, where b may be singular. These are found from the
+// eigenvalues mu = 1 / (lambda - sigma) of the shift-inverted matrix (a - sigma b)^{-1} b, the infinite eigenvalues give mu = 0 and are removed.
+fn generalised_eigenvalues<T: Scalar>(a: DMatrix<T>, b: &DMatrix<T>) -> Result<Vec<(T, T)>> {
+    let scale = a
+        .iter()
+        .chain(b.iter())
+        .fold(T::one(), |acc, x| if abs(*x) > acc { abs(*x) } else { acc });
+    // a - sigma b is only singular for all shifts if the pencil is singular, so give up after a few shifts
+    let (sigma, c) = std::iter::successors(Some(T::from(1.234) * scale), |sigma| {
+        Some(*sigma * T::from(-1.5))
+    })
+    .take(10)
+    .find_map(|sigma| (a.clone() - b * sigma).lu().solve(b).map(|c| (sigma, c)))
+    .ok_or_else(|| anyhow!("Singular matrix pencil"))?;
+    let mu = eigenvalues(c)?;
+    let mu_max = mu.iter().fold(T::zero(), |acc, (re, im)| {
+        let abs = (*re * *re + *im * *im).pow(T::from(0.5));
+        if abs > acc {
+            abs
+        } else {
+            acc
+        }
+    });
+    let tol = T::from(1e3) * T::EPSILON * mu_max;
+    Ok(mu
+        .into_iter()
+        .filter(|(re, im)| (*re * *re + *im * *im).pow(T::from(0.5)) > tol)
+        .map(|(re, im)| {
+            let abs2 = re * re + im * im;
+            (sigma + re / abs2, -im / abs2)
+        })
+        .collect())
+}
+
+// the eigenvalues (re, im) of a small dense matrix, found from the 1x1 and 2x2 diagonal blocks of its real Schur form. Complex conjugate
+// pairs are returned with exactly opposite imaginary parts, and real eigenvalues with an imaginary part of exactly zero.
+pub(crate) fn eigenvalues<T: Scalar>(a: DMatrix<T>) -> Result<Vec<(T, T)>> {
+    let n = a.nrows();
+    let (_, t) = Schur::try_new(a, nalgebra::convert(f64::EPSILON), 100 * n.max(1))
+        .ok_or_else(|| anyhow!("Schur decomposition did not converge"))?
+        .unpack();
+    let mut eigenvalues = Vec::with_capacity(n);
+    let mut i = 0;
+    while i < n {
+        if i + 1 == n || t[(i + 1, i)] == T::zero() {
+            eigenvalues.push((t[(i, i)], T::zero()));
+            i += 1;
+            continue;
+        }
+        let half = T::from(0.5);
+        let mean = half * (t[(i, i)] + t[(i + 1, i + 1)]);
+        let diff = half * (t[(i, i)] - t[(i + 1, i + 1)]);
+        let discriminant = diff * diff + t[(i, i + 1)] * t[(i + 1, i)];
+        let root = abs(discriminant).pow(half);
+        if discriminant >= T::zero() {
+            eigenvalues.push((mean + root, T::zero()));
+            eigenvalues.push((mean - root, T::zero()));
+        } else {
+            eigenvalues.push((mean, root));
+            eigenvalues.push((mean, -root));
+        }
+        i += 2;
+    }
+    Ok(eigenvalues)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OdeBuilder, OdeEquations, OdeSolverProblem};
+
+    use super::{
+        continuation, eigenvalues, generalised_eigenvalues, BifurcationKind, ContinuationOptions,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_dense_eigenvalues() {
+        // block diagonal with eigenvalues 1 +- 2i, 3 and -4, mixed by a permutation similarity transform
+        let a = M::from_row_slice(
+            4,
+            4,
+            &[
+                3.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, -2.0, 0.0, 0.0, -4.0, 0.0, 0.0, 2.0, 0.0, 1.0,
+            ],
+        );
+        let mut eigs = eigenvalues(a).unwrap();
+        eigs.sort_by(|a, b| (a.0, a.1).partial_cmp(&(b.0, b.1)).unwrap());
+        let expect = [(-4.0, 0.0), (1.0, -2.0), (1.0, 2.0), (3.0, 0.0)];
+        for ((re, im), (re_e, im_e)) in eigs.iter().zip(expect.iter()) {
+            assert!((re - re_e).abs() < 1e-10, "{:?}", eigs);
+            assert!((im - im_e).abs() < 1e-10, "{:?}", eigs);
+        }
+    }
+
+    #[test]
+    fn test_generalised_eigenvalues_singular_pencil() {
+        // a - sigma b is singular for every shift sigma
+        let a = M::from_row_slice(2, 2, &[1.0, 0.0, 0.0, 0.0]);
+        let b = M::from_row_slice(2, 2, &[1.0, 0.0, 0.0, 0.0]);
+        assert!(generalised_eigenvalues(a, &b).is_err());
+    }
+
+    // dy1/dt = p - y1^2
+    // dy2/dt = y1 - y2 (or 0 = y1 - y2 if algebraic)
+    // with steady states y1 = y2 = +-sqrt(p), and a fold at p = 0
+    fn fold_problem(algebraic: bool) -> OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>> {
+        OdeBuilder::new()
+            .p([1.0])
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, p: &V, _t, y: &mut V| {
+                    y[0] = p[0] - x[0] * x[0];
+                    y[1] = x[0] - x[1];
+                },
+                |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = -2.0 * x[0] * v[0];
+                    y[1] = v[0] - v[1];
+                },
+                move |v: &V, _p: &V, _t, beta, y: &mut V| {
+                    y[0] = v[0] + beta * y[0];
+                    y[1] = if algebraic {
+                        beta * y[1]
+                    } else {
+                        v[1] + beta * y[1]
+                    };
+                },
+                |_p: &V, _t| V::from_vec(vec![1.0, 1.0]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_continuation_fold() {
+        for algebraic in [false, true] {
+            let problem = fold_problem(algebraic);
+            let options = ContinuationOptions {
+                increasing: false,
+                param_max: 1.5,
+                ..Default::default()
+            };
+            let branch = continuation(problem, V::from_vec(vec![1.0]), 0, &options).unwrap();
+
+            // the branch goes around the fold and leaves at p = 1.5 on the lower branch
+            assert_eq!(branch.bifurcations.len(), 1);
+            let fold = &branch.bifurcations[0];
+            assert_eq!(fold.kind, BifurcationKind::Fold);
+            assert!(fold.param.abs() < 1e-8, "fold at p = {}", fold.param);
+            assert!(fold.y[0].abs() < 1e-4, "fold at y = {}", fold.y[0]);
+            let last = branch.points.last().unwrap();
+            assert!(last.param > 1.5);
+            assert!(last.y[0] < -1.2);
+
+            // steady states are on the parabola, stable on the upper branch and unstable on the lower
+            for point in branch.points.iter() {
+                assert!((point.param - point.y[0] * point.y[0]).abs() < 1e-6);
+                assert!((point.y[0] - point.y[1]).abs() < 1e-6);
+                if point.y[0].abs() > 1e-3 {
+                    assert_eq!(point.stable, point.y[0] > 0.0);
+                }
+            }
+            assert_eq!(
+                branch.points[0].eigenvalues.len(),
+                if algebraic { 1 } else { 2 }
+            );
+            assert_eq!(branch.params().len(), branch.points.len());
+            assert_eq!(branch.component(0).len(), branch.points.len());
+        }
+    }
+
+    #[test]
+    fn test_continuation_hopf() {
+        // hopf normal form, with steady state y = 0 and eigenvalues p +- i
+        let problem = OdeBuilder::new()
+            .p([-1.0])
+            .build_ode::<M, _, _, _>(
+                |x: &V, p: &V, _t, y: &mut V| {
+                    let r2 = x[0] * x[0] + x[1] * x[1];
+                    y[0] = p[0] * x[0] - x[1] - x[0] * r2;
+                    y[1] = x[0] + p[0] * x[1] - x[1] * r2;
+                },
+                |x: &V, p: &V, _t, v: &V, y: &mut V| {
+                    let r2 = x[0] * x[0] + x[1] * x[1];
+                    y[0] =
+                        (p[0] - r2 - 2.0 * x[0] * x[0]) * v[0] - (1.0 + 2.0 * x[0] * x[1]) * v[1];
+                    y[1] =
+                        (1.0 - 2.0 * x[0] * x[1]) * v[0] + (p[0] - r2 - 2.0 * x[1] * x[1]) * v[1];
+                },
+                |_p: &V, _t| V::from_vec(vec![0.0, 0.0]),
+            )
+            .unwrap();
+        let options = ContinuationOptions {
+            param_max: 1.0,
+            ..Default::default()
+        };
+        let branch = continuation(problem, V::from_vec(vec![-1.0]), 0, &options).unwrap();
+        assert_eq!(branch.bifurcations.len(), 1);
+        let hopf = &branch.bifurcations[0];
+        assert_eq!(hopf.kind, BifurcationKind::Hopf);
+        assert!(hopf.param.abs() < 1e-5, "hopf at p = {}", hopf.param);
+        for point in branch.points.iter() {
+            assert!(point.y.norm() < 1e-10);
+            assert_eq!(point.eigenvalues.len(), 2);
+            if point.param.abs() > 1e-3 {
+                assert_eq!(point.stable, point.param < 0.0);
+                for (re, im) in point.eigenvalues.iter() {
+                    assert!((re - point.param).abs() < 1e-6);
+                    assert!((im.abs() - 1.0).abs() < 1e-6);
+                }
+            }
+        }
+    }
+}
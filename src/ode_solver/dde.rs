@@ -0,0 +1,483 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use num_traits::{abs, One};
+
+use crate::{
+    scalar::Scalar, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+    OdeSolverStopReason, Vector,
+};
+
+// the solution at four equally spaced times over a step, from t_start to t_end
+#[derive(Clone)]
+struct HistoryStep<V: Vector> {
+    t_start: V::T,
+    t_end: V::T,
+    y: [V; 4],
+}
+
+/// The history of the solution of a delay differential equation after the initial time, used to evaluate the delayed states (see [DdeSolver]).
+///
+/// Each step of the solver is stored as the solution at four equally spaced times over the step (the end points, and two interior points found
+/// using [OdeSolverMethod::interpolate]), and the solution is interpolated using the cubic through these points. Steps that end more than the
+/// largest delay before the current time are discarded.
+#[derive(Clone)]
+pub struct DdeHistory<V: Vector> {
+    t0: V::T,
+    steps: Vec<HistoryStep<V>>,
+}
+
+impl<V: Vector> DdeHistory<V> {
+    /// Create an empty history starting at the initial time `t0`.
+    pub fn new(t0: V::T) -> Self {
+        Self { t0, steps: vec![] }
+    }
+
+    /// Discard all the steps, and set the initial time to `t0`.
+    pub fn reset(&mut self, t0: V::T) {
+        self.t0 = t0;
+        self.steps.clear();
+    }
+
+    /// The initial time, before which the history function is used.
+    pub fn t0(&self) -> V::T {
+        self.t0
+    }
+
+    /// The earliest time stored in the history (or the initial time if no steps are stored).
+    pub fn start_time(&self) -> V::T {
+        self.steps.first().map(|s| s.t_start).unwrap_or(self.t0)
+    }
+
+    /// The latest time stored in the history (or the initial time if no steps are stored).
+    pub fn end_time(&self) -> V::T {
+        self.steps.last().map(|s| s.t_end).unwrap_or(self.t0)
+    }
+
+    /// The number of steps stored.
+    pub fn number_of_steps(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Add a step from `t_start` to `t_end`, with the solution `y` at `t_start`, `t_start + h / 3`, `t_start + 2 h / 3` and `t_end`
+    /// (with `h = t_end - t_start`).
+    pub fn push_step(&mut self, t_start: V::T, t_end: V::T, y: [V; 4]) {
+        self.steps.push(HistoryStep { t_start, t_end, y });
+    }
+
+    /// Discard the steps that end before the time `t`.
+    pub fn discard_before(&mut self, t: V::T) {
+        let n = self.steps.partition_point(|s| s.t_end < t);
+        self.steps.drain(0..n);
+    }
+
+    /// Interpolate the solution at time `t` into `y`, returning false (and leaving `y` unchanged) if `t` is not after the initial time or
+    /// no steps are stored. Times outside the stored steps are extrapolated from the nearest step.
+    pub fn interpolate_inplace(&self, t: V::T, y: &mut V) -> bool {
+        if t <= self.t0 || self.steps.is_empty() {
+            return false;
+        }
+        let k = self
+            .steps
+            .partition_point(|s| s.t_end < t)
+            .min(self.steps.len() - 1);
+        let step = &self.steps[k];
+        let s = (t - step.t_start) / (step.t_end - step.t_start);
+
+        // lagrange basis for the nodes 0, 1/3, 2/3, 1
+        let one = V::T::one();
+        let third = one / V::T::from(3.0);
+        let (s0, s1, s2, s3) = (s, s - third, s - third - third, s - one);
+        let (a, b) = (V::T::from(4.5), V::T::from(13.5));
+        let weights = [
+            -a * s1 * s2 * s3,
+            b * s0 * s2 * s3,
+            -b * s0 * s1 * s3,
+            a * s0 * s1 * s2,
+        ];
+        y.copy_from(&step.y[0]);
+        *y *= crate::scale(weights[0]);
+        for (w, y_i) in weights.iter().zip(step.y.iter()).skip(1) {
+            y.axpy(*w, y_i, one);
+        }
+        true
+    }
+}
+
+/// A delay differential equation problem `y'(t) = f(t, y(t), y(t - tau_1), ..., y(t - tau_m))` with constant delays `tau_i > 0`, and
+/// `y(t) = phi(t)` for `t <= t0`, created using [crate::OdeBuilder::build_dde].
+///
+/// The right-hand side of the ODE problem `ode` evaluates the delayed states using the history function `phi` and the `history` of the solution,
+/// so the problem should be solved using a [DdeSolver], which updates the history after each step.
+pub struct DdeProblem<Eqn: OdeEquations> {
+    pub ode: OdeSolverProblem<Eqn>,
+    pub delays: Vec<Eqn::T>,
+    pub history: Rc<RefCell<DdeHistory<Eqn::V>>>,
+}
+
+impl<Eqn: OdeEquations> Clone for DdeProblem<Eqn> {
+    fn clone(&self) -> Self {
+        Self {
+            ode: self.ode.clone(),
+            delays: self.delays.clone(),
+            history: self.history.clone(),
+        }
+    }
+}
+
+/// Solver for delay differential equations ([DdeProblem]) using the method of steps, wrapping any of the ODE solvers (e.g. [crate::Bdf] or
+/// [crate::Sdirk]).
+///
+/// Each step is limited to the smallest delay (using [OdeSolverMethod::set_stop_time]), so that the delayed states during the step only depend on
+/// the solution at previous steps, which is stored in the [DdeHistory] of the problem after each step. The solution of a DDE generally has
+/// discontinuities in its derivatives at the times `t0 + tau_i`, `t0 + tau_i + tau_j`, ... (as the history function is not a solution of the DDE), so the
+/// solver steps exactly to each of these times (up to [Self::set_max_discontinuity_order] delays after `t0`) and restarts the underlying solver there.
+/// Note that restarting the underlying solver resets its statistics.
+pub struct DdeSolver<Eqn: OdeEquations, S: OdeSolverMethod<Eqn>> {
+    solver: S,
+    problem: Option<DdeProblem<Eqn>>,
+    // the discontinuities after the current time, in decreasing order
+    discontinuities: Vec<Eqn::T>,
+    max_discontinuity_order: usize,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn: OdeEquations, S: OdeSolverMethod<Eqn>> DdeSolver<Eqn, S> {
+    /// Create a new DDE solver using the given ODE solver, tracking discontinuities up to order 5.
+    pub fn new(solver: S) -> Self {
+        Self {
+            solver,
+            problem: None,
+            discontinuities: vec![],
+            max_discontinuity_order: 5,
+            tstop: None,
+        }
+    }
+
+    /// Set the number of delays after the initial time up to which discontinuities are tracked, i.e. `tau_i` (order 1), `tau_i + tau_j` (order 2), ...
+    /// Takes effect on the next call to [Self::set_problem].
+    pub fn set_max_discontinuity_order(&mut self, order: usize) {
+        self.max_discontinuity_order = order;
+    }
+
+    /// The underlying ODE solver.
+    pub fn solver(&self) -> &S {
+        &self.solver
+    }
+
+    /// The problem being solved, if set.
+    pub fn problem(&self) -> Option<&DdeProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    /// The current state of the solver, if it exists.
+    pub fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.solver.state()
+    }
+
+    /// Set the problem to solve, this resets the history of the problem and initialises the solver at the initial time.
+    /// An error is returned if the underlying solver does not support the problem.
+    pub fn set_problem(&mut self, problem: &DdeProblem<Eqn>) -> Result<()> {
+        let ode = &problem.ode;
+        problem.history.borrow_mut().reset(ode.t0);
+
+        // discontinuities at t0 + sums of up to max_discontinuity_order delays
+        let mut level = vec![ode.t0];
+        let mut discontinuities = Vec::new();
+        for _ in 0..self.max_discontinuity_order {
+            level = level
+                .iter()
+                .flat_map(|&d| problem.delays.iter().map(move |&tau| d + tau))
+                .collect();
+            Self::sort_and_dedup(&mut level);
+            discontinuities.extend(level.iter().copied());
+        }
+        Self::sort_and_dedup(&mut discontinuities);
+        discontinuities.reverse();
+        self.discontinuities = discontinuities;
+        self.tstop = None;
+
+        let state = OdeSolverState::new(ode);
+        self.solver.set_problem(state, ode)?;
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn sort_and_dedup(times: &mut Vec<Eqn::T>) {
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times.dedup_by(|a, b| abs(*a - *b) <= Self::roundoff(*b));
+    }
+
+    fn roundoff(t: Eqn::T) -> Eqn::T {
+        Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(t) + Eqn::T::one())
+    }
+
+    /// Set the time at which [Self::step] returns [OdeSolverStopReason::TstopReached], the solver steps exactly to this time.
+    pub fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let t = self
+            .solver
+            .state()
+            .ok_or(anyhow!(
+                "DdeSolver::set_stop_time() called before set_problem"
+            ))?
+            .t;
+        if tstop <= t + Self::roundoff(t) {
+            return Err(anyhow!("tstop is at or before current time t = {}", t));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    /// Take a step of the underlying ODE solver, limited to the smallest delay, the next discontinuity and the stop time, and add it to the history.
+    pub fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let problem = self
+            .problem
+            .as_ref()
+            .ok_or(anyhow!("DdeSolver::step() called before set_problem"))?;
+        let state = self.solver.state().unwrap();
+        let (t_prev, y_prev) = (state.t, state.y.clone());
+        while self
+            .discontinuities
+            .last()
+            .is_some_and(|&d| d <= t_prev + Self::roundoff(t_prev))
+        {
+            self.discontinuities.pop();
+        }
+
+        // limit the step so that the delayed states are in the history
+        let (tau_min, tau_max) = problem.delays.iter().skip(1).fold(
+            (problem.delays[0], problem.delays[0]),
+            |(min, max), &tau| {
+                (
+                    if tau < min { tau } else { min },
+                    if tau > max { tau } else { max },
+                )
+            },
+        );
+        let mut tstop = t_prev + tau_min;
+        if let Some(&d) = self.discontinuities.last() {
+            if d < tstop {
+                tstop = d;
+            }
+        }
+        if let Some(user_tstop) = self.tstop {
+            if user_tstop < tstop {
+                tstop = user_tstop;
+            }
+        }
+        self.solver.set_stop_time(tstop)?;
+        let reason = self.solver.step()?;
+
+        // add the step to the history
+        let state = self.solver.state().unwrap();
+        let t = state.t;
+        let y = state.y.clone();
+        let third = (t - t_prev) / Eqn::T::from(3.0);
+        let y1 = self.solver.interpolate(t_prev + third)?;
+        let y2 = self.solver.interpolate(t_prev + third + third)?;
+        {
+            let mut history = problem.history.borrow_mut();
+            history.push_step(t_prev, t, [y_prev, y1, y2, y]);
+            history.discard_before(t - tau_max);
+        }
+
+        match reason {
+            OdeSolverStopReason::TstopReached => {
+                let roundoff = Self::roundoff(t);
+                if self
+                    .discontinuities
+                    .last()
+                    .is_some_and(|&d| abs(d - t) <= roundoff)
+                {
+                    self.discontinuities.pop();
+                    let mut state = self.solver.state().unwrap().clone();
+                    problem
+                        .ode
+                        .eqn
+                        .rhs()
+                        .call_inplace(&state.y, state.t, &mut state.dy);
+                    self.solver.set_problem(state, &problem.ode)?;
+                }
+                if self
+                    .tstop
+                    .is_some_and(|user_tstop| abs(user_tstop - t) <= roundoff)
+                {
+                    self.tstop = None;
+                    Ok(OdeSolverStopReason::TstopReached)
+                } else {
+                    Ok(OdeSolverStopReason::InternalTimestep)
+                }
+            }
+            reason => Ok(reason),
+        }
+    }
+
+    /// Interpolate the solution at time `t`, which should be before the current time and not before the start of the stored history
+    /// (for times before the initial time the history function is used).
+    pub fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let problem = self.problem.as_ref().ok_or(anyhow!("Problem not set"))?;
+        let state = self.solver.state().ok_or(anyhow!("State not set"))?;
+        if t > state.t {
+            return Err(anyhow!("Interpolation time is after current time"));
+        }
+        let history = problem.history.borrow();
+        if t <= history.t0() {
+            return Ok(problem.ode.eqn.init(t));
+        }
+        if t < history.start_time() {
+            return Err(anyhow!(
+                "Interpolation time is before the start of the stored history"
+            ));
+        }
+        let mut y = Eqn::V::zeros(state.y.len());
+        history.interpolate_inplace(t, &mut y);
+        Ok(y)
+    }
+
+    /// Reinitialise the solver and solve the problem up to time `t`.
+    pub fn solve(&mut self, problem: &DdeProblem<Eqn>, t: Eqn::T) -> Result<Eqn::V> {
+        self.set_problem(problem)?;
+        self.set_stop_time(t)?;
+        while !matches!(self.step()?, OdeSolverStopReason::TstopReached) {}
+        Ok(self.state().unwrap().y.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::equations::OdeEquations, Bdf, DdeProblem, DdeSolver, NalgebraLU, OdeBuilder,
+        OdeSolverMethod, OdeSolverStopReason, Sdirk, Tableau,
+    };
+
+    use super::DdeHistory;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // y'(t) = -y(t - 1) with y(t) = 1 for t <= 0, the solution is y = 1 - t on [0, 1], y = 1 - t + (t - 1)^2 / 2 on [1, 2]
+    // and y = 1 - t + (t - 1)^2 / 2 - (t - 2)^3 / 6 on [2, 3]
+    fn delayed_decay() -> (
+        DdeProblem<impl OdeEquations<M = M, V = V, T = f64>>,
+        impl Fn(f64) -> f64,
+    ) {
+        let problem = OdeBuilder::new()
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_dde::<M, _, _, _>(
+                |_x: &V, d: &[V], _p: &V, _t, y: &mut V| y[0] = -d[0][0],
+                |_x: &V, _d: &[V], _p: &V, _t, _v: &V, y: &mut V| y[0] = 0.0,
+                vec![1.0],
+                |_p: &V, _t| V::from_vec(vec![1.0]),
+            )
+            .unwrap();
+        let exact = |t: f64| {
+            let mut y = 1.0 - t;
+            if t > 1.0 {
+                y += (t - 1.0).powi(2) / 2.0;
+            }
+            if t > 2.0 {
+                y -= (t - 2.0).powi(3) / 6.0;
+            }
+            y
+        };
+        (problem, exact)
+    }
+
+    #[test]
+    fn test_dde_history_interpolation() {
+        let mut history = DdeHistory::<V>::new(0.0);
+        let mut y = V::zeros(1);
+        assert!(!history.interpolate_inplace(0.5, &mut y));
+        let cubic = |t: f64| V::from_vec(vec![1.0 - 2.0 * t + t.powi(3)]);
+        for (t0, t1) in [(0.0, 0.5), (0.5, 2.0)] {
+            let h = (t1 - t0) / 3.0;
+            history.push_step(
+                t0,
+                t1,
+                [cubic(t0), cubic(t0 + h), cubic(t0 + 2.0 * h), cubic(t1)],
+            );
+        }
+        for t in [0.1, 0.5, 0.7, 1.9, 2.0] {
+            assert!(history.interpolate_inplace(t, &mut y));
+            assert!((y[0] - cubic(t)[0]).abs() < 1e-12);
+        }
+        assert!(!history.interpolate_inplace(-0.1, &mut y));
+        history.discard_before(1.0);
+        assert_eq!(history.number_of_steps(), 1);
+        assert_eq!(history.start_time(), 0.5);
+        assert_eq!(history.end_time(), 2.0);
+    }
+
+    #[test]
+    fn test_dde_bdf() {
+        let (problem, exact) = delayed_decay();
+        let mut solver = DdeSolver::new(Bdf::default());
+        let y = solver.solve(&problem, 3.0).unwrap();
+        assert!(
+            (y[0] - exact(3.0)).abs() < 1e-5,
+            "y {} exact {}",
+            y[0],
+            exact(3.0)
+        );
+        for t in [2.1, 2.5, 2.9] {
+            let y = solver.interpolate(t).unwrap();
+            assert!((y[0] - exact(t)).abs() < 1e-5);
+        }
+        assert_eq!(solver.interpolate(-1.0).unwrap()[0], 1.0);
+        assert!(solver.interpolate(0.5).is_err());
+    }
+
+    #[test]
+    fn test_dde_sdirk() {
+        let (problem, exact) = delayed_decay();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let mut solver = DdeSolver::new(Sdirk::new(tableau, NalgebraLU::default()));
+        let y = solver.solve(&problem, 3.0).unwrap();
+        assert!(
+            (y[0] - exact(3.0)).abs() < 1e-5,
+            "y {} exact {}",
+            y[0],
+            exact(3.0)
+        );
+    }
+
+    #[test]
+    fn test_dde_discontinuities() {
+        // y'(t) = -y(t - 1) / 2 - y(t - 0.4) / 2
+        let problem = OdeBuilder::new()
+            .build_dde::<M, _, _, _>(
+                |_x: &V, d: &[V], _p: &V, _t, y: &mut V| y[0] = -0.5 * (d[0][0] + d[1][0]),
+                |_x: &V, _d: &[V], _p: &V, _t, _v: &V, y: &mut V| y[0] = 0.0,
+                vec![1.0, 0.4],
+                |_p: &V, _t| V::from_vec(vec![1.0]),
+            )
+            .unwrap();
+        let mut solver = DdeSolver::new(Bdf::default());
+        solver.set_max_discontinuity_order(2);
+        solver.set_problem(&problem).unwrap();
+        solver.set_stop_time(2.5).unwrap();
+        let mut times = vec![];
+        loop {
+            let reason = solver.step().unwrap();
+            let state = solver.state().unwrap();
+            times.push(state.t);
+            if let OdeSolverStopReason::TstopReached = reason {
+                break;
+            }
+            // steps are limited by the smallest delay
+            assert!(
+                state.t - times[times.len().saturating_sub(2)] <= 0.4 + 1e-12 || times.len() == 1
+            );
+        }
+        for d in [0.4, 0.8, 1.0, 1.4, 2.0, 2.5] {
+            assert!(
+                times.iter().any(|&t| (t - d).abs() < 1e-12),
+                "{} not in {:?}",
+                d,
+                times
+            );
+        }
+        assert!(solver.solver().state().is_some());
+    }
+}
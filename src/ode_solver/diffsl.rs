@@ -4,7 +4,9 @@ use anyhow::Result;
 use diffsl::execution::Compiler;
 
 use crate::{
-    jacobian::{find_non_zeros_linear, find_non_zeros_nonlinear, JacobianColoring},
+    jacobian::{
+        find_non_zeros_linear, find_non_zeros_nonlinear, ColoringAlgorithm, JacobianColoring,
+    },
     op::{LinearOp, NonLinearOp, Op},
     OdeEquations,
 };
@@ -108,7 +110,11 @@ impl<'a> DiffSlRhs<'a> {
             let x0 = V::zeros(context.nstates);
             let t0 = 0.0;
             let non_zeros = find_non_zeros_nonlinear(&ret, &x0, t0);
-            ret.coloring = Some(JacobianColoring::new_from_non_zeros(&ret, non_zeros));
+            ret.coloring = Some(JacobianColoring::new_from_non_zeros(
+                &ret,
+                non_zeros,
+                ColoringAlgorithm::default(),
+            ));
         }
         ret
     }
@@ -124,7 +130,11 @@ impl<'a> DiffSlMass<'a> {
         if use_coloring {
             let t0 = 0.0;
             let non_zeros = find_non_zeros_linear(&ret, t0);
-            ret.coloring = Some(JacobianColoring::new_from_non_zeros(&ret, non_zeros));
+            ret.coloring = Some(JacobianColoring::new_from_non_zeros(
+                &ret,
+                non_zeros,
+                ColoringAlgorithm::default(),
+            ));
         }
         ret
     }
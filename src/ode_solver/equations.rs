@@ -1,6 +1,10 @@
 use std::rc::Rc;
 
-use crate::{op::unit::UnitCallable, scalar::Scalar, LinearOp, Matrix, NonLinearOp, Vector};
+use crate::{
+    op::{unit::UnitCallable, ResidualOp, StateMassOp},
+    scalar::Scalar,
+    LinearOp, Matrix, NonLinearOp, Op, Vector,
+};
 use serde::Serialize;
 
 #[derive(Clone, Debug, Serialize)]
@@ -10,6 +14,7 @@ pub struct OdeEquationsStatistics {
     pub number_of_mass_evals: usize,
     pub number_of_mass_matrix_evals: usize,
     pub number_of_jacobian_matrix_evals: usize,
+    pub number_of_root_evals: usize,
 }
 
 impl Default for OdeEquationsStatistics {
@@ -26,6 +31,7 @@ impl OdeEquationsStatistics {
             number_of_mass_evals: 0,
             number_of_mass_matrix_evals: 0,
             number_of_jacobian_matrix_evals: 0,
+            number_of_root_evals: 0,
         }
     }
 }
@@ -39,8 +45,13 @@ impl OdeEquationsStatistics {
 ///
 /// The ODE equations are defined by:
 /// - the right-hand side function `F(t, y)`, which is given as a [NonLinearOp] using the `Rhs` associated type and [Self::rhs] function,
-/// - the mass matrix `M` which is given as a [LinearOp] using the `Mass` associated type and the [Self::mass] function,
+/// - the mass matrix `M` which is given as a [LinearOp] using the `Mass` associated type and the [Self::mass] function.
+///   The mass matrix can depend on time, or on time and the state (i.e. `M(t, y)`), in which case it is given as a [StateMassOp] using the [Self::state_mass] function,
 /// - the initial condition `y_0(t_0)`, which is given using the [Self::init] function.
+///
+/// Alternatively, the equations can be a fully implicit DAE `F(t, y, y') = 0`, in which case the residual is given as a [ResidualOp]
+/// using the [Self::residual] function, and the rhs and mass are not used by the solvers that support the implicit form
+/// (see [crate::ode_solver::implicit_equations::ImplicitDae]).
 pub trait OdeEquations {
     type T: Scalar;
     type V: Vector<T = Self::T>;
@@ -56,34 +67,87 @@ pub trait OdeEquations {
     /// returns the right-hand side function `F(t, y)` as a [NonLinearOp]
     fn rhs(&self) -> &Rc<Self::Rhs>;
 
-    /// returns the mass matrix `M` as a [LinearOp]
+    /// returns the mass matrix `M` as a [LinearOp] (this is not used if the mass matrix depends on the state, see [Self::state_mass])
     fn mass(&self) -> &Rc<Self::Mass>;
 
+    /// returns the mass matrix `M(t, y)` as a [StateMassOp] if it depends on the state, otherwise `None`
+    fn state_mass(&self) -> Option<&dyn StateMassOp<M = Self::M, V = Self::V, T = Self::T>> {
+        None
+    }
+
     fn root(&self) -> Option<&Rc<Self::Root>> {
         None
     }
 
+    /// returns the residual `F(t, y, y')` as a [ResidualOp] if the equations are a fully implicit DAE, otherwise `None`
+    fn residual(&self) -> Option<&dyn ResidualOp<M = Self::M, V = Self::V, T = Self::T>> {
+        None
+    }
+
+    /// returns true if the equations are a fully implicit DAE `F(t, y, y') = 0` (see [Self::residual])
+    fn is_implicit(&self) -> bool {
+        self.residual().is_some()
+    }
+
     /// returns the initial condition, i.e. `y(t)`, where `t` is the initial time
     fn init(&self, t: Self::T) -> Self::V;
 
-    /// returns true if the mass matrix is constant over time
+    /// returns the initial time derivative, i.e. `y'(t)`, where `t` is the initial time. This is only used for fully implicit DAEs,
+    /// the default is zero.
+    fn init_dot(&self, _t: Self::T) -> Self::V {
+        Self::V::zeros(self.rhs().nstates())
+    }
+
+    /// returns true if the mass matrix is constant over time (this should be false if the mass matrix depends on the state)
     fn is_mass_constant(&self) -> bool {
         true
     }
+
+    /// returns true if the mass matrix depends on the state, i.e. `M(t, y)` (see [Self::state_mass])
+    fn is_mass_state_dependent(&self) -> bool {
+        self.state_mass().is_some()
+    }
+
+    /// returns the number of evaluations of the rhs, mass and root functions, aggregated from the [Op::statistics] of each operator
+    fn statistics(&self) -> OdeEquationsStatistics {
+        let rhs = self.rhs().statistics();
+        let mass = match self.state_mass() {
+            Some(state_mass) => state_mass.statistics(),
+            None => self.mass().statistics(),
+        };
+        let root = self.root().map(|r| r.statistics()).unwrap_or_default();
+        OdeEquationsStatistics {
+            number_of_rhs_evals: rhs.number_of_calls,
+            number_of_jac_mul_evals: rhs.number_of_jac_muls,
+            number_of_mass_evals: mass.number_of_calls,
+            number_of_mass_matrix_evals: mass.number_of_matrix_evals,
+            number_of_jacobian_matrix_evals: rhs.number_of_matrix_evals,
+            number_of_root_evals: root.number_of_calls,
+        }
+    }
 }
 
 /// This struct implements the ODE equation trait [OdeEquations] for a given right-hand side op, mass op, optional root op, and initial condition function.
-pub struct OdeSolverEquations<M, Rhs, I, Mass = UnitCallable<M>, Root = UnitCallable<M>>
-where
+/// A mass matrix that depends on the state is given as a [StateMassOp] using [Self::new_with_state_mass], in which case the mass op is the identity and is not used.
+pub struct OdeSolverEquations<
+    M,
+    Rhs,
+    I,
+    Mass = UnitCallable<M>,
+    Root = UnitCallable<M>,
+    StateMass = UnitCallable<M>,
+> where
     M: Matrix,
     Rhs: NonLinearOp<M = M, V = M::V, T = M::T>,
     Mass: LinearOp<M = M, V = M::V, T = M::T>,
     Root: NonLinearOp<M = M, V = M::V, T = M::T>,
+    StateMass: StateMassOp<M = M, V = M::V, T = M::T>,
     I: Fn(&M::V, M::T) -> M::V,
 {
     rhs: Rc<Rhs>,
     mass: Rc<Mass>,
     root: Option<Rc<Root>>,
+    state_mass: Option<Rc<StateMass>>,
     init: I,
     p: Rc<M::V>,
     mass_is_constant: bool,
@@ -110,6 +174,7 @@ where
             rhs,
             mass,
             root,
+            state_mass: None,
             init,
             p,
             mass_is_constant,
@@ -117,12 +182,42 @@ where
     }
 }
 
-impl<M, Rhs, Mass, Root, I> OdeEquations for OdeSolverEquations<M, Rhs, I, Mass, Root>
+impl<M, Rhs, Root, StateMass, I> OdeSolverEquations<M, Rhs, I, UnitCallable<M>, Root, StateMass>
+where
+    M: Matrix,
+    Rhs: NonLinearOp<M = M, V = M::V, T = M::T>,
+    Root: NonLinearOp<M = M, V = M::V, T = M::T>,
+    StateMass: StateMassOp<M = M, V = M::V, T = M::T>,
+    I: Fn(&M::V, M::T) -> M::V,
+{
+    pub fn new_with_state_mass(
+        rhs: Rc<Rhs>,
+        state_mass: Rc<StateMass>,
+        root: Option<Rc<Root>>,
+        init: I,
+        p: Rc<M::V>,
+    ) -> Self {
+        let mass = Rc::new(UnitCallable::new(rhs.nstates()));
+        Self {
+            rhs,
+            mass,
+            root,
+            state_mass: Some(state_mass),
+            init,
+            p,
+            mass_is_constant: false,
+        }
+    }
+}
+
+impl<M, Rhs, Mass, Root, StateMass, I> OdeEquations
+    for OdeSolverEquations<M, Rhs, I, Mass, Root, StateMass>
 where
     M: Matrix,
     Rhs: NonLinearOp<M = M, V = M::V, T = M::T>,
     Mass: LinearOp<M = M, V = M::V, T = M::T>,
     Root: NonLinearOp<M = M, V = M::V, T = M::T>,
+    StateMass: StateMassOp<M = M, V = M::V, T = M::T>,
     I: Fn(&M::V, M::T) -> M::V,
 {
     type T = M::T;
@@ -141,6 +236,11 @@ where
     fn root(&self) -> Option<&Rc<Self::Root>> {
         self.root.as_ref()
     }
+    fn state_mass(&self) -> Option<&dyn StateMassOp<M = Self::M, V = Self::V, T = Self::T>> {
+        self.state_mass
+            .as_ref()
+            .map(|m| m.as_ref() as &dyn StateMassOp<M = Self::M, V = Self::V, T = Self::T>)
+    }
     fn is_mass_constant(&self) -> bool {
         self.mass_is_constant
     }
@@ -160,6 +260,11 @@ where
         if let Some(r) = self.root.as_mut() {
             Rc::<Root>::get_mut(r).unwrap().set_params(self.p.clone())
         }
+        if let Some(m) = self.state_mass.as_mut() {
+            Rc::<StateMass>::get_mut(m)
+                .unwrap()
+                .set_params(self.p.clone())
+        }
     }
 }
 
@@ -0,0 +1,614 @@
+use anyhow::{anyhow, Result};
+use num_traits::{abs, One, Pow, Zero};
+use std::marker::PhantomData;
+use std::time::Instant;
+
+use crate::{
+    matrix::{default_solver::DefaultSolver, expm::phi_e1},
+    scale, DenseMatrix, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+    OdeSolverState, OdeSolverStatistics, OdeSolverStopReason, Op, RootFinder, Scalar, Vector,
+};
+
+use super::fixed_step::{fixed_step_size, hermite_interpolate, is_at_stop_time};
+
+/// The number of Arnoldi iterations between the error estimates in [krylov_phi].
+pub const KRYLOV_CHECK_INTERVAL: usize = 4;
+
+/// The actions `phi_k(tau J) w` for `k = 0..=p` of the phi-functions (see [phi_e1]) of the jacobian `J` of `op` at `(x, t)`, using only the
+/// jacobian-vector product [NonLinearOp::jac_mul_inplace]. These are approximated using the Arnoldi process as `phi_k(tau J) w = beta V_m phi_k(tau H_m) e_1`,
+/// where `V_m` is an orthonormal basis of the Krylov subspace of dimension `m` and `H_m` is the projection of `J` onto it. The dimension is increased
+/// until the estimated error `beta tau h_{m+1,m} |e_m^T phi_{k+1}(tau H_m) e_1|` is less than `tol * beta` for all `k`, returning an error if
+/// this fails for the maximum dimension `max_dim`. The error is estimated every [KRYLOV_CHECK_INTERVAL] iterations.
+#[allow(clippy::too_many_arguments)]
+pub fn krylov_phi<M, F>(
+    op: &F,
+    x: &M::V,
+    t: M::T,
+    w: &M::V,
+    tau: M::T,
+    p: usize,
+    tol: M::T,
+    max_dim: usize,
+) -> Result<Vec<M::V>>
+where
+    M: DenseMatrix + DefaultSolver,
+    F: NonLinearOp<V = M::V, T = M::T> + ?Sized,
+{
+    let n = w.len();
+    let beta = w.norm();
+    if beta == M::T::zero() {
+        return Ok(vec![M::V::zeros(n); p + 1]);
+    }
+    let max_dim = if max_dim < n { max_dim } else { n };
+    let dot = |a: &M::V, b: &M::V| a.binary_fold(b, M::T::zero(), |acc, x, y, _| acc + x * y);
+    let mut basis = vec![w.clone() * scale(M::T::one() / beta)];
+    let mut h = vec![vec![M::T::zero(); max_dim]; max_dim + 1];
+    for j in 0..max_dim {
+        // modified gram-schmidt
+        let mut v = op.jac_mul(x, t, &basis[j]);
+        for (i, v_i) in basis.iter().enumerate() {
+            h[i][j] = dot(&v, v_i);
+            v.axpy(-h[i][j], v_i, M::T::one());
+        }
+        h[j + 1][j] = v.norm();
+
+        // the subspace is invariant under J (a happy breakdown), otherwise the phi-functions of the projected matrix are only
+        // computed every few iterations to check the error estimate, as this is more expensive than an arnoldi iteration
+        let m = j + 1;
+        let residual = abs(tau) * h[j + 1][j];
+        let breakdown = residual <= M::T::EPSILON || m == n;
+        if breakdown || m % KRYLOV_CHECK_INTERVAL == 0 || m == max_dim {
+            let mut hm = M::zeros(m, m);
+            for (i, row) in h.iter().take(m).enumerate() {
+                for (k, &h_ik) in row.iter().take(m).enumerate() {
+                    hm[(i, k)] = tau * h_ik;
+                }
+            }
+            let phi = phi_e1(&hm, p + 1)?;
+            let error = (0..=p).fold(M::T::zero(), |acc, k| {
+                let e = residual * abs(phi[k + 1][m - 1]);
+                if e > acc {
+                    e
+                } else {
+                    acc
+                }
+            });
+            if breakdown || error <= tol {
+                return Ok(phi
+                    .iter()
+                    .take(p + 1)
+                    .map(|phi_k| {
+                        let mut ret = M::V::zeros(n);
+                        for (i, v_i) in basis.iter().enumerate() {
+                            ret.axpy(beta * phi_k[i], v_i, M::T::one());
+                        }
+                        ret
+                    })
+                    .collect());
+            }
+        }
+        v *= scale(M::T::one() / h[j + 1][j]);
+        basis.push(v);
+    }
+    Err(anyhow!(
+        "Krylov approximation of phi-function actions did not converge with a subspace of dimension {}",
+        max_dim
+    ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExponentialMethod {
+    Etdrk4,
+    Exprb32,
+}
+
+/// An exponential integrator for stiff semilinear problems `y' = J y + N(t, y)`, where at each step `J` is the jacobian of the right-hand side
+/// at the start of the step and `N` is the remainder. The stiff linear part is integrated exactly using the actions of the phi-functions of `J`
+/// (see [krylov_phi]), which only requires the jacobian-vector product of the right-hand side, so the jacobian is never assembled or factorised.
+/// The type `M` is the dense matrix type used for the small projected matrices in the Krylov approximation.
+///
+/// Two methods are available:
+/// - [Self::etdrk4], the fourth order exponential time differencing Runge-Kutta method of Cox & Matthews (2002), using fixed steps (see [super::fixed_step]).
+/// - [Self::exprb32], the third order exponential Rosenbrock method of Hochbruck, Ostermann & Schweitzer (2009), which uses the exponential Rosenbrock-Euler
+///   method as an embedded second order method to adapt the step size using the tolerances of the problem.
+///
+/// The mass matrix of the problem is not used, so it must be the identity ([OdeSolverMethod::set_problem] returns an error otherwise). Interpolation uses cubic Hermite interpolation within the last step.
+///
+/// Cox, S. M., & Matthews, P. C. (2002). Exponential time differencing for stiff systems. Journal of Computational Physics, 176(2), 430-455.
+///
+/// Hochbruck, M., Ostermann, A., & Schweitzer, J. (2009). Exponential Rosenbrock-type methods. SIAM Journal on Numerical Analysis, 47(1), 786-803.
+pub struct ExponentialIntegrator<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    method: ExponentialMethod,
+    max_krylov_dim: usize,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    _m: PhantomData<M>,
+}
+
+impl<M, Eqn> Default for ExponentialIntegrator<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    fn default() -> Self {
+        Self::exprb32()
+    }
+}
+
+impl<M, Eqn> ExponentialIntegrator<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 5.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+    const KRYLOV_TOL_FACTOR: f64 = 0.1;
+
+    fn new(method: ExponentialMethod) -> Self {
+        Self {
+            method,
+            max_krylov_dim: 50,
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(1),
+            old_dy: Eqn::V::zeros(1),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            _m: PhantomData,
+        }
+    }
+
+    /// The fourth order ETDRK4 method, with fixed steps.
+    pub fn etdrk4() -> Self {
+        Self::new(ExponentialMethod::Etdrk4)
+    }
+
+    /// The third order EXPRB32 method, with adaptive steps.
+    pub fn exprb32() -> Self {
+        Self::new(ExponentialMethod::Exprb32)
+    }
+
+    /// The order of the method.
+    pub fn order(&self) -> usize {
+        match self.method {
+            ExponentialMethod::Etdrk4 => 4,
+            ExponentialMethod::Exprb32 => 3,
+        }
+    }
+
+    /// Set the maximum dimension of the Krylov subspaces (default 50).
+    pub fn set_max_krylov_dim(&mut self, max_krylov_dim: usize) {
+        self.max_krylov_dim = max_krylov_dim;
+    }
+
+    // the action h phi_k(tau J) w
+    fn phi(&self, y: &Eqn::V, t: Eqn::T, w: &Eqn::V, tau: Eqn::T, k: usize) -> Result<Eqn::V> {
+        let problem = self.problem.as_ref().unwrap();
+        let tol = problem.rtol * Eqn::T::from(Self::KRYLOV_TOL_FACTOR);
+        let mut phi = krylov_phi::<M, _>(
+            problem.eqn.rhs().as_ref(),
+            y,
+            t,
+            w,
+            tau,
+            k,
+            tol,
+            self.max_krylov_dim,
+        )?;
+        Ok(phi.swap_remove(k) * scale(tau))
+    }
+
+    // the difference in the nonlinear remainder N(u) - N(y) = f(u) - f(y) - J (u - y)
+    fn remainder_diff(&self, y: &Eqn::V, f: &Eqn::V, t: Eqn::T, u: &Eqn::V, tu: Eqn::T) -> Eqn::V {
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+        let mut d = rhs.call(u, tu) - f;
+        let du = u.clone() - y;
+        d -= rhs.jac_mul(y, t, &du);
+        d
+    }
+
+    // a step of the Cox & Matthews ETDRK4 method, written in terms of the differences D_u = N(u) - N(y) so that the
+    // phi-functions only act on f(y) and the differences
+    fn etdrk4_step(&self, y: &Eqn::V, f: &Eqn::V, t: Eqn::T, h: Eqn::T) -> Result<Eqn::V> {
+        let one = Eqn::T::one();
+        let half = h * Eqn::T::from(0.5);
+        let t_half = t + half;
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+
+        // a = y + h/2 phi_1(h/2 J) f
+        let mut a = self.phi(y, t, f, half, 1)?;
+        a += y;
+        let da = self.remainder_diff(y, f, t, &a, t_half);
+
+        // b = a + h/2 phi_1(h/2 J) D_a
+        let mut b = self.phi(y, t, &da, half, 1)?;
+        b += &a;
+        let db = self.remainder_diff(y, f, t, &b, t_half);
+
+        // c = a + h/2 phi_1(h/2 J) (f(a) + 2 D_b - D_a)
+        let mut w = rhs.call(&a, t_half);
+        w.axpy(Eqn::T::from(2.0), &db, one);
+        w -= &da;
+        let mut c = self.phi(y, t, &w, half, 1)?;
+        c += &a;
+        let dc = self.remainder_diff(y, f, t, &c, t + h);
+
+        // y_{n+1} = y + h phi_1 f + h (2 phi_2 - 4 phi_3) (D_a + D_b) + h (-phi_2 + 4 phi_3) D_c
+        let mut y_new = self.phi(y, t, f, h, 1)?;
+        y_new += y;
+        let dab = da + &db;
+        let (four, two) = (Eqn::T::from(4.0), Eqn::T::from(2.0));
+        y_new.axpy(two, &self.phi(y, t, &dab, h, 2)?, one);
+        y_new.axpy(-four, &self.phi(y, t, &dab, h, 3)?, one);
+        y_new.axpy(-one, &self.phi(y, t, &dc, h, 2)?, one);
+        y_new.axpy(four, &self.phi(y, t, &dc, h, 3)?, one);
+        Ok(y_new)
+    }
+
+    // a step of the EXPRB32 method, returning the solution and the error estimate
+    fn exprb32_step(
+        &self,
+        y: &Eqn::V,
+        f: &Eqn::V,
+        t: Eqn::T,
+        h: Eqn::T,
+    ) -> Result<(Eqn::V, Eqn::V)> {
+        // the exponential Rosenbrock-Euler step u = y + h phi_1(h J) f
+        let mut u = self.phi(y, t, f, h, 1)?;
+        u += y;
+        let du = self.remainder_diff(y, f, t, &u, t + h);
+
+        // y_{n+1} = u + 2 h phi_3(h J) D_u
+        let error = self.phi(y, t, &du, h, 3)? * scale(Eqn::T::from(2.0));
+        Ok((u + &error, error))
+    }
+
+    fn error_norm(&self, error: &Eqn::V, y: &Eqn::V, y_new: &Eqn::V) -> Eqn::T {
+        let problem = self.problem.as_ref().unwrap();
+        let n = y.len();
+        let mut sum = Eqn::T::zero();
+        for i in 0..n {
+            let (y_i, y_new_i) = (abs(y[i]), abs(y_new[i]));
+            let scale = problem.atol[i] + problem.rtol * if y_i > y_new_i { y_i } else { y_new_i };
+            let e = error[i] / scale;
+            sum += e * e;
+        }
+        (sum / Eqn::T::from(n as f64)).pow(Eqn::T::from(0.5))
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let Some(state) = self.state.as_ref() else {
+            return Err(anyhow!("State not set"));
+        };
+        let (y, f, t) = (state.y.clone(), state.dy.clone(), state.t);
+        let mut h = fixed_step_size(t, state.h, self.tstop);
+        let y_new = match self.method {
+            ExponentialMethod::Etdrk4 => self.etdrk4_step(&y, &f, t, h)?,
+            ExponentialMethod::Exprb32 => loop {
+                let ret = self.exprb32_step(&y, &f, t, h);
+                let (y_new, error_norm) = match ret {
+                    Ok((y_new, error)) => {
+                        let error_norm = self.error_norm(&error, &y, &y_new);
+                        (Some(y_new), error_norm)
+                    }
+                    Err(_) => (None, Eqn::T::INFINITY),
+                };
+                let mut factor = if error_norm == Eqn::T::zero() {
+                    Eqn::T::from(Self::MAX_FACTOR)
+                } else if error_norm.is_nan() {
+                    Eqn::T::from(Self::MIN_FACTOR)
+                } else {
+                    Eqn::T::from(0.9) * error_norm.pow(Eqn::T::from(-1.0 / 3.0))
+                };
+                if factor < Eqn::T::from(Self::MIN_FACTOR) {
+                    factor = Eqn::T::from(Self::MIN_FACTOR);
+                }
+                if factor > Eqn::T::from(Self::MAX_FACTOR) {
+                    factor = Eqn::T::from(Self::MAX_FACTOR);
+                }
+                let h_new = h * factor;
+                self.state.as_mut().unwrap().h = h_new;
+                if error_norm <= Eqn::T::one() {
+                    break y_new.unwrap();
+                }
+                self.statistics.number_of_error_test_failures += 1;
+                if h_new < Eqn::T::from(Self::MIN_TIMESTEP) {
+                    return Err(anyhow!("Step size too small at t = {}", t));
+                }
+                h = fixed_step_size(t, h_new, self.tstop);
+            },
+        };
+
+        let problem = self.problem.as_ref().unwrap();
+        let state = self.state.as_mut().unwrap();
+        self.old_t = t;
+        self.old_y = y;
+        self.old_dy = f;
+        state.t = match self.tstop {
+            Some(tstop) if is_at_stop_time(t + h, h, tstop) => tstop,
+            _ => t + h,
+        };
+        state.dy = problem.eqn.rhs().call(&y_new, state.t);
+        state.y = y_new;
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = problem.eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<M, Eqn> OdeSolverMethod<Eqn> for ExponentialIntegrator<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("ExponentialIntegrator")?;
+        problem.check_mass_identity("ExponentialIntegrator", state.t)?;
+        state.dy = problem.eqn.rhs().call(&state.y, state.t);
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        Ok(hermite_interpolate(
+            self.old_t,
+            state.t,
+            &self.old_y,
+            &state.y,
+            &self.old_dy,
+            &state.dy,
+            t,
+        ))
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::tests::{test_interpolate, test_no_set_problem, test_take_state},
+        op::closure::Closure,
+        Bdf, NonLinearOp, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem, Vector,
+    };
+    use std::rc::Rc;
+
+    use super::{krylov_phi, ExponentialIntegrator};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // y' = L y + y (1 - y), where L is the 1D laplacian with zero dirichlet boundary conditions on n points, scaled by d
+    fn reaction_diffusion(
+        n: usize,
+        d: f64,
+        h0: f64,
+    ) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        let laplacian = move |x: &V, y: &mut V| {
+            for i in 0..n {
+                let left = if i > 0 { x[i - 1] } else { 0.0 };
+                let right = if i < n - 1 { x[i + 1] } else { 0.0 };
+                y[i] = d * (left - 2.0 * x[i] + right);
+            }
+        };
+        OdeBuilder::new()
+            .h0(h0)
+            .rtol(1e-6)
+            .atol([1e-8])
+            .build_ode::<M, _, _, _>(
+                move |x: &V, _p: &V, _t, y: &mut V| {
+                    laplacian(x, y);
+                    for i in 0..n {
+                        y[i] += x[i] * (1.0 - x[i]);
+                    }
+                },
+                move |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    laplacian(v, y);
+                    for i in 0..n {
+                        y[i] += (1.0 - 2.0 * x[i]) * v[i];
+                    }
+                },
+                move |_p: &V, _t| {
+                    V::from_vec(
+                        (0..n)
+                            .map(|i| (std::f64::consts::PI * (i + 1) as f64 / (n + 1) as f64).sin())
+                            .collect(),
+                    )
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_krylov_phi() {
+        // for a linear operator J, phi_k(tau J) w is found exactly from the dense phi-functions
+        let a = M::from_vec(3, 3, vec![-2.0, 1.0, 0.0, 1.0, -3.0, 1.0, 0.5, 1.0, -4.0]);
+        let a_op = a.clone();
+        let op = Closure::<M, _, _>::new(
+            move |x: &V, _p: &V, _t, y: &mut V| y.copy_from(&(&a_op * x)),
+            move |_x: &V, _p: &V, _t, v: &V, y: &mut V| y.copy_from(&(&a * v)),
+            3,
+            3,
+            Rc::new(V::zeros(0)),
+        );
+        let w = V::from_vec(vec![1.0, 0.0, 0.0]);
+        let tau = 0.7;
+        let phi = krylov_phi::<M, _>(&op, &V::zeros(3), 0.0, &w, tau, 2, 1e-12, 10).unwrap();
+        let jac = op.jacobian(&V::zeros(3), 0.0) * crate::scale(tau);
+        let expect = crate::matrix::expm::phi_e1(&jac, 2).unwrap();
+        for (phi_k, expect_k) in phi.iter().zip(expect.iter()) {
+            phi_k.assert_eq_st(expect_k, 1e-12);
+        }
+
+        // zero vectors have zero actions, and the subspace dimension is limited
+        let phi =
+            krylov_phi::<M, _>(&op, &V::zeros(3), 0.0, &V::zeros(3), tau, 1, 1e-12, 10).unwrap();
+        assert_eq!(phi[1], V::zeros(3));
+        assert!(krylov_phi::<M, _>(&op, &V::zeros(3), 0.0, &w, tau, 1, 1e-14, 1).is_err());
+    }
+
+    #[test]
+    fn test_exponential_reaction_diffusion() {
+        // a stiff problem, the spectral radius of the laplacian is about 4 d = 4000
+        let problem = reaction_diffusion(20, 1000.0, 1e-2);
+        let mut bdf = Bdf::default();
+        let mut reference_problem = reaction_diffusion(20, 1000.0, 1e-2);
+        reference_problem.rtol = 1e-10;
+        reference_problem.atol = Rc::new(V::from_element(20, 1e-12));
+        let expect = bdf.solve(&reference_problem, 1.0).unwrap();
+
+        let mut s = ExponentialIntegrator::<M, _>::exprb32();
+        let y = s.solve(&problem, 1.0).unwrap();
+        y.assert_eq_st(&expect, 1e-5);
+        assert!(s.statistics().number_of_steps < 100);
+        assert_eq!(s.order(), 3);
+
+        let mut s = ExponentialIntegrator::<M, _>::etdrk4();
+        let y = s.solve(&problem, 1.0).unwrap();
+        y.assert_eq_st(&expect, 1e-5);
+        assert_eq!(s.statistics().number_of_steps, 100);
+    }
+
+    #[test]
+    fn test_etdrk4_convergence_order() {
+        let errors = [0.2, 0.1].map(|h| {
+            let problem = reaction_diffusion(10, 10.0, h);
+            let mut reference_problem = reaction_diffusion(10, 10.0, h);
+            reference_problem.rtol = 1e-12;
+            reference_problem.atol = Rc::new(V::from_element(10, 1e-14));
+            let expect = Bdf::default().solve(&reference_problem, 2.0).unwrap();
+            let y = ExponentialIntegrator::<M, _>::etdrk4()
+                .solve(&problem, 2.0)
+                .unwrap();
+            (y - expect).norm()
+        });
+        let order = (errors[0] / errors[1]).log2();
+        assert!(order > 3.5, "{}", order);
+    }
+
+    #[test]
+    fn test_exponential_mass() {
+        // 2 y' = -y has a mass matrix that is not the identity
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0],
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -v[0],
+                |v: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * v[0] + beta * y[0],
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        let mut s = ExponentialIntegrator::<M, _>::default();
+        assert!(s
+            .set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .is_err());
+    }
+
+    #[test]
+    fn test_exponential_no_set_problem() {
+        test_no_set_problem::<M, _>(ExponentialIntegrator::<M, _>::default())
+    }
+
+    #[test]
+    fn test_exponential_take_state() {
+        test_take_state::<M, _>(ExponentialIntegrator::<M, _>::default())
+    }
+
+    #[test]
+    fn test_exponential_interpolate() {
+        test_interpolate::<M, _>(ExponentialIntegrator::<M, _>::default())
+    }
+}
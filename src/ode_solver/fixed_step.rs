@@ -0,0 +1,84 @@
+//! Helpers shared by the fixed step solvers ([super::symplectic::Symplectic], [super::symplectic::ImplicitMidpoint],
+//! [super::generalised_alpha::GeneralisedAlpha], [super::multirate::MriGark], the ETDRK4 method of [super::exponential::ExponentialIntegrator]
+//! and [super::linear::LinearOdeSolver]).
+//!
+//! These solvers take fixed steps of size `h` given by the initial state (i.e. the `h0` of the problem), except that the last step is reduced
+//! to step exactly to the stop time, so no error estimate or step size control is used. Unless stated otherwise by the solver, interpolation
+//! uses cubic Hermite interpolation within the last step (which is also used by [super::bvp::BvpSolution::interpolate]).
+
+use num_traits::{abs, One, Zero};
+
+use crate::{scale, Scalar, Vector};
+
+// the size of the next step of a fixed step solver, reduced to step exactly to tstop
+pub(crate) fn fixed_step_size<T: Scalar>(t: T, h: T, tstop: Option<T>) -> T {
+    match tstop {
+        Some(tstop) if t + h > tstop - troundoff(t, h) => tstop - t,
+        _ => h,
+    }
+}
+
+// true if the time t is at tstop, within the rounding error
+pub(crate) fn is_at_stop_time<T: Scalar>(t: T, h: T, tstop: T) -> bool {
+    abs(t - tstop) <= troundoff(t, h)
+}
+
+fn troundoff<T: Scalar>(t: T, h: T) -> T {
+    T::from(100.0) * T::EPSILON * (abs(t) + abs(h))
+}
+
+// the cubic hermite interpolant between (t0, y0, dy0) and (t1, y1, dy1)
+pub(crate) fn hermite_interpolate<V: Vector>(
+    t0: V::T,
+    t1: V::T,
+    y0: &V,
+    y1: &V,
+    dy0: &V,
+    dy1: &V,
+    t: V::T,
+) -> V {
+    let one = V::T::one();
+    let (two, three) = (V::T::from(2.0), V::T::from(3.0));
+    let h = t1 - t0;
+    if h == V::T::zero() {
+        return y1.clone();
+    }
+    let s = (t - t0) / h;
+    let s2 = s * s;
+    let s3 = s2 * s;
+    let mut y = y0.clone() * scale(two * s3 - three * s2 + one);
+    y.axpy(h * (s3 - two * s2 + s), dy0, one);
+    y.axpy(three * s2 - two * s3, y1, one);
+    y.axpy(h * (s3 - s2), dy1, one);
+    y
+}
+
+// the time derivative of the cubic hermite interpolant between (t0, y0, dy0) and (t1, y1, dy1)
+pub(crate) fn hermite_derivative<V: Vector>(
+    t0: V::T,
+    t1: V::T,
+    y0: &V,
+    y1: &V,
+    dy0: &V,
+    dy1: &V,
+    t: V::T,
+) -> V {
+    let one = V::T::one();
+    let (two, three, four, six) = (
+        V::T::from(2.0),
+        V::T::from(3.0),
+        V::T::from(4.0),
+        V::T::from(6.0),
+    );
+    let h = t1 - t0;
+    if h == V::T::zero() {
+        return dy1.clone();
+    }
+    let s = (t - t0) / h;
+    let s2 = s * s;
+    let mut dy = y0.clone() * scale((six * s2 - six * s) / h);
+    dy.axpy(three * s2 - four * s + one, dy0, one);
+    dy.axpy((six * s - six * s2) / h, y1, one);
+    dy.axpy(three * s2 - two * s, dy1, one);
+    dy
+}
@@ -0,0 +1,438 @@
+use anyhow::{anyhow, Result};
+use num_traits::{One, Zero};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::{
+    nonlinear_solver::NonLinearSolver,
+    op::generalised_alpha::{GeneralisedAlphaCallable, GeneralisedAlphaParameters},
+    solver::SolverProblem,
+    LinearSolver, NewtonNonlinearSolver, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+    OdeSolverState, OdeSolverStatistics, OdeSolverStopReason, Op, RootFinder, Vector,
+};
+
+use super::fixed_step::{fixed_step_size, hermite_interpolate, is_at_stop_time};
+
+/// The generalised-alpha method of Chung & Hulbert (1993) for second order systems `M q'' = g(t, q, q')`, written as the first order
+/// system `y = (q, v)` (see [crate::OdeBuilder::build_second_order] and [crate::OdeBuilder::build_second_order_with_mass]).
+///
+/// The method is second order, and the parameter `rho_inf` (between 0 and 1) sets the damping of high frequency modes, from
+/// annihilating them after one step (`rho_inf = 0`) to no damping (`rho_inf = 1`). The Newmark family of methods is available using
+/// [Self::newmark]. The acceleration at the end of each step is found using a modified Newton iteration (see [GeneralisedAlphaCallable]),
+/// where the jacobian is only updated when the step size changes or the iteration fails to converge.
+///
+/// The solver takes fixed steps (see [super::fixed_step]).
+///
+/// Chung, J., & Hulbert, G. M. (1993). A time integration algorithm for structural dynamics with improved numerical dissipation: the
+/// generalized-α method. Journal of Applied Mechanics, 60(2), 371-375.
+pub struct GeneralisedAlpha<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<GeneralisedAlphaCallable<Eqn>>,
+{
+    parameters: GeneralisedAlphaParameters<Eqn::T>,
+    nonlinear_solver: NewtonNonlinearSolver<GeneralisedAlphaCallable<Eqn>, LS>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    a: Option<Eqn::V>,
+    jacobian_h: Option<Eqn::T>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn, LS> GeneralisedAlpha<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<GeneralisedAlphaCallable<Eqn>>,
+{
+    const NEWTON_MAXITER: usize = 10;
+
+    /// The generalised-alpha method with spectral radius `rho_inf` at infinite frequency.
+    pub fn new(rho_inf: Eqn::T, linear_solver: LS) -> Self {
+        assert!(
+            rho_inf >= Eqn::T::zero() && rho_inf <= Eqn::T::one(),
+            "rho_inf must be between 0 and 1"
+        );
+        let one = Eqn::T::one();
+        let alpha_m = (Eqn::T::from(2.0) * rho_inf - one) / (rho_inf + one);
+        let alpha_f = rho_inf / (rho_inf + one);
+        let gamma = Eqn::T::from(0.5) - alpha_m + alpha_f;
+        let beta = (one - alpha_m + alpha_f) * (one - alpha_m + alpha_f) / Eqn::T::from(4.0);
+        Self::from_parameters(
+            GeneralisedAlphaParameters {
+                alpha_m,
+                alpha_f,
+                beta,
+                gamma,
+            },
+            linear_solver,
+        )
+    }
+
+    /// The Newmark method with parameters `beta` and `gamma`, e.g. the average acceleration (trapezoidal) method with `beta = 1/4` and
+    /// `gamma = 1/2`.
+    pub fn newmark(beta: Eqn::T, gamma: Eqn::T, linear_solver: LS) -> Self {
+        Self::from_parameters(
+            GeneralisedAlphaParameters {
+                alpha_m: Eqn::T::zero(),
+                alpha_f: Eqn::T::zero(),
+                beta,
+                gamma,
+            },
+            linear_solver,
+        )
+    }
+
+    fn from_parameters(parameters: GeneralisedAlphaParameters<Eqn::T>, linear_solver: LS) -> Self {
+        let mut nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
+        nonlinear_solver.set_max_iter(Self::NEWTON_MAXITER);
+        Self {
+            parameters,
+            nonlinear_solver,
+            problem: None,
+            state: None,
+            a: None,
+            jacobian_h: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(1),
+            old_dy: Eqn::V::zeros(1),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+        }
+    }
+
+    /// The parameters of the method.
+    pub fn parameters(&self) -> GeneralisedAlphaParameters<Eqn::T> {
+        self.parameters
+    }
+
+    fn reset_jacobian(&mut self, x: &Eqn::V, t: Eqn::T) {
+        let start = Instant::now();
+        self.nonlinear_solver.reset_jacobian(x, t);
+        self.statistics.timings.linear_solver_setup += start.elapsed();
+        self.statistics.number_of_linear_solver_setups += 1;
+    }
+
+    fn solve_nonlinear(&mut self, x: &mut Eqn::V, t: Eqn::T) -> Result<()> {
+        let start = Instant::now();
+        let ret = self.nonlinear_solver.solve_in_place(x, t);
+        self.statistics.timings.nonlinear_solver += start.elapsed();
+        self.statistics.number_of_nonlinear_solver_iterations += self.nonlinear_solver.niter();
+        self.statistics.number_of_linear_solves += self.nonlinear_solver.niter();
+        ret
+    }
+
+    // solve for the acceleration, updating the jacobian if the iteration fails
+    fn solve_acceleration(&mut self, a: &mut Eqn::V, t: Eqn::T, h: Eqn::T) -> Result<()> {
+        let guess = a.clone();
+        if self.jacobian_h != Some(h) {
+            self.reset_jacobian(a, t);
+            self.jacobian_h = Some(h);
+        }
+        if self.solve_nonlinear(a, t).is_err() {
+            self.statistics.number_of_nonlinear_solver_fails += 1;
+            a.copy_from(&guess);
+            self.reset_jacobian(a, t);
+            self.solve_nonlinear(a, t)?;
+        }
+        Ok(())
+    }
+
+    // the derivative dy = (v, a) of the state y = (q, v)
+    fn derivative(y: &Eqn::V, a: &Eqn::V) -> Eqn::V {
+        let n = a.len();
+        let mut dy = Eqn::V::zeros(2 * n);
+        for i in 0..n {
+            dy[i] = y[n + i];
+            dy[n + i] = a[i];
+        }
+        dy
+    }
+
+    // the initial acceleration a_0 solves M a_0 = g(t_0, q_0, v_0), which is a step of size zero with alpha_m = alpha_f = 0
+    fn initial_acceleration(&mut self) -> Result<()> {
+        let state = self.state.as_ref().unwrap();
+        let (y, t) = (state.y.clone(), state.t);
+        let n = y.len() / 2;
+        let callable = self.nonlinear_solver.problem().f.clone();
+        callable.set_parameters(GeneralisedAlphaParameters {
+            alpha_m: Eqn::T::zero(),
+            alpha_f: Eqn::T::zero(),
+            ..self.parameters
+        });
+        callable.set_h(Eqn::T::zero());
+        let mut a = Eqn::V::zeros(n);
+        callable.set_start(&y, &a);
+        let ret = self.solve_acceleration(&mut a, t, Eqn::T::zero());
+        callable.set_parameters(self.parameters);
+        ret?;
+        let state = self.state.as_mut().unwrap();
+        state.dy = Self::derivative(&state.y, &a);
+        self.old_dy = state.dy.clone();
+        self.a = Some(a);
+        Ok(())
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        if self.state.is_none() {
+            return Err(anyhow!("State not set"));
+        }
+        if self.a.is_none() {
+            self.initial_acceleration()?;
+        }
+        let state = self.state.as_ref().unwrap();
+        let (y, t) = (state.y.clone(), state.t);
+        let h = fixed_step_size(t, state.h, self.tstop);
+        let t_alpha = t + (Eqn::T::one() - self.parameters.alpha_f) * h;
+
+        let callable = self.nonlinear_solver.problem().f.clone();
+        let a_n = self.a.take().unwrap();
+        callable.set_h(h);
+        callable.set_start(&y, &a_n);
+        let mut a = a_n.clone();
+        if let Err(e) = self.solve_acceleration(&mut a, t_alpha, h) {
+            self.a = Some(a_n);
+            return Err(e);
+        }
+
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t;
+        self.old_y.copy_from(&state.y);
+        self.old_dy.copy_from(&state.dy);
+        state.y = callable.end_state(&a);
+        state.dy = Self::derivative(&state.y, &a);
+        state.t = match self.tstop {
+            Some(tstop) if is_at_stop_time(self.old_t + h, h, tstop) => tstop,
+            _ => self.old_t + h,
+        };
+        self.a = Some(a);
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = self.problem.as_ref().unwrap().eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<Eqn, LS> OdeSolverMethod<Eqn> for GeneralisedAlpha<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<GeneralisedAlphaCallable<Eqn>>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("GeneralisedAlpha")?;
+        problem.check_mass_not_state_dependent("GeneralisedAlpha")?;
+        let callable = Rc::new(GeneralisedAlphaCallable::new(&problem.eqn, self.parameters));
+        let n = state.y.len() / 2;
+        let atol = Rc::new(Eqn::V::from_vec(
+            (n..2 * n).map(|i| problem.atol[i]).collect(),
+        ));
+        let nonlinear_problem = SolverProblem::new(callable, atol, problem.rtol);
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // the initial acceleration is calculated in the first step
+        self.a = None;
+        self.jacobian_h = None;
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        if self.a.is_none() {
+            return Ok(state.y.clone());
+        }
+        Ok(hermite_interpolate(
+            self.old_t,
+            state.t,
+            &self.old_y,
+            &state.y,
+            &self.old_dy,
+            &state.dy,
+            t,
+        ))
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.a = None;
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        NalgebraLU, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+        OdeSolverStopReason,
+    };
+
+    use super::GeneralisedAlpha;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // 2 q'' = -2 q - 0.5 q', with q(0) = 1, q'(0) = 0
+    fn damped_oscillator(h: f64) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        OdeBuilder::new()
+            .h0(h)
+            .rtol(1e-10)
+            .atol([1e-10])
+            .build_second_order_with_mass::<M, _, _, _, _>(
+                |q: &V, v: &V, _p: &V, _t, f: &mut V| f[0] = -2.0 * q[0] - 0.5 * v[0],
+                |_q: &V, _v: &V, _p: &V, _t, dq: &V, dv: &V, y: &mut V| {
+                    y[0] = -2.0 * dq[0] - 0.5 * dv[0]
+                },
+                |x: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * x[0] + beta * y[0],
+                |_p: &V, _t| (V::from_element(1, 1.0), V::from_element(1, 0.0)),
+            )
+            .unwrap()
+    }
+
+    fn damped_oscillator_soln(t: f64) -> f64 {
+        let zeta: f64 = 0.125;
+        let omega = (1.0 - zeta * zeta).sqrt();
+        (-zeta * t).exp() * ((omega * t).cos() + zeta / omega * (omega * t).sin())
+    }
+
+    fn damped_oscillator_error<Eqn: OdeEquations<T = f64, V = V, M = M>>(
+        problem: &OdeSolverProblem<Eqn>,
+        mut s: impl OdeSolverMethod<Eqn>,
+    ) -> f64 {
+        let y = s.solve(problem, 2.0).unwrap();
+        (y[0] - damped_oscillator_soln(2.0)).abs()
+    }
+
+    #[test]
+    fn test_newmark_damped_oscillator() {
+        let (coarse, fine) = (damped_oscillator(0.1), damped_oscillator(0.05));
+        let e_coarse = damped_oscillator_error(
+            &coarse,
+            GeneralisedAlpha::newmark(0.25, 0.5, NalgebraLU::default()),
+        );
+        let e_fine = damped_oscillator_error(
+            &fine,
+            GeneralisedAlpha::newmark(0.25, 0.5, NalgebraLU::default()),
+        );
+        assert!(e_fine < 1e-3);
+        assert!(((e_coarse / e_fine).log2() - 2.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_generalised_alpha_damped_oscillator() {
+        let (coarse, fine) = (damped_oscillator(0.1), damped_oscillator(0.05));
+        let e_coarse =
+            damped_oscillator_error(&coarse, GeneralisedAlpha::new(0.8, NalgebraLU::default()));
+        let e_fine =
+            damped_oscillator_error(&fine, GeneralisedAlpha::new(0.8, NalgebraLU::default()));
+        assert!(e_fine < 1e-3);
+        assert!(((e_coarse / e_fine).log2() - 2.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_generalised_alpha_step() {
+        let problem = damped_oscillator(0.1);
+        let mut s = GeneralisedAlpha::new(0.5, NalgebraLU::default());
+        assert!(s.step().is_err());
+        s.set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .unwrap();
+        s.set_stop_time(0.25).unwrap();
+        let mut n = 0;
+        while let OdeSolverStopReason::InternalTimestep = s.step().unwrap() {
+            n += 1;
+        }
+        assert_eq!(n, 2);
+        let state = s.state().unwrap();
+        assert_eq!(state.t, 0.25);
+
+        // initial acceleration from M a = g, and the derivative is (v, a)
+        let y = s.interpolate(0.2).unwrap();
+        assert!((y[0] - damped_oscillator_soln(0.2)).abs() < 1e-3);
+        assert!((state.dy[0] - state.y[1]).abs() < 1e-12);
+        assert!(s.interpolate(0.3).is_err());
+        let statistics = s.statistics();
+        assert_eq!(statistics.number_of_steps, 3);
+        assert!(s.take_state().is_some());
+        assert!(s.step().is_err());
+    }
+}
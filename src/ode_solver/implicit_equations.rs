@@ -0,0 +1,209 @@
+use std::rc::Rc;
+
+use crate::{
+    op::{
+        implicit_dae::{ImplicitDaeMass, ImplicitDaeRhs},
+        unit::UnitCallable,
+        ResidualOp,
+    },
+    scalar::Scalar,
+    Matrix, NonLinearOp, OdeEquations, OdeEquationsStatistics, Op, Vector,
+};
+
+/// this is the trait that defines a fully implicit DAE of the form
+///
+/// $$
+///  F(t, y, y') = 0
+///  y(t_0) = y_0(t_0)
+///  y'(t_0) = y'_0(t_0)
+/// $$
+///
+/// The equations are defined by:
+/// - the residual `F(t, y, y')`, which is given as a [ResidualOp] (with its jacobians with respect to `y` and `y'`) using the `Residual` associated type and [Self::residual] function,
+/// - the initial condition `y_0(t_0)` and time derivative `y'_0(t_0)`, which are given using the [Self::init] and [Self::init_dot] functions.
+///
+/// To solve the equations, wrap them in an [ImplicitDae], which implements [OdeEquations].
+pub trait ImplicitDaeEquations {
+    type T: Scalar;
+    type V: Vector<T = Self::T>;
+    type M: Matrix<T = Self::T, V = Self::V>;
+    type Residual: ResidualOp<M = Self::M, V = Self::V, T = Self::T>;
+    type Root: NonLinearOp<M = Self::M, V = Self::V, T = Self::T>;
+
+    /// The parameters of the equations are assumed to be constant. This function sets the parameters to the given value before solving.
+    fn set_params(&mut self, p: Self::V);
+
+    /// returns the residual `F(t, y, y')` as a [ResidualOp]
+    fn residual(&self) -> &Rc<Self::Residual>;
+
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        None
+    }
+
+    /// returns the initial condition, i.e. `y(t)`, where `t` is the initial time
+    fn init(&self, t: Self::T) -> Self::V;
+
+    /// returns the initial time derivative, i.e. `y'(t)`, where `t` is the initial time
+    fn init_dot(&self, t: Self::T) -> Self::V;
+}
+
+/// Wraps a fully implicit DAE (see [ImplicitDaeEquations]) so it can be solved using the [OdeEquations] interface.
+/// The residual is returned by [OdeEquations::residual] and is used directly by the solvers that support the implicit form
+/// ([crate::Bdf] and [crate::SundialsIda]), the other solvers return an error from [crate::OdeSolverMethod::set_problem].
+/// The rhs and mass are the semi-explicit form `f(t, y) = -F(t, y, 0)` and `M = dF/dy'` (see [ImplicitDaeRhs] and [ImplicitDaeMass]),
+/// which are only equivalent to the residual if it is linear in `y'` with a constant `dF/dy'`.
+pub struct ImplicitDae<Eqn: ImplicitDaeEquations> {
+    eqn: Eqn,
+    // the rhs and mass share the residual, they are only `None` while the parameters of the residual are set
+    rhs: Option<Rc<ImplicitDaeRhs<Eqn::Residual>>>,
+    mass: Option<Rc<ImplicitDaeMass<Eqn::Residual>>>,
+}
+
+impl<Eqn: ImplicitDaeEquations> ImplicitDae<Eqn> {
+    pub fn new(eqn: Eqn) -> Self {
+        let mut ret = Self {
+            eqn,
+            rhs: None,
+            mass: None,
+        };
+        ret.build_semi_explicit();
+        ret
+    }
+
+    fn build_semi_explicit(&mut self) {
+        let residual = self.eqn.residual();
+        self.rhs = Some(Rc::new(ImplicitDaeRhs::new(residual.clone())));
+        self.mass = Some(Rc::new(ImplicitDaeMass::new(residual.clone())));
+    }
+
+    pub fn equations(&self) -> &Eqn {
+        &self.eqn
+    }
+}
+
+impl<Eqn: ImplicitDaeEquations> OdeEquations for ImplicitDae<Eqn> {
+    type T = Eqn::T;
+    type V = Eqn::V;
+    type M = Eqn::M;
+    type Rhs = ImplicitDaeRhs<Eqn::Residual>;
+    type Mass = ImplicitDaeMass<Eqn::Residual>;
+    type Root = Eqn::Root;
+
+    fn set_params(&mut self, p: Self::V) {
+        // release the references to the residual so that its parameters can be set
+        self.rhs = None;
+        self.mass = None;
+        self.eqn.set_params(p);
+        self.build_semi_explicit();
+    }
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        self.rhs.as_ref().unwrap()
+    }
+    fn mass(&self) -> &Rc<Self::Mass> {
+        self.mass.as_ref().unwrap()
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        self.eqn.root()
+    }
+    fn residual(&self) -> Option<&dyn ResidualOp<M = Self::M, V = Self::V, T = Self::T>> {
+        Some(self.eqn.residual().as_ref())
+    }
+    fn init(&self, t: Self::T) -> Self::V {
+        self.eqn.init(t)
+    }
+    fn init_dot(&self, t: Self::T) -> Self::V {
+        self.eqn.init_dot(t)
+    }
+
+    // the residual evaluations are reported as rhs evaluations, and the evaluations of both of its jacobians as jacobian evaluations
+    fn statistics(&self) -> OdeEquationsStatistics {
+        let residual = self.eqn.residual().statistics();
+        let root = self.root().map(|r| r.statistics()).unwrap_or_default();
+        OdeEquationsStatistics {
+            number_of_rhs_evals: residual.number_of_calls,
+            number_of_jac_mul_evals: residual.number_of_jac_muls,
+            number_of_mass_evals: 0,
+            number_of_mass_matrix_evals: 0,
+            number_of_jacobian_matrix_evals: residual.number_of_matrix_evals,
+            number_of_root_evals: root.number_of_calls,
+        }
+    }
+}
+
+/// This struct implements the [ImplicitDaeEquations] trait for a given residual op, optional root op, and initial condition functions.
+pub struct ImplicitDaeSolverEquations<M, Res, I, J, Root = UnitCallable<M>>
+where
+    M: Matrix,
+    Res: ResidualOp<M = M, V = M::V, T = M::T>,
+    Root: NonLinearOp<M = M, V = M::V, T = M::T>,
+    I: Fn(&M::V, M::T) -> M::V,
+    J: Fn(&M::V, M::T) -> M::V,
+{
+    residual: Rc<Res>,
+    root: Option<Rc<Root>>,
+    init: I,
+    init_dot: J,
+    p: Rc<M::V>,
+}
+
+impl<M, Res, I, J, Root> ImplicitDaeSolverEquations<M, Res, I, J, Root>
+where
+    M: Matrix,
+    Res: ResidualOp<M = M, V = M::V, T = M::T>,
+    Root: NonLinearOp<M = M, V = M::V, T = M::T>,
+    I: Fn(&M::V, M::T) -> M::V,
+    J: Fn(&M::V, M::T) -> M::V,
+{
+    pub fn new(
+        residual: Rc<Res>,
+        root: Option<Rc<Root>>,
+        init: I,
+        init_dot: J,
+        p: Rc<M::V>,
+    ) -> Self {
+        Self {
+            residual,
+            root,
+            init,
+            init_dot,
+            p,
+        }
+    }
+}
+
+impl<M, Res, I, J, Root> ImplicitDaeEquations for ImplicitDaeSolverEquations<M, Res, I, J, Root>
+where
+    M: Matrix,
+    Res: ResidualOp<M = M, V = M::V, T = M::T>,
+    Root: NonLinearOp<M = M, V = M::V, T = M::T>,
+    I: Fn(&M::V, M::T) -> M::V,
+    J: Fn(&M::V, M::T) -> M::V,
+{
+    type T = M::T;
+    type V = M::V;
+    type M = M;
+    type Residual = Res;
+    type Root = Root;
+
+    fn residual(&self) -> &Rc<Self::Residual> {
+        &self.residual
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        self.root.as_ref()
+    }
+    fn init(&self, t: Self::T) -> Self::V {
+        (self.init)(self.p.as_ref(), t)
+    }
+    fn init_dot(&self, t: Self::T) -> Self::V {
+        (self.init_dot)(self.p.as_ref(), t)
+    }
+    fn set_params(&mut self, p: Self::V) {
+        self.p = Rc::new(p);
+        Rc::<Res>::get_mut(&mut self.residual)
+            .unwrap()
+            .set_params(self.p.clone());
+        if let Some(r) = self.root.as_mut() {
+            Rc::<Root>::get_mut(r).unwrap().set_params(self.p.clone())
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::{anyhow, Result};
+use num_traits::{abs, One, Pow, Zero};
+use std::time::Instant;
+
+use crate::{
+    matrix::{default_solver::DefaultSolver, expm::expm_integral},
+    DenseMatrix, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+    OdeSolverStatistics, OdeSolverStopReason, Op, RootFinder, Scalar, Vector,
+};
+
+use super::fixed_step::{fixed_step_size, is_at_stop_time};
+
+/// An exact solver for linear ODEs with constant coefficients `y' = A y + b(t)` (see [crate::OdeBuilder::build_linear_ode]), using the
+/// matrix exponential of the small dense matrix `A` (of type `M`).
+///
+/// The matrix `A` is found from the jacobian-vector product of the right-hand side at the start of the solve, and the input `b(t) = f(t, 0)`
+/// from the right-hand side at zero, so the right-hand side must be affine in the state with a constant jacobian. Each step integrates the
+/// equations exactly assuming a constant input over the step, which is evaluated at the midpoint of the step. The solution is therefore exact
+/// (to rounding error) for piecewise-constant inputs, as long as the times at which the input changes are given using [Self::set_breakpoints],
+/// and second order accurate for smooth inputs. Interpolation within a step also uses the matrix exponential, so is exact as well.
+///
+/// Each step is taken to the next breakpoint or stop time if there is one, so no step size control is needed, or otherwise uses a fixed step
+/// (see [super::fixed_step]). The matrix exponential is only recalculated when the step size changes. The mass matrix of the problem is not
+/// used, so it must be the identity. [OdeSolverMethod::set_problem] returns an error if the mass matrix is not the identity, or if the
+/// right-hand side is not affine with a constant matrix `A` (checked at two other states and at the times `t0` and `t0 + h`).
+pub struct LinearOdeSolver<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    a: Option<M>,
+    propagator: Option<(Eqn::T, M, M)>,
+    breakpoints: Vec<Eqn::T>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    input: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+}
+
+impl<M, Eqn> Default for LinearOdeSolver<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, Eqn> LinearOdeSolver<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    pub fn new() -> Self {
+        Self {
+            a: None,
+            propagator: None,
+            breakpoints: Vec::new(),
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(1),
+            input: Eqn::V::zeros(1),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+        }
+    }
+
+    /// Set the times at which the input `b(t)` is discontinuous (e.g. the start and end of an infusion), so that the solver steps exactly to them.
+    /// Returns an error if any of the times are not finite.
+    pub fn set_breakpoints(&mut self, mut breakpoints: Vec<Eqn::T>) -> Result<()> {
+        if !breakpoints.iter().all(|&bp| abs(bp) < Eqn::T::INFINITY) {
+            return Err(anyhow!("Breakpoints must be finite"));
+        }
+        breakpoints.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        self.breakpoints = breakpoints;
+        Ok(())
+    }
+
+    /// The dense matrix `A`, available after the problem is set.
+    pub fn matrix(&self) -> Option<&M> {
+        self.a.as_ref()
+    }
+
+    // true if f(t, z) - f(t, 0) = A z at the times t, for the states z = y + v and z = y + 2 v where v has distinct entries, to within sqrt(eps)
+    // relative to the size of the terms of A z and f(t, 0)
+    fn is_affine(rhs: &Eqn::Rhs, a: &M, y: &Eqn::V, times: &[Eqn::T]) -> bool {
+        let n = y.len();
+        let zeros = Eqn::V::zeros(n);
+        let tol = Eqn::T::EPSILON.pow(Eqn::T::from(0.5));
+        [Eqn::T::one(), Eqn::T::from(2.0)].iter().all(|&c| {
+            let mut z = y.clone();
+            for i in 0..n {
+                z[i] += c * (Eqn::T::one() + Eqn::T::from(i as f64) / Eqn::T::from(n as f64));
+            }
+            let mut az = Eqn::V::zeros(n);
+            a.gemv(Eqn::T::one(), &z, Eqn::T::zero(), &mut az);
+            times.iter().all(|&t| {
+                let f0 = rhs.call(&zeros, t);
+                let fz = rhs.call(&z, t);
+                (0..n).all(|i| {
+                    let mut size = abs(f0[i]);
+                    for j in 0..n {
+                        size += abs(a[(i, j)] * z[j]);
+                    }
+                    abs(fz[i] - f0[i] - az[i]) <= tol * size
+                })
+            })
+        })
+    }
+
+    // the solution y(t0 + h) = exp(h A) y + (int_0^h exp(s A) ds) b
+    fn propagate(exp_ha: &M, integral: &M, y: &Eqn::V, b: &Eqn::V) -> Eqn::V {
+        let mut y_new = Eqn::V::zeros(y.len());
+        exp_ha.gemv(Eqn::T::one(), y, Eqn::T::zero(), &mut y_new);
+        integral.gemv(Eqn::T::one(), b, Eqn::T::one(), &mut y_new);
+        y_new
+    }
+
+    // the next breakpoint or stop time after t
+    fn next_stop(&self, t: Eqn::T, h: Eqn::T) -> Option<Eqn::T> {
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .find(|&&bp| bp > t && !is_at_stop_time(t, h, bp))
+            .copied();
+        match (breakpoint, self.tstop) {
+            (Some(bp), Some(tstop)) if tstop < bp => Some(tstop),
+            (Some(bp), _) => Some(bp),
+            (None, tstop) => tstop,
+        }
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let (Some(state), Some(problem)) = (self.state.as_ref(), self.problem.as_ref()) else {
+            return Err(anyhow!("State not set"));
+        };
+        let t = state.t;
+        let next_stop = self.next_stop(t, state.h);
+        let h = match next_stop {
+            Some(stop) => stop - t,
+            None => fixed_step_size(t, state.h, None),
+        };
+
+        // the input is constant over the step
+        let rhs = problem.eqn.rhs();
+        let zeros = Eqn::V::zeros(state.y.len());
+        let input = rhs.call(&zeros, t + h * Eqn::T::from(0.5));
+        if self.propagator.as_ref().map(|(h_prop, _, _)| *h_prop) != Some(h) {
+            let start = Instant::now();
+            let (exp_ha, integral) = expm_integral(self.a.as_ref().unwrap(), h)?;
+            self.propagator = Some((h, exp_ha, integral));
+            self.statistics.timings.linear_solver_setup += start.elapsed();
+            self.statistics.number_of_linear_solver_setups += 1;
+        }
+        let (_, exp_ha, integral) = self.propagator.as_ref().unwrap();
+        let y_new = Self::propagate(exp_ha, integral, &state.y, &input);
+
+        let state = self.state.as_mut().unwrap();
+        self.old_t = t;
+        self.old_y.copy_from(&state.y);
+        self.input = input;
+        state.t = match next_stop {
+            Some(stop) => stop,
+            None => t + h,
+        };
+        state.dy = rhs.call(&y_new, state.t);
+        state.y = y_new;
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = problem.eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<M, Eqn> OdeSolverMethod<Eqn> for LinearOdeSolver<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V> + DefaultSolver,
+    Eqn: OdeEquations,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("LinearOdeSolver")?;
+        problem.check_mass_identity("LinearOdeSolver", state.t)?;
+
+        // the columns of A are the jacobian-vector products with the unit vectors
+        let rhs = problem.eqn.rhs();
+        let n = state.y.len();
+        let mut a = M::zeros(n, n);
+        let mut unit = Eqn::V::zeros(n);
+        let mut column = Eqn::V::zeros(n);
+        for j in 0..n {
+            unit[j] = Eqn::T::one();
+            rhs.jac_mul_inplace(&state.y, state.t, &unit, &mut column);
+            for i in 0..n {
+                a[(i, j)] = column[i];
+            }
+            unit[j] = Eqn::T::zero();
+        }
+        if !Self::is_affine(rhs, &a, &state.y, &[state.t, state.t + state.h]) {
+            return Err(anyhow!(
+                "LinearOdeSolver requires a right-hand side f(t, y) = A y + b(t) that is affine in the state, with a constant matrix A"
+            ));
+        }
+        self.a = Some(a);
+        self.propagator = None;
+
+        state.dy = rhs.call(&state.y, state.t);
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.input = Eqn::V::zeros(n);
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        if t == self.old_t {
+            return Ok(self.old_y.clone());
+        }
+        if t == state.t {
+            return Ok(state.y.clone());
+        }
+        let (exp_ha, integral) = expm_integral(self.a.as_ref().unwrap(), t - self.old_t)?;
+        Ok(Self::propagate(
+            &exp_ha,
+            &integral,
+            &self.old_y,
+            &self.input,
+        ))
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::tests::{test_interpolate, test_no_set_problem, test_take_state},
+        OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverStopReason, Vector,
+    };
+
+    use super::LinearOdeSolver;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // a two compartment model y0' = -k0 y0 + r(t), y1' = k0 y0 - k1 y1, with an infusion r(t) = 1 for t < 1
+    fn two_compartment() -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        OdeBuilder::new()
+            .p([1.0, 0.5])
+            .build_linear_ode::<M, _, _, _>(
+                |x: &V, p: &V, _t, beta, y: &mut V| {
+                    y[0] = -p[0] * x[0] + beta * y[0];
+                    y[1] = p[0] * x[0] - p[1] * x[1] + beta * y[1];
+                },
+                |_p: &V, t, y: &mut V| {
+                    y[0] = if t < 1.0 { 1.0 } else { 0.0 };
+                    y[1] = 0.0;
+                },
+                |_p: &V, _t| V::from_element(2, 0.0),
+            )
+            .unwrap()
+    }
+
+    fn two_compartment_soln(t: f64) -> [f64; 2] {
+        // during the infusion
+        let during = |t: f64| {
+            let y0 = 1.0 - (-t).exp();
+            let y1 = 2.0 - 4.0 * (-0.5 * t).exp() + 2.0 * (-t).exp();
+            [y0, y1]
+        };
+        if t <= 1.0 {
+            return during(t);
+        }
+        // after the infusion, starting from the state at t = 1
+        let [a, b] = during(1.0);
+        let s = t - 1.0;
+        let y0 = a * (-s).exp();
+        let y1 = (b + 2.0 * a) * (-0.5 * s).exp() - 2.0 * a * (-s).exp();
+        [y0, y1]
+    }
+
+    #[test]
+    fn test_linear_ode_exact() {
+        let problem = two_compartment();
+        let mut s = LinearOdeSolver::<M, _>::default();
+        assert!(s.set_breakpoints(vec![1.0, f64::NAN]).is_err());
+        assert!(s.set_breakpoints(vec![f64::INFINITY]).is_err());
+        s.set_breakpoints(vec![1.0]).unwrap();
+        s.set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .unwrap();
+        s.set_stop_time(5.0).unwrap();
+
+        // one step to the breakpoint, and one to the stop time
+        assert!(matches!(
+            s.step().unwrap(),
+            OdeSolverStopReason::InternalTimestep
+        ));
+        assert_eq!(s.state().unwrap().t, 1.0);
+        for t in [0.1, 0.5, 1.0] {
+            let [y0, y1] = two_compartment_soln(t);
+            s.interpolate(t)
+                .unwrap()
+                .assert_eq_st(&V::from_vec(vec![y0, y1]), 1e-14);
+        }
+        assert!(matches!(
+            s.step().unwrap(),
+            OdeSolverStopReason::TstopReached
+        ));
+        assert_eq!(s.state().unwrap().t, 5.0);
+        for t in [1.5, 3.0, 5.0] {
+            let [y0, y1] = two_compartment_soln(t);
+            s.interpolate(t)
+                .unwrap()
+                .assert_eq_st(&V::from_vec(vec![y0, y1]), 1e-14);
+        }
+        let statistics = s.statistics();
+        assert_eq!(statistics.number_of_steps, 2);
+        assert_eq!(statistics.number_of_linear_solver_setups, 2);
+
+        let a = s.matrix().unwrap();
+        assert_eq!(a[(1, 0)], 1.0);
+        assert_eq!(a[(1, 1)], -0.5);
+    }
+
+    #[test]
+    fn test_linear_ode_fixed_steps() {
+        // without any stop times, steps of size h0 reuse the same matrix exponential
+        let problem = two_compartment();
+        let mut s = LinearOdeSolver::<M, _>::default();
+        s.set_breakpoints(vec![1.0]).unwrap();
+        s.set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .unwrap();
+        s.step().unwrap();
+        s.take_state().unwrap();
+
+        let y = s.solve(&problem, 10.0).unwrap();
+        let [y0, y1] = two_compartment_soln(10.0);
+        y.assert_eq_st(&V::from_vec(vec![y0, y1]), 1e-14);
+    }
+
+    #[test]
+    fn test_linear_ode_unsupported() {
+        // y' = -y^2 is not affine
+        let problem = OdeBuilder::new()
+            .build_ode::<M, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0] * x[0],
+                |x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -2.0 * x[0] * v[0],
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        assert!(LinearOdeSolver::<M, _>::default()
+            .set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .is_err());
+
+        // 2 y' = -y has a mass matrix that is not the identity
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0],
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -v[0],
+                |v: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * v[0] + beta * y[0],
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        assert!(LinearOdeSolver::<M, _>::default()
+            .set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .is_err());
+    }
+
+    #[test]
+    fn test_linear_ode_no_set_problem() {
+        test_no_set_problem::<M, _>(LinearOdeSolver::<M, _>::default())
+    }
+
+    #[test]
+    fn test_linear_ode_take_state() {
+        test_take_state::<M, _>(LinearOdeSolver::<M, _>::default())
+    }
+
+    #[test]
+    fn test_linear_ode_interpolate() {
+        test_interpolate::<M, _>(LinearOdeSolver::<M, _>::default())
+    }
+}
@@ -1,10 +1,11 @@
 use anyhow::Result;
-use num_traits::Zero;
+use serde::{Serialize, Serializer};
 use std::rc::Rc;
+use std::time::Duration;
 
 use crate::{
-    op::filter::FilterCallable, scalar::Scalar, LinearOp, Matrix, NonLinearSolver, OdeEquations,
-    OdeSolverProblem, SolverProblem, Vector, VectorIndex,
+    op::init::InitCallable, scalar::Scalar, NonLinearOp, NonLinearSolver, OdeEquations,
+    OdeEquationsStatistics, OdeSolverProblem, SolverProblem, Vector,
 };
 
 pub enum OdeSolverStopReason<T: Scalar> {
@@ -13,6 +14,60 @@ pub enum OdeSolverStopReason<T: Scalar> {
     TstopReached,
 }
 
+/// Statistics collected by an ODE solver, returned by [OdeSolverMethod::statistics].
+///
+/// The solver counters are reset every time [OdeSolverMethod::set_problem] is called. The `equations` field contains the evaluation counts
+/// of the operators in the problem (see [OdeEquations::statistics]), these are accumulated over the lifetime of the equations so are not reset.
+#[derive(Clone, Debug, Serialize)]
+pub struct OdeSolverStatistics<T: Scalar> {
+    pub number_of_linear_solver_setups: usize,
+    pub number_of_linear_solves: usize,
+    pub number_of_steps: usize,
+    pub number_of_error_test_failures: usize,
+    pub number_of_nonlinear_solver_iterations: usize,
+    pub number_of_nonlinear_solver_fails: usize,
+    pub initial_step_size: T,
+    pub final_step_size: T,
+    pub equations: OdeEquationsStatistics,
+    pub timings: OdeSolverTimings,
+}
+
+impl<T: Scalar> Default for OdeSolverStatistics<T> {
+    fn default() -> Self {
+        Self {
+            number_of_linear_solver_setups: 0,
+            number_of_linear_solves: 0,
+            number_of_steps: 0,
+            number_of_error_test_failures: 0,
+            number_of_nonlinear_solver_iterations: 0,
+            number_of_nonlinear_solver_fails: 0,
+            initial_step_size: T::zero(),
+            final_step_size: T::zero(),
+            equations: OdeEquationsStatistics::default(),
+            timings: OdeSolverTimings::default(),
+        }
+    }
+}
+
+/// Wall time spent by an ODE solver in each phase of the solve. `total` is the time spent in [OdeSolverMethod::step],
+/// and the other fields are the parts of this spent in the nonlinear solver, setting up (e.g. factorising) the linear solver, and root finding.
+/// Timings are serialized as seconds.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct OdeSolverTimings {
+    #[serde(serialize_with = "serialize_secs")]
+    pub total: Duration,
+    #[serde(serialize_with = "serialize_secs")]
+    pub nonlinear_solver: Duration,
+    #[serde(serialize_with = "serialize_secs")]
+    pub linear_solver_setup: Duration,
+    #[serde(serialize_with = "serialize_secs")]
+    pub root_finding: Duration,
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
 /// Trait for ODE solver methods. This is the main user interface for the ODE solvers.
 /// The solver is responsible for stepping the solution (given in the `OdeSolverState`), and interpolating the solution at a given time.
 /// However, the solver does not own the state, so the user is responsible for creating and managing the state. If the user
@@ -25,7 +80,7 @@ pub enum OdeSolverStopReason<T: Scalar> {
 ///
 /// fn solve_ode<Eqn: OdeEquations>(solver: &mut impl OdeSolverMethod<Eqn>, problem: &OdeSolverProblem<Eqn>, t: Eqn::T) -> Eqn::V {
 ///     let state = OdeSolverState::new(problem);
-///     solver.set_problem(state, problem);
+///     solver.set_problem(state, problem).unwrap();
 ///     while solver.state().unwrap().t <= t {
 ///         solver.step().unwrap();
 ///     }
@@ -38,7 +93,12 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
 
     /// Set the problem to solve, this performs any initialisation required by the solver. Call this before calling `step` or `solve`.
     /// The solver takes ownership of the initial state given by `state`, this is assumed to be consistent with any algebraic constraints.
-    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>);
+    /// An error is returned if the solver does not support the problem (e.g. a fully implicit DAE given to a solver that only supports the semi-explicit form).
+    fn set_problem(
+        &mut self,
+        state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()>;
 
     /// Step the solution forward by one step, altering the internal state of the solver.
     /// The return value is a `Result` containing the reason for stopping the solver, possible reasons are:
@@ -54,6 +114,9 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
     /// Interpolate the solution at a given time. This time should be between the current time and the last solver time step
     fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V>;
 
+    /// Get the statistics collected by the solver since the last call to [Self::set_problem], including the operator evaluation counts of the current problem
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T>;
+
     /// Get the current state of the solver, if it exists
     fn state(&self) -> Option<&OdeSolverState<Eqn::V>>;
 
@@ -65,7 +128,7 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
     /// Reinitialise the solver state and solve the problem up to time `t`
     fn solve(&mut self, problem: &OdeSolverProblem<Eqn>, t: Eqn::T) -> Result<Eqn::V> {
         let state = OdeSolverState::new(problem);
-        self.set_problem(state, problem);
+        self.set_problem(state, problem)?;
         self.set_stop_time(t)?;
         loop {
             if let OdeSolverStopReason::TstopReached = self.step()? {
@@ -76,14 +139,14 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
     }
 
     /// Reinitialise the solver state making it consistent with the algebraic constraints and solve the problem up to time `t`
-    fn make_consistent_and_solve<RS: NonLinearSolver<FilterCallable<Eqn::Rhs>>>(
+    fn make_consistent_and_solve<RS: NonLinearSolver<InitCallable<Eqn>>>(
         &mut self,
         problem: &OdeSolverProblem<Eqn>,
         t: Eqn::T,
         root_solver: &mut RS,
     ) -> Result<Eqn::V> {
         let state = OdeSolverState::new_consistent(problem, root_solver)?;
-        self.set_problem(state, problem);
+        self.set_problem(state, problem)?;
         self.set_stop_time(t)?;
         loop {
             if let OdeSolverStopReason::TstopReached = self.step()? {
@@ -94,16 +157,18 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
     }
 }
 
-/// State for the ODE solver, containing the current solution `y`, the current time `t`, and the current step size `h`.
+/// State for the ODE solver, containing the current solution `y`, its time derivative `dy`, the current time `t`, and the current step size `h`.
 #[derive(Clone)]
 pub struct OdeSolverState<V: Vector> {
     pub y: V,
+    pub dy: V,
     pub t: V::T,
     pub h: V::T,
 }
 
 impl<V: Vector> OdeSolverState<V> {
-    /// Create a new solver state from an ODE problem. Note that this does not make the state consistent with the algebraic constraints.
+    /// Create a new solver state from an ODE problem. Note that this does not make the state consistent with the algebraic constraints,
+    /// and the time derivative is given by [OdeEquations::init_dot] (which is zero unless the equations are a fully implicit DAE).
     /// If you need to make the state consistent, use `new_consistent` instead.
     pub fn new<Eqn>(ode_problem: &OdeSolverProblem<Eqn>) -> Self
     where
@@ -112,36 +177,42 @@ impl<V: Vector> OdeSolverState<V> {
         let t = ode_problem.t0;
         let h = ode_problem.h0;
         let y = ode_problem.eqn.init(t);
-        Self { y, t, h }
+        let dy = ode_problem.eqn.init_dot(t);
+        Self { y, dy, t, h }
     }
 
     /// Create a new solver state from an ODE problem, making the state consistent with the algebraic constraints.
+    ///
+    /// Like the `IDA_YA_YDP_INIT` option of IDA, this solves `F(t, y, y') = 0` (i.e. `M y' - f(t, y) = 0`) for the algebraic components of `y`
+    /// and the differential components of `y'`, keeping the differential components of `y` fixed. The algebraic components are found from the nullspace
+    /// of the mass matrix (or the jacobian of the residual with respect to `y'`, see [InitCallable]), and the nonlinear system is solved using
+    /// the given `root_solver`. If the mass matrix is diagonal with no zeros on the diagonal, `y'` is found directly from `M y' = f(t, y)`.
     pub fn new_consistent<Eqn, S>(
         ode_problem: &OdeSolverProblem<Eqn>,
         root_solver: &mut S,
     ) -> Result<Self>
     where
         Eqn: OdeEquations<T = V::T, V = V>,
-        S: NonLinearSolver<FilterCallable<Eqn::Rhs>> + ?Sized,
+        S: NonLinearSolver<InitCallable<Eqn>> + ?Sized,
     {
         let t = ode_problem.t0;
         let h = ode_problem.h0;
-        let mass_diagonal = ode_problem.eqn.mass().matrix(t).diagonal();
-        let indices = mass_diagonal.filter_indices(|x| x == Eqn::T::zero());
         let mut y = ode_problem.eqn.init(t);
-        if indices.len() == 0 {
-            return Ok(Self { y, t, h });
+        let mut dy = ode_problem.eqn.init_dot(t);
+        let f = Rc::new(InitCallable::new(&ode_problem.eqn, &y, &dy, t));
+        if let Some(mass_diagonal) = f.mass_diagonal() {
+            if !f.is_algebraic().contains(&true) {
+                // there are no algebraic components and the mass is diagonal, so y' = M^{-1} f(t, y)
+                dy = ode_problem.eqn.rhs().call(&y, t);
+                dy.component_div_assign(mass_diagonal);
+                return Ok(Self { y, dy, t, h });
+            }
         }
-        let mut y_filtered = y.filter(&indices);
-        let atol = Rc::new(ode_problem.atol.as_ref().filter(&indices));
-        let rhs = ode_problem.eqn.rhs().clone();
-        let f = Rc::new(FilterCallable::new(rhs, &y, indices));
-        let rtol = ode_problem.rtol;
-        let init_problem = SolverProblem::new(f, atol, rtol);
+        let mut x = f.pack(&y, &dy);
+        let init_problem = SolverProblem::new(f, ode_problem.atol.clone(), ode_problem.rtol);
         root_solver.set_problem(&init_problem);
-        root_solver.solve_in_place(&mut y_filtered, t)?;
-        let indices = init_problem.f.indices();
-        y.scatter_from(&y_filtered, indices);
-        Ok(Self { y, t, h })
+        root_solver.solve_in_place(&mut x, t)?;
+        init_problem.f.unpack(&x, &mut y, &mut dy);
+        Ok(Self { y, dy, t, h })
     }
 }
@@ -1,9 +1,24 @@
 pub mod bdf;
 pub mod builder;
+pub mod bvp;
+pub mod continuation;
+pub mod dde;
 pub mod equations;
+pub mod exponential;
+pub mod fixed_step;
+pub mod generalised_alpha;
+pub mod implicit_equations;
+pub mod linear;
 pub mod method;
+pub mod multirate;
+pub mod parareal;
+pub mod periodic_orbit;
 pub mod problem;
+pub mod rkc;
+pub mod sde;
 pub mod sdirk;
+pub mod steady_state;
+pub mod symplectic;
 pub mod tableau;
 pub mod test_models;
 
@@ -18,26 +33,35 @@ mod tests {
     use std::rc::Rc;
 
     use self::problem::OdeSolverSolution;
-    use self::test_models::exponential_decay::exponential_decay_problem_with_root;
+    use self::test_models::exponential_decay::{
+        exponential_decay_problem_no_jac, exponential_decay_problem_with_root,
+    };
 
     use super::test_models::{
         exponential_decay::exponential_decay_problem,
         exponential_decay_with_algebraic::exponential_decay_with_algebraic_problem,
-        robertson::robertson, robertson_ode::robertson_ode,
+        implicit_exponential_decay::implicit_exponential_decay_problem,
+        robertson::{robertson, robertson_no_jac},
+        robertson_ode::{robertson_ode, robertson_ode_autodiff},
+        state_dependent_mass::state_dependent_mass_problem,
     };
     use super::*;
+    use crate::linear_solver::gmres::Gmres;
     use crate::linear_solver::nalgebra::lu::LU;
     use crate::matrix::Matrix;
-    use crate::nonlinear_solver::newton::NewtonNonlinearSolver;
-    use crate::op::filter::FilterCallable;
+    use crate::nonlinear_solver::{
+        anderson::AndersonNonlinearSolver, broyden::BroydenNonlinearSolver,
+        newton::NewtonNonlinearSolver, newton_krylov::NewtonKrylovNonlinearSolver,
+    };
+    use crate::op::init::InitCallable;
     use crate::op::unit::UnitCallable;
     use crate::op::{NonLinearOp, Op};
     use crate::scalar::scale;
     use crate::{
         NonLinearSolver, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
-        OdeSolverStopReason,
+        OdeSolverStatistics, OdeSolverStopReason, OdeSolverTimings,
     };
-    use crate::{Sdirk, Tableau, Vector};
+    use crate::{OdeBuilder, Sdirk, Tableau, Vector};
     use num_traits::Zero;
     use num_traits::{abs, One};
     use tests::bdf::Bdf;
@@ -46,7 +70,7 @@ mod tests {
 
     fn test_ode_solver<M, Eqn>(
         method: &mut impl OdeSolverMethod<Eqn>,
-        mut root_solver: impl NonLinearSolver<FilterCallable<Eqn::Rhs>>,
+        mut root_solver: impl NonLinearSolver<InitCallable<Eqn>>,
         problem: &OdeSolverProblem<Eqn>,
         solution: OdeSolverSolution<M::V>,
         override_tol: Option<M::T>,
@@ -57,7 +81,7 @@ mod tests {
         Eqn: OdeEquations<M = M, T = M::T, V = M::V>,
     {
         let state = OdeSolverState::new_consistent(problem, &mut root_solver).unwrap();
-        method.set_problem(state, problem);
+        method.set_problem(state, problem).unwrap();
         let have_root = problem.eqn.as_ref().root().is_some();
         for point in solution.solution_points.iter() {
             let soln = if use_tstop {
@@ -107,6 +131,15 @@ mod tests {
         method.state().unwrap().y.clone()
     }
 
+    // timings are not deterministic, so are zeroed before taking snapshots of the statistics
+    fn statistics_without_timings<Eqn: OdeEquations>(
+        method: &impl OdeSolverMethod<Eqn>,
+    ) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = method.statistics();
+        statistics.timings = OdeSolverTimings::default();
+        statistics
+    }
+
     type Mcpu = nalgebra::DMatrix<f64>;
 
     #[test]
@@ -116,19 +149,32 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = exponential_decay_problem::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 27
+        number_of_linear_solves: 135
         number_of_steps: 27
         number_of_error_test_failures: 0
-        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_iterations: 108
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.1919383103666485
         final_step_size: 0.37881820951293194
+        equations:
+          number_of_rhs_evals: 111
+          number_of_jac_mul_evals: 2
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 1
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 110
+        number_of_calls: 111
         number_of_jac_muls: 2
         number_of_matrix_evals: 1
         "###);
@@ -141,19 +187,32 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = exponential_decay_problem::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 12
+        number_of_linear_solves: 84
         number_of_steps: 12
         number_of_error_test_failures: 0
-        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_iterations: 72
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.28998214001102113
         final_step_size: 0.9543072149538415
+        equations:
+          number_of_rhs_evals: 75
+          number_of_jac_mul_evals: 2
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 1
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 74
+        number_of_calls: 75
         number_of_jac_muls: 2
         number_of_matrix_evals: 1
         "###);
@@ -165,24 +224,167 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = exponential_decay_problem::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 19
+        number_of_linear_solves: 78
         number_of_steps: 31
         number_of_error_test_failures: 8
         number_of_nonlinear_solver_iterations: 78
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.011892071150027213
         final_step_size: 0.9795994412020951
+        equations:
+          number_of_rhs_evals: 81
+          number_of_jac_mul_evals: 2
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 1
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 80
+        number_of_calls: 81
         number_of_jac_muls: 2
         number_of_matrix_evals: 1
         "###);
     }
 
+    #[test]
+    fn test_bdf_nalgebra_exponential_decay_no_jac() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = exponential_decay_problem_no_jac::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 19
+        number_of_linear_solves: 78
+        number_of_steps: 31
+        number_of_error_test_failures: 8
+        number_of_nonlinear_solver_iterations: 78
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.011892071150027213
+        final_step_size: 0.9795994412020951
+        equations:
+          number_of_rhs_evals: 84
+          number_of_jac_mul_evals: 2
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 1
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 84
+        number_of_jac_muls: 2
+        number_of_matrix_evals: 1
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_exponential_decay_broyden() {
+        let mut s = Bdf::new(BroydenNonlinearSolver::new(LU::default()));
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = exponential_decay_problem::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 19
+        number_of_linear_solves: 78
+        number_of_steps: 31
+        number_of_error_test_failures: 8
+        number_of_nonlinear_solver_iterations: 78
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.011892071150027213
+        final_step_size: 0.9795994412020951
+        equations:
+          number_of_rhs_evals: 81
+          number_of_jac_mul_evals: 2
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 1
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_exponential_decay_anderson() {
+        let mut s = Bdf::new(AndersonNonlinearSolver::default());
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = exponential_decay_problem::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_linear_solves: 91
+        number_of_steps: 32
+        number_of_error_test_failures: 8
+        number_of_nonlinear_solver_iterations: 91
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.011892071150027213
+        final_step_size: 0.8691219764127732
+        equations:
+          number_of_rhs_evals: 94
+          number_of_jac_mul_evals: 0
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 0
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_exponential_decay_jfnk() {
+        let mut s = Bdf::new(NewtonKrylovNonlinearSolver::new(Gmres::new()));
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = exponential_decay_problem::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_linear_solves: 78
+        number_of_steps: 31
+        number_of_error_test_failures: 8
+        number_of_nonlinear_solver_iterations: 78
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.011892071150027213
+        final_step_size: 0.9795994412020951
+        equations:
+          number_of_rhs_evals: 81
+          number_of_jac_mul_evals: 156
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 0
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
     #[cfg(feature = "sundials")]
     #[test]
     fn test_sundials_exponential_decay() {
@@ -190,15 +392,28 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(crate::SundialsLinearSolver::new_dense());
         let (problem, soln) = exponential_decay_problem::<crate::SundialsMatrix>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 18
+        number_of_linear_solves: 63
         number_of_steps: 43
         number_of_error_test_failures: 3
         number_of_nonlinear_solver_iterations: 63
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.001
         final_step_size: 0.7770043351266953
+        equations:
+          number_of_rhs_evals: 63
+          number_of_jac_mul_evals: 36
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 18
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -208,27 +423,191 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_bdf_nalgebra_state_dependent_mass() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = state_dependent_mass_problem::<Mcpu>();
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 23
+        number_of_linear_solves: 100
+        number_of_steps: 30
+        number_of_error_test_failures: 6
+        number_of_nonlinear_solver_iterations: 100
+        number_of_nonlinear_solver_fails: 6
+        initial_step_size: 0.00447213595499958
+        final_step_size: 1.0325563276095318
+        equations:
+          number_of_rhs_evals: 104
+          number_of_jac_mul_evals: 75
+          number_of_mass_evals: 180
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 0
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
+    #[test]
+    fn test_tr_bdf2_nalgebra_state_dependent_mass() {
+        let tableau = Tableau::<Mcpu>::tr_bdf2();
+        let mut s = Sdirk::new(tableau, LU::default());
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = state_dependent_mass_problem::<Mcpu>();
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 36
+        number_of_linear_solves: 210
+        number_of_steps: 35
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 174
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.09066811409991334
+        final_step_size: 0.18530111312970354
+        equations:
+          number_of_rhs_evals: 178
+          number_of_jac_mul_evals: 111
+          number_of_mass_evals: 290
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 0
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
+    #[test]
+    fn test_state_dependent_mass_not_supported() {
+        let (problem, _soln) = state_dependent_mass_problem::<Mcpu>();
+        let state = OdeSolverState::new(&problem);
+        let mut s = rkc::Rkc::new();
+        let err = s.set_problem(state, &problem).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Rkc does not support a state-dependent mass matrix"
+        );
+        assert!(s.state().is_none());
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_implicit_dae() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = implicit_exponential_decay_problem::<Mcpu>();
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 18
+        number_of_linear_solves: 114
+        number_of_steps: 35
+        number_of_error_test_failures: 2
+        number_of_nonlinear_solver_iterations: 114
+        number_of_nonlinear_solver_fails: 7
+        initial_step_size: 0.06439209162167846
+        final_step_size: 0.5669575093744309
+        equations:
+          number_of_rhs_evals: 115
+          number_of_jac_mul_evals: 132
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 21
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
+    #[test]
+    fn test_implicit_dae_not_supported() {
+        let (problem, _soln) = implicit_exponential_decay_problem::<Mcpu>();
+        let state = OdeSolverState::new(&problem);
+        let mut s = Sdirk::new(Tableau::<Mcpu>::tr_bdf2(), LU::default());
+        let err = s.set_problem(state, &problem).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Sdirk does not support fully implicit DAEs"
+        );
+        assert!(s.state().is_none());
+    }
+
+    #[test]
+    fn test_consistent_init_non_diagonal_mass() {
+        // d(y + z)/dt = -y
+        // 0 = z - 2 y
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<Mcpu, _, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = -x[0];
+                    y[1] = x[1] - 2.0 * x[0];
+                },
+                |_x, _p, _t, v, y| {
+                    y[0] = -v[0];
+                    y[1] = v[1] - 2.0 * v[0];
+                },
+                |v, _p, _t, beta, y| {
+                    y[0] = v[0] + v[1] + beta * y[0];
+                    y[1] *= beta;
+                },
+                |_p, _t| nalgebra::DVector::from_vec(vec![1.0, 0.0]),
+            )
+            .unwrap();
+        let mut rs = NewtonNonlinearSolver::new(LU::default());
+        let state = OdeSolverState::new_consistent(&problem, &mut rs).unwrap();
+        // the differential component of y is unchanged, the algebraic component satisfies the constraint,
+        // and y' satisfies the differential equation
+        state
+            .y
+            .assert_eq_st(&nalgebra::DVector::from_vec(vec![1.0, 2.0]), 1e-10);
+        assert!(abs(state.dy[0] + state.dy[1] + state.y[0]) < 1e-10);
+    }
+
     #[test]
     fn test_bdf_nalgebra_exponential_decay_algebraic() {
         let mut s = Bdf::default();
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = exponential_decay_with_algebraic_problem::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 17
+        number_of_linear_solves: 58
         number_of_steps: 21
         number_of_error_test_failures: 8
         number_of_nonlinear_solver_iterations: 58
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.004450050658086208
         final_step_size: 0.20995860176773154
+        equations:
+          number_of_rhs_evals: 62
+          number_of_jac_mul_evals: 6
+          number_of_mass_evals: 69
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 2
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
         number_of_calls: 62
-        number_of_jac_muls: 4
-        number_of_matrix_evals: 1
+        number_of_jac_muls: 6
+        number_of_matrix_evals: 2
         "###);
     }
 
@@ -239,21 +618,34 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = robertson::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 433
+        number_of_linear_solves: 3575
         number_of_steps: 415
         number_of_error_test_failures: 6
-        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_iterations: 3154
         number_of_nonlinear_solver_fails: 12
         initial_step_size: 0.0011378590984747281
         final_step_size: 35000974461.348206
+        equations:
+          number_of_rhs_evals: 3158
+          number_of_jac_mul_evals: 42
+          number_of_mass_evals: 3165
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 14
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 3157
-        number_of_jac_muls: 40
-        number_of_matrix_evals: 13
+        number_of_calls: 3158
+        number_of_jac_muls: 42
+        number_of_matrix_evals: 14
         "###);
     }
 
@@ -264,21 +656,34 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = robertson::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 370
+        number_of_linear_solves: 3752
         number_of_steps: 346
         number_of_error_test_failures: 3
-        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_iterations: 3403
         number_of_nonlinear_solver_fails: 21
         initial_step_size: 0.00619535739618413
         final_step_size: 57384898746.15714
+        equations:
+          number_of_rhs_evals: 3407
+          number_of_jac_mul_evals: 60
+          number_of_mass_evals: 3414
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 20
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 3406
-        number_of_jac_muls: 58
-        number_of_matrix_evals: 19
+        number_of_calls: 3407
+        number_of_jac_muls: 60
+        number_of_matrix_evals: 20
         "###);
     }
 
@@ -288,21 +693,34 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = robertson::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 106
+        number_of_linear_solves: 985
         number_of_steps: 345
         number_of_error_test_failures: 5
         number_of_nonlinear_solver_iterations: 985
         number_of_nonlinear_solver_fails: 22
         initial_step_size: 0.0000045643545698038086
         final_step_size: 5435491162.573224
+        equations:
+          number_of_rhs_evals: 989
+          number_of_jac_mul_evals: 57
+          number_of_mass_evals: 996
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 19
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 988
-        number_of_jac_muls: 55
-        number_of_matrix_evals: 18
+        number_of_calls: 989
+        number_of_jac_muls: 57
+        number_of_matrix_evals: 19
         "###);
     }
 
@@ -313,15 +731,28 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(crate::SundialsLinearSolver::new_dense());
         let (problem, soln) = robertson::<crate::SundialsMatrix>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 59
+        number_of_linear_solves: 506
         number_of_steps: 355
         number_of_error_test_failures: 15
         number_of_nonlinear_solver_iterations: 506
         number_of_nonlinear_solver_fails: 5
         initial_step_size: 0.001
         final_step_size: 11535117835.253025
+        equations:
+          number_of_rhs_evals: 507
+          number_of_jac_mul_evals: 178
+          number_of_mass_evals: 687
+          number_of_mass_matrix_evals: 59
+          number_of_jacobian_matrix_evals: 59
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -331,27 +762,177 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_bdf_nalgebra_robertson_broyden() {
+        let mut s = Bdf::new(BroydenNonlinearSolver::new(LU::default()));
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = robertson::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 110
+        number_of_linear_solves: 803
+        number_of_steps: 336
+        number_of_error_test_failures: 5
+        number_of_nonlinear_solver_iterations: 803
+        number_of_nonlinear_solver_fails: 27
+        initial_step_size: 0.0000045643545698038086
+        final_step_size: 19255704198.100487
+        equations:
+          number_of_rhs_evals: 807
+          number_of_jac_mul_evals: 72
+          number_of_mass_evals: 814
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 24
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_jfnk() {
+        let gmres = Gmres::with_preconditioner(LU::default());
+        let mut s = Bdf::new(NewtonKrylovNonlinearSolver::new(gmres));
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = robertson::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 124
+        number_of_linear_solves: 907
+        number_of_steps: 339
+        number_of_error_test_failures: 7
+        number_of_nonlinear_solver_iterations: 907
+        number_of_nonlinear_solver_fails: 39
+        initial_step_size: 0.0000045643545698038086
+        final_step_size: 6008288749.402989
+        equations:
+          number_of_rhs_evals: 911
+          number_of_jac_mul_evals: 2405
+          number_of_mass_evals: 3209
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 38
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+    }
+
     #[test]
     fn test_bdf_nalgebra_robertson_colored() {
         let mut s = Bdf::default();
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = robertson::<Mcpu>(true);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 106
+        number_of_linear_solves: 985
         number_of_steps: 345
         number_of_error_test_failures: 5
         number_of_nonlinear_solver_iterations: 985
         number_of_nonlinear_solver_fails: 22
         initial_step_size: 0.0000045643545698038086
         final_step_size: 5435491162.573224
+        equations:
+          number_of_rhs_evals: 989
+          number_of_jac_mul_evals: 60
+          number_of_mass_evals: 995
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 19
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 988
-        number_of_jac_muls: 58
-        number_of_matrix_evals: 18
+        number_of_calls: 989
+        number_of_jac_muls: 60
+        number_of_matrix_evals: 19
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_no_jac() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = robertson_no_jac::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 106
+        number_of_linear_solves: 985
+        number_of_steps: 345
+        number_of_error_test_failures: 5
+        number_of_nonlinear_solver_iterations: 985
+        number_of_nonlinear_solver_fails: 22
+        initial_step_size: 0.0000045643545698038086
+        final_step_size: 5436998088.027775
+        equations:
+          number_of_rhs_evals: 1065
+          number_of_jac_mul_evals: 57
+          number_of_mass_evals: 996
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 19
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 1065
+        number_of_jac_muls: 57
+        number_of_matrix_evals: 19
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_no_jac_colored() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = robertson_no_jac::<Mcpu>(true);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 106
+        number_of_linear_solves: 985
+        number_of_steps: 345
+        number_of_error_test_failures: 5
+        number_of_nonlinear_solver_iterations: 985
+        number_of_nonlinear_solver_fails: 22
+        initial_step_size: 0.0000045643545698038086
+        final_step_size: 5436998088.027775
+        equations:
+          number_of_rhs_evals: 1068
+          number_of_jac_mul_evals: 57
+          number_of_mass_evals: 995
+          number_of_mass_matrix_evals: 2
+          number_of_jacobian_matrix_evals: 19
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 1068
+        number_of_jac_muls: 57
+        number_of_matrix_evals: 19
         "###);
     }
 
@@ -362,19 +943,32 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = robertson_ode::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 242
+        number_of_linear_solves: 2596
         number_of_steps: 230
         number_of_error_test_failures: 0
-        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_iterations: 2366
         number_of_nonlinear_solver_fails: 12
         initial_step_size: 0.0010137172178872197
         final_step_size: 45212162967.124176
+        equations:
+          number_of_rhs_evals: 2369
+          number_of_jac_mul_evals: 39
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 13
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 2368
+        number_of_calls: 2369
         number_of_jac_muls: 39
         number_of_matrix_evals: 13
         "###);
@@ -386,19 +980,106 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = robertson_ode::<Mcpu>(false);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 106
+        number_of_linear_solves: 981
         number_of_steps: 345
         number_of_error_test_failures: 5
         number_of_nonlinear_solver_iterations: 981
         number_of_nonlinear_solver_fails: 22
         initial_step_size: 0.0000038381494276795106
         final_step_size: 5636682847.540523
+        equations:
+          number_of_rhs_evals: 984
+          number_of_jac_mul_evals: 54
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 18
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 984
+        number_of_jac_muls: 54
+        number_of_matrix_evals: 18
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_ode_autodiff() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = robertson_ode_autodiff::<Mcpu>(false);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 106
+        number_of_linear_solves: 981
+        number_of_steps: 345
+        number_of_error_test_failures: 5
+        number_of_nonlinear_solver_iterations: 981
+        number_of_nonlinear_solver_fails: 22
+        initial_step_size: 0.0000038381494276795106
+        final_step_size: 5636682845.008513
+        equations:
+          number_of_rhs_evals: 984
+          number_of_jac_mul_evals: 54
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 18
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 984
+        number_of_jac_muls: 54
+        number_of_matrix_evals: 18
+        "###);
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_ode_autodiff_colored() {
+        let mut s = Bdf::default();
+        let rs = NewtonNonlinearSolver::new(LU::default());
+        let (problem, soln) = robertson_ode_autodiff::<Mcpu>(true);
+        test_ode_solver(&mut s, rs, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
+        ---
+        number_of_linear_solver_setups: 106
+        number_of_linear_solves: 981
+        number_of_steps: 345
+        number_of_error_test_failures: 5
+        number_of_nonlinear_solver_iterations: 981
+        number_of_nonlinear_solver_fails: 22
+        initial_step_size: 0.0000038381494276795106
+        final_step_size: 5636682845.008513
+        equations:
+          number_of_rhs_evals: 984
+          number_of_jac_mul_evals: 54
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 18
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 983
+        number_of_calls: 984
         number_of_jac_muls: 54
         number_of_matrix_evals: 18
         "###);
@@ -410,19 +1091,32 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = dydt_y2_problem::<Mcpu>(false, 10);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 65
+        number_of_linear_solves: 593
         number_of_steps: 205
         number_of_error_test_failures: 10
         number_of_nonlinear_solver_iterations: 593
         number_of_nonlinear_solver_fails: 7
         initial_step_size: 0.0000019982428436469115
         final_step_size: 1.0781694150073
+        equations:
+          number_of_rhs_evals: 596
+          number_of_jac_mul_evals: 60
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 6
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 595
+        number_of_calls: 596
         number_of_jac_muls: 60
         number_of_matrix_evals: 6
         "###);
@@ -434,19 +1128,32 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = dydt_y2_problem::<Mcpu>(true, 10);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 65
+        number_of_linear_solves: 593
         number_of_steps: 205
         number_of_error_test_failures: 10
         number_of_nonlinear_solver_iterations: 593
         number_of_nonlinear_solver_fails: 7
         initial_step_size: 0.0000019982428436469115
         final_step_size: 1.0781694150073
+        equations:
+          number_of_rhs_evals: 596
+          number_of_jac_mul_evals: 16
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 6
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 595
+        number_of_calls: 596
         number_of_jac_muls: 16
         number_of_matrix_evals: 6
         "###);
@@ -458,19 +1165,32 @@ mod tests {
         let rs = NewtonNonlinearSolver::new(LU::default());
         let (problem, soln) = gaussian_decay_problem::<Mcpu>(false, 10);
         test_ode_solver(&mut s, rs, &problem, soln, None, false);
-        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        insta::assert_yaml_snapshot!(statistics_without_timings(&s), @r###"
         ---
         number_of_linear_solver_setups: 14
+        number_of_linear_solves: 159
         number_of_steps: 58
         number_of_error_test_failures: 2
         number_of_nonlinear_solver_iterations: 159
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.0025148668593658707
         final_step_size: 0.19566316816600493
+        equations:
+          number_of_rhs_evals: 162
+          number_of_jac_mul_evals: 10
+          number_of_mass_evals: 0
+          number_of_mass_matrix_evals: 0
+          number_of_jacobian_matrix_evals: 1
+          number_of_root_evals: 0
+        timings:
+          total: 0
+          nonlinear_solver: 0
+          linear_solver_setup: 0
+          root_finding: 0
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 161
+        number_of_calls: 162
         number_of_jac_muls: 10
         number_of_matrix_evals: 1
         "###);
@@ -594,7 +1314,7 @@ mod tests {
             M::T::one(),
         );
         let state = OdeSolverState::new(&problem);
-        s.set_problem(state.clone(), &problem);
+        s.set_problem(state.clone(), &problem).unwrap();
         let t0 = M::T::zero();
         let t1 = M::T::one();
         s.interpolate(t0)
@@ -623,7 +1343,7 @@ mod tests {
             M::T::one(),
         );
         let state = OdeSolverState::new(&problem);
-        s.set_problem(state.clone(), &problem);
+        s.set_problem(state.clone(), &problem).unwrap();
         let state2 = s.take_state().unwrap();
         state2.y.assert_eq_st(&state.y, M::T::from(1e-9));
         assert!(s.take_state().is_none());
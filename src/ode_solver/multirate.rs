@@ -0,0 +1,502 @@
+use anyhow::{anyhow, Result};
+use num_traits::{One, Zero};
+use std::{cell::RefCell, rc::Rc, time::Instant};
+
+use crate::{
+    op::{
+        multirate::{MriFastCallable, MriForcing},
+        unit::UnitCallable,
+    },
+    NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+    OdeSolverStatistics, OdeSolverStopReason, Op, RootFinder, Vector,
+};
+
+use super::fixed_step::{fixed_step_size, hermite_interpolate, is_at_stop_time};
+
+/// The modified fast equations `v' = F_fast(t, v) + g(t)` that are integrated by the inner solver of a [MriGark] solver over each stage,
+/// where the forcing `g(t)` is a polynomial in time formed from the slow part of the right-hand side at the previous stages.
+pub struct MriFastEquations<F: NonLinearOp> {
+    rhs: Rc<MriFastCallable<F>>,
+    mass: Rc<UnitCallable<F::M>>,
+}
+
+impl<F: NonLinearOp> OdeEquations for MriFastEquations<F> {
+    type T = F::T;
+    type V = F::V;
+    type M = F::M;
+    type Rhs = MriFastCallable<F>;
+    type Mass = UnitCallable<F::M>;
+    type Root = UnitCallable<F::M>;
+
+    // the parameters are shared with the fast callable
+    fn set_params(&mut self, _p: Self::V) {}
+
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        &self.rhs
+    }
+
+    fn mass(&self) -> &Rc<Self::Mass> {
+        &self.mass
+    }
+
+    // the initial state of each stage is set by the outer solver
+    fn init(&self, _t: Self::T) -> Self::V {
+        Self::V::zeros(self.rhs.nstates())
+    }
+}
+
+// the coupling coefficients of an explicit MRI-GARK method, gamma_ij(theta) = gamma0_ij + gamma1_ij theta for stage i = 1..=s
+// and previous stage j < i, with the abscissae c_0 = 0, ..., c_s = 1
+struct MriTableau {
+    c: Vec<f64>,
+    gamma0: Vec<Vec<f64>>,
+    gamma1: Vec<Vec<f64>>,
+    order: usize,
+}
+
+impl MriTableau {
+    fn erk22() -> Self {
+        Self {
+            c: vec![0.0, 0.5, 1.0],
+            gamma0: vec![vec![0.5], vec![-0.5, 1.0]],
+            gamma1: vec![vec![0.0], vec![0.0, 0.0]],
+            order: 2,
+        }
+    }
+
+    fn erk33() -> Self {
+        Self {
+            c: vec![0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0],
+            gamma0: vec![
+                vec![1.0 / 3.0],
+                vec![-1.0 / 3.0, 2.0 / 3.0],
+                vec![0.0, -2.0 / 3.0, 1.0],
+            ],
+            gamma1: vec![vec![0.0], vec![0.0, 0.0], vec![0.5, 0.0, -0.5]],
+            order: 3,
+        }
+    }
+}
+
+/// A multirate infinitesimal GARK (MRI-GARK) solver for problems `y' = F_fast(t, y) + F_slow(t, y)` with a fast and a slow partition of the right-hand side
+/// (Sandu, 2019). The fast part is given as a separate [NonLinearOp] (e.g. using [crate::FastStatesCallable] to take the equations of a subset of the states),
+/// and the slow part is the remainder of the right-hand side of the problem. Each step of the explicit outer method is split into stages, and over each
+/// stage the modified fast equations `v' = F_fast(t, v) + g(t)` (see [MriFastEquations]) are sub-cycled with the inner solver of type `S`, which can be
+/// any [OdeSolverMethod] (e.g. a [crate::Bdf] solver if the fast part is stiff). The forcing `g(t)` couples in the slow part evaluated at the previous stages,
+/// so the slow part is only evaluated once per stage.
+///
+/// Two methods are available: the second order [Self::erk22] and the third order [Self::erk33] methods of Sandu (2019). The outer steps are fixed
+/// (see [super::fixed_step]), and the inner solver adapts its own steps using the tolerances of the problem. The mass matrix of the problem is not used, so
+/// it must be the identity ([OdeSolverMethod::set_problem] returns an error otherwise).
+///
+/// Sandu, A. (2019). A class of multirate infinitesimal GARK methods. SIAM Journal on Numerical Analysis, 57(5), 2300-2327.
+pub struct MriGark<Eqn, F, S>
+where
+    Eqn: OdeEquations,
+    F: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    S: OdeSolverMethod<MriFastEquations<F>>,
+{
+    tableau: MriTableau,
+    fast: Rc<F>,
+    forcing: Rc<RefCell<MriForcing<Eqn::V>>>,
+    inner: S,
+    inner_problem: Option<OdeSolverProblem<MriFastEquations<F>>>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn, F, S> MriGark<Eqn, F, S>
+where
+    Eqn: OdeEquations,
+    F: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    S: OdeSolverMethod<MriFastEquations<F>>,
+{
+    fn new(tableau: MriTableau, fast: Rc<F>, inner: S) -> Self {
+        let n = fast.nstates();
+        Self {
+            tableau,
+            fast,
+            forcing: Rc::new(RefCell::new(MriForcing::new(n))),
+            inner,
+            inner_problem: None,
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(1),
+            old_dy: Eqn::V::zeros(1),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+        }
+    }
+
+    /// The second order MRI-GARK-ERK22a method, with the fast part `fast` sub-cycled using the `inner` solver.
+    pub fn erk22(fast: Rc<F>, inner: S) -> Self {
+        Self::new(MriTableau::erk22(), fast, inner)
+    }
+
+    /// The third order MRI-GARK-ERK33a method, with the fast part `fast` sub-cycled using the `inner` solver.
+    pub fn erk33(fast: Rc<F>, inner: S) -> Self {
+        Self::new(MriTableau::erk33(), fast, inner)
+    }
+
+    /// The order of the outer method.
+    pub fn order(&self) -> usize {
+        self.tableau.order
+    }
+
+    /// The inner solver used for the fast part.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    // the slow part of the right-hand side F(t, y) - F_fast(t, y)
+    fn slow(&self, y: &Eqn::V, t: Eqn::T) -> Eqn::V {
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+        let mut f = rhs.call(y, t);
+        f -= self.fast.call(y, t);
+        f
+    }
+
+    // integrate the modified fast equations from (t0, y) to t1 using the inner solver
+    fn solve_fast(&mut self, y: Eqn::V, t0: Eqn::T, t1: Eqn::T) -> Result<Eqn::V> {
+        let inner_problem = self.inner_problem.as_ref().unwrap();
+        let dy = inner_problem.eqn.rhs().call(&y, t0);
+        let h = if inner_problem.h0 < t1 - t0 {
+            inner_problem.h0
+        } else {
+            t1 - t0
+        };
+        let state = OdeSolverState { y, dy, t: t0, h };
+        self.inner.set_problem(state, inner_problem)?;
+        self.inner.set_stop_time(t1)?;
+        while !matches!(self.inner.step()?, OdeSolverStopReason::TstopReached) {}
+
+        let inner_statistics = self.inner.statistics();
+        self.statistics.number_of_linear_solver_setups +=
+            inner_statistics.number_of_linear_solver_setups;
+        self.statistics.number_of_linear_solves += inner_statistics.number_of_linear_solves;
+        self.statistics.number_of_error_test_failures +=
+            inner_statistics.number_of_error_test_failures;
+        self.statistics.number_of_nonlinear_solver_iterations +=
+            inner_statistics.number_of_nonlinear_solver_iterations;
+        self.statistics.number_of_nonlinear_solver_fails +=
+            inner_statistics.number_of_nonlinear_solver_fails;
+        Ok(self.inner.take_state().unwrap().y)
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let Some(state) = self.state.as_ref() else {
+            return Err(anyhow!("State not set"));
+        };
+        let (y, f, t) = (state.y.clone(), state.dy.clone(), state.t);
+        let h = fixed_step_size(t, state.h, self.tstop);
+
+        // the slow part at each stage, which forces the fast equations over the following stages
+        let mut slow = vec![self.slow(&y, t)];
+        let mut y_stage = y.clone();
+        let nstages = self.tableau.c.len() - 1;
+        for i in 1..=nstages {
+            let dc = Eqn::T::from(self.tableau.c[i] - self.tableau.c[i - 1]);
+            let t0 = t + Eqn::T::from(self.tableau.c[i - 1]) * h;
+            let dt = dc * h;
+            {
+                let mut forcing = self.forcing.borrow_mut();
+                forcing.t0 = t0;
+                forcing.dt = dt;
+                forcing.g0 = Eqn::V::zeros(y.len());
+                forcing.g1 = Eqn::V::zeros(y.len());
+                for (j, slow_j) in slow.iter().enumerate() {
+                    let gamma0 = Eqn::T::from(self.tableau.gamma0[i - 1][j]) / dc;
+                    let gamma1 = Eqn::T::from(self.tableau.gamma1[i - 1][j]) / dc;
+                    forcing.g0.axpy(gamma0, slow_j, Eqn::T::one());
+                    forcing.g1.axpy(gamma1, slow_j, Eqn::T::one());
+                }
+            }
+            y_stage = self.solve_fast(y_stage, t0, t0 + dt)?;
+            if i < nstages {
+                slow.push(self.slow(&y_stage, t0 + dt));
+            }
+        }
+        let y_new = y_stage;
+
+        let problem = self.problem.as_ref().unwrap();
+        let state = self.state.as_mut().unwrap();
+        self.old_t = t;
+        self.old_y = y;
+        self.old_dy = f;
+        state.t = match self.tstop {
+            Some(tstop) if is_at_stop_time(t + h, h, tstop) => tstop,
+            _ => t + h,
+        };
+        state.dy = problem.eqn.rhs().call(&y_new, state.t);
+        state.y = y_new;
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = problem.eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<Eqn, F, S> OdeSolverMethod<Eqn> for MriGark<Eqn, F, S>
+where
+    Eqn: OdeEquations,
+    F: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    S: OdeSolverMethod<MriFastEquations<F>>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("MriGark")?;
+        problem.check_mass_identity("MriGark", state.t)?;
+        let n = state.y.len();
+        if self.fast.nstates() != n {
+            return Err(anyhow!(
+                "Fast right-hand side has the wrong number of states"
+            ));
+        }
+        state.dy = problem.eqn.rhs().call(&state.y, state.t);
+
+        // the inner problem uses the tolerances and initial step size of the outer problem
+        *self.forcing.borrow_mut() = MriForcing::new(n);
+        let inner_eqn = MriFastEquations {
+            rhs: Rc::new(MriFastCallable::new(
+                self.fast.clone(),
+                self.forcing.clone(),
+            )),
+            mass: Rc::new(UnitCallable::new(n)),
+        };
+        self.inner_problem = Some(OdeSolverProblem::new(
+            inner_eqn,
+            problem.rtol,
+            problem.atol.as_ref().clone(),
+            problem.t0,
+            problem.h0,
+        ));
+
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        Ok(hermite_interpolate(
+            self.old_t,
+            state.t,
+            &self.old_y,
+            &state.y,
+            &self.old_dy,
+            &state.dy,
+            t,
+        ))
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::tests::{test_interpolate, test_no_set_problem, test_take_state, TestEqn},
+        Bdf, FastStatesCallable, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+        Vector,
+    };
+    use std::rc::Rc;
+
+    use super::MriGark;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // a fast state y0 relaxing (with rate k) towards a function of the slow state y1, which is driven by the fast state. The initial
+    // fast state is on the slow manifold, so that there is no initial fast transient
+    fn fast_slow(k: f64, h0: f64) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        OdeBuilder::new()
+            .h0(h0)
+            .rtol(1e-8)
+            .atol([1e-10])
+            .p([k])
+            .build_ode::<M, _, _, _>(
+                |x: &V, p: &V, t: f64, y: &mut V| {
+                    y[0] = -p[0] * (x[0] - x[1].sin());
+                    y[1] = -x[0] + t.cos();
+                },
+                |x: &V, p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = -p[0] * (v[0] - x[1].cos() * v[1]);
+                    y[1] = -v[0];
+                },
+                |_p: &V, _t| V::from_vec(vec![0.5f64.sin(), 0.5]),
+            )
+            .unwrap()
+    }
+
+    fn reference(k: f64, t: f64) -> V {
+        let mut problem = fast_slow(k, 1e-3);
+        problem.rtol = 1e-10;
+        problem.atol = Rc::new(V::from_element(2, 1e-12));
+        Bdf::default().solve(&problem, t).unwrap()
+    }
+
+    #[test]
+    fn test_mri_gark_convergence_order() {
+        let k = 20.0;
+        let expect = reference(k, 2.0);
+        for order in [2, 3] {
+            let errors = [0.1, 0.05].map(|h| {
+                let problem = fast_slow(k, h);
+                let fast = Rc::new(FastStatesCallable::new(problem.eqn.rhs().clone(), &[0]));
+                let mut s = if order == 2 {
+                    MriGark::erk22(fast, Bdf::default())
+                } else {
+                    MriGark::erk33(fast, Bdf::default())
+                };
+                assert_eq!(s.order(), order);
+                let y = s.solve(&problem, 2.0).unwrap();
+                assert_eq!(s.statistics().number_of_steps, (2.0 / h).round() as usize);
+                (y - &expect).norm()
+            });
+            let observed = (errors[0] / errors[1]).log2();
+            assert!(
+                observed > order as f64 - 0.3,
+                "order {} observed {}",
+                order,
+                observed
+            );
+        }
+    }
+
+    #[test]
+    fn test_mri_gark_sub_cycles_fast_states() {
+        // the fast time scale is 1000 times faster than the outer step
+        let k = 1000.0;
+        let expect = reference(k, 1.0);
+        let problem = fast_slow(k, 0.05);
+        let fast = Rc::new(FastStatesCallable::new(problem.eqn.rhs().clone(), &[0]));
+        let mut s = MriGark::erk33(fast, Bdf::default());
+        let y = s.solve(&problem, 1.0).unwrap();
+        y.assert_eq_st(&expect, 1e-4);
+        let statistics = s.statistics();
+        assert_eq!(statistics.number_of_steps, 20);
+        assert!(s.inner().statistics().number_of_steps > 1);
+        assert!(statistics.number_of_linear_solver_setups > 0);
+    }
+
+    #[test]
+    fn test_mri_gark_mass() {
+        // 2 y' = -y has a mass matrix that is not the identity
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0],
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -v[0],
+                |v: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * v[0] + beta * y[0],
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        let fast = Rc::new(FastStatesCallable::new(problem.eqn.rhs().clone(), &[0]));
+        let mut s = MriGark::erk22(fast, Bdf::default());
+        assert!(s
+            .set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .is_err());
+    }
+
+    #[test]
+    fn test_mri_gark_no_set_problem() {
+        let fast = TestEqn::<M>::new().rhs().clone();
+        test_no_set_problem::<M, _>(MriGark::erk22(fast, Bdf::default()))
+    }
+
+    #[test]
+    fn test_mri_gark_take_state() {
+        let fast = TestEqn::<M>::new().rhs().clone();
+        test_take_state::<M, _>(MriGark::erk22(fast, Bdf::default()))
+    }
+
+    #[test]
+    fn test_mri_gark_interpolate() {
+        let fast = TestEqn::<M>::new().rhs().clone();
+        test_interpolate::<M, _>(MriGark::erk22(fast, Bdf::default()))
+    }
+}
@@ -0,0 +1,399 @@
+use anyhow::{anyhow, Result};
+use num_traits::{abs, Pow, Zero};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    scale, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStopReason,
+    Scalar, Vector,
+};
+
+/// Options for [parareal].
+#[derive(Clone, Debug)]
+pub struct PararealOptions<T: Scalar> {
+    /// The number of time slices of equal length that the time interval is divided into (default `16`).
+    pub nslices: usize,
+    /// The number of threads used for the fine solves (default the available parallelism of the machine).
+    pub nthreads: usize,
+    /// The maximum number of parareal iterations (default `16`). After `nslices` iterations the solution is the same as the serial fine solution, so
+    /// larger values have no effect.
+    pub max_iterations: usize,
+    /// The iterations have converged when the change in the state at each slice boundary between two iterations is less than this, measured in the weighted
+    /// RMS norm using the tolerances of the problem (default `1.0`).
+    pub tolerance: T,
+    /// The coarse solver uses the tolerances of the problem multiplied by this factor (default `100.0`).
+    pub coarse_tolerance_factor: T,
+}
+
+impl<T: Scalar> Default for PararealOptions<T> {
+    fn default() -> Self {
+        Self {
+            nslices: 16,
+            nthreads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            max_iterations: 16,
+            tolerance: T::one(),
+            coarse_tolerance_factor: T::from(100.0),
+        }
+    }
+}
+
+/// The result of [parareal].
+#[derive(Clone, Debug)]
+pub struct PararealSolution<V: Vector> {
+    /// The times of the slice boundaries, from the initial time of the problem to the final time.
+    pub t: Vec<V::T>,
+    /// The solution at the slice boundaries.
+    pub y: Vec<V>,
+    /// The number of parareal iterations.
+    pub iterations: usize,
+    /// True if the iterations converged to the tolerance.
+    pub converged: bool,
+    /// The largest change in the state at the slice boundaries for each iteration (see [PararealOptions::tolerance]).
+    pub corrections: Vec<V::T>,
+    /// The total number of fine solves over a time slice.
+    pub number_of_fine_solves: usize,
+    /// The wall-clock time taken.
+    pub elapsed: Duration,
+    /// An estimate of the time to solve the problem in serial with the fine solver, i.e. the sum over the time slices of the time taken by the last
+    /// fine solve over each slice.
+    pub serial_time: Duration,
+}
+
+impl<V: Vector> PararealSolution<V> {
+    /// The speedup over solving the problem in serial with the fine solver, i.e. [Self::serial_time] divided by [Self::elapsed].
+    pub fn speedup(&self) -> f64 {
+        self.serial_time.as_secs_f64() / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Solve a problem from its initial time to `t_final` using the parallel-in-time parareal algorithm (Lions, Maday & Turinici, 2001).
+///
+/// The time interval is divided into [PararealOptions::nslices] slices of equal length. An initial solution at the slice boundaries is found by a serial sweep of a
+/// cheap coarse solver (e.g. [crate::Sdirk], using the tolerances of the problem loosened by [PararealOptions::coarse_tolerance_factor]), and each iteration then solves all the slices in parallel with an accurate fine solver
+/// (e.g. [crate::Bdf]), starting from the current solution at their initial boundary, followed by a serial correction sweep of the coarse solver
+/// `U_{n+1} = G(U_n) + F(U_n^old) - G(U_n^old)`. The iterations stop when the solution at the boundaries converges (see [PararealOptions::tolerance]).
+/// After `k` iterations the first `k` slices are exact, so only the remaining slices are solved with the fine solver.
+///
+/// The fine solves run on [PararealOptions::nthreads] threads. Since the problems and solvers are not thread safe (e.g. they share their equations using
+/// [std::rc::Rc]), they are given as functions `problem`, `coarse` and `fine` that create them, and each thread creates its own problem and fine solver.
+/// The statistics of the iterations and the speedup over the serial fine solve are reported in the [PararealSolution].
+///
+/// Lions, J. L., Maday, Y., & Turinici, G. (2001). Résolution d'EDP par un schéma en temps « pararéel ». Comptes Rendus de l'Académie des Sciences, 332(7), 661-668.
+pub fn parareal<Eqn, C, F, P, CF, FF>(
+    problem: P,
+    coarse: CF,
+    fine: FF,
+    t_final: Eqn::T,
+    options: &PararealOptions<Eqn::T>,
+) -> Result<PararealSolution<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    Eqn::V: Send + Sync,
+    C: OdeSolverMethod<Eqn>,
+    F: OdeSolverMethod<Eqn>,
+    P: Fn() -> OdeSolverProblem<Eqn> + Sync,
+    CF: Fn() -> C,
+    FF: Fn() -> F + Sync,
+{
+    let start = Instant::now();
+    if options.nslices == 0 || options.nthreads == 0 {
+        return Err(anyhow!(
+            "The number of time slices and threads must be positive"
+        ));
+    }
+    let ode_problem = problem();
+    let t0 = ode_problem.t0;
+    if t_final <= t0 {
+        return Err(anyhow!(
+            "Final time {} is at or before the initial time {}",
+            t_final,
+            t0
+        ));
+    }
+    let nslices = options.nslices;
+    let dt = (t_final - t0) / Eqn::T::from(nslices as f64);
+    let t: Vec<Eqn::T> = (0..=nslices)
+        .map(|n| {
+            if n == nslices {
+                t_final
+            } else {
+                t0 + dt * Eqn::T::from(n as f64)
+            }
+        })
+        .collect();
+
+    // the initial coarse sweep, keeping the coarse solution over each slice for the corrections
+    let mut coarse_problem = ode_problem.clone();
+    coarse_problem.rtol *= options.coarse_tolerance_factor;
+    coarse_problem.atol =
+        Rc::new(coarse_problem.atol.as_ref().clone() * scale(options.coarse_tolerance_factor));
+    let mut coarse_solver = coarse();
+    let mut y = vec![OdeSolverState::new(&ode_problem).y];
+    let mut y_coarse = Vec::with_capacity(nslices);
+    for n in 0..nslices {
+        let y_next = solve_slice(&mut coarse_solver, &coarse_problem, &y[n], t[n], t[n + 1])?;
+        y_coarse.push(y_next.clone());
+        y.push(y_next);
+    }
+
+    let mut fine_times = vec![Duration::ZERO; nslices];
+    let mut number_of_fine_solves = 0;
+    let mut corrections = Vec::new();
+    let mut converged = false;
+    let mut iterations = 0;
+    let max_iterations = options.max_iterations.min(nslices);
+    while iterations < max_iterations {
+        // the first `iterations` slices are exact, so solve the remaining slices in parallel with the fine solver
+        let first = iterations;
+        let y_fine = solve_fine_slices(&problem, &fine, &y, &t, first, options.nthreads)?;
+        number_of_fine_solves += y_fine.len();
+        iterations += 1;
+
+        // the serial correction sweep
+        let mut correction = Eqn::T::zero();
+        for (i, (y_fine_n, time)) in y_fine.into_iter().enumerate() {
+            let n = first + i;
+            fine_times[n] = time;
+            let y_coarse_n = if i == 0 {
+                y_coarse[n].clone()
+            } else {
+                solve_slice(&mut coarse_solver, &coarse_problem, &y[n], t[n], t[n + 1])?
+            };
+            let mut y_next = y_fine_n - &y_coarse[n];
+            y_next += &y_coarse_n;
+            y_coarse[n] = y_coarse_n;
+            let change = weighted_norm(&ode_problem, &(y_next.clone() - &y[n + 1]), &y_next);
+            if change > correction {
+                correction = change;
+            }
+            y[n + 1] = y_next;
+        }
+        corrections.push(correction);
+        if correction <= options.tolerance || iterations == nslices {
+            converged = true;
+            break;
+        }
+    }
+
+    Ok(PararealSolution {
+        t,
+        y,
+        iterations,
+        converged,
+        corrections,
+        number_of_fine_solves,
+        elapsed: start.elapsed(),
+        serial_time: fine_times.iter().sum(),
+    })
+}
+
+// solve the slices first..nslices with the fine solver from the current solution at their initial boundary, divided into contiguous
+// chunks over the threads, returning the solution at their final boundary and the time taken
+fn solve_fine_slices<Eqn, F, P, FF>(
+    problem: &P,
+    fine: &FF,
+    y: &[Eqn::V],
+    t: &[Eqn::T],
+    first: usize,
+    nthreads: usize,
+) -> Result<Vec<(Eqn::V, Duration)>>
+where
+    Eqn: OdeEquations,
+    Eqn::V: Send + Sync,
+    F: OdeSolverMethod<Eqn>,
+    P: Fn() -> OdeSolverProblem<Eqn> + Sync,
+    FF: Fn() -> F + Sync,
+{
+    let nslices = t.len() - 1;
+    let remaining = nslices - first;
+    let nthreads = nthreads.min(remaining);
+    let chunk_size = remaining.div_ceil(nthreads);
+    let slices: Vec<usize> = (first..nslices).collect();
+    let y0: Vec<(Eqn::V, Eqn::T, Eqn::T)> = slices
+        .iter()
+        .map(|&n| (y[n].clone(), t[n], t[n + 1]))
+        .collect();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = y0
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || -> Result<Vec<(Eqn::V, Duration)>> {
+                    let ode_problem = problem();
+                    let mut fine_solver = fine();
+                    chunk
+                        .iter()
+                        .map(|(y, t0, t1)| {
+                            let start = Instant::now();
+                            let y_next = solve_slice(&mut fine_solver, &ode_problem, y, *t0, *t1)?;
+                            Ok((y_next, start.elapsed()))
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+        let mut ret = Vec::with_capacity(remaining);
+        for handle in handles {
+            let chunk = handle
+                .join()
+                .map_err(|_| anyhow!("Parareal fine solver thread panicked"))??;
+            ret.extend(chunk);
+        }
+        Ok(ret)
+    })
+}
+
+// solve the problem from (t0, y) to t1
+fn solve_slice<Eqn, S>(
+    solver: &mut S,
+    problem: &OdeSolverProblem<Eqn>,
+    y: &Eqn::V,
+    t0: Eqn::T,
+    t1: Eqn::T,
+) -> Result<Eqn::V>
+where
+    Eqn: OdeEquations,
+    S: OdeSolverMethod<Eqn>,
+{
+    let mut state = OdeSolverState::new(problem);
+    state.y.copy_from(y);
+    state.t = t0;
+    solver.set_problem(state, problem)?;
+    solver.set_stop_time(t1)?;
+    while !matches!(solver.step()?, OdeSolverStopReason::TstopReached) {}
+    Ok(solver.take_state().unwrap().y)
+}
+
+// the weighted RMS norm of e using the tolerances of the problem
+fn weighted_norm<Eqn: OdeEquations>(
+    problem: &OdeSolverProblem<Eqn>,
+    e: &Eqn::V,
+    y: &Eqn::V,
+) -> Eqn::T {
+    let n = y.len();
+    let mut sum = Eqn::T::zero();
+    for i in 0..n {
+        let e_i = e[i] / (problem.atol[i] + problem.rtol * abs(y[i]));
+        sum += e_i * e_i;
+    }
+    (sum / Eqn::T::from(n as f64)).pow(Eqn::T::from(0.5))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Bdf, NalgebraLU, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem, Sdirk,
+        Tableau, Vector,
+    };
+
+    use super::{parareal, PararealOptions};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // a damped, forced nonlinear system
+    fn forced_damped(rtol: f64) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        OdeBuilder::new()
+            .rtol(rtol)
+            .atol([rtol * 1e-2])
+            .build_ode::<M, _, _, _>(
+                |x: &V, _p: &V, t: f64, y: &mut V| {
+                    y[0] = -2.0 * x[0] + x[1];
+                    y[1] = x[0] - x[1] - 0.1 * x[1].powi(3) + t.sin();
+                },
+                |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = -2.0 * v[0] + v[1];
+                    y[1] = v[0] - v[1] - 0.3 * x[1].powi(2) * v[1];
+                },
+                |_p: &V, _t| V::from_vec(vec![1.0, 2.0]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parareal() {
+        let t_final = 40.0;
+        let expect = Bdf::default().solve(&forced_damped(1e-8), t_final).unwrap();
+
+        let options = PararealOptions {
+            nslices: 8,
+            nthreads: 4,
+            ..Default::default()
+        };
+        let solution = parareal(
+            || forced_damped(1e-8),
+            || Sdirk::new(Tableau::<M>::tr_bdf2(), NalgebraLU::default()),
+            Bdf::default,
+            t_final,
+            &options,
+        )
+        .unwrap();
+        assert!(solution.converged);
+        assert!(
+            solution.iterations < options.nslices,
+            "{}",
+            solution.iterations
+        );
+        assert_eq!(solution.corrections.len(), solution.iterations);
+        assert_eq!(solution.t.len(), 9);
+        assert_eq!(solution.t[8], t_final);
+        solution.y[8].assert_eq_st(&expect, 1e-5);
+        assert!(solution.speedup() > 0.0);
+    }
+
+    #[test]
+    fn test_parareal_threads() {
+        // the result does not depend on the number of threads, and with no tolerance the iterations run until all slices are exact
+        let solutions = [1, 3].map(|nthreads| {
+            let options = PararealOptions {
+                nslices: 5,
+                nthreads,
+                max_iterations: 10,
+                tolerance: 0.0,
+                ..Default::default()
+            };
+            parareal(
+                || forced_damped(1e-6),
+                || Sdirk::new(Tableau::<M>::tr_bdf2(), NalgebraLU::default()),
+                Bdf::default,
+                10.0,
+                &options,
+            )
+            .unwrap()
+        });
+        for solution in solutions.iter() {
+            assert_eq!(solution.iterations, 5);
+            assert_eq!(solution.number_of_fine_solves, 5 + 4 + 3 + 2 + 1);
+            assert!(solution.converged);
+        }
+        for (y0, y1) in solutions[0].y.iter().zip(solutions[1].y.iter()) {
+            assert_eq!(y0, y1);
+        }
+    }
+
+    #[test]
+    fn test_parareal_errors() {
+        let options = PararealOptions {
+            nslices: 0,
+            ..Default::default()
+        };
+        assert!(parareal(
+            || forced_damped(1e-6),
+            || Sdirk::new(Tableau::<M>::tr_bdf2(), NalgebraLU::default()),
+            Bdf::default,
+            10.0,
+            &options
+        )
+        .is_err());
+        let options = PararealOptions::default();
+        assert!(parareal(
+            || forced_damped(1e-6),
+            || Sdirk::new(Tableau::<M>::tr_bdf2(), NalgebraLU::default()),
+            Bdf::default,
+            0.0,
+            &options
+        )
+        .is_err());
+    }
+}
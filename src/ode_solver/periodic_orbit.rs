@@ -0,0 +1,220 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use nalgebra::DMatrix;
+use num_traits::{One, Pow, Zero};
+
+use crate::{
+    matrix::default_solver::DefaultSolver, op::shooting::ShootingCallable, NewtonNonlinearSolver,
+    NonLinearOp, NonLinearSolver, OdeEquations, OdeSolverMethod, OdeSolverProblem, Scalar,
+    SolverProblem, Vector,
+};
+
+use super::continuation::eigenvalues;
+
+/// A periodic orbit (limit cycle) found by [periodic_orbit].
+#[derive(Clone, Debug)]
+pub struct PeriodicOrbit<V: Vector> {
+    /// A point on the orbit (the initial state, at time `t0`).
+    pub y0: V,
+    /// The period of the orbit.
+    pub period: V::T,
+    /// The Floquet multipliers `(re, im)`, i.e. the eigenvalues of the monodromy matrix `dy(T; y0)/dy0`. One of these (the trivial multiplier,
+    /// corresponding to a perturbation along the orbit) is always close to one.
+    pub floquet_multipliers: Vec<(V::T, V::T)>,
+    /// True if the orbit is linearly stable, i.e. all the Floquet multipliers apart from the trivial multiplier lie inside the unit circle.
+    pub stable: bool,
+    /// The number of Newton iterations taken.
+    pub number_of_newton_iterations: usize,
+    /// The number of times the ODE was solved, including those used for the finite difference jacobian and the monodromy matrix.
+    pub number_of_solves: usize,
+}
+
+/// Find a periodic orbit (limit cycle) of an ODE using single shooting, starting from the initial guess `y0` and `period` for a point on the orbit
+/// and its period.
+///
+/// The unknowns `(y0, T)` are found by solving `y(T; y0) - y0 = 0`, where `y(T; y0)` is the solution of the ODE at time `t0 + T` found using the given
+/// `solver`, together with the phase condition `f(y_ref) . (y0 - y_ref) = 0` (with `y_ref` the initial guess) that fixes the point on the orbit, using
+/// [NewtonNonlinearSolver] (see [ShootingCallable]). The jacobian is recomputed if Newton's method fails to converge, up to 10 times, and
+/// an error is returned if any of the solves of the ODE fails.
+/// The monodromy matrix `dy(T; y0)/dy0` is calculated using forward differences (one ODE solve per state), so the ODE should be solved with a
+/// tolerance that is tight compared with the square root of the required accuracy. The stability of the orbit is given by the Floquet multipliers,
+/// the eigenvalues of the monodromy matrix.
+///
+/// The problem should be an ODE (i.e. without algebraic equations), as the initial state is not made consistent before each solve.
+pub fn periodic_orbit<Eqn, S>(
+    solver: &mut S,
+    problem: &OdeSolverProblem<Eqn>,
+    y0: &Eqn::V,
+    period: Eqn::T,
+) -> Result<PeriodicOrbit<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    S: OdeSolverMethod<Eqn>,
+{
+    let n = y0.len();
+    let t = problem.t0;
+    let callable = Rc::new(ShootingCallable::new(solver, problem, y0));
+
+    // the period uses the smallest absolute tolerance of the states
+    let atol_period = (0..n).fold(Eqn::T::INFINITY, |acc, i| {
+        if problem.atol[i] < acc {
+            problem.atol[i]
+        } else {
+            acc
+        }
+    });
+    let atol = Rc::new(callable.pack(&problem.atol, atol_period));
+    let mut newton = NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+    newton.set_max_iter(10);
+    newton.set_problem(&SolverProblem::new(callable.clone(), atol, problem.rtol));
+
+    let mut x = callable.pack(y0, period);
+    let mut number_of_newton_iterations = 0;
+    let mut attempt = 0;
+    loop {
+        newton.reset_jacobian(&x, t);
+        let result = newton.solve_in_place(&mut x, t);
+        number_of_newton_iterations += newton.niter();
+        attempt += 1;
+        if let Some(e) = callable.take_error() {
+            return Err(e);
+        }
+        match result {
+            Ok(()) => break,
+            Err(e) if attempt >= 10 => return Err(e),
+            Err(_) => (),
+        }
+    }
+
+    // monodromy matrix, the jacobian of H(x) with respect to y0 is Phi - I
+    let mut monodromy = DMatrix::<Eqn::T>::zeros(n, n);
+    let mut e_j = Eqn::V::zeros(n + 1);
+    for j in 0..n {
+        e_j[j] = Eqn::T::one();
+        let col = callable.jac_mul(&x, t, &e_j);
+        for i in 0..n {
+            monodromy[(i, j)] = col[i];
+        }
+        monodromy[(j, j)] += Eqn::T::one();
+        e_j[j] = Eqn::T::zero();
+    }
+    if let Some(e) = callable.take_error() {
+        return Err(e);
+    }
+    let floquet_multipliers = eigenvalues(monodromy)?;
+    let modulus = |(re, im): &(Eqn::T, Eqn::T)| (*re * *re + *im * *im).pow(Eqn::T::from(0.5));
+    let distance_to_one = |(re, im): &(Eqn::T, Eqn::T)| modulus(&(*re - Eqn::T::one(), *im));
+    let trivial = (0..floquet_multipliers.len()).fold(0, |best, i| {
+        if distance_to_one(&floquet_multipliers[i]) < distance_to_one(&floquet_multipliers[best]) {
+            i
+        } else {
+            best
+        }
+    });
+    let stable = floquet_multipliers
+        .iter()
+        .enumerate()
+        .all(|(i, mu)| i == trivial || modulus(mu) < Eqn::T::one());
+
+    let mut y0 = Eqn::V::zeros(n);
+    let period = callable.unpack(&x, &mut y0);
+    Ok(PeriodicOrbit {
+        y0,
+        period,
+        floquet_multipliers,
+        stable,
+        number_of_newton_iterations,
+        number_of_solves: callable.number_of_solves(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::{Bdf, OdeBuilder, OdeEquations, OdeSolverProblem};
+
+    use super::periodic_orbit;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // dr/dt = a r (1 - r^2), dtheta/dt = 1, which has a limit cycle on the unit circle with period 2 pi and nontrivial
+    // floquet multiplier exp(-4 pi a), so the limit cycle is stable for a > 0 and unstable for a < 0
+    fn hopf_problem(a: f64) -> OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>> {
+        OdeBuilder::new()
+            .rtol(1e-10)
+            .atol([1e-10])
+            .build_ode::<M, _, _, _>(
+                move |x: &V, _p: &V, _t, y: &mut V| {
+                    let r2 = x[0] * x[0] + x[1] * x[1];
+                    y[0] = a * x[0] * (1.0 - r2) - x[1];
+                    y[1] = x[0] + a * x[1] * (1.0 - r2);
+                },
+                move |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    let r2 = x[0] * x[0] + x[1] * x[1];
+                    y[0] = a * (1.0 - r2 - 2.0 * x[0] * x[0]) * v[0]
+                        - (1.0 + 2.0 * a * x[0] * x[1]) * v[1];
+                    y[1] = (1.0 - 2.0 * a * x[0] * x[1]) * v[0]
+                        + a * (1.0 - r2 - 2.0 * x[1] * x[1]) * v[1];
+                },
+                |_p: &V, _t| V::from_vec(vec![1.0, 0.0]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_periodic_orbit_hopf() {
+        let problem = hopf_problem(1.0);
+        let mut solver = Bdf::default();
+        let y0 = V::from_vec(vec![1.2, 0.1]);
+        let orbit = periodic_orbit(&mut solver, &problem, &y0, 6.0).unwrap();
+        assert!(
+            (orbit.period - 2.0 * PI).abs() < 1e-6,
+            "period {}",
+            orbit.period
+        );
+        assert!((orbit.y0.norm() - 1.0).abs() < 1e-6, "y0 {:?}", orbit.y0);
+
+        // the phase condition puts y0 on the line through the initial guess orthogonal to the flow
+        let f_ref = [1.2 - 0.1 - 1.2 * 1.45, 1.2 + 0.1 - 0.1 * 1.45];
+        let phase = f_ref[0] * (orbit.y0[0] - 1.2) + f_ref[1] * (orbit.y0[1] - 0.1);
+        assert!(phase.abs() < 1e-10);
+
+        assert!(orbit.stable);
+        assert_eq!(orbit.floquet_multipliers.len(), 2);
+        let mut multipliers = orbit.floquet_multipliers.clone();
+        multipliers.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        assert!(multipliers[0].0.abs() < 1e-4, "{:?}", multipliers);
+        assert!((multipliers[1].0 - 1.0).abs() < 1e-4, "{:?}", multipliers);
+        assert!(multipliers.iter().all(|m| m.1 == 0.0));
+        assert!(orbit.number_of_solves > orbit.number_of_newton_iterations);
+    }
+
+    #[test]
+    fn test_periodic_orbit_unstable() {
+        let problem = hopf_problem(-0.1);
+        let mut solver = Bdf::default();
+        let y0 = V::from_vec(vec![0.9, 0.1]);
+        let orbit = periodic_orbit(&mut solver, &problem, &y0, 6.5).unwrap();
+        assert!(
+            (orbit.period - 2.0 * PI).abs() < 1e-6,
+            "period {}",
+            orbit.period
+        );
+        assert!((orbit.y0.norm() - 1.0).abs() < 1e-6, "y0 {:?}", orbit.y0);
+        assert!(!orbit.stable);
+        let largest = orbit
+            .floquet_multipliers
+            .iter()
+            .fold(0.0f64, |acc, m| acc.max(m.0));
+        let expect = (0.4 * PI).exp();
+        assert!(
+            (largest - expect).abs() / expect < 1e-3,
+            "{:?}",
+            orbit.floquet_multipliers
+        );
+    }
+}
@@ -1,7 +1,9 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use std::rc::Rc;
 
-use crate::{vector::Vector, OdeEquations};
+use num_traits::One;
+
+use crate::{vector::Vector, LinearOp, OdeEquations, Op};
 
 pub struct OdeSolverProblem<Eqn: OdeEquations> {
     pub eqn: Rc<Eqn>,
@@ -43,6 +45,45 @@ impl<Eqn: OdeEquations> OdeSolverProblem<Eqn> {
         }
     }
 
+    /// Return an error if the equations are a fully implicit DAE, used by the solvers that only support the semi-explicit form `M y' = f(t, y)`.
+    pub(crate) fn check_not_implicit(&self, solver: &str) -> Result<()> {
+        if self.eqn.is_implicit() {
+            return Err(anyhow!("{} does not support fully implicit DAEs", solver));
+        }
+        Ok(())
+    }
+
+    /// Return an error if the mass matrix depends on the state, used by the solvers that only support a mass matrix `M(t)`.
+    pub(crate) fn check_mass_not_state_dependent(&self, solver: &str) -> Result<()> {
+        if self.eqn.is_mass_state_dependent() {
+            return Err(anyhow!(
+                "{} does not support a state-dependent mass matrix",
+                solver
+            ));
+        }
+        Ok(())
+    }
+
+    /// Return an error if the mass matrix is not the identity, used by the solvers that do not use the mass matrix. The mass matrix is checked
+    /// at time `t` using its product with a vector of distinct entries.
+    pub(crate) fn check_mass_identity(&self, solver: &str, t: Eqn::T) -> Result<()> {
+        self.check_mass_not_state_dependent(solver)?;
+        let n = self.eqn.rhs().nstates();
+        let mut v = Eqn::V::zeros(n);
+        for i in 0..n {
+            v[i] = Eqn::T::one() + Eqn::T::from(i as f64) / Eqn::T::from(n as f64);
+        }
+        let mut mv = Eqn::V::zeros(n);
+        self.eqn.mass().call_inplace(&v, t, &mut mv);
+        if (0..n).any(|i| mv[i] != v[i]) {
+            return Err(anyhow!(
+                "{} does not support a mass matrix that is not the identity",
+                solver
+            ));
+        }
+        Ok(())
+    }
+
     pub fn set_params(&mut self, p: Eqn::V) -> Result<()> {
         let eqn = Rc::get_mut(&mut self.eqn).context("Failed to get mutable reference to equations, is there a solver created with this problem?")?;
         eqn.set_params(p);
@@ -0,0 +1,570 @@
+use anyhow::{anyhow, Result};
+use num_traits::{abs, One, Pow, Zero};
+use std::time::Instant;
+
+use crate::{
+    scale, IndexType, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+    OdeSolverStatistics, OdeSolverStopReason, Op, RootFinder, Scalar, Vector,
+};
+
+use super::fixed_step::{fixed_step_size, hermite_interpolate, is_at_stop_time};
+
+/// An explicit, second order Runge-Kutta-Chebyshev (RKC) solver for stiff problems whose jacobian has eigenvalues close to the negative real axis,
+/// such as the semi-discretisation of diffusion-dominated PDEs (Sommeijer, Shampine & Verwer, 1998).
+///
+/// Each step uses `s` stages built from the Chebyshev polynomial recurrence, giving a stability region along the negative real axis that grows
+/// like `0.65 s^2`, so that the number of right-hand side evaluations per step only grows with the square root of the stiffness. The number of stages
+/// is chosen each step from the step size and an estimate of the spectral radius of the jacobian, which is found by power iteration using only the
+/// jacobian-vector product [NonLinearOp::jac_mul_inplace]. No linear systems are solved and the jacobian is never assembled, so the solver is suited
+/// to problems of very large dimension.
+///
+/// The step size is adapted using the error estimate and the tolerances of the problem, and the spectral radius is re-estimated every 25 steps and after
+/// a rejected step. The mass matrix of the problem is not used, so it must be the identity ([OdeSolverMethod::set_problem] returns an error otherwise).
+/// Interpolation uses cubic Hermite interpolation within the last step.
+///
+/// Sommeijer, B. P., Shampine, L. F., & Verwer, J. G. (1998). RKC: An explicit solver for parabolic PDEs. Journal of Computational and Applied Mathematics, 88(2), 315-326.
+pub struct Rkc<Eqn: OdeEquations> {
+    max_stages: IndexType,
+    stages: IndexType,
+    spectral_radius: Option<Eqn::T>,
+    spectral_radius_age: usize,
+    eigenvector: Option<Eqn::V>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    stage_prev: Eqn::V,
+    stage_curr: Eqn::V,
+    stage_f: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn: OdeEquations> Default for Rkc<Eqn> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Eqn: OdeEquations> Rkc<Eqn> {
+    // the damping of the stability polynomial, which keeps the stability region a finite distance from the negative real axis
+    const DAMPING: f64 = 2.0 / 13.0;
+    const MIN_FACTOR: f64 = 0.1;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+    const SPECTRAL_RADIUS_INTERVAL: usize = 25;
+    const SPECTRAL_RADIUS_SAFETY: f64 = 1.2;
+    const POWER_ITERATION_TOL: f64 = 0.01;
+    const MAX_POWER_ITERATIONS: usize = 50;
+
+    pub fn new() -> Self {
+        Self {
+            max_stages: 250,
+            stages: 0,
+            spectral_radius: None,
+            spectral_radius_age: 0,
+            eigenvector: None,
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(1),
+            old_dy: Eqn::V::zeros(1),
+            stage_prev: Eqn::V::zeros(1),
+            stage_curr: Eqn::V::zeros(1),
+            stage_f: Eqn::V::zeros(1),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+        }
+    }
+
+    /// Set the maximum number of stages per step (default 250). If the stiffness requires more stages than this, the step size is reduced instead.
+    pub fn set_max_stages(&mut self, max_stages: IndexType) {
+        assert!(max_stages >= 2, "Rkc requires at least two stages");
+        self.max_stages = max_stages;
+    }
+
+    /// The number of stages used in the last step.
+    pub fn number_of_stages(&self) -> IndexType {
+        self.stages
+    }
+
+    /// The last estimate of the spectral radius of the jacobian (including the safety factor), available after the first step.
+    pub fn spectral_radius(&self) -> Option<Eqn::T> {
+        self.spectral_radius
+    }
+
+    // estimate the spectral radius of the jacobian at (y, t) by power iteration, starting from the eigenvector found by the last estimate
+    fn estimate_spectral_radius(&mut self, y: &Eqn::V, t: Eqn::T) -> Eqn::T {
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+        let n = y.len();
+        // a deterministic pseudo-random start vector, so that it is not orthogonal to the dominant eigenvector
+        let mut v = self.eigenvector.take().unwrap_or_else(|| {
+            Eqn::V::from_vec(
+                (0..n)
+                    .map(|i| Eqn::T::from(((i * 7919 + 17) % 101) as f64 / 101.0 - 0.5))
+                    .collect(),
+            )
+        });
+        let norm = v.norm();
+        if norm > Eqn::T::zero() {
+            v *= scale(Eqn::T::one() / norm);
+        }
+        let mut w = Eqn::V::zeros(n);
+        let mut sigma = Eqn::T::zero();
+        for _ in 0..Self::MAX_POWER_ITERATIONS {
+            rhs.jac_mul_inplace(y, t, &v, &mut w);
+            let sigma_new = w.norm();
+            if sigma_new == Eqn::T::zero() {
+                sigma = sigma_new;
+                break;
+            }
+            v.axpy(Eqn::T::one() / sigma_new, &w, Eqn::T::zero());
+            let converged =
+                abs(sigma_new - sigma) <= Eqn::T::from(Self::POWER_ITERATION_TOL) * sigma_new;
+            sigma = sigma_new;
+            if converged {
+                break;
+            }
+        }
+        self.eigenvector = Some(v);
+        self.spectral_radius_age = 0;
+        sigma * Eqn::T::from(Self::SPECTRAL_RADIUS_SAFETY)
+    }
+
+    // the number of stages needed for stability with step size h, the stability interval of the damped method is about 0.653 s^2
+    fn stages_for(h: Eqn::T, spectral_radius: Eqn::T) -> IndexType {
+        let s = (Eqn::T::one() + Eqn::T::from(1.54) * h * spectral_radius).pow(Eqn::T::from(0.5));
+        let s: f64 = s.into();
+        let s = 1 + s.floor() as IndexType;
+        if s < 2 {
+            2
+        } else {
+            s
+        }
+    }
+
+    // the largest step size that is stable using s stages
+    fn max_step_for(s: IndexType, spectral_radius: Eqn::T) -> Eqn::T {
+        let s = Eqn::T::from((s - 1) as f64);
+        (s * s - Eqn::T::one()) / (Eqn::T::from(1.54) * spectral_radius)
+    }
+
+    // a step of the RKC method with s stages, using the three term recurrence of the shifted and scaled Chebyshev polynomials. The stages are
+    // stored in the stage buffers, and the solution is written to y_new
+    fn rkc_step(
+        &mut self,
+        y: &Eqn::V,
+        f: &Eqn::V,
+        t: Eqn::T,
+        h: Eqn::T,
+        s: IndexType,
+        y_new: &mut Eqn::V,
+    ) {
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+        let (one, two, four) = (Eqn::T::one(), Eqn::T::from(2.0), Eqn::T::from(4.0));
+        let w0 = one + Eqn::T::from(Self::DAMPING) / Eqn::T::from((s * s) as f64);
+
+        // the chebyshev polynomials T_j(w0) and their first and second derivatives
+        let mut cheb = vec![one, w0];
+        let mut dcheb = vec![Eqn::T::zero(), one];
+        let mut ddcheb = vec![Eqn::T::zero(), Eqn::T::zero()];
+        for j in 2..=s {
+            cheb.push(two * w0 * cheb[j - 1] - cheb[j - 2]);
+            dcheb.push(two * cheb[j - 1] + two * w0 * dcheb[j - 1] - dcheb[j - 2]);
+            ddcheb.push(four * dcheb[j - 1] + two * w0 * ddcheb[j - 1] - ddcheb[j - 2]);
+        }
+        let w1 = dcheb[s] / ddcheb[s];
+        let b = |j: IndexType| {
+            let j = if j < 2 { 2 } else { j };
+            ddcheb[j] / (dcheb[j] * dcheb[j])
+        };
+        let c = |j: IndexType| match j {
+            0 => Eqn::T::zero(),
+            1 => w1 * ddcheb[2] / (dcheb[2] * dcheb[2]),
+            _ => w1 * ddcheb[j] / dcheb[j],
+        };
+
+        // Y_1 = y + b_1 w1 h f
+        let (y_prev, y_curr, f_curr) = (
+            &mut self.stage_prev,
+            &mut self.stage_curr,
+            &mut self.stage_f,
+        );
+        y_prev.copy_from(y);
+        y_curr.copy_from(y);
+        y_curr.axpy(b(1) * w1 * h, f, one);
+        for j in 2..=s {
+            // Y_j = (1 - mu_j - nu_j) y + mu_j Y_{j-1} + nu_j Y_{j-2} + mu~_j h f(Y_{j-1}) + gamma~_j h f
+            let mu = two * b(j) * w0 / b(j - 1);
+            let nu = -b(j) / b(j - 2);
+            let mu_tilde = two * b(j) * w1 / b(j - 1);
+            let a_prev = one - b(j - 1) * cheb[j - 1];
+            let gamma_tilde = -a_prev * mu_tilde;
+            rhs.call_inplace(y_curr, t + c(j - 1) * h, f_curr);
+            // Y_{j-2} is no longer needed, so Y_j is stored in its place
+            y_prev.axpy(one - mu - nu, y, nu);
+            y_prev.axpy(mu, y_curr, one);
+            y_prev.axpy(mu_tilde * h, f_curr, one);
+            y_prev.axpy(gamma_tilde * h, f, one);
+            std::mem::swap(y_prev, y_curr);
+        }
+        y_new.copy_from(y_curr);
+    }
+
+    // the RKC error estimate 0.8 (y - y_new) + 0.4 h (f + f_new), scaled by the tolerances
+    fn error_norm(
+        &self,
+        y: &Eqn::V,
+        f: &Eqn::V,
+        y_new: &Eqn::V,
+        f_new: &Eqn::V,
+        h: Eqn::T,
+    ) -> Eqn::T {
+        let problem = self.problem.as_ref().unwrap();
+        let n = y.len();
+        let (a, b) = (Eqn::T::from(0.8), Eqn::T::from(0.4) * h);
+        let mut sum = Eqn::T::zero();
+        for i in 0..n {
+            let (y_i, y_new_i) = (abs(y[i]), abs(y_new[i]));
+            let scale = problem.atol[i] + problem.rtol * if y_i > y_new_i { y_i } else { y_new_i };
+            let e = (a * (y[i] - y_new[i]) + b * (f[i] + f_new[i])) / scale;
+            sum += e * e;
+        }
+        (sum / Eqn::T::from(n as f64)).pow(Eqn::T::from(0.5))
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let Some(state) = self.state.as_ref() else {
+            return Err(anyhow!("State not set"));
+        };
+        let (y, f, t) = (state.y.clone(), state.dy.clone(), state.t);
+        let mut h_try = state.h;
+        let mut y_new = Eqn::V::zeros(y.len());
+        let (f_new, h) = loop {
+            let spectral_radius = match self.spectral_radius {
+                Some(spectral_radius)
+                    if self.spectral_radius_age < Self::SPECTRAL_RADIUS_INTERVAL =>
+                {
+                    spectral_radius
+                }
+                _ => {
+                    let spectral_radius = self.estimate_spectral_radius(&y, t);
+                    self.spectral_radius = Some(spectral_radius);
+                    spectral_radius
+                }
+            };
+            let mut h = fixed_step_size(t, h_try, self.tstop);
+            let mut s = Self::stages_for(h, spectral_radius);
+            if s > self.max_stages {
+                s = self.max_stages;
+                h = Self::max_step_for(s, spectral_radius);
+            }
+            self.rkc_step(&y, &f, t, h, s, &mut y_new);
+            let f_new = self.problem.as_ref().unwrap().eqn.rhs().call(&y_new, t + h);
+            let error_norm = self.error_norm(&y, &f, &y_new, &f_new, h);
+            self.stages = s;
+
+            let mut factor = if error_norm == Eqn::T::zero() {
+                Eqn::T::from(Self::MAX_FACTOR)
+            } else if error_norm.is_nan() {
+                Eqn::T::from(Self::MIN_FACTOR)
+            } else {
+                Eqn::T::from(0.8) * error_norm.pow(Eqn::T::from(-1.0 / 3.0))
+            };
+            if factor < Eqn::T::from(Self::MIN_FACTOR) {
+                factor = Eqn::T::from(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::from(Self::MAX_FACTOR) {
+                factor = Eqn::T::from(Self::MAX_FACTOR);
+            }
+            let h_new = h * factor;
+            self.state.as_mut().unwrap().h = h_new;
+            if error_norm <= Eqn::T::one() {
+                break (f_new, h);
+            }
+            self.statistics.number_of_error_test_failures += 1;
+            if h_new < Eqn::T::from(Self::MIN_TIMESTEP) {
+                return Err(anyhow!("Step size too small at t = {}", t));
+            }
+            // the failure may be due to an out of date spectral radius
+            if self.spectral_radius_age > 0 {
+                self.spectral_radius = None;
+            }
+            h_try = h_new;
+        };
+        self.spectral_radius_age += 1;
+
+        let problem = self.problem.as_ref().unwrap();
+        let state = self.state.as_mut().unwrap();
+        self.old_t = t;
+        self.old_y = y;
+        self.old_dy = f;
+        state.t = match self.tstop {
+            Some(tstop) if is_at_stop_time(t + h, h, tstop) => tstop,
+            _ => t + h,
+        };
+        state.dy = f_new;
+        state.y = y_new;
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = problem.eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<Eqn: OdeEquations> OdeSolverMethod<Eqn> for Rkc<Eqn> {
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("Rkc")?;
+        problem.check_mass_identity("Rkc", state.t)?;
+        state.dy = problem.eqn.rhs().call(&state.y, state.t);
+        let n = state.y.len();
+        self.stage_prev = Eqn::V::zeros(n);
+        self.stage_curr = Eqn::V::zeros(n);
+        self.stage_f = Eqn::V::zeros(n);
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.stages = 0;
+        self.spectral_radius = None;
+        self.spectral_radius_age = 0;
+        self.eigenvector = None;
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        Ok(hermite_interpolate(
+            self.old_t,
+            state.t,
+            &self.old_y,
+            &state.y,
+            &self.old_dy,
+            &state.dy,
+            t,
+        ))
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::tests::{test_interpolate, test_no_set_problem, test_take_state},
+        Bdf, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem, Vector,
+    };
+    use std::rc::Rc;
+
+    use super::Rkc;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // the heat equation y_t = d y_xx + y (1 - y) on (0, 1) with zero dirichlet boundary conditions, discretised on n interior points
+    fn heat_equation(
+        n: usize,
+        d: f64,
+    ) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        let dx2 = ((n + 1) * (n + 1)) as f64;
+        let laplacian = move |x: &V, y: &mut V| {
+            for i in 0..n {
+                let left = if i > 0 { x[i - 1] } else { 0.0 };
+                let right = if i < n - 1 { x[i + 1] } else { 0.0 };
+                y[i] = d * dx2 * (left - 2.0 * x[i] + right);
+            }
+        };
+        OdeBuilder::new()
+            .rtol(1e-6)
+            .atol([1e-8])
+            .build_ode::<M, _, _, _>(
+                move |x: &V, _p: &V, _t, y: &mut V| {
+                    laplacian(x, y);
+                    for i in 0..n {
+                        y[i] += x[i] * (1.0 - x[i]);
+                    }
+                },
+                move |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    laplacian(v, y);
+                    for i in 0..n {
+                        y[i] += (1.0 - 2.0 * x[i]) * v[i];
+                    }
+                },
+                move |_p: &V, _t| {
+                    V::from_vec(
+                        (0..n)
+                            .map(|i| {
+                                let x = (i + 1) as f64 / (n + 1) as f64;
+                                (std::f64::consts::PI * x).sin()
+                                    + 0.5 * (5.0 * std::f64::consts::PI * x).sin()
+                            })
+                            .collect(),
+                    )
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rkc_heat_equation() {
+        let n = 100;
+        let d = 0.1;
+        let problem = heat_equation(n, d);
+        let mut reference_problem = heat_equation(n, d);
+        reference_problem.rtol = 1e-10;
+        reference_problem.atol = Rc::new(V::from_element(n, 1e-12));
+        let expect = Bdf::default().solve(&reference_problem, 1.0).unwrap();
+
+        let mut s = Rkc::default();
+        let y = s.solve(&problem, 1.0).unwrap();
+        y.assert_eq_st(&expect, 1e-5);
+
+        // the spectral radius of the laplacian is about 4 d (n + 1)^2, and needs many stages for the explicit method to be stable
+        let laplacian_radius = 4.0 * d * ((n + 1) * (n + 1)) as f64;
+        let spectral_radius = s.spectral_radius().unwrap();
+        assert!(
+            spectral_radius > 0.9 * laplacian_radius,
+            "{}",
+            spectral_radius
+        );
+        assert!(
+            spectral_radius < 1.3 * laplacian_radius,
+            "{}",
+            spectral_radius
+        );
+        assert!(s.number_of_stages() > 2);
+
+        // forward euler would need at least rho / 2 right-hand side evaluations per unit time to be stable
+        let statistics = s.statistics();
+        assert!(
+            (statistics.equations.number_of_rhs_evals as f64) < 0.5 * laplacian_radius,
+            "{}",
+            statistics.equations.number_of_rhs_evals
+        );
+        assert_eq!(statistics.number_of_linear_solver_setups, 0);
+    }
+
+    #[test]
+    fn test_rkc_max_stages() {
+        // limiting the number of stages limits the step size instead
+        let n = 50;
+        let problem = heat_equation(n, 1.0);
+        let mut s = Rkc::default();
+        s.set_max_stages(5);
+        s.solve(&problem, 0.1).unwrap();
+        assert!(s.number_of_stages() <= 5);
+        let spectral_radius = s.spectral_radius().unwrap();
+        assert!(s.statistics().number_of_steps as f64 > 0.1 * spectral_radius * 1.54 / 15.0);
+    }
+
+    #[test]
+    fn test_rkc_mass() {
+        // 2 y' = -y has a mass matrix that is not the identity
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0],
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -v[0],
+                |v: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * v[0] + beta * y[0],
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        let mut s = Rkc::default();
+        assert!(s
+            .set_problem(crate::OdeSolverState::new(&problem), &problem)
+            .is_err());
+    }
+
+    #[test]
+    fn test_rkc_no_set_problem() {
+        test_no_set_problem::<M, _>(Rkc::default())
+    }
+
+    #[test]
+    fn test_rkc_take_state() {
+        test_take_state::<M, _>(Rkc::default())
+    }
+
+    #[test]
+    fn test_rkc_interpolate() {
+        test_interpolate::<M, _>(Rkc::default())
+    }
+}
@@ -0,0 +1,873 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use num_traits::{One, Pow, Zero};
+
+use crate::{NonLinearOp, OdeEquations, OdeSolverProblem, Op, Scalar, Vector};
+
+/// A seeded pseudo-random number generator (xoshiro256**), used to sample the Wiener increments of a stochastic differential equation
+/// so that the solutions found by [sde_solve] and [sde_ensemble] are reproducible.
+#[derive(Clone, Debug)]
+pub struct SdeRng {
+    s: [u64; 4],
+    spare: Option<f64>,
+}
+
+impl SdeRng {
+    /// Create a new generator, with the state initialised from `seed` using splitmix64.
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut splitmix = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        let s = [splitmix(), splitmix(), splitmix(), splitmix()];
+        Self { s, spare: None }
+    }
+
+    /// The next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    /// A sample from the uniform distribution on `(0, 1]`.
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A sample from the standard normal distribution, using the Box-Muller transform.
+    pub fn normal(&mut self) -> f64 {
+        if let Some(z) = self.spare.take() {
+            return z;
+        }
+        let r = (-2.0 * self.uniform().ln()).sqrt();
+        let theta = 2.0 * std::f64::consts::PI * self.uniform();
+        self.spare = Some(r * theta.sin());
+        r * theta.cos()
+    }
+}
+
+/// The structure of the noise of a stochastic differential equation (see [SdeProblem]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdeNoise {
+    /// Each state has its own independent Wiener process, `dy_i = f_i(t, y) dt + g_i(t, y) dW_i`, so the diffusion has `n` outputs.
+    /// The [SdeMethod::Milstein] and [SdeMethod::Sriw1] schemes also assume that `g_i` only depends on `y_i`.
+    Diagonal,
+    /// The given number `m` of independent Wiener processes are shared by all the states, `dy = f(t, y) dt + G(t, y) dW`, so the diffusion
+    /// has `n * m` outputs (the `n x m` matrix `G` in column-major order). The [SdeMethod::Milstein] and [SdeMethod::Sriw1] schemes require
+    /// scalar noise (`m = 1`).
+    General(usize),
+}
+
+/// The scheme used to solve a stochastic differential equation (see [SdeOptions]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdeMethod {
+    /// The Euler-Maruyama scheme with a fixed step size, of strong order 0.5 (strong order 1 for additive noise).
+    EulerMaruyama,
+    /// The Milstein scheme with a fixed step size, of strong order 1, for diagonal or scalar noise. The derivative of the diffusion is
+    /// calculated using its jacobian.
+    Milstein,
+    /// The adaptive stochastic Runge-Kutta scheme SRIW1 of Rößler (2010), of strong order 1.5, for diagonal or scalar noise (including
+    /// additive noise). The step size is chosen using the relative and absolute tolerances of the ODE problem. When a step is rejected, the
+    /// increments of the Wiener process and of the iterated integral `I_(1,0)` over the rejected step are split at the new step size by sampling
+    /// from their distribution conditional on the increments over the whole step, and the remainder is kept for the following steps, so the
+    /// rejected steps do not change the distribution of the Wiener process.
+    Sriw1,
+}
+
+/// Options for [sde_solve] and [sde_ensemble].
+#[derive(Clone, Debug)]
+pub struct SdeOptions<T: Scalar> {
+    /// The scheme used (default [SdeMethod::Sriw1]).
+    pub method: SdeMethod,
+    /// The step size of the fixed step schemes, or the initial step size of the adaptive schemes (default `1e-3`).
+    pub h: T,
+    /// The maximum number of steps taken, including rejected steps (default `1000000`).
+    pub max_steps: usize,
+}
+
+impl<T: Scalar> Default for SdeOptions<T> {
+    fn default() -> Self {
+        Self {
+            method: SdeMethod::Sriw1,
+            h: T::from(1e-3),
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+/// A stochastic differential equation (in the Itô sense) `dy = f(t, y) dt + g(t, y) dW`, where the drift `f` is the right-hand side of the
+/// ODE problem `ode`, and the diffusion `g` is an operator (e.g. a [crate::Closure]) with `n` states whose outputs are given by `noise`.
+///
+/// The initial condition, tolerances and parameters are those of the ODE problem. The mass matrix of the ODE problem is not used, so it
+/// must be the identity ([SdeProblem::new] returns an error otherwise).
+pub struct SdeProblem<Eqn, G>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    pub ode: OdeSolverProblem<Eqn>,
+    pub diffusion: Rc<G>,
+    pub noise: SdeNoise,
+}
+
+impl<Eqn, G> Clone for SdeProblem<Eqn, G>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            ode: self.ode.clone(),
+            diffusion: self.diffusion.clone(),
+            noise: self.noise,
+        }
+    }
+}
+
+impl<Eqn, G> SdeProblem<Eqn, G>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    /// Create a new problem, checking that the size of the diffusion matches the noise and that the mass matrix is the identity.
+    pub fn new(ode: OdeSolverProblem<Eqn>, diffusion: G, noise: SdeNoise) -> Result<Self> {
+        ode.check_not_implicit("SdeProblem")?;
+        ode.check_mass_identity("SdeProblem", ode.t0)?;
+        let n = ode.eqn.rhs().nstates();
+        let nout = match noise {
+            SdeNoise::Diagonal => n,
+            SdeNoise::General(0) => {
+                return Err(anyhow!("there should be at least one Wiener process"))
+            }
+            SdeNoise::General(m) => n * m,
+        };
+        if diffusion.nstates() != n || diffusion.nout() != nout {
+            return Err(anyhow!(
+                "the diffusion should have {} states and {} outputs, but has {} states and {} outputs",
+                n,
+                nout,
+                diffusion.nstates(),
+                diffusion.nout()
+            ));
+        }
+        Ok(Self {
+            ode,
+            diffusion: Rc::new(diffusion),
+            noise,
+        })
+    }
+
+    /// The number of independent Wiener processes.
+    pub fn nnoise(&self) -> usize {
+        match self.noise {
+            SdeNoise::Diagonal => self.ode.eqn.rhs().nstates(),
+            SdeNoise::General(m) => m,
+        }
+    }
+}
+
+/// A sample path of a stochastic differential equation found by [sde_solve].
+#[derive(Clone, Debug)]
+pub struct SdeSolution<V: Vector> {
+    /// The output times.
+    pub t: Vec<V::T>,
+    /// The solution at the output times.
+    pub y: Vec<V>,
+    /// The Wiener process `W(t) - W(t0)` at the output times.
+    pub w: Vec<V>,
+    /// The number of accepted steps.
+    pub number_of_steps: usize,
+    /// The number of rejected steps (for the adaptive schemes).
+    pub number_of_rejected_steps: usize,
+}
+
+/// The mean and variance over an ensemble of sample paths found by [sde_ensemble].
+#[derive(Clone, Debug)]
+pub struct SdeEnsemble<V: Vector> {
+    /// The output times.
+    pub t: Vec<V::T>,
+    /// The mean of the solution at the output times.
+    pub mean: Vec<V>,
+    /// The (unbiased) sample variance of the solution at the output times.
+    pub variance: Vec<V>,
+    /// The number of sample paths.
+    pub ntrajectories: usize,
+}
+
+// an increment of the Wiener process W, and of the independent process Z used for the iterated integral I_(1,0) by SRIW1
+struct Increment<V: Vector> {
+    h: V::T,
+    dw: V,
+    dz: V,
+}
+
+// samples the increments of the Wiener processes, keeping the parts of rejected steps that are still in the future so that the path
+// is consistent when steps are rejected (rejection sampling with memory)
+struct BrownianPath<V: Vector> {
+    nnoise: usize,
+    future: Vec<Increment<V>>,
+}
+
+impl<V: Vector> BrownianPath<V> {
+    fn new(nnoise: usize) -> Self {
+        Self {
+            nnoise,
+            future: vec![],
+        }
+    }
+
+    fn sample(&self, variance: V::T, rng: &mut SdeRng) -> V {
+        let sd = variance.pow(V::T::from(0.5));
+        let mut x = V::zeros(self.nnoise);
+        for i in 0..self.nnoise {
+            x[i] = sd * V::T::from(rng.normal());
+        }
+        x
+    }
+
+    // the next increment, of length at most h
+    fn next(&mut self, h: V::T, rng: &mut SdeRng) -> Increment<V> {
+        match self.future.pop() {
+            Some(inc) if inc.h <= h => inc,
+            Some(inc) => {
+                let (first, rest) = self.split(inc, h, rng);
+                self.future.push(rest);
+                first
+            }
+            None => Increment {
+                h,
+                dw: self.sample(h, rng),
+                dz: self.sample(h, rng),
+            },
+        }
+    }
+
+    // return the rejected increment, the next increment will be of length at most h
+    fn reject(&mut self, inc: Increment<V>, h: V::T, rng: &mut SdeRng) {
+        let (first, rest) = self.split(inc, h, rng);
+        self.future.push(rest);
+        self.future.push(first);
+    }
+
+    // split an increment at h, sampling the increments (dW, dZ) of each part from their distribution conditional on the increment of W
+    // and on the integral I_(1,0) = h / 2 (dW + dZ / sqrt(3)) over the whole step, which is the brownian bridge for W together with the
+    // integral of the bridge. An unconditional sample x ~ N(0, D) of the parts is corrected to x + D C^T (C D C^T)^-1 (b - C x), where
+    // the rows of C give the increment and the integral over the whole step (b) from the parts
+    fn split(&self, inc: Increment<V>, h: V::T, rng: &mut SdeRng) -> (Increment<V>, Increment<V>) {
+        let c = |x: f64| V::T::from(x);
+        let (h1, h2) = (h, inc.h - h);
+        let hh = inc.h;
+        let sqrt3 = c(3.0).pow(c(0.5));
+        let (mut dw1, mut dz1) = (self.sample(h1, rng), self.sample(h1, rng));
+        let (mut dw2, mut dz2) = (self.sample(h2, rng), self.sample(h2, rng));
+        let a1 = h1 / c(2.0) + h2;
+        let (b1, b2) = (h1 / (c(2.0) * sqrt3), h2 / c(2.0));
+        let b3 = h2 / (c(2.0) * sqrt3);
+        for i in 0..self.nnoise {
+            let integral = hh / c(2.0) * (inc.dw[i] + inc.dz[i] / sqrt3);
+            let r1 = inc.dw[i] - dw1[i] - dw2[i];
+            let r2 = integral - (a1 * dw1[i] + b1 * dz1[i] + b2 * dw2[i] + b3 * dz2[i]);
+            // C D C^T = [[h, h^2 / 2], [h^2 / 2, h^3 / 3]]
+            let l1 = c(4.0) / hh * r1 - c(6.0) / (hh * hh) * r2;
+            let l2 = -c(6.0) / (hh * hh) * r1 + c(12.0) / (hh * hh * hh) * r2;
+            dw1[i] += h1 * (l1 + a1 * l2);
+            dz1[i] += h1 * b1 * l2;
+            dw2[i] += h2 * (l1 + b2 * l2);
+            dz2[i] += h2 * b3 * l2;
+        }
+        (
+            Increment {
+                h: h1,
+                dw: dw1,
+                dz: dz1,
+            },
+            Increment {
+                h: h2,
+                dw: dw2,
+                dz: dz2,
+            },
+        )
+    }
+}
+
+// the noise term G(t, y) dW
+fn noise_term<V: Vector>(noise: SdeNoise, g: &V, dw: &V) -> V {
+    match noise {
+        SdeNoise::Diagonal => {
+            let mut y = g.clone();
+            y.component_mul_assign(dw);
+            y
+        }
+        SdeNoise::General(m) => {
+            let n = g.len() / m;
+            let mut y = V::zeros(n);
+            for j in 0..m {
+                for i in 0..n {
+                    y[i] += g[i + n * j] * dw[j];
+                }
+            }
+            y
+        }
+    }
+}
+
+// the increments for each state, for diagonal or scalar noise
+fn per_state<V: Vector>(noise: SdeNoise, dw: &V, n: usize) -> V {
+    match noise {
+        SdeNoise::Diagonal => dw.clone(),
+        SdeNoise::General(_) => V::from_element(n, dw[0]),
+    }
+}
+
+// the elementwise product a * b
+fn mul<V: Vector>(a: &V, b: &V) -> V {
+    let mut y = a.clone();
+    y.component_mul_assign(b);
+    y
+}
+
+// take a step of the fixed step schemes
+fn fixed_step<Eqn, G>(
+    problem: &SdeProblem<Eqn, G>,
+    method: SdeMethod,
+    y: &Eqn::V,
+    t: Eqn::T,
+    inc: &Increment<Eqn::V>,
+) -> Eqn::V
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    let one = Eqn::T::one();
+    let f = problem.ode.eqn.rhs().call(y, t);
+    let g = problem.diffusion.call(y, t);
+    let mut y_new = y.clone();
+    y_new.axpy(inc.h, &f, one);
+    y_new += noise_term(problem.noise, &g, &inc.dw);
+    if method == SdeMethod::Milstein {
+        // 0.5 g g' (dW^2 - h)
+        let dw = per_state(problem.noise, &inc.dw, y.len());
+        let mut ito = mul(&dw, &dw);
+        ito.add_scalar_mut(-inc.h);
+        let mut correction = problem.diffusion.jac_mul(y, t, &g);
+        correction.component_mul_assign(&ito);
+        y_new.axpy(Eqn::T::from(0.5), &correction, one);
+    }
+    y_new
+}
+
+// take a step of SRIW1, returning the new state and the scaled error estimate
+fn sriw1_step<Eqn, G>(
+    problem: &SdeProblem<Eqn, G>,
+    y: &Eqn::V,
+    t: Eqn::T,
+    inc: &Increment<Eqn::V>,
+) -> (Eqn::V, Eqn::T)
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    let n = y.len();
+    let one = Eqn::T::one();
+    let c = |x: f64| Eqn::T::from(x);
+    let h = inc.h;
+    let sqrt_h = h.pow(c(0.5));
+    let dw = per_state(problem.noise, &inc.dw, n);
+    let dz = per_state(problem.noise, &inc.dz, n);
+    let dw2 = mul(&dw, &dw);
+
+    // the iterated integrals I_(1,1) / sqrt(h), I_(1,0) / h and I_(1,1,1) / h
+    let mut chi1 = dw2.clone() * crate::scale(one / (c(2.0) * sqrt_h));
+    chi1.add_scalar_mut(-sqrt_h / c(2.0));
+    let mut chi2 = dw.clone() * crate::scale(c(0.5));
+    chi2.axpy(c(0.5) / c(3.0).pow(c(0.5)), &dz, one);
+    let mut chi3 = dw2.clone();
+    chi3.add_scalar_mut(-c(3.0) * h);
+    chi3.component_mul_assign(&dw);
+    chi3 *= crate::scale(one / (c(6.0) * h));
+
+    let rhs = problem.ode.eqn.rhs();
+    let diffusion = problem.diffusion.as_ref();
+    let f1 = rhs.call(y, t);
+    let g1 = diffusion.call(y, t);
+
+    let mut h0 = y.clone();
+    h0.axpy(c(0.75) * h, &f1, one);
+    h0.axpy(c(1.5), &mul(&chi2, &g1), one);
+    let mut h12 = y.clone();
+    h12.axpy(c(0.25) * h, &f1, one);
+    h12.axpy(c(0.5) * sqrt_h, &g1, one);
+    let mut h13 = y.clone();
+    h13.axpy(h, &f1, one);
+    h13.axpy(-sqrt_h, &g1, one);
+    let g2 = diffusion.call(&h12, t + c(0.25) * h);
+    let g3 = diffusion.call(&h13, t + h);
+    let mut h14 = y.clone();
+    h14.axpy(c(0.25) * h, &f1, one);
+    h14.axpy(-c(5.0) * sqrt_h, &g1, one);
+    h14.axpy(c(3.0) * sqrt_h, &g2, one);
+    h14.axpy(c(0.5) * sqrt_h, &g3, one);
+    let g4 = diffusion.call(&h14, t + c(0.25) * h);
+    let f2 = rhs.call(&h0, t + c(0.75) * h);
+
+    let combine = |b: [f64; 4]| {
+        let mut x = g1.clone() * crate::scale(c(b[0]));
+        x.axpy(c(b[1]), &g2, one);
+        x.axpy(c(b[2]), &g3, one);
+        x.axpy(c(b[3]), &g4, one);
+        x
+    };
+    let third = 1.0 / 3.0;
+    let mut y_new = y.clone();
+    y_new.axpy(h * c(third), &f1, one);
+    y_new.axpy(h * c(2.0 * third), &f2, one);
+    y_new += mul(&dw, &combine([-1.0, 4.0 * third, 2.0 * third, 0.0]));
+    y_new += mul(&chi1, &combine([-1.0, 4.0 * third, -third, 0.0]));
+
+    // the error is estimated by the difference with the euler and milstein schemes
+    let mut err_stochastic = mul(&chi2, &combine([2.0, -4.0 * third, -2.0 * third, 0.0]));
+    err_stochastic += mul(&chi3, &combine([-2.0, 5.0 * third, -2.0 * third, 1.0]));
+    y_new += &err_stochastic;
+    let mut err_deterministic = f2 - &f1;
+    err_deterministic *= crate::scale(c(2.0 * third) * h);
+
+    let rtol = problem.ode.rtol;
+    let atol = problem.ode.atol.as_ref();
+    let mut sum = Eqn::T::zero();
+    for i in 0..n {
+        let (y_i, y_new_i) = (num_traits::abs(y[i]), num_traits::abs(y_new[i]));
+        let scale = atol[i] + rtol * if y_i > y_new_i { y_i } else { y_new_i };
+        let e =
+            (num_traits::abs(err_deterministic[i]) + num_traits::abs(err_stochastic[i])) / scale;
+        sum += e * e;
+    }
+    (y_new, (sum / Eqn::T::from(n as f64)).pow(c(0.5)))
+}
+
+/// Solve a stochastic differential equation, returning a sample path at the output times `t_eval` (which should be nondecreasing and not
+/// before the initial time). The Wiener increments are sampled using `rng`, so the same sample path is found for the same state of the
+/// generator.
+///
+/// The fixed step schemes use the step size `options.h`, reduced to step exactly to each output time. The adaptive scheme [SdeMethod::Sriw1]
+/// controls the rms of the error estimate scaled by `atol + rtol * |y|`, using the tolerances of the ODE problem.
+pub fn sde_solve<Eqn, G>(
+    problem: &SdeProblem<Eqn, G>,
+    options: &SdeOptions<Eqn::T>,
+    t_eval: &[Eqn::T],
+    rng: &mut SdeRng,
+) -> Result<SdeSolution<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    if options.method != SdeMethod::EulerMaruyama
+        && matches!(problem.noise, SdeNoise::General(m) if m > 1)
+    {
+        return Err(anyhow!(
+            "the {:?} scheme requires diagonal or scalar noise",
+            options.method
+        ));
+    }
+    if options.h <= Eqn::T::zero() {
+        return Err(anyhow!("the step size should be positive"));
+    }
+    let mut t = problem.ode.t0;
+    if t_eval.first().is_some_and(|&t_first| t_first < t) {
+        return Err(anyhow!(
+            "the output times should not be before the initial time"
+        ));
+    }
+    if t_eval.windows(2).any(|w| w[1] < w[0]) {
+        return Err(anyhow!("the output times should be nondecreasing"));
+    }
+
+    let mut y = problem.ode.eqn.init(t);
+    let mut w = Eqn::V::zeros(problem.nnoise());
+    let mut path = BrownianPath::new(problem.nnoise());
+    let mut h = options.h;
+    let mut solution = SdeSolution {
+        t: Vec::with_capacity(t_eval.len()),
+        y: Vec::with_capacity(t_eval.len()),
+        w: Vec::with_capacity(t_eval.len()),
+        number_of_steps: 0,
+        number_of_rejected_steps: 0,
+    };
+    let (safety, facmin, facmax) = (Eqn::T::from(0.9), Eqn::T::from(0.2), Eqn::T::from(5.0));
+    for &t_out in t_eval {
+        while t < t_out {
+            if solution.number_of_steps + solution.number_of_rejected_steps >= options.max_steps {
+                return Err(anyhow!(
+                    "maximum number of steps ({}) exceeded at t = {}",
+                    options.max_steps,
+                    t
+                ));
+            }
+            let remaining = t_out - t;
+            let h_max = if options.method == SdeMethod::Sriw1 {
+                h
+            } else {
+                options.h
+            };
+            let inc = path.next(if h_max < remaining { h_max } else { remaining }, rng);
+            let y_new = match options.method {
+                SdeMethod::EulerMaruyama | SdeMethod::Milstein => {
+                    fixed_step(problem, options.method, &y, t, &inc)
+                }
+                SdeMethod::Sriw1 => {
+                    let (y_new, err) = sriw1_step(problem, &y, t, &inc);
+                    let factor = if err > Eqn::T::zero() {
+                        let factor = safety * err.pow(Eqn::T::from(-2.0 / 3.0));
+                        if factor < facmin {
+                            facmin
+                        } else if factor > facmax {
+                            facmax
+                        } else {
+                            factor
+                        }
+                    } else {
+                        facmax
+                    };
+                    if err > Eqn::T::one() || err.is_nan() {
+                        solution.number_of_rejected_steps += 1;
+                        let h_new = if err.is_nan() { facmin } else { factor } * inc.h;
+                        if h_new <= Eqn::T::EPSILON * (num_traits::abs(t) + Eqn::T::one()) {
+                            return Err(anyhow!("step size too small at t = {}", t));
+                        }
+                        path.reject(inc, h_new, rng);
+                        h = h_new;
+                        continue;
+                    }
+                    // don't let a step that was cut short to reach an output time limit the next step
+                    if inc.h >= h || inc.h < remaining {
+                        h = factor * inc.h;
+                    }
+                    y_new
+                }
+            };
+            t = if inc.h >= remaining { t_out } else { t + inc.h };
+            y = y_new;
+            w += &inc.dw;
+            solution.number_of_steps += 1;
+        }
+        solution.t.push(t_out);
+        solution.y.push(y.clone());
+        solution.w.push(w.clone());
+    }
+    Ok(solution)
+}
+
+/// Solve a stochastic differential equation for `ntrajectories` sample paths using [sde_solve], returning the mean and variance of the
+/// solution at the output times `t_eval`. The generator for each sample path is seeded from a generator seeded with `seed`, so the
+/// statistics are reproducible.
+pub fn sde_ensemble<Eqn, G>(
+    problem: &SdeProblem<Eqn, G>,
+    options: &SdeOptions<Eqn::T>,
+    t_eval: &[Eqn::T],
+    ntrajectories: usize,
+    seed: u64,
+) -> Result<SdeEnsemble<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    if ntrajectories == 0 {
+        return Err(anyhow!("there should be at least one trajectory"));
+    }
+    let n = problem.ode.eqn.rhs().nstates();
+    let mut mean = vec![Eqn::V::zeros(n); t_eval.len()];
+    let mut sum_squares = vec![Eqn::V::zeros(n); t_eval.len()];
+    let mut seeds = SdeRng::new(seed);
+    for k in 0..ntrajectories {
+        let mut rng = SdeRng::new(seeds.next_u64());
+        let solution = sde_solve(problem, options, t_eval, &mut rng)?;
+
+        // welford's algorithm
+        let count = Eqn::T::from((k + 1) as f64);
+        for ((mean_j, sum_j), y_j) in mean
+            .iter_mut()
+            .zip(sum_squares.iter_mut())
+            .zip(solution.y.iter())
+        {
+            let delta = y_j.clone() - &*mean_j;
+            mean_j.axpy(Eqn::T::one() / count, &delta, Eqn::T::one());
+            let delta2 = y_j.clone() - &*mean_j;
+            *sum_j += mul(&delta, &delta2);
+        }
+    }
+    let scale = if ntrajectories > 1 {
+        Eqn::T::one() / Eqn::T::from((ntrajectories - 1) as f64)
+    } else {
+        Eqn::T::zero()
+    };
+    let variance = sum_squares
+        .into_iter()
+        .map(|s| s * crate::scale(scale))
+        .collect();
+    Ok(SdeEnsemble {
+        t: t_eval.to_vec(),
+        mean,
+        variance,
+        ntrajectories,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{Closure, OdeBuilder, OdeEquations, OdeSolverProblem};
+
+    use super::{
+        sde_ensemble, sde_solve, BrownianPath, Increment, SdeMethod, SdeNoise, SdeOptions,
+        SdeProblem, SdeRng,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // geometric brownian motion dy = mu y dt + sigma y dW, with solution y(t) = y0 exp((mu - sigma^2 / 2) t + sigma W(t))
+    const MU: f64 = 0.5;
+    const SIGMA: f64 = 0.8;
+
+    type GbmDiffusion = Closure<M, fn(&V, &V, f64, &mut V), fn(&V, &V, f64, &V, &mut V)>;
+
+    fn gbm_problem(
+        rtol: f64,
+    ) -> SdeProblem<impl OdeEquations<M = M, V = V, T = f64>, GbmDiffusion> {
+        let ode = OdeBuilder::new()
+            .rtol(rtol)
+            .atol([rtol])
+            .build_ode::<M, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y.copy_from(&(x * MU)),
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y.copy_from(&(v * MU)),
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        let diffusion: GbmDiffusion = Closure::new(
+            |x: &V, _p: &V, _t, y: &mut V| y.copy_from(&(x * SIGMA)),
+            |_x: &V, _p: &V, _t, v: &V, y: &mut V| y.copy_from(&(v * SIGMA)),
+            1,
+            1,
+            Rc::new(V::zeros(0)),
+        );
+        SdeProblem::new(ode, diffusion, SdeNoise::Diagonal).unwrap()
+    }
+
+    fn mean_strong_error(
+        problem: &SdeProblem<
+            impl OdeEquations<M = M, V = V, T = f64>,
+            impl crate::NonLinearOp<M = M, V = V, T = f64>,
+        >,
+        method: SdeMethod,
+        h: f64,
+    ) -> f64 {
+        let options = SdeOptions {
+            method,
+            h,
+            ..Default::default()
+        };
+        let nsamples = 20;
+        (0..nsamples)
+            .map(|seed| {
+                let mut rng = SdeRng::new(seed);
+                let solution = sde_solve(problem, &options, &[1.0], &mut rng).unwrap();
+                let exact = ((MU - 0.5 * SIGMA * SIGMA) + SIGMA * solution.w[0][0]).exp();
+                (solution.y[0][0] - exact).abs()
+            })
+            .sum::<f64>()
+            / nsamples as f64
+    }
+
+    #[test]
+    fn test_sde_rng() {
+        let mut rng = SdeRng::new(42);
+        let mut rng2 = SdeRng::new(42);
+        let samples = (0..100000).map(|_| rng.normal()).collect::<Vec<_>>();
+        assert!(samples.iter().all(|&x| x == rng2.normal()));
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.01, "mean {}", mean);
+        assert!((variance - 1.0).abs() < 0.02, "variance {}", variance);
+        assert_ne!(SdeRng::new(43).next_u64(), SdeRng::new(42).next_u64());
+    }
+
+    #[test]
+    fn test_brownian_path_split() {
+        // splitting increments sampled over h gives independent increments over each part, with variances h1 and h2
+        let (h, h1) = (1.0, 0.3);
+        let mut rng = SdeRng::new(0);
+        let path = BrownianPath::<V>::new(1);
+        let nsamples = 100000;
+        let mut moments = [[0.0; 4]; 4];
+        for _ in 0..nsamples {
+            let inc = Increment {
+                h,
+                dw: path.sample(h, &mut rng),
+                dz: path.sample(h, &mut rng),
+            };
+            let (dw, dz) = (inc.dw[0], inc.dz[0]);
+            let (first, rest) = path.split(inc, h1, &mut rng);
+            assert!((first.dw[0] + rest.dw[0] - dw).abs() < 1e-12);
+            let integral = |inc: &Increment<V>| 0.5 * inc.h * (inc.dw[0] + inc.dz[0] / 3f64.sqrt());
+            let whole = 0.5 * h * (dw + dz / 3f64.sqrt());
+            assert!(
+                (integral(&first) + integral(&rest) + rest.h * first.dw[0] - whole).abs() < 1e-12
+            );
+            let x = [first.dw[0], first.dz[0], rest.dw[0], rest.dz[0]];
+            for (row, xi) in moments.iter_mut().zip(x) {
+                for (m, xj) in row.iter_mut().zip(x) {
+                    *m += xi * xj / nsamples as f64;
+                }
+            }
+        }
+        let variance = [h1, h1, h - h1, h - h1];
+        for (i, row) in moments.iter().enumerate() {
+            for (j, m) in row.iter().enumerate() {
+                let expected = if i == j { variance[i] } else { 0.0 };
+                assert!((m - expected).abs() < 0.02, "{} {} {}", i, j, m);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sde_strong_convergence() {
+        let problem = gbm_problem(1e-4);
+        let em = mean_strong_error(&problem, SdeMethod::EulerMaruyama, 1e-3);
+        let milstein = mean_strong_error(&problem, SdeMethod::Milstein, 1e-3);
+        let sriw1 = mean_strong_error(&problem, SdeMethod::Sriw1, 1e-3);
+        assert!(em < 5e-2, "euler-maruyama {}", em);
+        assert!(
+            milstein < em / 5.0,
+            "milstein {} euler-maruyama {}",
+            milstein,
+            em
+        );
+        assert!(sriw1 < 1e-3, "sriw1 {}", sriw1);
+    }
+
+    #[test]
+    fn test_sde_reproducible() {
+        let problem = gbm_problem(1e-3);
+        let options = SdeOptions::default();
+        let t_eval = [0.0, 0.5, 1.0];
+        let s1 = sde_solve(&problem, &options, &t_eval, &mut SdeRng::new(1)).unwrap();
+        let s2 = sde_solve(&problem, &options, &t_eval, &mut SdeRng::new(1)).unwrap();
+        let s3 = sde_solve(&problem, &options, &t_eval, &mut SdeRng::new(2)).unwrap();
+        assert_eq!(s1.y, s2.y);
+        assert_ne!(s1.y[2], s3.y[2]);
+        assert_eq!(s1.y[0][0], 1.0);
+        assert_eq!(s1.t, t_eval.to_vec());
+        assert!(s1.number_of_rejected_steps > 0);
+    }
+
+    #[test]
+    fn test_sde_ensemble() {
+        let problem = gbm_problem(1e-3);
+        let options = SdeOptions::default();
+        let ensemble = sde_ensemble(&problem, &options, &[0.0, 1.0], 2000, 0).unwrap();
+        assert_eq!(ensemble.ntrajectories, 2000);
+        assert_eq!(ensemble.variance[0][0], 0.0);
+        // E[y(1)] = exp(mu), Var[y(1)] = exp(2 mu) (exp(sigma^2) - 1)
+        let mean = MU.exp();
+        let variance = (2.0 * MU).exp() * ((SIGMA * SIGMA).exp() - 1.0);
+        let standard_error = (variance / 2000.0).sqrt();
+        assert!(
+            (ensemble.mean[1][0] - mean).abs() < 4.0 * standard_error,
+            "mean {} expected {}",
+            ensemble.mean[1][0],
+            mean
+        );
+        assert!(
+            (ensemble.variance[1][0] - variance).abs() / variance < 0.3,
+            "variance {} expected {}",
+            ensemble.variance[1][0],
+            variance
+        );
+    }
+
+    // two states driven by two shared wiener processes with additive noise, dy = -y dt + G dW, with stationary covariance G G^T / 2
+    fn general_noise_problem() -> OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>> {
+        OdeBuilder::new()
+            .build_ode::<M, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y.copy_from(&(-x)),
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y.copy_from(&(-v)),
+                |_p: &V, _t| V::from_vec(vec![1.0, -1.0]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sde_general_noise() {
+        let ode = general_noise_problem();
+        let p = Rc::new(V::zeros(0));
+        // G = [[1, 0], [1, 1]] in column-major order
+        let diffusion = Closure::new(
+            |_x: &V, _p: &V, _t, y: &mut V| y.copy_from(&V::from_vec(vec![1.0, 1.0, 0.0, 1.0])),
+            |_x: &V, _p: &V, _t, _v: &V, y: &mut V| y.fill(0.0),
+            2,
+            4,
+            p.clone(),
+        );
+        let problem = SdeProblem::new(ode, diffusion, SdeNoise::General(2)).unwrap();
+        assert_eq!(problem.nnoise(), 2);
+        let options = SdeOptions {
+            method: SdeMethod::EulerMaruyama,
+            h: 1e-2,
+            ..Default::default()
+        };
+        let ensemble = sde_ensemble(&problem, &options, &[5.0], 2000, 3).unwrap();
+        // Var = diag(G G^T) / 2 = (0.5, 1.0), mean = y0 exp(-5)
+        assert!(ensemble.mean[0][0].abs() < 0.1);
+        assert!(ensemble.mean[0][1].abs() < 0.1);
+        assert!((ensemble.variance[0][0] - 0.5).abs() < 0.1);
+        assert!((ensemble.variance[0][1] - 1.0).abs() < 0.15);
+
+        let options = SdeOptions::<f64>::default();
+        assert!(sde_solve(&problem, &options, &[1.0], &mut SdeRng::new(0)).is_err());
+
+        let wrong_size = Closure::new(
+            |_x: &V, _p: &V, _t, y: &mut V| y.fill(1.0),
+            |_x: &V, _p: &V, _t, _v: &V, y: &mut V| y.fill(0.0),
+            2,
+            2,
+            p,
+        );
+        assert!(
+            SdeProblem::new(general_noise_problem(), wrong_size, SdeNoise::General(2)).is_err()
+        );
+    }
+
+    #[test]
+    fn test_sde_mass() {
+        // 2 dy = -y dt + dW has a mass matrix that is not the identity
+        let ode = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0],
+                |_x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -v[0],
+                |v: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * v[0] + beta * y[0],
+                |_p: &V, _t| V::from_element(1, 1.0),
+            )
+            .unwrap();
+        let diffusion = Closure::new(
+            |_x: &V, _p: &V, _t, y: &mut V| y.fill(1.0),
+            |_x: &V, _p: &V, _t, _v: &V, y: &mut V| y.fill(0.0),
+            1,
+            1,
+            Rc::new(V::zeros(0)),
+        );
+        assert!(SdeProblem::new(ode, diffusion, SdeNoise::Diagonal).is_err());
+    }
+}
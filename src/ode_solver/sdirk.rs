@@ -5,10 +5,12 @@ use num_traits::Pow;
 use num_traits::Zero;
 use std::ops::MulAssign;
 use std::rc::Rc;
+use std::time::Instant;
 
 use crate::matrix::MatrixRef;
 use crate::vector::VectorRef;
 use crate::NewtonNonlinearSolver;
+use crate::OdeSolverStatistics;
 use crate::OdeSolverStopReason;
 use crate::RootFinder;
 use crate::Tableau;
@@ -19,8 +21,6 @@ use crate::{
 };
 use crate::{LinearSolver, NonLinearOp};
 
-use super::bdf::BdfStatistics;
-
 /// A singly diagonally implicit Runge-Kutta method. Can optionally have an explicit first stage for ESDIRK methods.
 /// The particular method is defined by the [Tableau] used to create the solver.
 /// If the `beta` matrix of the [Tableau] is present this is used for interpolation, otherwise hermite interpolation is used.
@@ -49,7 +49,7 @@ where
     old_f: Eqn::V,
     f: Eqn::V,
     a_rows: Vec<Eqn::V>,
-    statistics: BdfStatistics<Eqn::T>,
+    statistics: OdeSolverStatistics<Eqn::T>,
     root_finder: Option<RootFinder<Eqn::V>>,
     tstop: Option<Eqn::T>,
 }
@@ -143,7 +143,7 @@ where
         let old_y = <Eqn::V as Vector>::zeros(n);
         let old_f = <Eqn::V as Vector>::zeros(n);
         let f = <Eqn::V as Vector>::zeros(n);
-        let statistics = BdfStatistics::default();
+        let statistics = OdeSolverStatistics::default();
         Self {
             tableau,
             nonlinear_solver,
@@ -163,136 +163,7 @@ where
         }
     }
 
-    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
-        &self.statistics
-    }
-
-    fn handle_tstop(&mut self, tstop: Eqn::T) -> Result<Option<OdeSolverStopReason<Eqn::T>>> {
-        let state = self.state.as_mut().unwrap();
-
-        // check if the we are at tstop
-        let troundoff = Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(state.t) + abs(state.h));
-        if abs(state.t - tstop) <= troundoff {
-            self.tstop = None;
-            return Ok(Some(OdeSolverStopReason::TstopReached));
-        } else if tstop < state.t - troundoff {
-            return Err(anyhow::anyhow!(
-                "tstop = {} is less than current time t = {}",
-                tstop,
-                state.t
-            ));
-        }
-
-        // check if the next step will be beyond tstop, if so adjust the step size
-        if state.t + state.h > tstop + troundoff {
-            let factor = (tstop - state.t) / state.h;
-            state.h *= factor;
-            self.nonlinear_solver.problem().f.set_h(state.h);
-        }
-        Ok(None)
-    }
-}
-
-impl<M, Eqn, LS> OdeSolverMethod<Eqn> for Sdirk<M, Eqn, LS>
-where
-    LS: LinearSolver<SdirkCallable<Eqn>>,
-    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
-    Eqn: OdeEquations,
-    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
-    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
-{
-    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
-        self.problem.as_ref()
-    }
-
-    fn set_problem(
-        &mut self,
-        mut state: OdeSolverState<<Eqn>::V>,
-        problem: &OdeSolverProblem<Eqn>,
-    ) {
-        // update initial step size based on function
-        let mut scale_factor = state.y.abs();
-        scale_factor *= scale(problem.rtol);
-        scale_factor += problem.atol.as_ref();
-
-        // compute first step based on alg in Hairer, Norsett, Wanner
-        // Solving Ordinary Differential Equations I, Nonstiff Problems
-        // Section II.4.2
-        let f0 = problem.eqn.rhs().call(&state.y, state.t);
-        let hf0 = &f0 * scale(state.h);
-
-        let mut tmp = f0.clone();
-        tmp.component_div_assign(&scale_factor);
-        let d0 = tmp.norm();
-
-        tmp = state.y.clone();
-        tmp.component_div_assign(&scale_factor);
-        let d1 = f0.norm();
-
-        let h0 = if d0 < Eqn::T::from(1e-5) || d1 < Eqn::T::from(1e-5) {
-            Eqn::T::from(1e-6)
-        } else {
-            Eqn::T::from(0.01) * (d0 / d1)
-        };
-
-        let y1 = &state.y + hf0;
-        let t1 = state.t + h0;
-        let f1 = problem.eqn.rhs().call(&y1, t1);
-
-        let mut df = f1 - &f0;
-        df *= scale(Eqn::T::one() / h0);
-        df.component_div_assign(&scale_factor);
-        let d2 = df.norm();
-
-        let mut max_d = d2;
-        if max_d < d1 {
-            max_d = d1;
-        }
-        let h1 = if max_d < Eqn::T::from(1e-15) {
-            let h1 = h0 * Eqn::T::from(1e-3);
-            if h1 < Eqn::T::from(1e-6) {
-                Eqn::T::from(1e-6)
-            } else {
-                h1
-            }
-        } else {
-            (Eqn::T::from(0.01) / max_d)
-                .pow(Eqn::T::one() / Eqn::T::from(1.0 + self.tableau.order() as f64))
-        };
-
-        state.h = Eqn::T::from(100.0) * h0;
-        if state.h > h1 {
-            state.h = h1;
-        }
-
-        // setup linear solver for first step
-        let callable = Rc::new(SdirkCallable::new(problem, self.gamma));
-        callable.set_h(state.h);
-        let nonlinear_problem = SolverProblem::new_from_ode_problem(callable, problem);
-        self.nonlinear_solver.set_problem(&nonlinear_problem);
-
-        // update statistics
-        self.statistics = BdfStatistics::default();
-        self.statistics.initial_step_size = state.h;
-
-        self.diff = M::zeros(state.y.len(), self.tableau.s());
-        self.old_f = f0.clone();
-        self.f = f0;
-        self.old_t = state.t;
-        self.old_y = state.y.clone();
-        self.state = Some(state);
-        self.problem = Some(problem.clone());
-        if let Some(root_fn) = problem.eqn.root() {
-            let state = self.state.as_ref().unwrap();
-            self.root_finder = Some(RootFinder::new(root_fn.nout()));
-            self.root_finder
-                .as_ref()
-                .unwrap()
-                .init(root_fn.as_ref(), &state.y, state.t);
-        }
-    }
-
-    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
         // optionally do the first step
         let state = self.state.as_mut().unwrap();
         let n = state.y.len();
@@ -334,9 +205,12 @@ where
                 }
 
                 if i == start {
+                    let start = Instant::now();
                     self.nonlinear_solver.reset_jacobian(&dy, t);
+                    self.statistics.timings.linear_solver_setup += start.elapsed();
                 }
-                let solve_result = self.nonlinear_solver.solve_in_place(&mut dy, t);
+                let solve_result =
+                    Self::solve_stage(&mut self.nonlinear_solver, &mut self.statistics, &mut dy, t);
 
                 // if we didn't update the jacobian and the solve failed, then we update the jacobian and try again
                 let solve_result = if solve_result.is_err() && !updated_jacobian {
@@ -355,9 +229,11 @@ where
                         dy.copy_from_view(&self.diff.column(i - 1));
                         dy.axpy_v(-c, &self.diff.column(i - 2), Eqn::T::one() + c);
                     }
+                    let start = Instant::now();
                     self.nonlinear_solver.reset_jacobian(&dy, t);
+                    self.statistics.timings.linear_solver_setup += start.elapsed();
                     self.statistics.number_of_nonlinear_solver_fails += 1;
-                    self.nonlinear_solver.solve_in_place(&mut dy, t)
+                    Self::solve_stage(&mut self.nonlinear_solver, &mut self.statistics, &mut dy, t)
                 } else {
                     solve_result
                 };
@@ -390,6 +266,7 @@ where
             self.nonlinear_solver
                 .linear_solver()
                 .solve_in_place(&mut error)?;
+            self.statistics.number_of_linear_solves += 1;
 
             // do not include algebraic variables in error calculation
             //let algebraic = self.problem.as_ref().unwrap().eqn.algebraic_indices();
@@ -452,6 +329,7 @@ where
             .copy_from_view(&self.diff.column(self.diff.ncols() - 1));
         self.old_f.mul_assign(scale(Eqn::T::one() / dt));
         std::mem::swap(&mut self.old_f, &mut self.f);
+        state.dy.copy_from(&self.f);
 
         {
             let y1 = self.nonlinear_solver.problem().f.get_last_f_eval();
@@ -467,12 +345,14 @@ where
 
         // check for root within accepted step
         if let Some(root_fn) = self.problem.as_ref().unwrap().eqn.root() {
+            let start = Instant::now();
             let ret = self.root_finder.as_ref().unwrap().check_root(
                 &|t| self.interpolate(t),
                 root_fn.as_ref(),
                 &self.state.as_ref().unwrap().y,
                 self.state.as_ref().unwrap().t,
             );
+            self.statistics.timings.root_finding += start.elapsed();
             if let Some(root) = ret {
                 return Ok(OdeSolverStopReason::RootFound(root));
             }
@@ -489,6 +369,174 @@ where
         Ok(OdeSolverStopReason::InternalTimestep)
     }
 
+    fn solve_stage(
+        nonlinear_solver: &mut NewtonNonlinearSolver<SdirkCallable<Eqn>, LS>,
+        statistics: &mut OdeSolverStatistics<Eqn::T>,
+        dy: &mut Eqn::V,
+        t: Eqn::T,
+    ) -> Result<()> {
+        let start = Instant::now();
+        let ret = nonlinear_solver.solve_in_place(dy, t);
+        statistics.timings.nonlinear_solver += start.elapsed();
+        statistics.number_of_nonlinear_solver_iterations += nonlinear_solver.niter();
+        statistics.number_of_linear_solves += nonlinear_solver.niter();
+        ret
+    }
+
+    fn handle_tstop(&mut self, tstop: Eqn::T) -> Result<Option<OdeSolverStopReason<Eqn::T>>> {
+        let state = self.state.as_mut().unwrap();
+
+        // check if the we are at tstop
+        let troundoff = Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(state.t) + abs(state.h));
+        if abs(state.t - tstop) <= troundoff {
+            self.tstop = None;
+            return Ok(Some(OdeSolverStopReason::TstopReached));
+        } else if tstop < state.t - troundoff {
+            return Err(anyhow::anyhow!(
+                "tstop = {} is less than current time t = {}",
+                tstop,
+                state.t
+            ));
+        }
+
+        // check if the next step will be beyond tstop, if so adjust the step size
+        if state.t + state.h > tstop + troundoff {
+            let factor = (tstop - state.t) / state.h;
+            state.h *= factor;
+            self.nonlinear_solver.problem().f.set_h(state.h);
+        }
+        Ok(None)
+    }
+}
+
+impl<M, Eqn, LS> OdeSolverMethod<Eqn> for Sdirk<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<<Eqn>::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("Sdirk")?;
+        // update initial step size based on function
+        let mut scale_factor = state.y.abs();
+        scale_factor *= scale(problem.rtol);
+        scale_factor += problem.atol.as_ref();
+
+        // compute first step based on alg in Hairer, Norsett, Wanner
+        // Solving Ordinary Differential Equations I, Nonstiff Problems
+        // Section II.4.2
+        let f0 = problem.eqn.rhs().call(&state.y, state.t);
+        let hf0 = &f0 * scale(state.h);
+
+        let mut tmp = f0.clone();
+        tmp.component_div_assign(&scale_factor);
+        let d0 = tmp.norm();
+
+        tmp = state.y.clone();
+        tmp.component_div_assign(&scale_factor);
+        let d1 = f0.norm();
+
+        let h0 = if d0 < Eqn::T::from(1e-5) || d1 < Eqn::T::from(1e-5) {
+            Eqn::T::from(1e-6)
+        } else {
+            Eqn::T::from(0.01) * (d0 / d1)
+        };
+
+        let y1 = &state.y + hf0;
+        let t1 = state.t + h0;
+        let f1 = problem.eqn.rhs().call(&y1, t1);
+
+        let mut df = f1 - &f0;
+        df *= scale(Eqn::T::one() / h0);
+        df.component_div_assign(&scale_factor);
+        let d2 = df.norm();
+
+        let mut max_d = d2;
+        if max_d < d1 {
+            max_d = d1;
+        }
+        let h1 = if max_d < Eqn::T::from(1e-15) {
+            let h1 = h0 * Eqn::T::from(1e-3);
+            if h1 < Eqn::T::from(1e-6) {
+                Eqn::T::from(1e-6)
+            } else {
+                h1
+            }
+        } else {
+            (Eqn::T::from(0.01) / max_d)
+                .pow(Eqn::T::one() / Eqn::T::from(1.0 + self.tableau.order() as f64))
+        };
+
+        state.h = Eqn::T::from(100.0) * h0;
+        if state.h > h1 {
+            state.h = h1;
+        }
+
+        // setup linear solver for first step
+        let callable = Rc::new(SdirkCallable::new(problem, self.gamma));
+        callable.set_h(state.h);
+        let nonlinear_problem = SolverProblem::new_from_ode_problem(callable, problem);
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // update statistics
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+
+        // the explicit first stage uses the time derivative, which is not the rhs if the mass depends on the state. Take it from a
+        // linearisation of the first implicit stage about y0, i.e. y' = (M(y0) - h gamma f'(y0))^{-1} f(y0), so the derivatives of the
+        // algebraic components are consistent with the constraints
+        let f0 = if problem.eqn.is_mass_state_dependent() && !self.is_sdirk {
+            let mut dy = f0;
+            self.nonlinear_solver
+                .problem()
+                .f
+                .set_phi_direct(state.y.clone());
+            self.nonlinear_solver
+                .reset_jacobian(&<Eqn::V as Vector>::zeros(dy.len()), state.t);
+            self.nonlinear_solver
+                .linear_solver()
+                .solve_in_place(&mut dy)?;
+            self.statistics.number_of_linear_solves += 1;
+            dy
+        } else {
+            f0
+        };
+
+        self.diff = M::zeros(state.y.len(), self.tableau.s());
+        self.old_f = f0.clone();
+        self.f = f0;
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t);
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
     fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<()> {
         self.tstop = Some(tstop);
         if let Some(OdeSolverStopReason::TstopReached) = self.handle_tstop(tstop)? {
@@ -554,6 +602,14 @@ where
         self.state.as_ref()
     }
 
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
     fn take_state(&mut self) -> Option<OdeSolverState<<Eqn>::V>> {
         Option::take(&mut self.state)
     }
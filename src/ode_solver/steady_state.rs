@@ -0,0 +1,297 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use num_traits::One;
+
+use crate::{
+    matrix::{default_solver::DefaultSolver, MatrixRef},
+    op::{bdf::BdfCallable, steady_state::SteadyStateCallable},
+    vector::DefaultDenseMatrix,
+    Bdf, NewtonNonlinearSolver, NonLinearOp, NonLinearSolver, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Scalar, SolverProblem, Vector,
+    VectorRef,
+};
+
+/// Options for [steady_state].
+#[derive(Clone, Debug)]
+pub struct SteadyStateOptions<T: Scalar> {
+    /// The initial pseudo time step (default `1e-3`).
+    pub dt0: T,
+    /// The maximum pseudo time step (default `1e10`).
+    pub dt_max: T,
+    /// The factor `r` in the step size rule `dt_{k+1} = r dt_k ||f(y_{k-1})|| / ||f(y_k)||` (default `1.5`), so the step grows even when `||f(y)||` decreases slowly.
+    pub dt_growth: T,
+    /// The maximum number of pseudo time steps (default `200`).
+    pub max_steps: usize,
+    /// The steady state is found when the norm of the time derivative `||f(y)||` (see [SteadyStateCallable]) is less than this (default `1e-8`).
+    pub tolerance: T,
+    /// Switch to Newton's method on `f(y) = 0` when `||f(y)||` is less than this factor times its initial value (default `1e-3`).
+    pub newton_switch: T,
+    /// If pseudo-transient continuation fails, integrate the problem using [Bdf] until `||f(y)||` is small (default `true`).
+    pub bdf_fallback: bool,
+    /// The maximum time to integrate for when falling back to [Bdf], measured from the initial time of the problem (default `1e10`).
+    pub bdf_max_time: T,
+}
+
+impl<T: Scalar> Default for SteadyStateOptions<T> {
+    fn default() -> Self {
+        Self {
+            dt0: T::from(1e-3),
+            dt_max: T::from(1e10),
+            dt_growth: T::from(1.5),
+            max_steps: 200,
+            tolerance: T::from(1e-8),
+            newton_switch: T::from(1e-3),
+            bdf_fallback: true,
+            bdf_max_time: T::from(1e10),
+        }
+    }
+}
+
+/// The result of [steady_state].
+#[derive(Clone, Debug)]
+pub struct SteadyStateSolution<V: Vector> {
+    /// The steady state.
+    pub y: V,
+    /// The norm of the time derivative `||f(y)||` at the steady state.
+    pub residual_norm: V::T,
+    /// The number of (successful) pseudo time steps taken.
+    pub number_of_pseudo_time_steps: usize,
+    /// The total number of Newton iterations, for both the pseudo time steps and the final Newton solve.
+    pub number_of_newton_iterations: usize,
+    /// True if pseudo-transient continuation failed and the steady state was found by integrating the problem using [Bdf].
+    pub used_bdf_fallback: bool,
+}
+
+/// Find the steady state of an ODE or DAE, i.e. the state `y` such that `f(t0, y) = 0` for equations of the form `M y' = f(t, y)`,
+/// or `G(t0, y, 0) = 0` for a fully implicit DAE, starting from the initial condition of the problem.
+///
+/// Newton's method applied directly to `f(y) = 0` often fails from a poor initial guess, so this uses pseudo-transient continuation \[1\]:
+/// implicit Euler steps `M (y_{k+1} - y_k) = dt_k f(y_{k+1})` are taken (using [BdfCallable], so the algebraic equations of a DAE, given by the
+/// nullspace of the mass matrix, are satisfied after every step) with a pseudo time step that grows as the solution approaches the steady state,
+/// using the switched evolution relaxation rule `dt_{k+1} = r dt_k ||f(y_{k-1})|| / ||f(y_k)||` (with the growth factor `r` given by
+/// [SteadyStateOptions::dt_growth]). Once `||f(y)||` has been reduced by the factor
+/// [SteadyStateOptions::newton_switch], the solver switches to Newton's method on `f(y) = 0` for fast final convergence (if Newton's method
+/// fails, pseudo time stepping continues). If a pseudo time step fails to converge the step is halved.
+///
+/// If the pseudo time stepping does not converge in [SteadyStateOptions::max_steps] steps and [SteadyStateOptions::bdf_fallback] is set, the
+/// problem is integrated using the [Bdf] solver until `||f(y)||` is small, and the result is refined using Newton's method.
+///
+/// \[1\] Kelley, C. T., & Keyes, D. E. (1998). Convergence analysis of pseudo-transient continuation. SIAM Journal on Numerical Analysis, 35(2), 508-523.
+pub fn steady_state<Eqn>(
+    problem: &OdeSolverProblem<Eqn>,
+    options: &SteadyStateOptions<Eqn::T>,
+) -> Result<SteadyStateSolution<Eqn::V>>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    Eqn::V: DefaultDenseMatrix,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    let t = problem.t0;
+    let y = problem.eqn.init(t);
+
+    let f = Rc::new(SteadyStateCallable::new(&problem.eqn));
+    let f_norm = |y: &Eqn::V| f.call(y, t).norm();
+    let mut newton = NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+    newton.set_problem(&SolverProblem::new_from_ode_problem(f.clone(), problem));
+
+    // implicit euler steps, M (y - y0) - dt f(y) = 0
+    let callable = Rc::new(BdfCallable::new(problem));
+    let mut ie_solver = NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+    ie_solver.set_max_iter(10);
+    ie_solver.set_problem(&SolverProblem::new_from_ode_problem(
+        callable.clone(),
+        problem,
+    ));
+
+    let mut solution = SteadyStateSolution {
+        residual_norm: f_norm(&y),
+        y,
+        number_of_pseudo_time_steps: 0,
+        number_of_newton_iterations: 0,
+        used_bdf_fallback: false,
+    };
+    if solution.residual_norm <= options.tolerance {
+        return Ok(solution);
+    }
+
+    // tries newton's method on f(y) = 0 from the current solution, returns true if the steady state has been found
+    let try_newton = |newton: &mut NewtonNonlinearSolver<_, _>,
+                      solution: &mut SteadyStateSolution<Eqn::V>| {
+        let mut y = solution.y.clone();
+        let result = newton.solve_in_place(&mut y, t);
+        solution.number_of_newton_iterations += newton.niter();
+        if result.is_ok() {
+            let norm = f_norm(&y);
+            if norm < solution.residual_norm {
+                solution.y = y;
+                solution.residual_norm = norm;
+            }
+        }
+        solution.residual_norm <= options.tolerance
+    };
+
+    let mut dt = options.dt0;
+    let dt_min = options.dt0 * Eqn::T::from(1e-10);
+    let mut newton_threshold = options.newton_switch * solution.residual_norm;
+    let zeros = Eqn::V::zeros(solution.y.len());
+    let mut steps = 0;
+    while steps < options.max_steps {
+        callable.set_c(dt, Eqn::T::one());
+        callable.set_psi_and_y0(zeros.clone(), &solution.y);
+        callable.set_jacobian_is_stale();
+        ie_solver.reset_jacobian(&solution.y, t);
+        let mut y_new = solution.y.clone();
+        let result = ie_solver.solve_in_place(&mut y_new, t);
+        solution.number_of_newton_iterations += ie_solver.niter();
+        if result.is_err() {
+            dt *= Eqn::T::from(0.5);
+            if dt < dt_min {
+                break;
+            }
+            continue;
+        }
+        steps += 1;
+        solution.number_of_pseudo_time_steps = steps;
+
+        // switched evolution relaxation
+        let norm = f_norm(&y_new);
+        dt *= options.dt_growth * solution.residual_norm / norm;
+        if dt > options.dt_max {
+            dt = options.dt_max;
+        }
+        solution.y = y_new;
+        solution.residual_norm = norm;
+        if solution.residual_norm <= options.tolerance {
+            return Ok(solution);
+        }
+
+        if solution.residual_norm <= newton_threshold {
+            if try_newton(&mut newton, &mut solution) {
+                return Ok(solution);
+            }
+            newton_threshold = solution.residual_norm * Eqn::T::from(0.1);
+        }
+    }
+
+    if options.bdf_fallback {
+        solution.used_bdf_fallback = true;
+        let mut state = OdeSolverState::new(problem);
+        state.y.copy_from(&solution.y);
+        let mut solver = Bdf::default();
+        solver.set_problem(state, problem)?;
+        solver.set_stop_time(t + options.bdf_max_time)?;
+        let mut newton_threshold = options.newton_switch * solution.residual_norm;
+        loop {
+            let stop_reason = solver.step()?;
+            let state = solver.state().unwrap();
+            let norm = f_norm(&state.y);
+            if norm <= newton_threshold || norm <= options.tolerance {
+                solution.y.copy_from(&state.y);
+                solution.residual_norm = norm;
+                if norm <= options.tolerance || try_newton(&mut newton, &mut solution) {
+                    return Ok(solution);
+                }
+                newton_threshold = norm * Eqn::T::from(0.1);
+            }
+            if let OdeSolverStopReason::TstopReached = stop_reason {
+                solution.y.copy_from(&state.y);
+                solution.residual_norm = norm;
+                break;
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Steady state not found, ||f(y)|| = {} after {} pseudo time steps",
+        solution.residual_norm,
+        solution.number_of_pseudo_time_steps
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::problem::OdeSolverProblem, NewtonNonlinearSolver, NonLinearSolver, OdeBuilder,
+        OdeEquations, SolverProblem, Vector,
+    };
+    use std::rc::Rc;
+
+    use super::{steady_state, SteadyStateCallable, SteadyStateOptions};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // dy1/dt = -atan(y1 - 2)
+    // dy2/dt = y1 - y2 (or 0 = y1 - y2 if algebraic)
+    // with steady state y = [2, 2]. Newton's method on f(y) = 0 diverges from y1 = 10
+    fn atan_problem(algebraic: bool) -> OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>> {
+        OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x: &V, _p: &V, _t, y: &mut V| {
+                    y[0] = -(x[0] - 2.0).atan();
+                    y[1] = x[0] - x[1];
+                },
+                |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = -v[0] / (1.0 + (x[0] - 2.0).powi(2));
+                    y[1] = v[0] - v[1];
+                },
+                move |v: &V, _p: &V, _t, beta, y: &mut V| {
+                    y[0] = v[0] + beta * y[0];
+                    y[1] = if algebraic {
+                        beta * y[1]
+                    } else {
+                        v[1] + beta * y[1]
+                    };
+                },
+                |_p: &V, _t| V::from_vec(vec![10.0, 0.0]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_steady_state_pseudo_transient() {
+        for algebraic in [false, true] {
+            let problem = atan_problem(algebraic);
+
+            // plain newton from the initial condition fails
+            let f = Rc::new(SteadyStateCallable::new(&problem.eqn));
+            let mut newton = NewtonNonlinearSolver::new(crate::NalgebraLU::default());
+            newton.set_problem(&SolverProblem::new_from_ode_problem(f, &problem));
+            let mut y = problem.eqn.init(problem.t0);
+            assert!(newton.solve_in_place(&mut y, problem.t0).is_err());
+
+            let options = SteadyStateOptions {
+                bdf_fallback: false,
+                ..Default::default()
+            };
+            let soln = steady_state(&problem, &options).unwrap();
+            soln.y.assert_eq_st(&V::from_vec(vec![2.0, 2.0]), 1e-8);
+            assert!(soln.residual_norm <= options.tolerance);
+            assert!(soln.number_of_pseudo_time_steps > 0);
+            assert!(!soln.used_bdf_fallback);
+        }
+    }
+
+    #[test]
+    fn test_steady_state_bdf_fallback() {
+        let problem = atan_problem(false);
+        let options = SteadyStateOptions {
+            max_steps: 0,
+            ..Default::default()
+        };
+        let soln = steady_state(&problem, &options).unwrap();
+        soln.y.assert_eq_st(&V::from_vec(vec![2.0, 2.0]), 1e-8);
+        assert!(soln.used_bdf_fallback);
+        assert_eq!(soln.number_of_pseudo_time_steps, 0);
+
+        let options = SteadyStateOptions {
+            max_steps: 0,
+            bdf_fallback: false,
+            ..Default::default()
+        };
+        assert!(steady_state(&problem, &options).is_err());
+    }
+}
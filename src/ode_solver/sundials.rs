@@ -1,9 +1,8 @@
 use anyhow::{anyhow, Result};
-use num_traits::Zero;
-use serde::Serialize;
 use std::{
     ffi::{c_int, c_long, c_void, CStr},
     rc::Rc,
+    time::Instant,
 };
 use sundials_sys::{
     realtype, IDACalcIC, IDACreate, IDAFree, IDAGetDky, IDAGetIntegratorStats,
@@ -16,9 +15,11 @@ use sundials_sys::{
 };
 
 use crate::{
-    scale, vector::sundials::get_suncontext, LinearOp, Matrix, NonLinearOp, OdeEquations,
-    OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, SundialsMatrix,
-    SundialsVector, Vector,
+    op::{init::InitCallable, ResidualOp},
+    scale,
+    vector::sundials::get_suncontext,
+    LinearOp, Matrix, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+    OdeSolverStatistics, OdeSolverStopReason, Op, SundialsMatrix, SundialsVector, Vector,
 };
 
 pub fn sundials_check(retval: c_int) -> Result<()> {
@@ -31,71 +32,52 @@ pub fn sundials_check(retval: c_int) -> Result<()> {
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
-pub struct SundialsStatistics {
-    pub number_of_linear_solver_setups: usize,
-    pub number_of_steps: usize,
-    pub number_of_error_test_failures: usize,
-    pub number_of_nonlinear_solver_iterations: usize,
-    pub number_of_nonlinear_solver_fails: usize,
-    pub initial_step_size: realtype,
-    pub final_step_size: realtype,
-}
-
-impl SundialsStatistics {
-    fn new() -> Self {
-        Self {
-            number_of_linear_solver_setups: 0,
-            number_of_steps: 0,
-            number_of_error_test_failures: 0,
-            number_of_nonlinear_solver_iterations: 0,
-            number_of_nonlinear_solver_fails: 0,
-            initial_step_size: 0.0,
-            final_step_size: 0.0,
-        }
-    }
-    fn new_from_ida(ida_mem: *mut c_void) -> Result<Self> {
-        let mut nsteps: c_long = 0;
-        let mut nrevals: c_long = 0;
-        let mut nlinsetups: c_long = 0;
-        let mut netfails: c_long = 0;
-        let mut klast: c_int = 0;
-        let mut kcur: c_int = 0;
-        let mut hinused: realtype = 0.;
-        let mut hlast: realtype = 0.;
-        let mut hcur: realtype = 0.;
-        let mut tcur: realtype = 0.;
-
-        sundials_check(unsafe {
-            IDAGetIntegratorStats(
-                ida_mem,
-                &mut nsteps,
-                &mut nrevals,
-                &mut nlinsetups,
-                &mut netfails,
-                &mut klast,
-                &mut kcur,
-                &mut hinused,
-                &mut hlast,
-                &mut hcur,
-                &mut tcur,
-            )
-        })?;
-
-        let mut nniters: c_long = 0;
-        let mut nncfails: c_long = 0;
-        sundials_check(unsafe { IDAGetNonlinSolvStats(ida_mem, &mut nniters, &mut nncfails) })?;
-
-        Ok(Self {
-            number_of_linear_solver_setups: nlinsetups.try_into().unwrap(),
-            number_of_steps: nsteps.try_into().unwrap(),
-            number_of_error_test_failures: netfails.try_into().unwrap(),
-            number_of_nonlinear_solver_iterations: nniters.try_into().unwrap(),
-            number_of_nonlinear_solver_fails: nncfails.try_into().unwrap(),
-            initial_step_size: hinused,
-            final_step_size: hcur,
-        })
-    }
+/// Collects the integrator and nonlinear solver statistics from IDA into `statistics`, leaving the other fields untouched.
+fn update_statistics_from_ida(
+    ida_mem: *mut c_void,
+    statistics: &mut OdeSolverStatistics<realtype>,
+) -> Result<()> {
+    let mut nsteps: c_long = 0;
+    let mut nrevals: c_long = 0;
+    let mut nlinsetups: c_long = 0;
+    let mut netfails: c_long = 0;
+    let mut klast: c_int = 0;
+    let mut kcur: c_int = 0;
+    let mut hinused: realtype = 0.;
+    let mut hlast: realtype = 0.;
+    let mut hcur: realtype = 0.;
+    let mut tcur: realtype = 0.;
+
+    sundials_check(unsafe {
+        IDAGetIntegratorStats(
+            ida_mem,
+            &mut nsteps,
+            &mut nrevals,
+            &mut nlinsetups,
+            &mut netfails,
+            &mut klast,
+            &mut kcur,
+            &mut hinused,
+            &mut hlast,
+            &mut hcur,
+            &mut tcur,
+        )
+    })?;
+
+    let mut nniters: c_long = 0;
+    let mut nncfails: c_long = 0;
+    sundials_check(unsafe { IDAGetNonlinSolvStats(ida_mem, &mut nniters, &mut nncfails) })?;
+
+    statistics.number_of_linear_solver_setups = nlinsetups.try_into().unwrap();
+    // IDA uses a direct linear solver, so there is one linear solve per newton iteration
+    statistics.number_of_linear_solves = nniters.try_into().unwrap();
+    statistics.number_of_steps = nsteps.try_into().unwrap();
+    statistics.number_of_error_test_failures = netfails.try_into().unwrap();
+    statistics.number_of_nonlinear_solver_iterations = nniters.try_into().unwrap();
+    statistics.number_of_nonlinear_solver_fails = nncfails.try_into().unwrap();
+    statistics.initial_step_size = hinused;
+    statistics.final_step_size = hcur;
+    Ok(())
 }
 
 struct SundialsData<Eqn>
@@ -132,7 +114,7 @@ where
     problem: Option<OdeSolverProblem<Eqn>>,
     yp: SundialsVector,
     jacobian: SundialsMatrix,
-    statistics: SundialsStatistics,
+    statistics: OdeSolverStatistics<realtype>,
     state: Option<OdeSolverState<Eqn::V>>,
 }
 
@@ -151,6 +133,11 @@ where
         let y = SundialsVector::new_not_owned(y);
         let yp = SundialsVector::new_not_owned(yp);
         let mut rr = SundialsVector::new_not_owned(rr);
+        // a fully implicit DAE gives the residual F(t, y, y') directly
+        if let Some(residual) = data.eqn.residual() {
+            residual.call_inplace(&y, &yp, t, &mut rr);
+            return 0;
+        }
         // F(t, y, y') =  M y' - f(t, y)
         // rr = f(t, y)
         data.eqn.rhs().call_inplace(&y, t, &mut rr);
@@ -163,7 +150,7 @@ where
         t: realtype,
         c_j: realtype,
         y: N_Vector,
-        _yp: N_Vector,
+        yp: N_Vector,
         _r: N_Vector,
         jac: SUNMatrix,
         user_data: *mut c_void,
@@ -174,9 +161,17 @@ where
         let data = unsafe { &mut *(user_data as *mut SundialsData<Eqn>) };
         let eqn = &data.eqn;
 
-        // jac = c_j * M - rhs_jac
         let y = SundialsVector::new_not_owned(y);
         let mut jac = SundialsMatrix::new_not_owned(jac);
+
+        // jac = dF/dy + c_j * dF/dy' for a fully implicit DAE
+        if let Some(residual) = eqn.residual() {
+            let yp = SundialsVector::new_not_owned(yp);
+            residual.jacobian_inplace(&y, &yp, t, c_j, &mut jac);
+            return 0;
+        }
+
+        // jac = c_j * M - rhs_jac
         eqn.mass().matrix_inplace(t, &mut data.mass);
         eqn.rhs().jacobian_inplace(&y, t, &mut data.rhs_jac);
         data.rhs_jac *= scale(-1.0);
@@ -200,37 +195,24 @@ where
             problem: None,
             yp,
             linear_solver: std::ptr::null_mut(),
-            statistics: SundialsStatistics::new(),
+            statistics: OdeSolverStatistics::default(),
             jacobian,
             state: None,
         }
     }
 
-    pub fn get_statistics(&self) -> &SundialsStatistics {
-        &self.statistics
-    }
-
     pub fn calc_ic(&mut self, t: realtype) -> Result<()> {
         if self.problem.is_none() {
             return Err(anyhow!("Problem not set"));
         }
-        let diag = self
-            .problem
-            .as_ref()
-            .unwrap()
-            .eqn
-            .mass()
-            .matrix(t)
-            .diagonal();
-        let id = diag.filter_indices(|x| x == Eqn::T::zero());
-        let number_of_states = self.problem.as_ref().unwrap().eqn.rhs().nstates();
+        // the algebraic components are found from the nullspace of dF/dy' (the mass matrix for non-implicit equations)
+        let eqn = &self.problem.as_ref().unwrap().eqn;
+        let init = InitCallable::new(eqn, &eqn.init(t), &self.yp, t);
+        let number_of_states = init.is_algebraic().len();
         // need to convert to realtype sundials vector
         let mut id_realtype = SundialsVector::new_serial(number_of_states);
-        for i in 0..number_of_states {
-            match id[i] {
-                1 => id_realtype[i] = 1.0,
-                _ => id_realtype[i] = 0.0,
-            }
+        for (i, &is_algebraic) in init.is_algebraic().iter().enumerate() {
+            id_realtype[i] = if is_algebraic { 0.0 } else { 1.0 };
         }
         Self::check(unsafe { IDASetId(self.ida_mem, id_realtype.sundials_vector()) })?;
         Self::check(unsafe { IDACalcIC(self.ida_mem, IDA_YA_YDP_INIT, t) })?;
@@ -271,14 +253,28 @@ where
         self.state.as_ref()
     }
 
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
     fn take_state(&mut self) -> Option<OdeSolverState<<Eqn>::V>> {
         Option::take(&mut self.state)
     }
 
-    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
+    fn set_problem(
+        &mut self,
+        state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_mass_not_state_dependent("SundialsIda")?;
         self.state = Some(state);
         let state = self.state.as_ref().unwrap();
         self.problem = Some(problem.clone());
+        self.statistics = OdeSolverStatistics::default();
         let eqn = problem.eqn.as_ref();
         let number_of_states = eqn.rhs().nstates();
         let ctx = *get_suncontext();
@@ -286,11 +282,12 @@ where
 
         // set user data
         self.data = Some(SundialsData::new(problem.eqn.clone()));
-        Self::check(unsafe { IDASetUserData(self.ida_mem, &self.data as *const _ as *mut c_void) })
-            .unwrap();
+        Self::check(unsafe {
+            IDASetUserData(self.ida_mem, &self.data as *const _ as *mut c_void)
+        })?;
 
         // initialize
-        self.yp = SundialsVector::zeros(number_of_states);
+        self.yp = state.dy.clone();
         Self::check(unsafe {
             IDAInit(
                 ida_mem,
@@ -299,13 +296,12 @@ where
                 state.y.sundials_vector(),
                 self.yp.sundials_vector(),
             )
-        })
-        .unwrap();
+        })?;
 
         // tolerances
         let rtol = problem.rtol;
         let atol = problem.atol.as_ref();
-        Self::check(unsafe { IDASVtolerances(ida_mem, rtol, atol.sundials_vector()) }).unwrap();
+        Self::check(unsafe { IDASVtolerances(ida_mem, rtol, atol.sundials_vector()) })?;
 
         // linear solver
         self.jacobian = SundialsMatrix::new_dense(number_of_states, number_of_states);
@@ -316,14 +312,14 @@ where
                 ctx,
             )
         };
-        Self::check(unsafe { SUNLinSolInitialize(self.linear_solver) }).unwrap();
+        Self::check(unsafe { SUNLinSolInitialize(self.linear_solver) })?;
         Self::check(unsafe {
             IDASetLinearSolver(ida_mem, self.linear_solver, self.jacobian.sundials_matrix())
-        })
-        .unwrap();
+        })?;
 
         // set jacobian function
-        Self::check(unsafe { IDASetJacFn(ida_mem, Some(Self::jacobian)) }).unwrap();
+        Self::check(unsafe { IDASetJacFn(ida_mem, Some(Self::jacobian)) })?;
+        Ok(())
     }
 
     fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
@@ -336,6 +332,7 @@ where
             return Err(anyhow!("Problem not set"));
         }
         let itask = IDA_ONE_STEP;
+        let start = Instant::now();
         let retval = unsafe {
             IDASolve(
                 self.ida_mem,
@@ -347,8 +344,11 @@ where
             )
         };
 
+        state.dy.copy_from(&self.yp);
+
         // update stats
-        self.statistics = SundialsStatistics::new_from_ida(self.ida_mem).unwrap();
+        self.statistics.timings.total += start.elapsed();
+        update_statistics_from_ida(self.ida_mem, &mut self.statistics).unwrap();
 
         // check return value
         match retval {
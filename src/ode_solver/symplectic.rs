@@ -0,0 +1,703 @@
+use anyhow::{anyhow, Result};
+use num_traits::{One, Zero};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::{
+    matrix::default_solver::DefaultSolver, nonlinear_solver::NonLinearSolver,
+    op::sdirk::SdirkCallable, scale, solver::SolverProblem, LinearSolver, NewtonNonlinearSolver,
+    NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+    OdeSolverStatistics, OdeSolverStopReason, Op, RootFinder, Vector, VectorRef,
+};
+
+use super::fixed_step::{fixed_step_size, hermite_interpolate, is_at_stop_time};
+
+/// A symplectic integrator for separable Hamiltonian systems `y = (q, p)`, `q' = dT/dp(p)`, `p' = -dV/dq(q)` (see
+/// [crate::OdeBuilder::build_separable_hamiltonian]), including second order systems `q'' = f(t, q)` that do not depend on the velocity
+/// (see [crate::OdeBuilder::build_second_order]).
+///
+/// The solver takes fixed steps (see [super::fixed_step]), and the reduced last step breaks the symplectic property for that step. Each step is
+/// a composition of velocity Verlet (kick-drift-kick leapfrog) steps, so the energy error of the solution is bounded over long times rather than
+/// drifting as it does for [crate::Bdf] or [crate::Sdirk]. The right-hand side of the problem is evaluated at each kick and drift, taking the first
+/// half of the result (the velocity) for the drift and the second half (the force) for the kick, and the mass matrix is not used, so
+/// it must be the identity ([OdeSolverMethod::set_problem] returns an error otherwise).
+///
+/// The problem must be separable: the velocity must only depend on `p` and the force only on `q`. Non-separable Hamiltonians and second order
+/// systems with a velocity-dependent force (e.g. damping) are not solved correctly by these methods, so [OdeSolverMethod::set_problem] returns
+/// an error if the jacobian of the right-hand side at the initial state shows either dependence. Use [ImplicitMidpoint] or
+/// [crate::GeneralisedAlpha] for these problems instead.
+pub struct Symplectic<Eqn: OdeEquations> {
+    weights: Vec<Eqn::T>,
+    order: usize,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    f: Eqn::V,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn: OdeEquations> Default for Symplectic<Eqn> {
+    fn default() -> Self {
+        Self::velocity_verlet()
+    }
+}
+
+impl<Eqn: OdeEquations> Symplectic<Eqn> {
+    fn new(weights: Vec<f64>, order: usize) -> Self {
+        let n = 1;
+        Self {
+            weights: weights.into_iter().map(Eqn::T::from).collect(),
+            order,
+            problem: None,
+            state: None,
+            f: Eqn::V::zeros(n),
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(n),
+            old_dy: Eqn::V::zeros(n),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+        }
+    }
+
+    /// The second order velocity Verlet (Störmer-Verlet) method.
+    pub fn velocity_verlet() -> Self {
+        Self::new(vec![1.0], 2)
+    }
+
+    /// The fourth order method of Yoshida (1990), a composition of three velocity Verlet steps.
+    ///
+    /// Yoshida, H. (1990). Construction of higher order symplectic integrators. Physics letters A, 150(5-7), 262-268.
+    pub fn yoshida4() -> Self {
+        let cbrt2 = 2.0f64.powf(1.0 / 3.0);
+        let w1 = 1.0 / (2.0 - cbrt2);
+        let w0 = -cbrt2 / (2.0 - cbrt2);
+        Self::new(vec![w1, w0, w1], 4)
+    }
+
+    /// The sixth order method of Yoshida (1990) (solution A), a composition of seven velocity Verlet steps.
+    ///
+    /// Yoshida, H. (1990). Construction of higher order symplectic integrators. Physics letters A, 150(5-7), 262-268.
+    pub fn yoshida6() -> Self {
+        let w1 = -1.17767998417887;
+        let w2 = 0.235573213359357;
+        let w3 = 0.784513610477560;
+        let w0 = 1.0 - 2.0 * (w1 + w2 + w3);
+        Self::new(vec![w3, w2, w1, w0, w1, w2, w3], 6)
+    }
+
+    /// The order of the method.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    // p += c * dp/dt, using the force in the second half of f
+    fn kick(y: &mut Eqn::V, f: &Eqn::V, c: Eqn::T) {
+        let n = y.len() / 2;
+        for i in n..2 * n {
+            y[i] += c * f[i];
+        }
+    }
+
+    // true if the velocity (first half of the right-hand side) does not depend on q and the force (second half) does not depend on p,
+    // checked using the jacobian-vector products with a vector of distinct entries in each half of the state at (y, t)
+    fn is_separable(rhs: &Eqn::Rhs, y: &Eqn::V, t: Eqn::T) -> bool {
+        let n = y.len() / 2;
+        let mut v = Eqn::V::zeros(2 * n);
+        for i in 0..n {
+            v[i] = Eqn::T::one() + Eqn::T::from(i as f64) / Eqn::T::from(n as f64);
+        }
+        let jv_q = rhs.jac_mul(y, t, &v);
+        let mut v_p = Eqn::V::zeros(2 * n);
+        for i in 0..n {
+            v_p[n + i] = v[i];
+        }
+        let jv_p = rhs.jac_mul(y, t, &v_p);
+        (0..n).all(|i| jv_q[i] == Eqn::T::zero() && jv_p[n + i] == Eqn::T::zero())
+    }
+
+    // q += c * dq/dt, using the velocity in the first half of f
+    fn drift(y: &mut Eqn::V, f: &Eqn::V, c: Eqn::T) {
+        let n = y.len() / 2;
+        for i in 0..n {
+            y[i] += c * f[i];
+        }
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let (Some(state), Some(problem)) = (self.state.as_mut(), self.problem.as_ref()) else {
+            return Err(anyhow!("State not set"));
+        };
+        let rhs = problem.eqn.rhs();
+        let h = fixed_step_size(state.t, state.h, self.tstop);
+        self.old_t = state.t;
+        self.old_y.copy_from(&state.y);
+        self.old_dy.copy_from(&state.dy);
+
+        // the force in f is always up to date at the start of each substep
+        let half = Eqn::T::from(0.5);
+        let mut t = state.t;
+        for &w in self.weights.iter() {
+            let hw = h * w;
+            Self::kick(&mut state.y, &self.f, half * hw);
+            rhs.call_inplace(&state.y, t, &mut self.f);
+            Self::drift(&mut state.y, &self.f, hw);
+            t += hw;
+            rhs.call_inplace(&state.y, t, &mut self.f);
+            Self::kick(&mut state.y, &self.f, half * hw);
+        }
+        state.t = match self.tstop {
+            Some(tstop) if is_at_stop_time(self.old_t + h, h, tstop) => tstop,
+            _ => self.old_t + h,
+        };
+        rhs.call_inplace(&state.y, state.t, &mut self.f);
+        state.dy.copy_from(&self.f);
+
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = problem.eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<Eqn: OdeEquations> OdeSolverMethod<Eqn> for Symplectic<Eqn> {
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("Symplectic")?;
+        problem.check_mass_identity("Symplectic", state.t)?;
+        if state.y.len() % 2 != 0 {
+            return Err(anyhow!(
+                "Symplectic requires a system y = (q, p) with an even number of states"
+            ));
+        }
+        if !Self::is_separable(problem.eqn.rhs(), &state.y, state.t) {
+            return Err(anyhow!(
+                "Symplectic requires a separable system, where the velocity only depends on p and the force only depends on q"
+            ));
+        }
+        self.f = problem.eqn.rhs().call(&state.y, state.t);
+        state.dy.copy_from(&self.f);
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        Ok(hermite_interpolate(
+            self.old_t,
+            state.t,
+            &self.old_y,
+            &state.y,
+            &self.old_dy,
+            &state.dy,
+            t,
+        ))
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        Option::take(&mut self.state)
+    }
+}
+
+/// The implicit midpoint rule `y_{n+1} = y_n + h f(t_n + h / 2, (y_n + y_{n+1}) / 2)`, the one-stage Gauss-Legendre method, which is
+/// second order and symplectic for any (including non-separable) Hamiltonian system, and conserves quadratic invariants exactly.
+///
+/// The solver takes fixed steps (see [super::fixed_step]). The implicit equation is solved using Newton's method (see [SdirkCallable]), and the
+/// jacobian is only updated when the Newton iteration fails to converge. Interpolation is linear within the last step (the collocation
+/// polynomial of the method).
+pub struct ImplicitMidpoint<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+{
+    nonlinear_solver: NewtonNonlinearSolver<SdirkCallable<Eqn>, LS>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    statistics: OdeSolverStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn> Default for ImplicitMidpoint<Eqn, <Eqn::M as DefaultSolver>::LS<SdirkCallable<Eqn>>>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+{
+    fn default() -> Self {
+        Self::new(Eqn::M::default_solver())
+    }
+}
+
+impl<Eqn, LS> ImplicitMidpoint<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+{
+    const NEWTON_MAXITER: usize = 10;
+
+    pub fn new(linear_solver: LS) -> Self {
+        let mut nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
+        nonlinear_solver.set_max_iter(Self::NEWTON_MAXITER);
+        Self {
+            nonlinear_solver,
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(1),
+            statistics: OdeSolverStatistics::default(),
+            root_finder: None,
+            tstop: None,
+        }
+    }
+
+    fn solve_nonlinear(&mut self, x: &mut Eqn::V, t: Eqn::T) -> Result<()> {
+        let start = Instant::now();
+        let ret = self.nonlinear_solver.solve_in_place(x, t);
+        self.statistics.timings.nonlinear_solver += start.elapsed();
+        self.statistics.number_of_nonlinear_solver_iterations += self.nonlinear_solver.niter();
+        self.statistics.number_of_linear_solves += self.nonlinear_solver.niter();
+        ret
+    }
+
+    fn reset_jacobian(&mut self, x: &Eqn::V, t: Eqn::T) {
+        let start = Instant::now();
+        self.nonlinear_solver.reset_jacobian(x, t);
+        self.statistics.timings.linear_solver_setup += start.elapsed();
+        self.statistics.number_of_linear_solver_setups =
+            self.nonlinear_solver.problem().f.number_of_jac_evals();
+    }
+
+    fn _step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let Some(state) = self.state.as_ref() else {
+            return Err(anyhow!("State not set"));
+        };
+        let h = fixed_step_size(state.t, state.h, self.tstop);
+        let t_mid = state.t + h * Eqn::T::from(0.5);
+
+        // solve M x = h f(t + h / 2, y + x / 2), with x = y_{n+1} - y_n
+        let callable = self.nonlinear_solver.problem().f.clone();
+        callable.set_h(h);
+        callable.set_phi_direct(state.y.clone());
+        let guess = state.dy.clone() * scale(h);
+        let mut x = guess.clone();
+        self.reset_jacobian(&x, t_mid);
+        if self.solve_nonlinear(&mut x, t_mid).is_err() {
+            self.statistics.number_of_nonlinear_solver_fails += 1;
+            callable.set_jacobian_is_stale();
+            x.copy_from(&guess);
+            self.reset_jacobian(&x, t_mid);
+            self.solve_nonlinear(&mut x, t_mid)?;
+        }
+
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t;
+        self.old_y.copy_from(&state.y);
+        state.y += &x;
+        state.dy.copy_from(&x);
+        state.dy *= scale(Eqn::T::one() / h);
+        state.t = match self.tstop {
+            Some(tstop) if is_at_stop_time(self.old_t + h, h, tstop) => tstop,
+            _ => self.old_t + h,
+        };
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = h;
+
+        // check for root within accepted step
+        if let Some(root_fn) = self.problem.as_ref().unwrap().eqn.root() {
+            let start = Instant::now();
+            let state = self.state.as_ref().unwrap();
+            let ret = self.root_finder.as_ref().unwrap().check_root(
+                &|t| self.interpolate(t),
+                root_fn.as_ref(),
+                &state.y,
+                state.t,
+            );
+            self.statistics.timings.root_finding += start.elapsed();
+            if let Some(root) = ret {
+                return Ok(OdeSolverStopReason::RootFound(root));
+            }
+        }
+
+        let state = self.state.as_ref().unwrap();
+        if let Some(tstop) = self.tstop {
+            if is_at_stop_time(state.t, h, tstop) {
+                self.tstop = None;
+                return Ok(OdeSolverStopReason::TstopReached);
+            }
+        }
+        Ok(OdeSolverStopReason::InternalTimestep)
+    }
+}
+
+impl<Eqn, LS> OdeSolverMethod<Eqn> for ImplicitMidpoint<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<Eqn::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<()> {
+        problem.check_not_implicit("ImplicitMidpoint")?;
+        problem.check_mass_not_state_dependent("ImplicitMidpoint")?;
+        let callable = Rc::new(SdirkCallable::new(problem, Eqn::T::from(0.5)));
+        callable.set_h(state.h);
+        let nonlinear_problem = SolverProblem::new_from_ode_problem(callable, problem);
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // the initial guess for the first step
+        state.dy = problem.eqn.rhs().call(&state.y, state.t);
+        self.old_t = state.t;
+        self.old_y = state.y.clone();
+        self.statistics = OdeSolverStatistics::default();
+        self.statistics.initial_step_size = state.h;
+        self.tstop = None;
+        self.root_finder = problem.eqn.root().map(|root_fn| {
+            let root_finder = RootFinder::new(root_fn.nout());
+            root_finder.init(root_fn.as_ref(), &state.y, state.t);
+            root_finder
+        });
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>> {
+        let start = Instant::now();
+        let ret = self._step();
+        self.statistics.timings.total += start.elapsed();
+        ret
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<()> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if tstop <= state.t || is_at_stop_time(state.t, state.h, tstop) {
+            return Err(anyhow!(
+                "Stop time is at or before current time t = {}",
+                state.t
+            ));
+        }
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V> {
+        let state = self.state.as_ref().ok_or(anyhow!("State not set"))?;
+        if t > state.t || t < self.old_t {
+            return Err(anyhow!("Interpolation time is not within the current step"));
+        }
+        let dt = state.t - self.old_t;
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t) / dt;
+        let mut y = self.old_y.clone();
+        y.axpy(theta, &state.y, Eqn::T::one() - theta);
+        Ok(y)
+    }
+
+    fn statistics(&self) -> OdeSolverStatistics<Eqn::T> {
+        let mut statistics = self.statistics.clone();
+        if let Some(problem) = self.problem.as_ref() {
+            statistics.equations = problem.eqn.statistics();
+        }
+        statistics
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::tests::{test_interpolate, test_no_set_problem, test_take_state, TestEqn},
+        NalgebraLU, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
+        Vector,
+    };
+
+    use super::{ImplicitMidpoint, Symplectic};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // H = p^2 / 2 + q^2 / 2, with q(0) = 1, p(0) = 0
+    fn harmonic_oscillator(h: f64) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        OdeBuilder::new()
+            .h0(h)
+            .rtol(1e-12)
+            .atol([1e-12])
+            .build_separable_hamiltonian::<M, _, _, _, _, _>(
+                |p: &V, _params: &V, _t, y: &mut V| y.copy_from(p),
+                |_p: &V, _params: &V, _t, v: &V, y: &mut V| y.copy_from(v),
+                |q: &V, _params: &V, _t, y: &mut V| y.copy_from(q),
+                |_q: &V, _params: &V, _t, v: &V, y: &mut V| y.copy_from(v),
+                |_params: &V, _t| (V::from_element(1, 1.0), V::from_element(1, 0.0)),
+            )
+            .unwrap()
+    }
+
+    // H = |p|^2 / 2 - 1 / |q|, with an elliptic orbit of eccentricity 0.5
+    fn kepler(h: f64) -> OdeSolverProblem<impl OdeEquations<T = f64, V = V, M = M>> {
+        OdeBuilder::new()
+            .h0(h)
+            .build_separable_hamiltonian::<M, _, _, _, _, _>(
+                |p: &V, _params: &V, _t, y: &mut V| y.copy_from(p),
+                |_p: &V, _params: &V, _t, v: &V, y: &mut V| y.copy_from(v),
+                |q: &V, _params: &V, _t, y: &mut V| {
+                    let r3 = (q[0] * q[0] + q[1] * q[1]).powf(1.5);
+                    y[0] = q[0] / r3;
+                    y[1] = q[1] / r3;
+                },
+                |q: &V, _params: &V, _t, v: &V, y: &mut V| {
+                    let r2 = q[0] * q[0] + q[1] * q[1];
+                    let r3 = r2.powf(1.5);
+                    let qv = q[0] * v[0] + q[1] * v[1];
+                    y[0] = v[0] / r3 - 3.0 * q[0] * qv / (r3 * r2);
+                    y[1] = v[1] / r3 - 3.0 * q[1] * qv / (r3 * r2);
+                },
+                |_params: &V, _t| {
+                    let e: f64 = 0.5;
+                    (
+                        V::from_vec(vec![1.0 - e, 0.0]),
+                        V::from_vec(vec![0.0, ((1.0 + e) / (1.0 - e)).sqrt()]),
+                    )
+                },
+            )
+            .unwrap()
+    }
+
+    fn harmonic_oscillator_energy(y: &V) -> f64 {
+        0.5 * (y[0] * y[0] + y[1] * y[1])
+    }
+
+    fn kepler_energy(y: &V) -> f64 {
+        0.5 * (y[2] * y[2] + y[3] * y[3]) - 1.0 / (y[0] * y[0] + y[1] * y[1]).sqrt()
+    }
+
+    fn harmonic_oscillator_error<Eqn: OdeEquations<T = f64, V = V, M = M>>(
+        problem: &OdeSolverProblem<Eqn>,
+        mut s: impl OdeSolverMethod<Eqn>,
+    ) -> f64 {
+        let y = s.solve(problem, 1.0).unwrap();
+        ((y[0] - 1.0f64.cos()).powi(2) + (y[1] + 1.0f64.sin()).powi(2)).sqrt()
+    }
+
+    fn observed_order<Eqn: OdeEquations<T = f64, V = V, M = M>>(
+        coarse: &OdeSolverProblem<Eqn>,
+        fine: &OdeSolverProblem<Eqn>,
+        method: impl Fn() -> Symplectic<Eqn>,
+    ) -> f64 {
+        let e_coarse = harmonic_oscillator_error(coarse, method());
+        let e_fine = harmonic_oscillator_error(fine, method());
+        (e_coarse / e_fine).log2()
+    }
+
+    #[test]
+    fn test_symplectic_convergence_order() {
+        let (coarse, fine) = (harmonic_oscillator(0.1), harmonic_oscillator(0.05));
+        for (method, order) in [
+            (Symplectic::velocity_verlet as fn() -> _, 2.0),
+            (Symplectic::yoshida4, 4.0),
+        ] {
+            let observed = observed_order(&coarse, &fine, method);
+            assert!((observed - order).abs() < 0.2, "{} {}", observed, order);
+        }
+        let (coarse, fine) = (harmonic_oscillator(0.25), harmonic_oscillator(0.125));
+        let observed = observed_order(&coarse, &fine, Symplectic::yoshida6);
+        assert!((observed - 6.0).abs() < 0.3, "{}", observed);
+        assert_eq!(Symplectic::<TestEqn<M>>::yoshida6().order(), 6);
+    }
+
+    #[test]
+    fn test_symplectic_kepler_energy() {
+        // 100 orbits of period 2 pi, the energy error is bounded rather than drifting
+        let problem = kepler(0.01);
+        let e0 = kepler_energy(&problem.eqn.init(0.0));
+        let mut s = Symplectic::yoshida4();
+        let final_time = 200.0 * std::f64::consts::PI;
+        let y = s.solve(&problem, final_time).unwrap();
+        let state = s.state().unwrap();
+        assert_eq!(state.t, final_time);
+        assert!((kepler_energy(&y) - e0).abs() < 1e-6);
+
+        // the orbit returns to the initial position
+        y.assert_eq_st(&problem.eqn.init(0.0), 1e-2);
+        assert_eq!(s.statistics().number_of_steps, 62832);
+    }
+
+    #[test]
+    fn test_symplectic_interpolate() {
+        let problem = harmonic_oscillator(0.1);
+        let mut s = Symplectic::velocity_verlet();
+        s.solve(&problem, 0.95).unwrap();
+        let t = s.state().unwrap().t;
+        assert_eq!(t, 0.95);
+        let y = s.interpolate(0.9).unwrap();
+        assert!((y[0] - 0.9f64.cos()).abs() < 1e-2);
+        assert!(s.interpolate(1.0).is_err());
+    }
+
+    #[test]
+    fn test_symplectic_non_separable() {
+        // damped oscillator q'' = -q - q', the force depends on the velocity
+        let problem = OdeBuilder::new()
+            .h0(0.1)
+            .build_second_order::<M, _, _, _>(
+                |q: &V, v: &V, _p: &V, _t, a: &mut V| a.copy_from(&(-q - v)),
+                |_q: &V, _v: &V, _p: &V, _t, dq: &V, dv: &V, y: &mut V| y.copy_from(&(-dq - dv)),
+                |_p: &V, _t| (V::from_element(1, 1.0), V::from_element(1, 0.0)),
+            )
+            .unwrap();
+        let mut s = Symplectic::velocity_verlet();
+        assert!(s
+            .set_problem(OdeSolverState::new(&problem), &problem)
+            .is_err());
+    }
+
+    #[test]
+    fn test_symplectic_mass() {
+        // 2 q'' = -q is separable, but has a mass matrix that is not the identity
+        let problem = OdeBuilder::new()
+            .h0(0.1)
+            .build_second_order_with_mass::<M, _, _, _, _>(
+                |q: &V, _v: &V, _p: &V, _t, a: &mut V| a.copy_from(&(-q)),
+                |_q: &V, _v: &V, _p: &V, _t, dq: &V, _dv: &V, y: &mut V| y.copy_from(&(-dq)),
+                |v: &V, _p: &V, _t, beta, y: &mut V| y.axpy(2.0, v, beta),
+                |_p: &V, _t| (V::from_element(1, 1.0), V::from_element(1, 0.0)),
+            )
+            .unwrap();
+        let mut s = Symplectic::velocity_verlet();
+        assert!(s
+            .set_problem(OdeSolverState::new(&problem), &problem)
+            .is_err());
+    }
+
+    #[test]
+    fn test_implicit_midpoint_energy() {
+        // implicit midpoint conserves quadratic invariants exactly
+        let problem = harmonic_oscillator(0.1);
+        let e0 = harmonic_oscillator_energy(&problem.eqn.init(0.0));
+        let mut s = ImplicitMidpoint::<_, NalgebraLU<f64, _>>::default();
+        let y = s.solve(&problem, 100.0).unwrap();
+        assert!((harmonic_oscillator_energy(&y) - e0).abs() < 1e-10);
+        assert!(
+            harmonic_oscillator_error(
+                &harmonic_oscillator(0.01),
+                ImplicitMidpoint::<_, NalgebraLU<f64, _>>::default()
+            ) < 1e-4
+        );
+        assert_eq!(s.statistics().number_of_steps, 1000);
+    }
+
+    #[test]
+    fn test_implicit_midpoint_no_set_problem() {
+        test_no_set_problem::<M, _>(ImplicitMidpoint::<_, NalgebraLU<f64, _>>::default())
+    }
+
+    #[test]
+    fn test_implicit_midpoint_take_state() {
+        test_take_state::<M, _>(ImplicitMidpoint::<_, NalgebraLU<f64, _>>::default())
+    }
+
+    #[test]
+    fn test_implicit_midpoint_interpolate() {
+        test_interpolate::<M, _>(ImplicitMidpoint::<_, NalgebraLU<f64, _>>::default())
+    }
+}
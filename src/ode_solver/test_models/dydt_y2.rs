@@ -1,6 +1,7 @@
+use super::ProblemAndSolution;
 use crate::{
     ode_solver::problem::OdeSolverSolution, scalar::scale, DenseMatrix, OdeBuilder, OdeEquations,
-    OdeSolverProblem, Vector,
+    Vector,
 };
 use num_traits::One;
 use std::ops::MulAssign;
@@ -21,10 +22,7 @@ fn rhs_jac<M: DenseMatrix>(x: &M::V, _p: &M::V, _t: M::T, v: &M::V, y: &mut M::V
 pub fn dydt_y2_problem<M: DenseMatrix + 'static>(
     use_coloring: bool,
     size: usize,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let size2 = size;
     let y0 = -200.;
     let tlast = 20.0;
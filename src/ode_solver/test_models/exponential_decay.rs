@@ -1,6 +1,7 @@
+use super::ProblemAndSolution;
 use crate::{
     matrix::Matrix, ode_solver::problem::OdeSolverSolution, scalar::scale, OdeBuilder,
-    OdeEquations, OdeSolverProblem, Vector,
+    OdeEquations, Vector,
 };
 use nalgebra::ComplexField;
 use num_traits::Zero;
@@ -29,10 +30,7 @@ fn exponential_decay_root<M: Matrix>(x: &M::V, _p: &M::V, _t: M::T, y: &mut M::V
 
 pub fn exponential_decay_problem<M: Matrix + 'static>(
     use_coloring: bool,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let problem = OdeBuilder::new()
         .p([0.1])
         .use_coloring(use_coloring)
@@ -53,12 +51,28 @@ pub fn exponential_decay_problem<M: Matrix + 'static>(
     (problem, soln)
 }
 
+pub fn exponential_decay_problem_no_jac<M: Matrix + 'static>(
+    use_coloring: bool,
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
+    let problem = OdeBuilder::new()
+        .p([0.1])
+        .use_coloring(use_coloring)
+        .build_ode_no_jac(exponential_decay::<M>, exponential_decay_init::<M>)
+        .unwrap();
+    let p = [M::T::from(0.1)];
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = M::T::from(i as f64);
+        let y0: M::V = problem.eqn.init(M::T::zero());
+        let y = y0 * scale(M::T::exp(-p[0] * t));
+        soln.push(y, t);
+    }
+    (problem, soln)
+}
+
 pub fn exponential_decay_problem_with_root<M: Matrix + 'static>(
     use_coloring: bool,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let problem = OdeBuilder::new()
         .p([0.1])
         .use_coloring(use_coloring)
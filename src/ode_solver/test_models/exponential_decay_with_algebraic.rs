@@ -1,6 +1,7 @@
+use super::ProblemAndSolution;
 use crate::{
     ode_solver::problem::OdeSolverSolution, scalar::scale, DenseMatrix, OdeBuilder, OdeEquations,
-    OdeSolverProblem, Vector,
+    Vector,
 };
 use nalgebra::ComplexField;
 use num_traits::One;
@@ -59,10 +60,7 @@ fn exponential_decay_with_algebraic_init<M: DenseMatrix>(_p: &M::V, _t: M::T) ->
 
 pub fn exponential_decay_with_algebraic_problem<M: DenseMatrix + 'static>(
     use_coloring: bool,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let p = M::V::from_vec(vec![0.1.into()]);
     let problem = OdeBuilder::new()
         .p([0.1])
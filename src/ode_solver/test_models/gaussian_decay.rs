@@ -1,5 +1,5 @@
+use super::ProblemAndSolution;
 use crate::ode_solver::problem::OdeSolverSolution;
-use crate::OdeSolverProblem;
 use crate::{scalar::scale, DenseMatrix, OdeBuilder, OdeEquations, Vector};
 use num_traits::Pow;
 use num_traits::Zero;
@@ -22,10 +22,7 @@ fn gaussian_decay_jacobian<M: DenseMatrix>(_x: &M::V, p: &M::V, t: M::T, v: &M::
 pub fn gaussian_decay_problem<M: DenseMatrix + 'static>(
     use_coloring: bool,
     size: usize,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let size2 = size;
     let problem = OdeBuilder::new()
         .p([0.1].repeat(size))
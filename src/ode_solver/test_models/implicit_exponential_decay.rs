@@ -0,0 +1,88 @@
+use super::ProblemAndSolution;
+use crate::{
+    ode_solver::problem::OdeSolverSolution, DenseMatrix, OdeBuilder, OdeEquations, Vector,
+};
+use nalgebra::ComplexField;
+use num_traits::Zero;
+
+// fully implicit DAE that is nonlinear in the time derivative, with an algebraic constraint
+// exp(dy/dt) - exp(-a y) = 0
+// exp(dz/dt) - exp(-b z) = 0
+// w - y = 0
+// the solution is y = y0 exp(-a t), z = z0 exp(-b t), w = y
+fn implicit_exponential_decay_residual<M: DenseMatrix>(
+    x: &M::V,
+    x_dot: &M::V,
+    p: &M::V,
+    _t: M::T,
+    y: &mut M::V,
+) {
+    y[0] = M::T::exp(x_dot[0]) - M::T::exp(-p[0] * x[0]);
+    y[1] = M::T::exp(x_dot[1]) - M::T::exp(-p[1] * x[1]);
+    y[2] = x[2] - x[0];
+}
+
+// dF/dy v = [a exp(-a y) v[0], b exp(-b z) v[1], v[2] - v[0]]
+fn implicit_exponential_decay_jacobian<M: DenseMatrix>(
+    x: &M::V,
+    _x_dot: &M::V,
+    p: &M::V,
+    _t: M::T,
+    v: &M::V,
+    y: &mut M::V,
+) {
+    y[0] = p[0] * M::T::exp(-p[0] * x[0]) * v[0];
+    y[1] = p[1] * M::T::exp(-p[1] * x[1]) * v[1];
+    y[2] = v[2] - v[0];
+}
+
+// dF/dy' v = [exp(dy/dt) v[0], exp(dz/dt) v[1], 0]
+fn implicit_exponential_decay_jacobian_dot<M: DenseMatrix>(
+    _x: &M::V,
+    x_dot: &M::V,
+    _p: &M::V,
+    _t: M::T,
+    v: &M::V,
+    y: &mut M::V,
+) {
+    y[0] = M::T::exp(x_dot[0]) * v[0];
+    y[1] = M::T::exp(x_dot[1]) * v[1];
+    y[2] = M::T::zero();
+}
+
+fn implicit_exponential_decay_init<M: DenseMatrix>(_p: &M::V, _t: M::T) -> M::V {
+    M::V::from_vec(vec![1.0.into(), 2.0.into(), 1.0.into()])
+}
+
+fn implicit_exponential_decay_init_dot<M: DenseMatrix>(p: &M::V, _t: M::T) -> M::V {
+    M::V::from_vec(vec![-p[0], -p[1] * M::T::from(2.0), -p[0]])
+}
+
+pub fn implicit_exponential_decay_problem<M: DenseMatrix + 'static>(
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
+    let p = [0.1, 0.2];
+    let problem = OdeBuilder::new()
+        .p(p)
+        .rtol(1e-6)
+        .atol([1e-6])
+        .build_implicit_dae(
+            implicit_exponential_decay_residual::<M>,
+            implicit_exponential_decay_jacobian::<M>,
+            implicit_exponential_decay_jacobian_dot::<M>,
+            implicit_exponential_decay_init::<M>,
+            implicit_exponential_decay_init_dot::<M>,
+        )
+        .unwrap();
+
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = i as f64;
+        let y = (-p[0] * t).exp();
+        let z = 2.0 * (-p[1] * t).exp();
+        soln.push(
+            M::V::from_vec(vec![y.into(), z.into(), y.into()]),
+            M::T::from(t),
+        );
+    }
+    (problem, soln)
+}
@@ -2,5 +2,15 @@ pub mod dydt_y2;
 pub mod exponential_decay;
 pub mod exponential_decay_with_algebraic;
 pub mod gaussian_decay;
+pub mod implicit_exponential_decay;
 pub mod robertson;
 pub mod robertson_ode;
+pub mod state_dependent_mass;
+
+use crate::{ode_solver::problem::OdeSolverSolution, OdeEquations, OdeSolverProblem};
+
+/// A test problem together with the reference solution it is checked against.
+pub type ProblemAndSolution<Eqn> = (
+    OdeSolverProblem<Eqn>,
+    OdeSolverSolution<<Eqn as OdeEquations>::V>,
+);
@@ -1,14 +1,11 @@
+use super::ProblemAndSolution;
 use crate::{
-    matrix::Matrix, ode_solver::problem::OdeSolverSolution, OdeBuilder, OdeEquations,
-    OdeSolverProblem, Vector,
+    matrix::Matrix, ode_solver::problem::OdeSolverSolution, OdeBuilder, OdeEquations, Vector,
 };
 
 pub fn robertson<M: Matrix + 'static>(
     use_coloring: bool,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let problem = OdeBuilder::new()
         .p([0.04, 1.0e4, 3.0e7])
         .rtol(1e-4)
@@ -41,6 +38,36 @@ pub fn robertson<M: Matrix + 'static>(
         )
         .unwrap();
 
+    (problem, robertson_solution())
+}
+
+pub fn robertson_no_jac<M: Matrix + 'static>(
+    use_coloring: bool,
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
+    let problem = OdeBuilder::new()
+        .p([0.04, 1.0e4, 3.0e7])
+        .rtol(1e-4)
+        .atol([1.0e-8, 1.0e-6, 1.0e-6])
+        .use_coloring(use_coloring)
+        .constant_mass(true)
+        .build_ode_with_mass_no_jac(
+            |x: &M::V, p: &M::V, _t: M::T, y: &mut M::V| {
+                y[0] = -p[0] * x[0] + p[1] * x[1] * x[2];
+                y[1] = p[0] * x[0] - p[1] * x[1] * x[2] - p[2] * x[1] * x[1];
+                y[2] = x[0] + x[1] + x[2] - M::T::from(1.0);
+            },
+            |x: &M::V, _p: &M::V, _t: M::T, beta: M::T, y: &mut M::V| {
+                y[0] = x[0] + beta * y[0];
+                y[1] = x[1] + beta * y[1];
+                y[2] = beta * y[2];
+            },
+            |_p: &M::V, _t: M::T| M::V::from_vec(vec![1.0.into(), 0.0.into(), 0.0.into()]),
+        )
+        .unwrap();
+    (problem, robertson_solution())
+}
+
+fn robertson_solution<V: Vector>() -> OdeSolverSolution<V> {
     let mut soln = OdeSolverSolution::default();
     let data = vec![
         (vec![1.0, 0.0, 0.0], 0.0),
@@ -60,12 +87,12 @@ pub fn robertson<M: Matrix + 'static>(
 
     for (values, time) in data {
         soln.push(
-            M::V::from_vec(values.into_iter().map(|v| v.into()).collect()),
+            V::from_vec(values.into_iter().map(|v| v.into()).collect()),
             time.into(),
         );
     }
 
-    (problem, soln)
+    soln
 }
 
 /* -----------------------------------------------------------------
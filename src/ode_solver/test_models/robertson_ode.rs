@@ -1,14 +1,12 @@
+use super::ProblemAndSolution;
 use crate::{
-    ode_solver::problem::OdeSolverSolution, Matrix, OdeBuilder, OdeEquations, OdeSolverProblem,
-    Vector,
+    ode_solver::problem::OdeSolverSolution, AutodiffFunction, AutodiffScalar, Matrix, OdeBuilder,
+    OdeEquations, Scalar, Vector,
 };
 
 pub fn robertson_ode<M: Matrix + 'static>(
     use_coloring: bool,
-) -> (
-    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
-    OdeSolverSolution<M::V>,
-) {
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
     let problem = OdeBuilder::new()
         .p([0.04, 1.0e4, 3.0e7])
         .rtol(1e-4)
@@ -35,6 +33,35 @@ pub fn robertson_ode<M: Matrix + 'static>(
         )
         .unwrap();
 
+    (problem, robertson_ode_solution())
+}
+
+struct RobertsonOde;
+
+impl<T: Scalar> AutodiffFunction<T> for RobertsonOde {
+    fn call<S: AutodiffScalar<T>>(&self, x: &[S], p: &[T], _t: T, y: &mut [S]) {
+        y[0] = x[0] * (-p[0]) + x[1] * x[2] * p[1];
+        y[1] = x[0] * p[0] - x[1] * x[2] * p[1] - x[1] * x[1] * p[2];
+        y[2] = x[1] * x[1] * p[2];
+    }
+}
+
+pub fn robertson_ode_autodiff<M: Matrix + 'static>(
+    use_coloring: bool,
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
+    let problem = OdeBuilder::new()
+        .p([0.04, 1.0e4, 3.0e7])
+        .rtol(1e-4)
+        .atol([1.0e-8, 1.0e-6, 1.0e-6])
+        .use_coloring(use_coloring)
+        .build_ode_autodiff(RobertsonOde, |_p: &M::V, _t: M::T| {
+            M::V::from_vec(vec![1.0.into(), 0.0.into(), 0.0.into()])
+        })
+        .unwrap();
+    (problem, robertson_ode_solution())
+}
+
+fn robertson_ode_solution<V: Vector>() -> OdeSolverSolution<V> {
     let mut soln = OdeSolverSolution::default();
     let data = vec![
         (vec![1.0, 0.0, 0.0], 0.0),
@@ -54,11 +81,11 @@ pub fn robertson_ode<M: Matrix + 'static>(
 
     for (values, time) in data {
         soln.push(
-            M::V::from_vec(values.into_iter().map(|v| v.into()).collect()),
+            V::from_vec(values.into_iter().map(|v| v.into()).collect()),
             time.into(),
         );
     }
-    (problem, soln)
+    soln
 }
 
 /* -----------------------------------------------------------------
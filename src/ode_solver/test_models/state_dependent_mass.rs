@@ -0,0 +1,93 @@
+use super::ProblemAndSolution;
+use crate::{
+    ode_solver::problem::OdeSolverSolution, DenseMatrix, OdeBuilder, OdeEquations, Vector,
+};
+use nalgebra::ComplexField;
+use num_traits::Zero;
+
+// problem with a state-dependent mass matrix and an algebraic constraint
+// exp(y) dy/dt = -a
+// exp(z) dz/dt = -b
+// 0 = w - y
+// the solution is y = ln(exp(y0) - a t), z = ln(exp(z0) - b t), w = y
+fn state_dependent_mass_rhs<M: DenseMatrix>(x: &M::V, p: &M::V, _t: M::T, y: &mut M::V) {
+    y[0] = -p[0];
+    y[1] = -p[1];
+    y[2] = x[2] - x[0];
+}
+
+// Jv = [0, 0, v[2] - v[0]]
+fn state_dependent_mass_rhs_jacobian<M: DenseMatrix>(
+    _x: &M::V,
+    _p: &M::V,
+    _t: M::T,
+    v: &M::V,
+    y: &mut M::V,
+) {
+    y[0] = M::T::zero();
+    y[1] = M::T::zero();
+    y[2] = v[2] - v[0];
+}
+
+// y = M(x) v + beta * y = | exp(x[0]) 0         0 | | v[0] | + beta | y[0] |
+//                         | 0         exp(x[1]) 0 | | v[1] |        | y[1] |
+//                         | 0         0         0 | | v[2] |        | y[2] |
+fn state_dependent_mass<M: DenseMatrix>(
+    v: &M::V,
+    x: &M::V,
+    _p: &M::V,
+    _t: M::T,
+    beta: M::T,
+    y: &mut M::V,
+) {
+    y[0] = M::T::exp(x[0]) * v[0] + beta * y[0];
+    y[1] = M::T::exp(x[1]) * v[1] + beta * y[1];
+    y[2] = beta * y[2];
+}
+
+// y = d(M(x) v)/dx w = [exp(x[0]) v[0] w[0], exp(x[1]) v[1] w[1], 0]
+fn state_dependent_mass_jacobian<M: DenseMatrix>(
+    v: &M::V,
+    x: &M::V,
+    _p: &M::V,
+    _t: M::T,
+    w: &M::V,
+    y: &mut M::V,
+) {
+    y[0] = M::T::exp(x[0]) * v[0] * w[0];
+    y[1] = M::T::exp(x[1]) * v[1] * w[1];
+    y[2] = M::T::zero();
+}
+
+// the algebraic variable is not consistent with the constraint
+fn state_dependent_mass_init<M: DenseMatrix>(_p: &M::V, _t: M::T) -> M::V {
+    M::V::from_vec(vec![1.0.into(), 2.0.into(), 0.0.into()])
+}
+
+pub fn state_dependent_mass_problem<M: DenseMatrix + 'static>(
+) -> ProblemAndSolution<impl OdeEquations<M = M, V = M::V, T = M::T>> {
+    let problem = OdeBuilder::new()
+        .p([0.1, 0.2])
+        .rtol(1e-6)
+        .atol([1e-6])
+        .build_ode_with_state_mass(
+            state_dependent_mass_rhs::<M>,
+            state_dependent_mass_rhs_jacobian::<M>,
+            state_dependent_mass::<M>,
+            state_dependent_mass_jacobian::<M>,
+            state_dependent_mass_init::<M>,
+        )
+        .unwrap();
+
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = i as f64;
+        let y = (1.0f64.exp() - 0.1 * t).ln();
+        let z = (2.0f64.exp() - 0.2 * t).ln();
+        soln.push(
+            M::V::from_vec(vec![y.into(), z.into(), y.into()]),
+            M::T::from(t),
+        );
+    }
+    (problem, soln)
+}
@@ -0,0 +1,207 @@
+// callable for the pseudo-arclength continuation of the steady states of an ODE or DAE in one of its parameters,
+// the unknowns are u = (y, p) and the residual is H(u) = (F(y, p), tangent . (u - u0) - ds)
+
+use std::cell::RefCell;
+
+use anyhow::Result;
+use num_traits::{One, Pow, Zero};
+
+use crate::{
+    ode_solver::equations::OdeEquations, scale, LinearOp, NonLinearOp, OdeSolverProblem, Op,
+    Scalar, Vector,
+};
+
+/// Callable used for the pseudo-arclength continuation of steady states (see [crate::continuation]).
+///
+/// The unknowns are the state and the continuation parameter `u = (y, p)` (so the callable has one more state than the equations), and the
+/// residual is `H(u) = (F(y, p), tangent . (u - u0) - ds)`, where `F` is the steady state residual of [crate::op::steady_state::SteadyStateCallable],
+/// and the last equation restricts the solution to the hyperplane a distance `ds` along the `tangent` from the previous point `u0`.
+///
+/// The callable owns the problem so that it can set the parameters of the equations using [OdeSolverProblem::set_params], the derivative of `F`
+/// with respect to the parameter is calculated using finite differences.
+pub struct ArclengthCallable<Eqn: OdeEquations> {
+    problem: RefCell<OdeSolverProblem<Eqn>>,
+    params: RefCell<Eqn::V>,
+    param_index: usize,
+    nstates: usize,
+    zeros: Eqn::V,
+    u0: RefCell<Eqn::V>,
+    tangent: RefCell<Eqn::V>,
+    ds: RefCell<Eqn::T>,
+    y: RefCell<Eqn::V>,
+    v_y: RefCell<Eqn::V>,
+    tmp: RefCell<Eqn::V>,
+}
+
+impl<Eqn: OdeEquations> ArclengthCallable<Eqn> {
+    /// Create the callable for continuation in the parameter `params[param_index]`, this sets the parameters of the problem to `params`.
+    /// Fails if the parameters of the problem cannot be set (e.g. if a solver has been created with the problem).
+    pub fn new(
+        mut problem: OdeSolverProblem<Eqn>,
+        params: Eqn::V,
+        param_index: usize,
+    ) -> Result<Self> {
+        assert!(param_index < params.len(), "parameter index out of bounds");
+        problem.set_params(params.clone())?;
+        let nstates = problem.eqn.rhs().nstates();
+        Ok(Self {
+            problem: RefCell::new(problem),
+            params: RefCell::new(params),
+            param_index,
+            nstates,
+            zeros: Eqn::V::zeros(nstates),
+            u0: RefCell::new(Eqn::V::zeros(nstates + 1)),
+            tangent: RefCell::new(Eqn::V::zeros(nstates + 1)),
+            ds: RefCell::new(Eqn::T::zero()),
+            y: RefCell::new(Eqn::V::zeros(nstates)),
+            v_y: RefCell::new(Eqn::V::zeros(nstates)),
+            tmp: RefCell::new(Eqn::V::zeros(nstates)),
+        })
+    }
+
+    /// The problem, with the parameters set to the last parameter value used.
+    pub fn problem(&self) -> std::cell::Ref<'_, OdeSolverProblem<Eqn>> {
+        self.problem.borrow()
+    }
+
+    /// Set the previous point `u0`, the unit `tangent` at that point and the arclength step `ds`.
+    pub fn set_step(&self, u0: &Eqn::V, tangent: &Eqn::V, ds: Eqn::T) {
+        self.u0.borrow_mut().copy_from(u0);
+        self.tangent.borrow_mut().copy_from(tangent);
+        self.ds.replace(ds);
+    }
+
+    /// Pack the state `y` and parameter `p` into the unknowns `u`.
+    pub fn pack(&self, y: &Eqn::V, p: Eqn::T) -> Eqn::V {
+        let mut u = Eqn::V::zeros(self.nstates + 1);
+        for i in 0..self.nstates {
+            u[i] = y[i];
+        }
+        u[self.nstates] = p;
+        u
+    }
+
+    /// Unpack the unknowns `u` into the state `y`, and return the parameter.
+    pub fn unpack(&self, u: &Eqn::V, y: &mut Eqn::V) -> Eqn::T {
+        for i in 0..self.nstates {
+            y[i] = u[i];
+        }
+        u[self.nstates]
+    }
+
+    fn set_param(&self, p: Eqn::T) {
+        let mut params = self.params.borrow_mut();
+        if params[self.param_index] != p {
+            params[self.param_index] = p;
+            self.problem
+                .borrow_mut()
+                .set_params(params.clone())
+                .expect("ArclengthCallable: failed to set parameters");
+        }
+    }
+
+    /// The steady state residual `F(y, p)`.
+    pub fn residual_inplace(&self, y: &Eqn::V, p: Eqn::T, out: &mut Eqn::V) {
+        self.set_param(p);
+        let problem = self.problem.borrow();
+        let t = problem.t0;
+        if let Some(residual) = problem.eqn.residual() {
+            residual.call_inplace(y, &self.zeros, t, out);
+        } else {
+            problem.eqn.rhs().call_inplace(y, t, out);
+            *out *= scale(-Eqn::T::one());
+        }
+    }
+
+    /// The jacobian of the steady state residual with respect to the state multiplied by a vector, `dF/dy v`.
+    pub fn state_jac_mul_inplace(&self, y: &Eqn::V, p: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        self.set_param(p);
+        let problem = self.problem.borrow();
+        let t = problem.t0;
+        if let Some(residual) = problem.eqn.residual() {
+            residual.jac_mul_inplace(y, &self.zeros, t, v, out);
+        } else {
+            problem.eqn.rhs().jac_mul_inplace(y, t, v, out);
+            *out *= scale(-Eqn::T::one());
+        }
+    }
+
+    /// The derivative of the steady state residual with respect to the parameter `dF/dp`, calculated using forward differences.
+    pub fn param_derivative_inplace(&self, y: &Eqn::V, p: Eqn::T, out: &mut Eqn::V) {
+        let h = Eqn::T::EPSILON.pow(Eqn::T::from(0.5)) * (Eqn::T::one() + num_traits::abs(p));
+        let mut tmp = self.tmp.borrow_mut();
+        self.residual_inplace(y, p + h, &mut tmp);
+        self.residual_inplace(y, p, out);
+        out.axpy(Eqn::T::one() / h, &tmp, -Eqn::T::one() / h);
+    }
+
+    /// The mass matrix multiplied by a vector, `M v` (or `dG/dy' v` for a fully implicit DAE), so that the linearisation of the equations about the
+    /// steady state is `M v' = -dF/dy v`.
+    pub fn mass_mul_inplace(&self, y: &Eqn::V, p: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        self.set_param(p);
+        let problem = self.problem.borrow();
+        let t = problem.t0;
+        if let Some(residual) = problem.eqn.residual() {
+            residual.jac_dot_mul_inplace(y, &self.zeros, t, v, out);
+        } else if let Some(state_mass) = problem.eqn.state_mass() {
+            state_mass.gemv_inplace(y, t, v, Eqn::T::zero(), out);
+        } else {
+            problem.eqn.mass().gemv_inplace(v, t, Eqn::T::zero(), out);
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> Op for ArclengthCallable<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.nstates + 1
+    }
+    fn nout(&self) -> usize {
+        self.nstates + 1
+    }
+    fn nparams(&self) -> usize {
+        0
+    }
+}
+
+impl<Eqn: OdeEquations> NonLinearOp for ArclengthCallable<Eqn> {
+    // H(u) = (F(y, p), tangent . (u - u0) - ds)
+    fn call_inplace(&self, x: &Eqn::V, _t: Eqn::T, out: &mut Eqn::V) {
+        let mut y = self.y.borrow_mut();
+        let p = self.unpack(x, &mut y);
+        let mut f = self.tmp.borrow_mut();
+        self.residual_inplace(&y, p, &mut f);
+        for i in 0..self.nstates {
+            out[i] = f[i];
+        }
+        let tangent = self.tangent.borrow();
+        let u0 = self.u0.borrow();
+        let arclength = tangent.binary_fold(x, Eqn::T::zero(), |acc, t_i, x_i, i| {
+            acc + t_i * (x_i - u0[i])
+        });
+        out[self.nstates] = arclength - *self.ds.borrow();
+    }
+
+    // dH/du v = (dF/dy v_y + dF/dp v_p, tangent . v)
+    fn jac_mul_inplace(&self, x: &Eqn::V, _t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        let mut y = self.y.borrow_mut();
+        let p = self.unpack(x, &mut y);
+        let mut v_y = self.v_y.borrow_mut();
+        let v_p = self.unpack(v, &mut v_y);
+        let mut jv = Eqn::V::zeros(self.nstates);
+        self.state_jac_mul_inplace(&y, p, &v_y, &mut jv);
+        if v_p != Eqn::T::zero() {
+            let mut f_p = Eqn::V::zeros(self.nstates);
+            self.param_derivative_inplace(&y, p, &mut f_p);
+            jv.axpy(v_p, &f_p, Eqn::T::one());
+        }
+        for i in 0..self.nstates {
+            out[i] = jv[i];
+        }
+        let tangent = self.tangent.borrow();
+        out[self.nstates] =
+            tangent.binary_fold(v, Eqn::T::zero(), |acc, t_i, v_i, _| acc + t_i * v_i);
+    }
+}
@@ -1,27 +1,30 @@
 use crate::{
-    matrix::MatrixRef, ode_solver::equations::OdeEquations, LinearOp, Matrix, MatrixSparsity,
+    ode_solver::equations::OdeEquations, scale, IndexType, LinearOp, Matrix, MatrixSparsity,
     OdeSolverProblem, Vector, VectorRef,
 };
-use num_traits::Zero;
+use num_traits::{One, Zero};
 use std::{
-    cell::RefCell,
-    ops::{AddAssign, Deref, SubAssign},
+    cell::{RefCell, RefMut},
+    ops::{AddAssign, Deref, MulAssign, SubAssign},
     rc::Rc,
 };
 
 use super::{NonLinearOp, Op};
 
-// callable to solve for F(y) = M (y' + psi) - c * f(y) = 0
+// callable to solve for F(y) = M (y' + psi) - c * f(y) = 0, or for a fully implicit DAE G(t, y, y') = 0
+// the residual F(y) = G(t, y, (y - y0 + psi) / c) = 0
 pub struct BdfCallable<Eqn: OdeEquations> {
     eqn: Rc<Eqn>,
     psi_neg_y0: RefCell<Eqn::V>,
     c: RefCell<Eqn::T>,
     tmp: RefCell<Eqn::V>,
+    mass_state_jac_mul: RefCell<Eqn::V>,
     rhs_jac: RefCell<Eqn::M>,
     mass_jac: RefCell<Eqn::M>,
     jacobian_is_stale: RefCell<bool>,
     number_of_jac_evals: RefCell<usize>,
     sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+    jacobian_index: Option<(Vec<IndexType>, Vec<IndexType>)>,
 }
 
 impl<Eqn: OdeEquations> BdfCallable<Eqn> {
@@ -33,15 +36,25 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
         let jacobian_is_stale = RefCell::new(true);
         let number_of_jac_evals = RefCell::new(0);
         let tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
+        let mass_state_jac_mul = RefCell::new(<Eqn::V as Vector>::zeros(n));
 
         // create the mass and rhs jacobians according to the sparsity pattern
         let mass_sparsity = eqn.mass().sparsity();
         let rhs_jac_sparsity = eqn.rhs().sparsity();
         let rhs_jac = RefCell::new(Eqn::M::new_from_sparsity(n, n, rhs_jac_sparsity));
-        let sparsity = if let Some(rhs_jac_sparsity) = rhs_jac_sparsity {
-            mass_sparsity.map(|mass_sparsity| mass_sparsity.union(rhs_jac_sparsity).unwrap())
-        } else {
-            None
+        // the jacobian has the union of the mass and rhs jacobian sparsity, precompute where the
+        // non-zeros of each are in the union so the jacobian can be formed in a single pass
+        // (a state-dependent mass has no sparsity, so the jacobian is dense)
+        let (sparsity, jacobian_index) = match (mass_sparsity, rhs_jac_sparsity) {
+            _ if eqn.is_mass_state_dependent() => (None, None),
+            // a fully implicit DAE has the sparsity of the residual jacobian
+            _ if eqn.is_implicit() => (eqn.residual().unwrap().sparsity().cloned(), None),
+            (Some(mass_sparsity), Some(rhs_jac_sparsity)) => {
+                let (sparsity, mass_index, rhs_index) =
+                    mass_sparsity.union_with_index(rhs_jac_sparsity).unwrap();
+                (Some(sparsity), Some((mass_index, rhs_index)))
+            }
+            _ => (None, None),
         };
 
         // if mass is constant then pre-compute it
@@ -60,7 +73,9 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
             jacobian_is_stale,
             number_of_jac_evals,
             tmp,
+            mass_state_jac_mul,
             sparsity,
+            jacobian_index,
         }
     }
 
@@ -77,10 +92,7 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
     pub fn number_of_jac_evals(&self) -> usize {
         *self.number_of_jac_evals.borrow()
     }
-    pub fn set_c(&self, h: Eqn::T, alpha: Eqn::T)
-    where
-        for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
-    {
+    pub fn set_c(&self, h: Eqn::T, alpha: Eqn::T) {
         self.c.replace(h * alpha);
     }
     pub fn set_psi_and_y0(&self, psi: Eqn::V, y0: &Eqn::V) {
@@ -93,6 +105,25 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
     pub fn set_jacobian_is_stale(&self) {
         self.jacobian_is_stale.replace(true);
     }
+
+    // the time derivative y' = (y - y0 + psi) / c implied by the BDF formula
+    fn x_dot(&self, x: &Eqn::V) -> RefMut<'_, Eqn::V> {
+        let mut tmp = self.tmp.borrow_mut();
+        tmp.copy_from(x);
+        tmp.add_assign(self.psi_neg_y0.borrow().deref());
+        let c = *self.c.borrow().deref();
+        tmp.mul_assign(scale(Eqn::T::one() / c));
+        tmp
+    }
+
+    // y = mass_jac + beta * rhs_jac
+    fn assign_jacobian(&self, y: &mut Eqn::M, mass_jac: &Eqn::M, beta: Eqn::T, rhs_jac: &Eqn::M) {
+        if let Some((mass_index, rhs_index)) = self.jacobian_index.as_ref() {
+            y.scale_add_and_assign_mapped(mass_jac, mass_index, beta, rhs_jac, rhs_index);
+        } else {
+            y.scale_add_and_assign(mass_jac, beta, rhs_jac);
+        }
+    }
 }
 
 impl<Eqn: OdeEquations> Op for BdfCallable<Eqn> {
@@ -117,43 +148,81 @@ impl<Eqn: OdeEquations> Op for BdfCallable<Eqn> {
 impl<Eqn: OdeEquations> NonLinearOp for BdfCallable<Eqn>
 where
     for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
-    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
 {
     // F(y) = M (y - y0 + psi) - c * f(y) = 0
     fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
         let psi_neg_y0_ref = self.psi_neg_y0.borrow();
         let psi_neg_y0 = psi_neg_y0_ref.deref();
 
+        if let Some(residual) = self.eqn.residual() {
+            // F(y) = G(t, y, (y - y0 + psi) / c)
+            let x_dot = self.x_dot(x);
+            residual.call_inplace(x, &x_dot, t, y);
+            return;
+        }
+
         self.eqn.rhs().call_inplace(x, t, y);
 
         let mut tmp = self.tmp.borrow_mut();
         tmp.copy_from(x);
         tmp.add_assign(psi_neg_y0);
         let c = *self.c.borrow().deref();
-        // y = M tmp - c * y
-        self.eqn.mass().gemv_inplace(&tmp, t, -c, y);
+        // y = M(y) tmp - c * y
+        if let Some(state_mass) = self.eqn.state_mass() {
+            state_mass.gemv_inplace(x, t, &tmp, -c, y);
+        } else {
+            self.eqn.mass().gemv_inplace(&tmp, t, -c, y);
+        }
     }
-    // (M - c * f'(y)) v
+    // (M - c * f'(y)) v, plus d(M(y) (y - y0 + psi))/dy v if the mass depends on the state
+    // or (dG/dy + 1/c dG/dy') v for a fully implicit DAE
     fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        if let Some(residual) = self.eqn.residual() {
+            let x_dot = self.x_dot(x);
+            let c = *self.c.borrow().deref();
+            residual.jac_mul_inplace(x, &x_dot, t, v, y);
+            let mut jac_dot_mul = self.mass_state_jac_mul.borrow_mut();
+            residual.jac_dot_mul_inplace(x, &x_dot, t, v, &mut jac_dot_mul);
+            y.axpy(Eqn::T::one() / c, &jac_dot_mul, Eqn::T::one());
+            return;
+        }
         self.eqn.rhs().jac_mul_inplace(x, t, v, y);
         let c = *self.c.borrow().deref();
         // y = Mv - c y
-        self.eqn.mass().gemv_inplace(v, t, -c, y);
+        if let Some(state_mass) = self.eqn.state_mass() {
+            state_mass.gemv_inplace(x, t, v, -c, y);
+            let mut tmp = self.tmp.borrow_mut();
+            tmp.copy_from(x);
+            tmp.add_assign(self.psi_neg_y0.borrow().deref());
+            let mut mass_state_jac_mul = self.mass_state_jac_mul.borrow_mut();
+            state_mass.jac_mul_inplace(x, t, &tmp, v, &mut mass_state_jac_mul);
+            y.add_assign(mass_state_jac_mul.deref());
+        } else {
+            self.eqn.mass().gemv_inplace(v, t, -c, y);
+        }
     }
 
     fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
-        if *self.jacobian_is_stale.borrow() {
+        if let Some(residual) = self.eqn.residual() {
+            // the jacobian of the residual depends on c, so is recalculated each time
+            let x_dot = self.x_dot(x);
+            let c = *self.c.borrow().deref();
+            residual.jacobian_inplace(x, &x_dot, t, Eqn::T::one() / c, y);
+        } else if self.eqn.is_mass_state_dependent() {
+            // the mass matrix and its derivative change with the state, so calculate the jacobian column by column
+            self._default_jacobian_inplace(x, t, y);
+        } else if *self.jacobian_is_stale.borrow() {
             // calculate the mass and rhs jacobians
             let mut rhs_jac = self.rhs_jac.borrow_mut();
             self.eqn.rhs().jacobian_inplace(x, t, &mut rhs_jac);
             let c = *self.c.borrow().deref();
             if self.eqn.is_mass_constant() {
                 let mass_jac = self.mass_jac.borrow();
-                y.scale_add_and_assign(mass_jac.deref(), -c, rhs_jac.deref());
+                self.assign_jacobian(y, mass_jac.deref(), -c, rhs_jac.deref());
             } else {
                 let mut mass_jac = self.mass_jac.borrow_mut();
                 self.eqn.mass().matrix_inplace(t, &mut mass_jac);
-                y.scale_add_and_assign(mass_jac.deref(), -c, rhs_jac.deref());
+                self.assign_jacobian(y, mass_jac.deref(), -c, rhs_jac.deref());
             }
             self.jacobian_is_stale.replace(false);
         } else {
@@ -161,7 +230,7 @@ where
             let rhs_jac = self.rhs_jac.borrow();
             let mass_jac = self.mass_jac.borrow();
             let c = *self.c.borrow().deref();
-            y.scale_add_and_assign(mass_jac.deref(), -c, rhs_jac.deref());
+            self.assign_jacobian(y, mass_jac.deref(), -c, rhs_jac.deref());
         }
         let number_of_jac_evals = *self.number_of_jac_evals.borrow() + 1;
         self.number_of_jac_evals.replace(number_of_jac_evals);
@@ -170,14 +239,92 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::jacobian::check::check_jacobian;
     use crate::ode_solver::test_models::exponential_decay::exponential_decay_problem;
-    use crate::op::NonLinearOp;
+    use crate::ode_solver::test_models::implicit_exponential_decay::implicit_exponential_decay_problem;
+    use crate::ode_solver::test_models::robertson::robertson;
+    use crate::ode_solver::test_models::state_dependent_mass::state_dependent_mass_problem;
+    use crate::op::{NonLinearOp, Op};
     use crate::vector::Vector;
+    use crate::{Matrix, OdeEquations, OdeSolverProblem};
 
     use super::BdfCallable;
     type Mcpu = nalgebra::DMatrix<f64>;
+    type Msparse = nalgebra_sparse::CscMatrix<f64>;
     type Vcpu = nalgebra::DVector<f64>;
 
+    fn check_sparse_jacobian<Eqn, EqnDense>(
+        problem: &OdeSolverProblem<Eqn>,
+        problem_dense: &OdeSolverProblem<EqnDense>,
+    ) where
+        Eqn: OdeEquations<M = Msparse, V = Vcpu, T = f64>,
+        EqnDense: OdeEquations<M = Mcpu, V = Vcpu, T = f64>,
+    {
+        let n = problem.eqn.rhs().nstates();
+        let psi_neg_y0 = Vcpu::from_element(n, 1.1);
+        let y = Vcpu::from_element(n, 1.2);
+        let t = 0.9;
+        let mut bdf_callable = BdfCallable::new(problem);
+        let mut bdf_callable_dense = BdfCallable::new(problem_dense);
+        bdf_callable.set_psi_neg_y0_direct(psi_neg_y0.clone());
+        bdf_callable_dense.set_psi_neg_y0_direct(psi_neg_y0);
+        let mut jac = Msparse::new_from_sparsity(n, n, bdf_callable.sparsity());
+        // check a stale and a non-stale jacobian (only c has changed)
+        for c in [0.1, 0.3] {
+            bdf_callable.set_c_direct(c);
+            bdf_callable_dense.set_c_direct(c);
+            bdf_callable.jacobian_inplace(&y, t, &mut jac);
+            let jac_dense = bdf_callable_dense.jacobian(&y, t);
+            let mut v = Vcpu::zeros(n);
+            for j in 0..n {
+                v[j] = 1.0;
+                let mut col = Vcpu::zeros(n);
+                let mut col_dense = Vcpu::zeros(n);
+                jac.gemv(1.0, &v, 0.0, &mut col);
+                jac_dense.gemv(1.0, &v, 0.0, &mut col_dense);
+                col.assert_eq_st(&col_dense, 1e-10);
+                v[j] = 0.0;
+            }
+        }
+    }
+
+    #[test]
+    fn test_bdf_sparse_jacobian() {
+        // mass and rhs jacobian have different sparsity
+        let (problem, _soln) = robertson::<Msparse>(true);
+        let (problem_dense, _soln) = robertson::<Mcpu>(true);
+        assert_eq!(problem.eqn.mass().sparsity().unwrap().nnz(), 2);
+        assert_eq!(BdfCallable::new(&problem).sparsity().unwrap().nnz(), 9);
+        check_sparse_jacobian(&problem, &problem_dense);
+
+        // unit mass matrix
+        let (problem, _soln) = exponential_decay_problem::<Msparse>(true);
+        let (problem_dense, _soln) = exponential_decay_problem::<Mcpu>(true);
+        check_sparse_jacobian(&problem, &problem_dense);
+    }
+
+    #[test]
+    fn test_bdf_state_dependent_mass_jacobian() {
+        let (problem, _soln) = state_dependent_mass_problem::<Mcpu>();
+        let mut bdf_callable = BdfCallable::new(&problem);
+        bdf_callable.set_c_direct(0.1);
+        bdf_callable.set_psi_neg_y0_direct(Vcpu::from_vec(vec![0.3, -0.2, 0.1]));
+        let y = Vcpu::from_vec(vec![1.1, 1.9, 1.0]);
+        let check = check_jacobian(&bdf_callable, &y, 0.5);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+    }
+
+    #[test]
+    fn test_bdf_implicit_dae_jacobian() {
+        let (problem, _soln) = implicit_exponential_decay_problem::<Mcpu>();
+        let mut bdf_callable = BdfCallable::new(&problem);
+        bdf_callable.set_c_direct(0.1);
+        bdf_callable.set_psi_neg_y0_direct(Vcpu::from_vec(vec![0.3, -0.2, 0.1]));
+        let y = Vcpu::from_vec(vec![1.1, 1.9, 1.0]);
+        let check = check_jacobian(&bdf_callable, &y, 0.5);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+    }
+
     #[test]
     fn test_bdf_callable() {
         let (problem, _soln) = exponential_decay_problem::<Mcpu>(false);
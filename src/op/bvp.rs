@@ -0,0 +1,428 @@
+// callables for solving a two-point boundary value problem y' = f(t, y), g(y(a), y(b)) = 0 on a mesh a = t_0 < t_1 < ... < t_{N-1} = b,
+// the unknowns are the states at the mesh points x = (y_0, ..., y_{N-1}) and the residual is
+// H(x) = (r_0(y_0, y_1), ..., r_{N-2}(y_{N-2}, y_{N-1}), g(y_0, y_{N-1})), where r_k is the residual on the segment [t_k, t_{k+1}]
+
+use anyhow::Result;
+use num_traits::{One, Pow, Zero};
+
+use crate::{
+    jacobian::{ColoringAlgorithm, JacobianColoring},
+    ode_solver::equations::OdeEquations,
+    Matrix, MatrixSparsity, NonLinearOp, OdeSolverMethod, OdeSolverProblem, Op, Scalar, Vector,
+};
+
+use super::shooting::ShootingSolver;
+
+// the non-zeros of the jacobian of H with n states and nnodes mesh points, the residual on each segment depends on the states at both
+// ends of the segment, and the boundary conditions depend on the first and last states
+fn bvp_non_zeros(n: usize, nnodes: usize) -> Vec<(usize, usize)> {
+    let mut non_zeros = Vec::with_capacity(2 * n * n * nnodes);
+    for k in 0..nnodes - 1 {
+        for i in 0..n {
+            for j in 0..2 * n {
+                non_zeros.push((k * n + i, k * n + j));
+            }
+        }
+    }
+    let bc = (nnodes - 1) * n;
+    for i in 0..n {
+        for j in 0..n {
+            non_zeros.push((bc + i, j));
+        }
+        for j in 0..n {
+            non_zeros.push((bc + i, bc + j));
+        }
+    }
+    non_zeros
+}
+
+// the state at mesh point k
+fn get_node<V: Vector>(x: &V, k: usize, n: usize) -> V {
+    let mut y = V::zeros(n);
+    for i in 0..n {
+        y[i] = x[k * n + i];
+    }
+    y
+}
+
+// set the residual on segment k (or the boundary conditions for k = N - 1)
+fn set_node<V: Vector>(out: &mut V, k: usize, y: &V) {
+    let n = y.len();
+    for i in 0..n {
+        out[k * n + i] = y[i];
+    }
+}
+
+// the boundary conditions g(y_0, y_{N-1}) if v is None, or their jacobian multiplied by v
+fn boundary_inplace<G: NonLinearOp>(
+    bc: &G,
+    x: &G::V,
+    v: Option<&G::V>,
+    n: usize,
+    nnodes: usize,
+    t: G::T,
+    out: &mut G::V,
+) {
+    let ends = |x: &G::V| {
+        let mut ya_yb = G::V::zeros(2 * n);
+        for i in 0..n {
+            ya_yb[i] = x[i];
+            ya_yb[n + i] = x[(nnodes - 1) * n + i];
+        }
+        ya_yb
+    };
+    let mut g = G::V::zeros(n);
+    match v {
+        None => bc.call_inplace(&ends(x), t, &mut g),
+        Some(v) => bc.jac_mul_inplace(&ends(x), t, &ends(v), &mut g),
+    }
+    set_node(out, nnodes - 1, &g);
+}
+
+fn check_boundary<G: NonLinearOp>(bc: &G, n: usize) {
+    assert_eq!(
+        bc.nstates(),
+        2 * n,
+        "boundary conditions should take the states at both ends of the interval"
+    );
+    assert_eq!(
+        bc.nout(),
+        n,
+        "there should be one boundary condition per state"
+    );
+}
+
+/// Callable used to solve a two-point boundary value problem `y' = f(t, y)`, `g(y(a), y(b)) = 0` by multiple shooting (see [crate::bvp_shooting]).
+///
+/// The unknowns are the states at the mesh points `x = (y_0, ..., y_{N-1})`, and the residual is `H(x) = (y(t_1; t_0, y_0) - y_1, ...,
+/// y(t_{N-1}; t_{N-2}, y_{N-2}) - y_{N-1}, g(y_0, y_{N-1}))`, where `y(t_{k+1}; t_k, y_k)` is the solution at `t_{k+1}` starting from `y_k` at `t_k`
+/// found using the given ODE solver. The boundary conditions `g` are an operator with `2n` states `(y(a), y(b))` and `n` outputs.
+///
+/// The jacobian of the solution on each segment is applied to a vector using a forward difference along that vector, and the jacobian of `H` is
+/// calculated from its (block bidiagonal) sparsity pattern using colouring.
+pub struct MultipleShootingCallable<'a, Eqn: OdeEquations, G, S: OdeSolverMethod<Eqn>> {
+    // caches the solution at the end of each segment for the unknowns x
+    solver: ShootingSolver<'a, Eqn, S, Vec<Eqn::V>>,
+    bc: &'a G,
+    mesh: Vec<Eqn::T>,
+    nstates: usize,
+    sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+    coloring: Option<JacobianColoring<Eqn::M>>,
+}
+
+impl<'a, Eqn, G, S> MultipleShootingCallable<'a, Eqn, G, S>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    S: OdeSolverMethod<Eqn>,
+{
+    /// Create the callable for the given `mesh` (which should be increasing, with at least two points) and boundary conditions `bc`.
+    pub fn new(
+        solver: &'a mut S,
+        problem: &'a OdeSolverProblem<Eqn>,
+        bc: &'a G,
+        mesh: Vec<Eqn::T>,
+    ) -> Self {
+        let nstates = problem.eqn.rhs().nstates();
+        check_boundary(bc, nstates);
+        let mut ret = Self {
+            solver: ShootingSolver::new(solver, problem),
+            bc,
+            mesh,
+            nstates,
+            sparsity: None,
+            coloring: None,
+        };
+        let non_zeros = bvp_non_zeros(nstates, ret.mesh.len());
+        ret.sparsity = Some(
+            MatrixSparsity::try_from_indices(ret.nout(), ret.nstates(), non_zeros.clone())
+                .expect("invalid sparsity pattern"),
+        );
+        ret.coloring = Some(JacobianColoring::new_from_non_zeros(
+            &ret,
+            non_zeros,
+            ColoringAlgorithm::default(),
+        ));
+        ret
+    }
+
+    /// The mesh points.
+    pub fn mesh(&self) -> &[Eqn::T] {
+        self.mesh.as_slice()
+    }
+
+    /// The number of times the ODE has been solved on a segment.
+    pub fn number_of_solves(&self) -> usize {
+        self.solver.number_of_solves()
+    }
+
+    /// The error from the first failed solve of the ODE (if any) since the last call to this function (see [ShootingSolver]).
+    pub fn take_error(&self) -> Option<anyhow::Error> {
+        self.solver.take_error()
+    }
+
+    /// Solve the ODE on segment `k`, from `y0` at time `t_k` to `t_{k+1}`, returning the final state.
+    pub fn solve_segment(&self, k: usize, y0: &Eqn::V) -> Result<Eqn::V> {
+        let (y, _dy) = self.solver.solve(self.mesh[k], y0, self.mesh[k + 1])?;
+        Ok(y)
+    }
+
+    // solve segment k, recording the error and returning NaN if the solve fails
+    fn solve_segment_or_nan(&self, k: usize, y0: &Eqn::V) -> Eqn::V {
+        self.solver
+            .solve_or_nan(self.mesh[k], y0, self.mesh[k + 1])
+            .0
+    }
+
+    // the solution at the end of each segment for the unknowns x, reusing the last solve if x has not changed
+    fn solve_cached(&self, x: &Eqn::V) -> Vec<Eqn::V> {
+        self.solver.cached(x, || {
+            (0..self.mesh.len() - 1)
+                .map(|k| self.solve_segment_or_nan(k, &get_node(x, k, self.nstates)))
+                .collect()
+        })
+    }
+}
+
+impl<Eqn, G, S> Op for MultipleShootingCallable<'_, Eqn, G, S>
+where
+    Eqn: OdeEquations,
+    S: OdeSolverMethod<Eqn>,
+{
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.nstates * self.mesh.len()
+    }
+    fn nout(&self) -> usize {
+        self.nstates * self.mesh.len()
+    }
+    fn nparams(&self) -> usize {
+        0
+    }
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.sparsity.as_ref()
+    }
+}
+
+impl<Eqn, G, S> NonLinearOp for MultipleShootingCallable<'_, Eqn, G, S>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    S: OdeSolverMethod<Eqn>,
+{
+    // H(x) = (y(t_{k+1}; t_k, y_k) - y_{k+1}, ..., g(y_0, y_{N-1}))
+    fn call_inplace(&self, x: &Eqn::V, _t: Eqn::T, out: &mut Eqn::V) {
+        let n = self.nstates;
+        let y = self.solve_cached(x);
+        for (k, y_k) in y.iter().enumerate() {
+            for i in 0..n {
+                out[k * n + i] = y_k[i] - x[(k + 1) * n + i];
+            }
+        }
+        boundary_inplace(self.bc, x, None, n, self.mesh.len(), self.mesh[0], out);
+    }
+
+    // dH/dx v = (Phi_k v_k - v_{k+1}, ..., dg/dya v_0 + dg/dyb v_{N-1}), where Phi_k v_k is found using a forward difference
+    fn jac_mul_inplace(&self, x: &Eqn::V, _t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        let n = self.nstates;
+        let y = self.solve_cached(x);
+        let problem = self.solver.problem();
+        let rtol = if problem.rtol > Eqn::T::EPSILON {
+            problem.rtol
+        } else {
+            Eqn::T::EPSILON
+        };
+        for (k, y_k) in y.iter().enumerate() {
+            let v_k = get_node(v, k, n);
+            let v_norm = v_k.norm();
+            let mut jv = Eqn::V::zeros(n);
+            if v_norm > Eqn::T::zero() {
+                let mut y0 = get_node(x, k, n);
+                let h = rtol.pow(Eqn::T::from(0.5)) * (Eqn::T::one() + y0.norm()) / v_norm;
+                y0.axpy(h, &v_k, Eqn::T::one());
+                jv.copy_from(&self.solve_segment_or_nan(k, &y0));
+                jv.axpy(-Eqn::T::one() / h, y_k, Eqn::T::one() / h);
+            }
+            for i in 0..n {
+                out[k * n + i] = jv[i] - v[(k + 1) * n + i];
+            }
+        }
+        boundary_inplace(self.bc, x, Some(v), n, self.mesh.len(), self.mesh[0], out);
+    }
+
+    fn jacobian_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::M) {
+        if let Some(coloring) = self.coloring.as_ref() {
+            coloring.jacobian_inplace(self, x, t, y);
+        } else {
+            self._default_jacobian_inplace(x, t, y);
+        }
+    }
+}
+
+/// Callable used to solve a two-point boundary value problem `y' = f(t, y)`, `g(y(a), y(b)) = 0` by collocation (see [crate::bvp_collocation]).
+///
+/// The unknowns are the states at the mesh points `x = (y_0, ..., y_{N-1})`, and the solution is approximated by a cubic that satisfies the ODE at
+/// both ends and the middle of each segment (the 3-stage Lobatto IIIA method, which is fourth order). The residual on the segment `[t_k, t_{k+1}]`
+/// with `h = t_{k+1} - t_k` is
+///
+/// `r_k = y_{k+1} - y_k - h / 6 (f_k + 4 f_{k+1/2} + f_{k+1})`,
+///
+/// where `f_{k+1/2} = f(t_k + h / 2, y_{k+1/2})` and `y_{k+1/2} = (y_k + y_{k+1}) / 2 - h / 8 (f_{k+1} - f_k)` is the value of the cubic in the middle of the segment.
+/// The boundary conditions `g` are an operator with `2n` states `(y(a), y(b))` and `n` outputs.
+///
+/// The jacobian-vector product is calculated exactly using the jacobian of the right-hand side, and the jacobian of the residual is calculated from
+/// its (block bidiagonal) sparsity pattern using colouring.
+pub struct CollocationCallable<'a, Eqn: OdeEquations, G> {
+    problem: &'a OdeSolverProblem<Eqn>,
+    bc: &'a G,
+    mesh: Vec<Eqn::T>,
+    nstates: usize,
+    sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+    coloring: Option<JacobianColoring<Eqn::M>>,
+}
+
+impl<'a, Eqn, G> CollocationCallable<'a, Eqn, G>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    /// Create the callable for the given `mesh` (which should be increasing, with at least two points) and boundary conditions `bc`.
+    pub fn new(problem: &'a OdeSolverProblem<Eqn>, bc: &'a G, mesh: Vec<Eqn::T>) -> Self {
+        let nstates = problem.eqn.rhs().nstates();
+        check_boundary(bc, nstates);
+        let mut ret = Self {
+            problem,
+            bc,
+            mesh,
+            nstates,
+            sparsity: None,
+            coloring: None,
+        };
+        let non_zeros = bvp_non_zeros(nstates, ret.mesh.len());
+        ret.sparsity = Some(
+            MatrixSparsity::try_from_indices(ret.nout(), ret.nstates(), non_zeros.clone())
+                .expect("invalid sparsity pattern"),
+        );
+        ret.coloring = Some(JacobianColoring::new_from_non_zeros(
+            &ret,
+            non_zeros,
+            ColoringAlgorithm::default(),
+        ));
+        ret
+    }
+
+    /// The mesh points.
+    pub fn mesh(&self) -> &[Eqn::T] {
+        self.mesh.as_slice()
+    }
+
+    // the state y_{k+1/2} and time in the middle of segment k, given the states and right-hand sides at both ends
+    fn midpoint(
+        &self,
+        k: usize,
+        y_k: &Eqn::V,
+        y_k1: &Eqn::V,
+        f_k: &Eqn::V,
+        f_k1: &Eqn::V,
+    ) -> (Eqn::V, Eqn::T) {
+        let h = self.mesh[k + 1] - self.mesh[k];
+        let half = Eqn::T::from(0.5);
+        let mut y_m = y_k.clone();
+        y_m.axpy(half, y_k1, half);
+        y_m.axpy(-h / Eqn::T::from(8.0), f_k1, Eqn::T::one());
+        y_m.axpy(h / Eqn::T::from(8.0), f_k, Eqn::T::one());
+        (y_m, self.mesh[k] + half * h)
+    }
+}
+
+impl<Eqn: OdeEquations, G> Op for CollocationCallable<'_, Eqn, G> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.nstates * self.mesh.len()
+    }
+    fn nout(&self) -> usize {
+        self.nstates * self.mesh.len()
+    }
+    fn nparams(&self) -> usize {
+        0
+    }
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.sparsity.as_ref()
+    }
+}
+
+impl<Eqn, G> NonLinearOp for CollocationCallable<'_, Eqn, G>
+where
+    Eqn: OdeEquations,
+    G: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    // H(x) = (y_{k+1} - y_k - h / 6 (f_k + 4 f_{k+1/2} + f_{k+1}), ..., g(y_0, y_{N-1}))
+    fn call_inplace(&self, x: &Eqn::V, _t: Eqn::T, out: &mut Eqn::V) {
+        let n = self.nstates;
+        let rhs = self.problem.eqn.rhs();
+        let mut y_k = get_node(x, 0, n);
+        let mut f_k = rhs.call(&y_k, self.mesh[0]);
+        for k in 0..self.mesh.len() - 1 {
+            let h = self.mesh[k + 1] - self.mesh[k];
+            let y_k1 = get_node(x, k + 1, n);
+            let f_k1 = rhs.call(&y_k1, self.mesh[k + 1]);
+            let (y_m, t_m) = self.midpoint(k, &y_k, &y_k1, &f_k, &f_k1);
+            let f_m = rhs.call(&y_m, t_m);
+            let mut r = y_k1.clone();
+            r.axpy(-Eqn::T::one(), &y_k, Eqn::T::one());
+            let h6 = h / Eqn::T::from(6.0);
+            r.axpy(-h6, &f_k, Eqn::T::one());
+            r.axpy(-Eqn::T::from(4.0) * h6, &f_m, Eqn::T::one());
+            r.axpy(-h6, &f_k1, Eqn::T::one());
+            set_node(out, k, &r);
+            y_k = y_k1;
+            f_k = f_k1;
+        }
+        boundary_inplace(self.bc, x, None, n, self.mesh.len(), self.mesh[0], out);
+    }
+
+    // dr_k = v_{k+1} - v_k - h / 6 (J_k v_k + 4 J_{k+1/2} v_{k+1/2} + J_{k+1} v_{k+1}),
+    // where v_{k+1/2} = (v_k + v_{k+1}) / 2 - h / 8 (J_{k+1} v_{k+1} - J_k v_k)
+    fn jac_mul_inplace(&self, x: &Eqn::V, _t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        let n = self.nstates;
+        let rhs = self.problem.eqn.rhs();
+        let mut y_k = get_node(x, 0, n);
+        let mut v_k = get_node(v, 0, n);
+        let mut f_k = rhs.call(&y_k, self.mesh[0]);
+        let mut jv_k = rhs.jac_mul(&y_k, self.mesh[0], &v_k);
+        for k in 0..self.mesh.len() - 1 {
+            let h = self.mesh[k + 1] - self.mesh[k];
+            let y_k1 = get_node(x, k + 1, n);
+            let v_k1 = get_node(v, k + 1, n);
+            let f_k1 = rhs.call(&y_k1, self.mesh[k + 1]);
+            let jv_k1 = rhs.jac_mul(&y_k1, self.mesh[k + 1], &v_k1);
+            let (y_m, t_m) = self.midpoint(k, &y_k, &y_k1, &f_k, &f_k1);
+            let (v_m, _) = self.midpoint(k, &v_k, &v_k1, &jv_k, &jv_k1);
+            let jv_m = rhs.jac_mul(&y_m, t_m, &v_m);
+            let mut r = v_k1.clone();
+            r.axpy(-Eqn::T::one(), &v_k, Eqn::T::one());
+            let h6 = h / Eqn::T::from(6.0);
+            r.axpy(-h6, &jv_k, Eqn::T::one());
+            r.axpy(-Eqn::T::from(4.0) * h6, &jv_m, Eqn::T::one());
+            r.axpy(-h6, &jv_k1, Eqn::T::one());
+            set_node(out, k, &r);
+            y_k = y_k1;
+            v_k = v_k1;
+            f_k = f_k1;
+            jv_k = jv_k1;
+        }
+        boundary_inplace(self.bc, x, Some(v), n, self.mesh.len(), self.mesh[0], out);
+    }
+
+    fn jacobian_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::M) {
+        if let Some(coloring) = self.coloring.as_ref() {
+            coloring.jacobian_inplace(self, x, t, y);
+        } else {
+            self._default_jacobian_inplace(x, t, y);
+        }
+    }
+}
@@ -1,7 +1,12 @@
 use std::{cell::RefCell, rc::Rc};
 
+use anyhow::Result;
+
 use crate::{
-    jacobian::{find_non_zeros_nonlinear, JacobianColoring},
+    jacobian::{
+        find_non_zeros_nonlinear, find_non_zeros_with_probes, is_symmetric, ColoringAlgorithm,
+        JacobianColoring,
+    },
     matrix::MatrixSparsity,
     Matrix, Vector,
 };
@@ -21,6 +26,7 @@ where
     nparams: usize,
     p: Rc<M::V>,
     coloring: Option<JacobianColoring<M>>,
+    coloring_algorithm: ColoringAlgorithm,
     sparsity: Option<M::Sparsity>,
     statistics: RefCell<OpStatistics>,
 }
@@ -42,17 +48,52 @@ where
             p,
             statistics: RefCell::new(OpStatistics::default()),
             coloring: None,
+            coloring_algorithm: ColoringAlgorithm::default(),
             sparsity: None,
         }
     }
 
-    pub fn calculate_sparsity(&mut self, y0: &M::V, t0: M::T) {
-        let non_zeros = find_non_zeros_nonlinear(self, y0, t0);
-        self.sparsity = Some(
-            MatrixSparsity::try_from_indices(self.nout(), self.nstates(), non_zeros.clone())
-                .expect("invalid sparsity pattern"),
-        );
-        self.coloring = Some(JacobianColoring::new_from_non_zeros(self, non_zeros));
+    /// Set the algorithm used to colour the jacobian when the sparsity pattern is calculated or set (default is [ColoringAlgorithm::Greedy]).
+    pub fn set_coloring_algorithm(&mut self, algorithm: ColoringAlgorithm) {
+        self.coloring_algorithm = algorithm;
+    }
+
+    pub fn calculate_sparsity(&mut self, y0: &M::V, t0: M::T) -> Result<()> {
+        self.calculate_sparsity_with_probes(y0, t0, 0)
+    }
+
+    /// Calculate the sparsity pattern of the jacobian using the union of the non-zero entries detected by [find_non_zeros_nonlinear]
+    /// at `y0` and at `nprobes` perturbed states (see [find_non_zeros_with_probes]).
+    pub fn calculate_sparsity_with_probes(
+        &mut self,
+        y0: &M::V,
+        t0: M::T,
+        nprobes: usize,
+    ) -> Result<()> {
+        let non_zeros = find_non_zeros_with_probes(self, y0, t0, nprobes, |x| {
+            find_non_zeros_nonlinear(self, x, t0)
+        });
+        self.set_sparsity(non_zeros)
+    }
+
+    /// Set the sparsity pattern of the jacobian from a list of non-zero entries `(row, col)`. The jacobian is then calculated using colouring.
+    pub fn set_sparsity(&mut self, non_zeros: Vec<(usize, usize)>) -> Result<()> {
+        if self.coloring_algorithm == ColoringAlgorithm::Star && !is_symmetric(&non_zeros) {
+            return Err(anyhow::anyhow!(
+                "star colouring requires a symmetric jacobian sparsity pattern"
+            ));
+        }
+        self.sparsity = Some(MatrixSparsity::try_from_indices(
+            self.nout(),
+            self.nstates(),
+            non_zeros.clone(),
+        )?);
+        self.coloring = Some(JacobianColoring::new_from_non_zeros(
+            self,
+            non_zeros,
+            self.coloring_algorithm,
+        ));
+        Ok(())
     }
 }
 
@@ -0,0 +1,229 @@
+use std::{cell::RefCell, rc::Rc};
+
+use num_traits::Zero;
+
+use anyhow::Result;
+
+use crate::{
+    jacobian::{find_non_zeros_with_probes, is_symmetric, ColoringAlgorithm, JacobianColoring},
+    matrix::MatrixSparsity,
+    scalar::{
+        dual::{AutodiffScalar, Dual},
+        tracer::Tracer,
+    },
+    Matrix, Scalar, Vector,
+};
+
+use super::{NonLinearOp, Op, OpStatistics};
+
+/// A function `y = f(x, p, t)` that is generic over the scalar type of the states `x` and outputs `y`.
+/// This is used to calculate the action of the jacobian exactly by evaluating the function with dual numbers (see [Dual]).
+///
+/// # Example
+///
+/// ```
+/// use diffsol::{AutodiffFunction, AutodiffScalar};
+///
+/// // dy/dt = -a y^2
+/// struct Decay;
+///
+/// impl AutodiffFunction<f64> for Decay {
+///     fn call<S: AutodiffScalar<f64>>(&self, x: &[S], p: &[f64], _t: f64, y: &mut [S]) {
+///         y[0] = -x[0] * x[0] * p[0];
+///     }
+/// }
+/// ```
+pub trait AutodiffFunction<T: Scalar> {
+    fn call<S: AutodiffScalar<T>>(&self, x: &[S], p: &[T], t: T, y: &mut [S]);
+}
+
+/// A [NonLinearOp] defined by an [AutodiffFunction]. The action of the jacobian is calculated exactly using forward-mode
+/// automatic differentiation with dual numbers.
+pub struct ClosureAutodiff<M, F>
+where
+    M: Matrix,
+    F: AutodiffFunction<M::T>,
+{
+    func: F,
+    nstates: usize,
+    nout: usize,
+    nparams: usize,
+    p: Rc<M::V>,
+    // the parameters copied into a slice for the function, updated in set_params
+    params: Vec<M::T>,
+    // workspaces for the states and outputs, reused between evaluations
+    x_work: RefCell<Vec<M::T>>,
+    out_work: RefCell<Vec<M::T>>,
+    x_dual: RefCell<Vec<Dual<M::T>>>,
+    out_dual: RefCell<Vec<Dual<M::T>>>,
+    coloring: Option<JacobianColoring<M>>,
+    coloring_algorithm: ColoringAlgorithm,
+    sparsity: Option<M::Sparsity>,
+    statistics: RefCell<OpStatistics>,
+}
+
+impl<M, F> ClosureAutodiff<M, F>
+where
+    M: Matrix,
+    F: AutodiffFunction<M::T>,
+{
+    pub fn new(func: F, nstates: usize, nout: usize, p: Rc<M::V>) -> Self {
+        let nparams = p.len();
+        let params = Self::params_from(&p);
+        Self {
+            func,
+            nstates,
+            nout,
+            nparams,
+            p,
+            params,
+            x_work: RefCell::new(vec![M::T::zero(); nstates]),
+            out_work: RefCell::new(vec![M::T::zero(); nout]),
+            x_dual: RefCell::new(vec![Dual::constant(M::T::zero()); nstates]),
+            out_dual: RefCell::new(vec![Dual::constant(M::T::zero()); nout]),
+            statistics: RefCell::new(OpStatistics::default()),
+            coloring: None,
+            coloring_algorithm: ColoringAlgorithm::default(),
+            sparsity: None,
+        }
+    }
+
+    /// Set the algorithm used to colour the jacobian when the sparsity pattern is calculated or set (default is [ColoringAlgorithm::Greedy]).
+    pub fn set_coloring_algorithm(&mut self, algorithm: ColoringAlgorithm) {
+        self.coloring_algorithm = algorithm;
+    }
+
+    pub fn calculate_sparsity(&mut self, y0: &M::V, t0: M::T) -> Result<()> {
+        self.calculate_sparsity_with_probes(y0, t0, 0)
+    }
+
+    /// Calculate the sparsity pattern of the jacobian using the union of the non-zero entries found by tracing the dependencies of each output (see [Tracer])
+    /// at `y0` and at `nprobes` perturbed states (see [find_non_zeros_with_probes]).
+    pub fn calculate_sparsity_with_probes(
+        &mut self,
+        y0: &M::V,
+        t0: M::T,
+        nprobes: usize,
+    ) -> Result<()> {
+        let non_zeros = find_non_zeros_with_probes(self, y0, t0, nprobes, |x| {
+            self.find_non_zeros_traced(x, t0)
+        });
+        self.set_sparsity(non_zeros)
+    }
+
+    /// Set the sparsity pattern of the jacobian from a list of non-zero entries `(row, col)`. The jacobian is then calculated using colouring.
+    pub fn set_sparsity(&mut self, non_zeros: Vec<(usize, usize)>) -> Result<()> {
+        if self.coloring_algorithm == ColoringAlgorithm::Star && !is_symmetric(&non_zeros) {
+            return Err(anyhow::anyhow!(
+                "star colouring requires a symmetric jacobian sparsity pattern"
+            ));
+        }
+        self.sparsity = Some(MatrixSparsity::try_from_indices(
+            self.nout(),
+            self.nstates(),
+            non_zeros.clone(),
+        )?);
+        self.coloring = Some(JacobianColoring::new_from_non_zeros(
+            self,
+            non_zeros,
+            self.coloring_algorithm,
+        ));
+        Ok(())
+    }
+
+    /// Find the structural non-zero entries of the jacobian at `(x, t)` by evaluating the function with [Tracer] scalars,
+    /// marking each input in turn as a dependency.
+    pub fn find_non_zeros_traced(&self, x: &M::V, t: M::T) -> Vec<(usize, usize)> {
+        let p = &self.params;
+        let mut x_tracer: Vec<Tracer<M::T>> =
+            (0..self.nstates).map(|i| Tracer::from(x[i])).collect();
+        let mut triplets = Vec::with_capacity(self.nstates);
+        for j in 0..self.nstates {
+            x_tracer[j].depends = true;
+            let mut out = vec![Tracer::from(M::T::zero()); self.nout];
+            self.func.call(&x_tracer, p, t, &mut out);
+            for (i, out_i) in out.iter().enumerate() {
+                if out_i.depends {
+                    triplets.push((i, j));
+                }
+            }
+            x_tracer[j].depends = false;
+        }
+        triplets
+    }
+
+    fn params_from(p: &M::V) -> Vec<M::T> {
+        (0..p.len()).map(|i| p[i]).collect()
+    }
+}
+
+impl<M, F> Op for ClosureAutodiff<M, F>
+where
+    M: Matrix,
+    F: AutodiffFunction<M::T>,
+{
+    type V = M::V;
+    type T = M::T;
+    type M = M;
+    fn nstates(&self) -> usize {
+        self.nstates
+    }
+    fn nout(&self) -> usize {
+        self.nout
+    }
+    fn nparams(&self) -> usize {
+        self.nparams
+    }
+    fn set_params(&mut self, p: Rc<M::V>) {
+        assert_eq!(p.len(), self.nparams);
+        self.params = Self::params_from(&p);
+        self.p = p;
+    }
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.sparsity.as_ref()
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.statistics.borrow().clone()
+    }
+}
+
+impl<M, F> NonLinearOp for ClosureAutodiff<M, F>
+where
+    M: Matrix,
+    F: AutodiffFunction<M::T>,
+{
+    fn call_inplace(&self, x: &M::V, t: M::T, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_call();
+        let mut x_work = self.x_work.borrow_mut();
+        let mut out = self.out_work.borrow_mut();
+        for (i, x_i) in x_work.iter_mut().enumerate() {
+            *x_i = x[i];
+        }
+        out.fill(M::T::zero());
+        self.func.call(&x_work, &self.params, t, &mut out);
+        for (i, out_i) in out.iter().enumerate() {
+            y[i] = *out_i;
+        }
+    }
+    fn jac_mul_inplace(&self, x: &M::V, t: M::T, v: &M::V, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_jac_mul();
+        let mut x_dual = self.x_dual.borrow_mut();
+        let mut out = self.out_dual.borrow_mut();
+        for (i, x_i) in x_dual.iter_mut().enumerate() {
+            *x_i = Dual::new(x[i], v[i]);
+        }
+        out.fill(Dual::constant(M::T::zero()));
+        self.func.call(&x_dual, &self.params, t, &mut out);
+        for (i, out_i) in out.iter().enumerate() {
+            y[i] = out_i.eps;
+        }
+    }
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.statistics.borrow_mut().increment_matrix();
+        if let Some(coloring) = self.coloring.as_ref() {
+            coloring.jacobian_inplace(self, x, t, y);
+        } else {
+            self._default_jacobian_inplace(x, t, y);
+        }
+    }
+}
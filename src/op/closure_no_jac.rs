@@ -1,9 +1,24 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{Matrix, Vector};
+use num_traits::{One, Zero};
+
+use anyhow::Result;
+
+use crate::{
+    jacobian::{
+        find_non_zeros_nonlinear_from_call, find_non_zeros_with_probes, is_symmetric,
+        ColoringAlgorithm, JacobianColoring,
+    },
+    matrix::MatrixSparsity,
+    scale, Matrix, Scalar, Vector,
+};
 
 use super::{NonLinearOp, Op, OpStatistics};
 
+/// A [NonLinearOp] defined by a closure that evaluates the function only. The action of the jacobian is
+/// approximated using a directional (forward) finite difference, and if the sparsity pattern has been calculated
+/// (see [Self::calculate_sparsity]) the full jacobian is calculated using colour-compressed finite differences. When the full jacobian
+/// is calculated, `f(x)` is only evaluated once and shared by the finite differences of all the columns (or colours).
 pub struct ClosureNoJac<M, F>
 where
     M: Matrix,
@@ -14,6 +29,11 @@ where
     nout: usize,
     nparams: usize,
     p: Rc<M::V>,
+    typical_x: M::V,
+    coloring: Option<JacobianColoring<M>>,
+    coloring_algorithm: ColoringAlgorithm,
+    sparsity: Option<M::Sparsity>,
+    f0: RefCell<Option<M::V>>,
     statistics: RefCell<OpStatistics>,
 }
 
@@ -30,9 +50,85 @@ where
             nout,
             nparams,
             p,
+            typical_x: M::V::from_element(nstates, M::T::one()),
+            coloring: None,
+            coloring_algorithm: ColoringAlgorithm::default(),
+            sparsity: None,
+            f0: RefCell::new(None),
             statistics: RefCell::new(OpStatistics::default()),
         }
     }
+
+    /// Set the typical magnitude of each state (e.g. the absolute tolerance of the solver). This is used as a lower bound
+    /// for the magnitude of `x` when choosing the finite difference step size (default is 1).
+    pub fn set_typical_x(&mut self, typical_x: &M::V) {
+        assert_eq!(typical_x.len(), self.nstates);
+        self.typical_x = typical_x.abs();
+    }
+
+    /// Set the algorithm used to colour the jacobian when the sparsity pattern is calculated or set (default is [ColoringAlgorithm::Greedy]).
+    pub fn set_coloring_algorithm(&mut self, algorithm: ColoringAlgorithm) {
+        self.coloring_algorithm = algorithm;
+    }
+
+    pub fn calculate_sparsity(&mut self, y0: &M::V, t0: M::T) -> Result<()> {
+        self.calculate_sparsity_with_probes(y0, t0, 0)
+    }
+
+    /// Calculate the sparsity pattern of the jacobian using the union of the non-zero entries detected by [find_non_zeros_nonlinear_from_call]
+    /// at `y0` and at `nprobes` perturbed states (see [find_non_zeros_with_probes]).
+    pub fn calculate_sparsity_with_probes(
+        &mut self,
+        y0: &M::V,
+        t0: M::T,
+        nprobes: usize,
+    ) -> Result<()> {
+        let non_zeros = find_non_zeros_with_probes(self, y0, t0, nprobes, |x| {
+            find_non_zeros_nonlinear_from_call(self, x, t0)
+        });
+        self.set_sparsity(non_zeros)
+    }
+
+    /// Set the sparsity pattern of the jacobian from a list of non-zero entries `(row, col)`. The jacobian is then calculated using colouring.
+    pub fn set_sparsity(&mut self, non_zeros: Vec<(usize, usize)>) -> Result<()> {
+        if self.coloring_algorithm == ColoringAlgorithm::Star && !is_symmetric(&non_zeros) {
+            return Err(anyhow::anyhow!(
+                "star colouring requires a symmetric jacobian sparsity pattern"
+            ));
+        }
+        self.sparsity = Some(MatrixSparsity::try_from_indices(
+            self.nout(),
+            self.nstates(),
+            non_zeros.clone(),
+        )?);
+        self.coloring = Some(JacobianColoring::new_from_non_zeros(
+            self,
+            non_zeros,
+            self.coloring_algorithm,
+        ));
+        Ok(())
+    }
+
+    /// Step size for the finite difference approximation of `J(x) v`, chosen so that the perturbation `h v` of each
+    /// state is at most the square root of machine epsilon relative to the magnitude of that state (bounded below by its typical magnitude).
+    fn finite_difference_step(&self, x: &M::V, v: &M::V) -> M::T {
+        let max_ratio = x.binary_fold(v, M::T::zero(), |acc, xi, vi, i| {
+            let xi = num_traits::abs(xi);
+            let scale = if xi > self.typical_x[i] {
+                xi
+            } else {
+                self.typical_x[i]
+            };
+            let ratio = num_traits::abs(vi) / scale;
+            if ratio > acc {
+                ratio
+            } else {
+                acc
+            }
+        });
+        let eps: f64 = M::T::EPSILON.into();
+        M::T::from(eps.sqrt()) / max_ratio
+    }
 }
 
 impl<M, F> Op for ClosureNoJac<M, F>
@@ -56,6 +152,9 @@ where
         assert_eq!(p.len(), self.nparams);
         self.p = p;
     }
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.sparsity.as_ref()
+    }
     fn statistics(&self) -> OpStatistics {
         self.statistics.borrow().clone()
     }
@@ -70,7 +169,37 @@ where
         self.statistics.borrow_mut().increment_call();
         (self.func)(x, self.p.as_ref(), t, y)
     }
-    fn jac_mul_inplace(&self, _x: &Self::V, _t: Self::T, _v: &Self::V, _y: &mut Self::V) {
-        unimplemented!()
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.statistics.borrow_mut().increment_jac_mul();
+        if v.norm() == M::T::zero() {
+            y.copy_from(&M::V::zeros(self.nout));
+            return;
+        }
+        // J(x) v ~= (f(x + h v) - f(x)) / h, the evaluations of f are counted as calls, and f(x) is reused if the
+        // full jacobian is being calculated
+        let h = self.finite_difference_step(x, v);
+        let mut x_plus = x.clone();
+        x_plus.axpy(h, v, M::T::one());
+        self.call_inplace(&x_plus, t, y);
+        if let Some(f0) = self.f0.borrow().as_ref() {
+            *y -= f0;
+        } else {
+            let mut f0 = M::V::zeros(self.nout);
+            self.call_inplace(x, t, &mut f0);
+            *y -= &f0;
+        }
+        *y *= scale(M::T::one() / h);
+    }
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.statistics.borrow_mut().increment_matrix();
+        let mut f0 = M::V::zeros(self.nout);
+        self.call_inplace(x, t, &mut f0);
+        self.f0.replace(Some(f0));
+        if let Some(coloring) = self.coloring.as_ref() {
+            coloring.jacobian_inplace(self, x, t, y);
+        } else {
+            self._default_jacobian_inplace(x, t, y);
+        }
+        self.f0.replace(None);
     }
 }
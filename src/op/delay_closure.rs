@@ -0,0 +1,141 @@
+use std::{
+    cell::{RefCell, RefMut},
+    rc::Rc,
+};
+
+use crate::{ode_solver::dde::DdeHistory, Matrix, Vector};
+
+use super::{NonLinearOp, Op, OpStatistics};
+
+/// The right-hand side `f(t, y(t), y(t - tau_1), ..., y(t - tau_m), p)` of a delay differential equation with constant delays `tau_i`
+/// (see [crate::OdeBuilder::build_dde]).
+///
+/// The delayed states are evaluated using the history function `phi(p, t)` for times before the initial time, and otherwise from the
+/// [DdeHistory] of the solution, which is updated by [crate::DdeSolver] after each step. The jacobian is with respect to the current state only,
+/// as the solver limits the step size to the smallest delay, so the delayed states do not depend on the state at the end of a step.
+pub struct DelayClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &[M::V], &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &[M::V], &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, M::T) -> M::V,
+{
+    func: F,
+    jacobian_action: G,
+    history_fn: Rc<H>,
+    history: Rc<RefCell<DdeHistory<M::V>>>,
+    delays: Vec<M::T>,
+    delayed: RefCell<Vec<M::V>>,
+    nstates: usize,
+    nparams: usize,
+    p: Rc<M::V>,
+    statistics: RefCell<OpStatistics>,
+}
+
+impl<M, F, G, H> DelayClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &[M::V], &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &[M::V], &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, M::T) -> M::V,
+{
+    pub fn new(
+        func: F,
+        jacobian_action: G,
+        history_fn: Rc<H>,
+        history: Rc<RefCell<DdeHistory<M::V>>>,
+        delays: Vec<M::T>,
+        nstates: usize,
+        p: Rc<M::V>,
+    ) -> Self {
+        let nparams = p.len();
+        let delayed = RefCell::new(vec![M::V::zeros(nstates); delays.len()]);
+        Self {
+            func,
+            jacobian_action,
+            history_fn,
+            history,
+            delays,
+            delayed,
+            nstates,
+            nparams,
+            p,
+            statistics: RefCell::new(OpStatistics::default()),
+        }
+    }
+
+    /// The delays `tau_i`.
+    pub fn delays(&self) -> &[M::T] {
+        self.delays.as_slice()
+    }
+
+    // the delayed states y(t - tau_i)
+    fn delayed_states(&self, t: M::T) -> RefMut<'_, Vec<M::V>> {
+        let mut delayed = self.delayed.borrow_mut();
+        let history = self.history.borrow();
+        for (y, &tau) in delayed.iter_mut().zip(self.delays.iter()) {
+            let t_delayed = t - tau;
+            if !history.interpolate_inplace(t_delayed, y) {
+                // before any steps are taken the solution is extrapolated as constant
+                let t_delayed = if t_delayed < history.t0() {
+                    t_delayed
+                } else {
+                    history.t0()
+                };
+                y.copy_from(&(self.history_fn)(self.p.as_ref(), t_delayed));
+            }
+        }
+        delayed
+    }
+}
+
+impl<M, F, G, H> Op for DelayClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &[M::V], &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &[M::V], &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, M::T) -> M::V,
+{
+    type V = M::V;
+    type T = M::T;
+    type M = M;
+    fn nstates(&self) -> usize {
+        self.nstates
+    }
+    fn nout(&self) -> usize {
+        self.nstates
+    }
+    fn nparams(&self) -> usize {
+        self.nparams
+    }
+    fn set_params(&mut self, p: Rc<M::V>) {
+        assert_eq!(p.len(), self.nparams);
+        self.p = p;
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.statistics.borrow().clone()
+    }
+}
+
+impl<M, F, G, H> NonLinearOp for DelayClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &[M::V], &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &[M::V], &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, M::T) -> M::V,
+{
+    fn call_inplace(&self, x: &M::V, t: M::T, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_call();
+        let delayed = self.delayed_states(t);
+        (self.func)(x, delayed.as_slice(), self.p.as_ref(), t, y)
+    }
+    fn jac_mul_inplace(&self, x: &M::V, t: M::T, v: &M::V, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_jac_mul();
+        let delayed = self.delayed_states(t);
+        (self.jacobian_action)(x, delayed.as_slice(), self.p.as_ref(), t, v, y)
+    }
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.statistics.borrow_mut().increment_matrix();
+        self._default_jacobian_inplace(x, t, y);
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use num_traits::{One, Zero};
+
+use crate::{ode_solver::equations::OdeEquations, LinearOp, Vector};
+
+use super::{NonLinearOp, Op};
+
+/// The parameters of a generalised-alpha step, see [crate::GeneralisedAlpha].
+#[derive(Clone, Copy, Debug)]
+pub struct GeneralisedAlphaParameters<T> {
+    pub alpha_m: T,
+    pub alpha_f: T,
+    pub beta: T,
+    pub gamma: T,
+}
+
+// callable to solve for the acceleration a at the end of a step of the generalised-alpha method applied to the second order system
+// y = (q, v), y' = (v, M^{-1} g(t, q, v)), i.e.
+// F(a) = M ((1 - alpha_m) a + alpha_m a_n) - g(t_n + (1 - alpha_f) h, (1 - alpha_f) q + alpha_f q_n, (1 - alpha_f) v + alpha_f v_n) = 0
+// with the newmark updates
+// q = q_n + h v_n + h^2 ((1/2 - beta) a_n + beta a)
+// v = v_n + h ((1 - gamma) a_n + gamma a)
+pub struct GeneralisedAlphaCallable<Eqn: OdeEquations> {
+    eqn: Rc<Eqn>,
+    n: usize,
+    parameters: Cell<GeneralisedAlphaParameters<Eqn::T>>,
+    h: Cell<Eqn::T>,
+    y_n: RefCell<Eqn::V>,
+    a_n: RefCell<Eqn::V>,
+    y: RefCell<Eqn::V>,
+    tmp: RefCell<Eqn::V>,
+    out: RefCell<Eqn::V>,
+}
+
+impl<Eqn: OdeEquations> GeneralisedAlphaCallable<Eqn> {
+    pub fn new(eqn: &Rc<Eqn>, parameters: GeneralisedAlphaParameters<Eqn::T>) -> Self {
+        let nstates = eqn.rhs().nstates();
+        assert_eq!(
+            nstates % 2,
+            0,
+            "a second order system should have an even number of states"
+        );
+        let n = nstates / 2;
+        Self {
+            eqn: eqn.clone(),
+            n,
+            parameters: Cell::new(parameters),
+            h: Cell::new(Eqn::T::zero()),
+            y_n: RefCell::new(Eqn::V::zeros(nstates)),
+            a_n: RefCell::new(Eqn::V::zeros(n)),
+            y: RefCell::new(Eqn::V::zeros(nstates)),
+            tmp: RefCell::new(Eqn::V::zeros(nstates)),
+            out: RefCell::new(Eqn::V::zeros(nstates)),
+        }
+    }
+
+    pub fn parameters(&self) -> GeneralisedAlphaParameters<Eqn::T> {
+        self.parameters.get()
+    }
+
+    pub fn set_parameters(&self, parameters: GeneralisedAlphaParameters<Eqn::T>) {
+        self.parameters.set(parameters);
+    }
+
+    pub fn h(&self) -> Eqn::T {
+        self.h.get()
+    }
+
+    pub fn set_h(&self, h: Eqn::T) {
+        self.h.set(h);
+    }
+
+    /// Set the state `y_n = (q_n, v_n)` and acceleration `a_n` at the start of the step.
+    pub fn set_start(&self, y_n: &Eqn::V, a_n: &Eqn::V) {
+        self.y_n.borrow_mut().copy_from(y_n);
+        self.a_n.borrow_mut().copy_from(a_n);
+    }
+
+    /// The state `(q, v)` at the end of the step given the acceleration `a`.
+    pub fn end_state(&self, a: &Eqn::V) -> Eqn::V {
+        let mut y = self.y_n.borrow().clone();
+        self.newmark_update(a, &mut y);
+        y
+    }
+
+    // y = (q, v) at the end of the step
+    fn newmark_update(&self, a: &Eqn::V, y: &mut Eqn::V) {
+        let GeneralisedAlphaParameters { beta, gamma, .. } = self.parameters.get();
+        let h = self.h.get();
+        let half = Eqn::T::from(0.5);
+        let one = Eqn::T::one();
+        let y_n = self.y_n.borrow();
+        let a_n = self.a_n.borrow();
+        let n = self.n;
+        for i in 0..n {
+            y[i] = y_n[i] + h * y_n[n + i] + h * h * ((half - beta) * a_n[i] + beta * a[i]);
+            y[n + i] = y_n[n + i] + h * ((one - gamma) * a_n[i] + gamma * a[i]);
+        }
+    }
+
+    // y = (1 - alpha_f) y + alpha_f y_n
+    fn intermediate_state(&self, y: &mut Eqn::V) {
+        let alpha_f = self.parameters.get().alpha_f;
+        y.axpy(alpha_f, &self.y_n.borrow(), Eqn::T::one() - alpha_f);
+    }
+
+    // out = the lower half of M (0, x)
+    fn mass_lower(&self, x: &Eqn::V, t: Eqn::T, out: &mut Eqn::V) {
+        let n = self.n;
+        let mut tmp = self.tmp.borrow_mut();
+        let mut full = self.out.borrow_mut();
+        for i in 0..n {
+            tmp[i] = Eqn::T::zero();
+            tmp[n + i] = x[i];
+        }
+        self.eqn
+            .mass()
+            .gemv_inplace(&tmp, t, Eqn::T::zero(), &mut full);
+        for i in 0..n {
+            out[i] = full[n + i];
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> Op for GeneralisedAlphaCallable<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.n
+    }
+    fn nout(&self) -> usize {
+        self.n
+    }
+    fn nparams(&self) -> usize {
+        self.eqn.rhs().nparams()
+    }
+}
+
+impl<Eqn: OdeEquations> NonLinearOp for GeneralisedAlphaCallable<Eqn> {
+    // F(a) = M ((1 - alpha_m) a + alpha_m a_n) - g(t, y_alpha)
+    fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
+        let alpha_m = self.parameters.get().alpha_m;
+        let n = self.n;
+        let mut a = x.clone();
+        a.axpy(alpha_m, &self.a_n.borrow(), Eqn::T::one() - alpha_m);
+        self.mass_lower(&a, t, y);
+
+        let mut state = self.y.borrow_mut();
+        self.newmark_update(x, &mut state);
+        self.intermediate_state(&mut state);
+        let mut f = self.out.borrow_mut();
+        self.eqn.rhs().call_inplace(&state, t, &mut f);
+        for i in 0..n {
+            y[i] -= f[n + i];
+        }
+    }
+
+    // (1 - alpha_m) M v - (1 - alpha_f) (h^2 beta dg/dq + h gamma dg/dv) v
+    fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        let GeneralisedAlphaParameters {
+            alpha_m,
+            alpha_f,
+            beta,
+            gamma,
+        } = self.parameters.get();
+        let h = self.h.get();
+        let one = Eqn::T::one();
+        let n = self.n;
+        self.mass_lower(v, t, y);
+        *y *= crate::scale(one - alpha_m);
+
+        let mut state = self.y.borrow_mut();
+        self.newmark_update(x, &mut state);
+        self.intermediate_state(&mut state);
+        let mut dy = Eqn::V::zeros(2 * n);
+        for i in 0..n {
+            dy[i] = (one - alpha_f) * h * h * beta * v[i];
+            dy[n + i] = (one - alpha_f) * h * gamma * v[i];
+        }
+        let mut f = self.out.borrow_mut();
+        self.eqn.rhs().jac_mul_inplace(&state, t, &dy, &mut f);
+        for i in 0..n {
+            y[i] -= f[n + i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        op::{NonLinearOp, Op},
+        OdeBuilder, Vector,
+    };
+
+    use super::{GeneralisedAlphaCallable, GeneralisedAlphaParameters};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_generalised_alpha_jacobian() {
+        // 2 q'' = -q^3 - 0.5 q'
+        let problem = OdeBuilder::new()
+            .build_second_order_with_mass::<M, _, _, _, _>(
+                |q: &V, v: &V, _p: &V, _t, a: &mut V| a[0] = -q[0].powi(3) - 0.5 * v[0],
+                |q: &V, _v: &V, _p: &V, _t, dq: &V, dv: &V, y: &mut V| {
+                    y[0] = -3.0 * q[0].powi(2) * dq[0] - 0.5 * dv[0]
+                },
+                |x: &V, _p: &V, _t, beta, y: &mut V| y[0] = 2.0 * x[0] + beta * y[0],
+                |_p: &V, _t| (V::from_element(1, 1.0), V::from_element(1, 0.5)),
+            )
+            .unwrap();
+        let parameters = GeneralisedAlphaParameters {
+            alpha_m: 0.2,
+            alpha_f: 0.4,
+            beta: 0.3,
+            gamma: 0.7,
+        };
+        let callable = GeneralisedAlphaCallable::new(&problem.eqn, parameters);
+        callable.set_h(0.1);
+        callable.set_start(&V::from_vec(vec![1.0, 0.5]), &V::from_element(1, -0.2));
+        assert_eq!(callable.nstates(), 1);
+
+        // the residual with the end state found directly
+        let a = V::from_element(1, 0.3);
+        let y = callable.end_state(&a);
+        let q = 0.6 * y[0] + 0.4 * 1.0;
+        let v = 0.6 * y[1] + 0.4 * 0.5;
+        let expect = 2.0 * (0.8 * 0.3 + 0.2 * -0.2) + q.powi(3) + 0.5 * v;
+        let f = callable.call(&a, 0.0);
+        assert!((f[0] - expect).abs() < 1e-12);
+
+        // the jacobian matches finite differences
+        let eps = 1e-7;
+        let fd = (callable.call(&V::from_element(1, 0.3 + eps), 0.0)[0] - f[0]) / eps;
+        let jac = callable.jac_mul(&a, 0.0, &V::from_element(1, 1.0));
+        jac.assert_eq_st(&V::from_element(1, fd), 1e-5);
+    }
+}
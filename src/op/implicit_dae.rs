@@ -0,0 +1,152 @@
+// the semi-explicit form M y' = f(t, y) of a fully implicit DAE F(t, y, y') = 0 that is linear in y',
+// given by f(t, y) = -F(t, y, 0) and M = dF/dy'
+
+use std::{cell::RefCell, rc::Rc};
+
+use num_traits::{One, Zero};
+
+use crate::{scale, Matrix, Vector};
+
+use super::{LinearOp, NonLinearOp, Op, ResidualOp};
+
+/// The right-hand side `f(t, y) = -F(t, y, 0)` of a fully implicit DAE `F(t, y, y') = 0`. If the residual is linear in `y'`
+/// (i.e. `F(t, y, y') = M y' - f(t, y)`) this is the right-hand side of the equivalent semi-explicit form, see [ImplicitDaeMass] for the mass.
+pub struct ImplicitDaeRhs<R: ResidualOp> {
+    residual: Rc<R>,
+    zeros: R::V,
+}
+
+impl<R: ResidualOp> ImplicitDaeRhs<R> {
+    pub fn new(residual: Rc<R>) -> Self {
+        let zeros = R::V::zeros(residual.nstates());
+        Self { residual, zeros }
+    }
+}
+
+impl<R: ResidualOp> Op for ImplicitDaeRhs<R> {
+    type V = R::V;
+    type T = R::T;
+    type M = R::M;
+    fn nstates(&self) -> usize {
+        self.residual.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.residual.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.residual.nparams()
+    }
+    // the parameters are shared with the residual
+    fn set_params(&mut self, _p: Rc<Self::V>) {}
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.residual.sparsity()
+    }
+}
+
+impl<R: ResidualOp> NonLinearOp for ImplicitDaeRhs<R> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.residual.call_inplace(x, &self.zeros, t, y);
+        *y *= scale(-Self::T::one());
+    }
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.residual.jac_mul_inplace(x, &self.zeros, t, v, y);
+        *y *= scale(-Self::T::one());
+    }
+}
+
+/// The mass `M = dF/dy'` of a fully implicit DAE `F(t, y, y') = 0`, evaluated at `y = y' = 0`. If the residual is linear in `y'` and
+/// `dF/dy'` does not depend on the state (i.e. `F(t, y, y') = M(t) y' - f(t, y)`) this is the mass of the equivalent semi-explicit form
+/// with the right-hand side [ImplicitDaeRhs].
+pub struct ImplicitDaeMass<R: ResidualOp> {
+    residual: Rc<R>,
+    zeros: R::V,
+    tmp: RefCell<R::V>,
+}
+
+impl<R: ResidualOp> ImplicitDaeMass<R> {
+    pub fn new(residual: Rc<R>) -> Self {
+        let n = residual.nstates();
+        Self {
+            residual,
+            zeros: R::V::zeros(n),
+            tmp: RefCell::new(R::V::zeros(n)),
+        }
+    }
+}
+
+impl<R: ResidualOp> Op for ImplicitDaeMass<R> {
+    type V = R::V;
+    type T = R::T;
+    type M = R::M;
+    fn nstates(&self) -> usize {
+        self.residual.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.residual.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.residual.nparams()
+    }
+    // the parameters are shared with the residual
+    fn set_params(&mut self, _p: Rc<Self::V>) {}
+}
+
+impl<R: ResidualOp> LinearOp for ImplicitDaeMass<R> {
+    fn gemv_inplace(&self, x: &Self::V, t: Self::T, beta: Self::T, y: &mut Self::V) {
+        let mut tmp = self.tmp.borrow_mut();
+        self.residual
+            .jac_dot_mul_inplace(&self.zeros, &self.zeros, t, x, &mut tmp);
+        if beta == Self::T::zero() {
+            y.copy_from(&tmp);
+        } else {
+            y.axpy(Self::T::one(), &tmp, beta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{op::residual_closure::ResidualClosure, LinearOp, NonLinearOp, Vector};
+
+    use super::{ImplicitDaeMass, ImplicitDaeRhs};
+
+    #[test]
+    fn semi_explicit_form() {
+        // F(t, y, y') = M y' - f(t, y) with M = [[2, 0], [0, 0]] and f(t, y) = [-y0, y0 - y1]
+        let residual = Rc::new(ResidualClosure::<DMatrix<f64>, _, _, _>::new(
+            |x: &DVector<f64>,
+             x_dot: &DVector<f64>,
+             _p: &DVector<f64>,
+             _t,
+             y: &mut DVector<f64>| {
+                y[0] = 2.0 * x_dot[0] + x[0];
+                y[1] = x[1] - x[0];
+            },
+            |_x: &DVector<f64>, _x_dot, _p, _t, v: &DVector<f64>, y: &mut DVector<f64>| {
+                y[0] = v[0];
+                y[1] = v[1] - v[0];
+            },
+            |_x: &DVector<f64>, _x_dot, _p, _t, v: &DVector<f64>, y: &mut DVector<f64>| {
+                y[0] = 2.0 * v[0];
+                y[1] = 0.0;
+            },
+            2,
+            Rc::new(DVector::zeros(0)),
+        ));
+        let rhs = ImplicitDaeRhs::new(residual.clone());
+        let mass = ImplicitDaeMass::new(residual);
+        let y = DVector::from_vec(vec![1.0, 3.0]);
+        rhs.call(&y, 0.0)
+            .assert_eq_st(&DVector::from_vec(vec![-1.0, -2.0]), 1e-14);
+        let v = DVector::from_vec(vec![0.5, 2.0]);
+        rhs.jac_mul(&y, 0.0, &v)
+            .assert_eq_st(&DVector::from_vec(vec![-0.5, -1.5]), 1e-14);
+        let mut out = DVector::from_vec(vec![1.0, 1.0]);
+        mass.gemv_inplace(&v, 0.0, 2.0, &mut out);
+        out.assert_eq_st(&DVector::from_vec(vec![3.0, 2.0]), 1e-14);
+    }
+}
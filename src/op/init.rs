@@ -0,0 +1,458 @@
+// callable for the consistent initialisation of an ODE or DAE, it solves F(t, y, y') = 0 for the
+// algebraic components of y and the differential components of y', keeping the other components fixed
+
+use std::{
+    cell::RefCell,
+    ops::{AddAssign, Deref, MulAssign},
+    rc::Rc,
+};
+
+use num_traits::{One, Zero};
+
+use crate::{
+    ode_solver::equations::OdeEquations, scale, IndexType, LinearOp, Matrix, MatrixSparsity,
+    NonLinearOp, Op, Scalar, Vector,
+};
+
+/// Callable used to make the initial state of an ODE or DAE consistent, similar to the `IDA_YA_YDP_INIT` option of IDA.
+///
+/// The algebraic components of the state are found from the nullspace of the jacobian of the residual `F(t, y, y')` with respect to `y'`
+/// (i.e. the mass matrix `M` for equations of the form `M y' = f(t, y)`). If the mass matrix is diagonal (found from its sparsity if it is sparse)
+/// these are the zeros on the diagonal, otherwise a rank-revealing (column pivoted) Gram-Schmidt factorisation of the dense `dF/dy'` is used,
+/// so a mass matrix with off-diagonal structure is handled correctly. The unknowns of the callable are then the algebraic
+/// components of `y` and the differential components of `y'`, and it computes the residual `F(t, y, y')`.
+pub struct InitCallable<Eqn: OdeEquations> {
+    eqn: Rc<Eqn>,
+    is_algebraic: Vec<bool>,
+    mass_diagonal: Option<Eqn::V>,
+    y: RefCell<Eqn::V>,
+    dy: RefCell<Eqn::V>,
+    v_y: RefCell<Eqn::V>,
+    v_dy: RefCell<Eqn::V>,
+    tmp: RefCell<Eqn::V>,
+    rhs_jac: RefCell<Eqn::M>,
+    mass_jac: RefCell<Eqn::M>,
+    sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+    jacobian_index: Option<(Vec<IndexType>, Vec<IndexType>)>,
+}
+
+impl<Eqn: OdeEquations> InitCallable<Eqn> {
+    /// Create the callable at time `t`, with the initial guess `y` and `dy` for the state and its time derivative.
+    pub fn new(eqn: &Rc<Eqn>, y: &Eqn::V, dy: &Eqn::V, t: Eqn::T) -> Self {
+        let n = y.len();
+        let (is_algebraic, mass_diagonal) = Self::algebraic_components(eqn, y, dy, t);
+
+        // the jacobian has the columns of dF/dy' for the differential components and of dF/dy for the algebraic components,
+        // so has the sparsity of the residual jacobian, or the union of the mass and rhs jacobian sparsity
+        let (mass_sparsity, rhs_jac_sparsity) = if let Some(residual) = eqn.residual() {
+            (residual.sparsity(), residual.sparsity())
+        } else {
+            (eqn.mass().sparsity(), eqn.rhs().sparsity())
+        };
+        let (sparsity, jacobian_index) = match (mass_sparsity, rhs_jac_sparsity) {
+            _ if eqn.is_mass_state_dependent() => (None, None),
+            _ if eqn.is_implicit() => (eqn.residual().unwrap().sparsity().cloned(), None),
+            (Some(mass_sparsity), Some(rhs_jac_sparsity)) => {
+                let (sparsity, mass_index, rhs_index) =
+                    mass_sparsity.union_with_index(rhs_jac_sparsity).unwrap();
+                (Some(sparsity), Some((mass_index, rhs_index)))
+            }
+            _ => (None, None),
+        };
+        let rhs_jac = RefCell::new(Eqn::M::new_from_sparsity(n, n, rhs_jac_sparsity));
+        let mass_jac = RefCell::new(Eqn::M::new_from_sparsity(n, n, mass_sparsity));
+
+        Self {
+            eqn: eqn.clone(),
+            is_algebraic,
+            mass_diagonal,
+            y: RefCell::new(y.clone()),
+            dy: RefCell::new(dy.clone()),
+            v_y: RefCell::new(Eqn::V::zeros(n)),
+            v_dy: RefCell::new(Eqn::V::zeros(n)),
+            tmp: RefCell::new(Eqn::V::zeros(n)),
+            rhs_jac,
+            mass_jac,
+            sparsity,
+            jacobian_index,
+        }
+    }
+
+    /// Returns true for the components of the state that are algebraic (i.e. their time derivative does not appear in the equations).
+    pub fn is_algebraic(&self) -> &[bool] {
+        &self.is_algebraic
+    }
+
+    /// Returns the diagonal of the mass matrix if it is diagonal and does not depend on the state (so `y'` can be found directly
+    /// from `M y' = f(t, y)` when there are no algebraic components), otherwise `None`.
+    pub fn mass_diagonal(&self) -> Option<&Eqn::V> {
+        self.mass_diagonal.as_ref()
+    }
+
+    /// Pack the unknowns of the callable from the state `y` and its time derivative `dy`.
+    pub fn pack(&self, y: &Eqn::V, dy: &Eqn::V) -> Eqn::V {
+        let mut x = Eqn::V::zeros(y.len());
+        for (i, &is_algebraic) in self.is_algebraic.iter().enumerate() {
+            x[i] = if is_algebraic { y[i] } else { dy[i] };
+        }
+        x
+    }
+
+    /// Unpack the unknowns of the callable into the state `y` and its time derivative `dy`, the other components are left unchanged.
+    pub fn unpack(&self, x: &Eqn::V, y: &mut Eqn::V, dy: &mut Eqn::V) {
+        for (i, &is_algebraic) in self.is_algebraic.iter().enumerate() {
+            if is_algebraic {
+                y[i] = x[i];
+            } else {
+                dy[i] = x[i];
+            }
+        }
+    }
+
+    // the algebraic components, and the diagonal of the mass matrix if it is diagonal and does not depend on the state
+    fn algebraic_components(
+        eqn: &Eqn,
+        y: &Eqn::V,
+        dy: &Eqn::V,
+        t: Eqn::T,
+    ) -> (Vec<bool>, Option<Eqn::V>) {
+        let n = y.len();
+        let is_semi_explicit = !eqn.is_implicit() && !eqn.is_mass_state_dependent();
+        if is_semi_explicit {
+            if let Some(sparsity) = eqn.mass().sparsity().filter(|s| s.is_sparse()) {
+                if sparsity.indices().iter().all(|(i, j)| i == j) {
+                    let diagonal = eqn.mass().matrix(t).diagonal();
+                    let is_algebraic = (0..n).map(|i| diagonal[i] == Eqn::T::zero()).collect();
+                    return (is_algebraic, Some(diagonal));
+                }
+            }
+        }
+
+        // otherwise form dF/dy' column by column
+        let mut v = Eqn::V::zeros(n);
+        let mut col = Eqn::V::zeros(n);
+        let mut diagonal = Eqn::V::zeros(n);
+        let mut is_diagonal = true;
+        let mut columns = Vec::with_capacity(n);
+        for j in 0..n {
+            v[j] = Eqn::T::one();
+            Self::dfddy_mul(eqn, y, dy, t, &v, &mut col);
+            v[j] = Eqn::T::zero();
+            diagonal[j] = col[j];
+            is_diagonal = is_diagonal && (0..n).all(|i| i == j || col[i] == Eqn::T::zero());
+            columns.push((0..n).map(|i| col[i]).collect::<Vec<_>>());
+        }
+        if is_diagonal {
+            let is_algebraic = (0..n).map(|i| diagonal[i] == Eqn::T::zero()).collect();
+            return (is_algebraic, is_semi_explicit.then_some(diagonal));
+        }
+        let mut is_algebraic = vec![true; n];
+        for j in rank_revealing_columns(columns) {
+            is_algebraic[j] = false;
+        }
+        (is_algebraic, None)
+    }
+
+    // out = dF/dy' v
+    fn dfddy_mul(eqn: &Eqn, y: &Eqn::V, dy: &Eqn::V, t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        if let Some(residual) = eqn.residual() {
+            residual.jac_dot_mul_inplace(y, dy, t, v, out);
+        } else if let Some(state_mass) = eqn.state_mass() {
+            state_mass.gemv_inplace(y, t, v, Eqn::T::zero(), out);
+        } else {
+            LinearOp::call_inplace(eqn.mass().as_ref(), v, t, out);
+        }
+    }
+
+    // out = dF/dy v
+    fn dfdy_mul(&self, y: &Eqn::V, dy: &Eqn::V, t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        if let Some(residual) = self.eqn.residual() {
+            residual.jac_mul_inplace(y, dy, t, v, out);
+        } else {
+            // F = M(y) y' - f(t, y), so dF/dy v = d(M(y) y')/dy v - f'(y) v
+            self.eqn.rhs().jac_mul_inplace(y, t, v, out);
+            out.mul_assign(scale(-Eqn::T::one()));
+            if let Some(state_mass) = self.eqn.state_mass() {
+                let mut tmp = self.tmp.borrow_mut();
+                state_mass.jac_mul_inplace(y, t, dy, v, &mut tmp);
+                out.add_assign(&*tmp);
+            }
+        }
+    }
+
+    // y = mass_jac + beta * rhs_jac
+    fn assign_jacobian(&self, y: &mut Eqn::M, mass_jac: &Eqn::M, beta: Eqn::T, rhs_jac: &Eqn::M) {
+        if let Some((mass_index, rhs_index)) = self.jacobian_index.as_ref() {
+            y.scale_add_and_assign_mapped(mass_jac, mass_index, beta, rhs_jac, rhs_index);
+        } else {
+            y.scale_add_and_assign(mass_jac, beta, rhs_jac);
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> Op for InitCallable<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.is_algebraic.len()
+    }
+    fn nout(&self) -> usize {
+        self.is_algebraic.len()
+    }
+    fn nparams(&self) -> usize {
+        self.eqn.rhs().nparams()
+    }
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.sparsity.as_ref()
+    }
+}
+
+impl<Eqn: OdeEquations> NonLinearOp for InitCallable<Eqn> {
+    // F(t, y, y'), or M(y) y' - f(t, y)
+    fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, out: &mut Eqn::V) {
+        let mut y = self.y.borrow_mut();
+        let mut dy = self.dy.borrow_mut();
+        self.unpack(x, &mut y, &mut dy);
+        if let Some(residual) = self.eqn.residual() {
+            residual.call_inplace(&y, &dy, t, out);
+        } else {
+            self.eqn.rhs().call_inplace(&y, t, out);
+            if let Some(state_mass) = self.eqn.state_mass() {
+                state_mass.gemv_inplace(&y, t, &dy, -Eqn::T::one(), out);
+            } else {
+                self.eqn.mass().gemv_inplace(&dy, t, -Eqn::T::one(), out);
+            }
+        }
+    }
+
+    // dF/dy v_y + dF/dy' v_dy, where v_y and v_dy are the algebraic and differential components of v
+    fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        let mut y = self.y.borrow_mut();
+        let mut dy = self.dy.borrow_mut();
+        self.unpack(x, &mut y, &mut dy);
+        let mut v_y = self.v_y.borrow_mut();
+        let mut v_dy = self.v_dy.borrow_mut();
+        for (i, &is_algebraic) in self.is_algebraic.iter().enumerate() {
+            if is_algebraic {
+                v_y[i] = v[i];
+                v_dy[i] = Eqn::T::zero();
+            } else {
+                v_y[i] = Eqn::T::zero();
+                v_dy[i] = v[i];
+            }
+        }
+        self.dfdy_mul(&y, &dy, t, &v_y, out);
+        let mut tmp = self.tmp.borrow_mut();
+        Self::dfddy_mul(&self.eqn, &y, &dy, t, &v_dy, &mut tmp);
+        out.add_assign(&*tmp);
+    }
+
+    // the differential columns of dF/dy' plus the algebraic columns of dF/dy
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        if self.eqn.is_mass_state_dependent() {
+            // the mass matrix and its derivative change with the state, so calculate the jacobian column by column
+            self._default_jacobian_inplace(x, t, y);
+            return;
+        }
+        let (mut state, mut state_dot) = (self.y.borrow_mut(), self.dy.borrow_mut());
+        self.unpack(x, &mut state, &mut state_dot);
+        let mut rhs_jac = self.rhs_jac.borrow_mut();
+        let mut mass_jac = self.mass_jac.borrow_mut();
+        let beta = if let Some(residual) = self.eqn.residual() {
+            // dF/dy' is the difference of the jacobians of the residual with c = 1 and c = 0
+            residual.jacobian_inplace(&state, &state_dot, t, Eqn::T::zero(), &mut rhs_jac);
+            residual.jacobian_inplace(&state, &state_dot, t, Eqn::T::one(), &mut mass_jac);
+            let jac = mass_jac.clone();
+            mass_jac.scale_add_and_assign(&jac, -Eqn::T::one(), rhs_jac.deref());
+            Eqn::T::one()
+        } else {
+            self.eqn.rhs().jacobian_inplace(&state, t, &mut rhs_jac);
+            self.eqn.mass().matrix_inplace(t, &mut mass_jac);
+            -Eqn::T::one()
+        };
+        let zeros = Eqn::V::zeros(self.nstates());
+        for (j, &is_algebraic) in self.is_algebraic.iter().enumerate() {
+            if is_algebraic {
+                mass_jac.set_column(j, &zeros);
+            } else {
+                rhs_jac.set_column(j, &zeros);
+            }
+        }
+        self.assign_jacobian(y, mass_jac.deref(), beta, rhs_jac.deref());
+    }
+}
+
+/// Returns the indices of a maximal set of linearly independent columns, using Gram-Schmidt orthogonalisation with column pivoting
+/// (at each step the remaining column with the largest norm is chosen). A column is considered dependent if its norm, after removing
+/// the components in the direction of the chosen columns, is less than `sqrt(EPSILON)` times the largest initial column norm.
+fn rank_revealing_columns<T: Scalar>(mut columns: Vec<Vec<T>>) -> Vec<usize> {
+    let norm2 = |c: &Vec<T>| c.iter().fold(T::zero(), |acc, &x| acc + x * x);
+    let mut norms2 = columns.iter().map(norm2).collect::<Vec<_>>();
+    let max_norm2 = norms2
+        .iter()
+        .fold(T::zero(), |acc, &x| if x > acc { x } else { acc });
+    let tol2 = T::EPSILON * max_norm2;
+    let mut chosen = vec![false; columns.len()];
+    let mut ret = Vec::new();
+    loop {
+        // on ties the first column is chosen, so the result is deterministic
+        let pivot = (0..columns.len()).filter(|&j| !chosen[j]).fold(
+            None,
+            |acc: Option<usize>, j| match acc {
+                Some(p) if norms2[p] >= norms2[j] => Some(p),
+                _ => Some(j),
+            },
+        );
+        let Some(pivot) = pivot else {
+            break;
+        };
+        if norms2[pivot] <= tol2 {
+            break;
+        }
+        chosen[pivot] = true;
+        ret.push(pivot);
+        let inv_norm = T::one() / norms2[pivot].pow(T::from(0.5));
+        let q = columns[pivot]
+            .iter()
+            .map(|&x| x * inv_norm)
+            .collect::<Vec<_>>();
+        for j in 0..columns.len() {
+            if chosen[j] {
+                continue;
+            }
+            let dot = q
+                .iter()
+                .zip(columns[j].iter())
+                .fold(T::zero(), |acc, (&a, &b)| acc + a * b);
+            for (c, &qi) in columns[j].iter_mut().zip(q.iter()) {
+                *c -= dot * qi;
+            }
+            norms2[j] = norm2(&columns[j]);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::jacobian::check::check_jacobian;
+    use crate::ode_solver::test_models::{
+        exponential_decay::exponential_decay_problem,
+        implicit_exponential_decay::implicit_exponential_decay_problem, robertson::robertson,
+    };
+    use crate::{
+        NalgebraLU, NewtonNonlinearSolver, NonLinearOp, NonLinearSolver, OdeBuilder, OdeEquations,
+        OdeSolverState, Op, Vector,
+    };
+
+    use super::{rank_revealing_columns, InitCallable};
+    type Mcpu = nalgebra::DMatrix<f64>;
+    type Msparse = nalgebra_sparse::CscMatrix<f64>;
+    type Vcpu = nalgebra::DVector<f64>;
+
+    // the assembled jacobian should match the one calculated column by column
+    fn check_assembled_jacobian<Eqn: OdeEquations<M = Mcpu, V = Vcpu, T = f64>>(
+        init: &InitCallable<Eqn>,
+        x: &Vcpu,
+    ) {
+        let jac = init.jacobian(x, 0.0);
+        let mut expect = Mcpu::zeros(x.len(), x.len());
+        init._default_jacobian_inplace(x, 0.0, &mut expect);
+        assert!((jac - expect).amax() < 1e-12);
+    }
+
+    #[test]
+    fn test_rank_revealing_columns() {
+        // | 1 1 0 |
+        // | 0 0 0 |
+        // | 0 0 2 |
+        let columns = vec![
+            vec![1.0, 0.0, 0.0],
+            vec![1.0, 0.0, 0.0],
+            vec![0.0, 0.0, 2.0],
+        ];
+        let mut independent = rank_revealing_columns(columns);
+        independent.sort();
+        assert_eq!(independent, vec![0, 2]);
+        assert!(rank_revealing_columns(vec![vec![0.0, 0.0], vec![0.0, 0.0]]).is_empty());
+    }
+
+    #[test]
+    fn test_init_callable_non_diagonal_mass() {
+        // d(y + z)/dt = -y
+        // 0 = z - 2 y
+        // the second row and column of the mass matrix are zero, but so is the (1, 1) entry of the first row
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<Mcpu, _, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = -x[0];
+                    y[1] = x[1] - 2.0 * x[0];
+                },
+                |_x, _p, _t, v, y| {
+                    y[0] = -v[0];
+                    y[1] = v[1] - 2.0 * v[0];
+                },
+                |v, _p, _t, beta, y| {
+                    y[0] = v[0] + v[1] + beta * y[0];
+                    y[1] *= beta;
+                },
+                |_p, _t| Vcpu::from_vec(vec![1.0, 0.0]),
+            )
+            .unwrap();
+        let y = problem.eqn.init(0.0);
+        let dy = Vcpu::zeros(2);
+        let init = InitCallable::new(&problem.eqn, &y, &dy, 0.0);
+        assert_eq!(init.is_algebraic(), &[false, true]);
+        assert!(init.mass_diagonal().is_none());
+        let x = init.pack(&y, &dy);
+        let check = check_jacobian(&init, &x, 0.0);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+        check_assembled_jacobian(&init, &x);
+    }
+
+    #[test]
+    fn test_init_callable_implicit_dae() {
+        let (problem, _soln) = implicit_exponential_decay_problem::<Mcpu>();
+        let eqn = Rc::clone(&problem.eqn);
+        let y = Vcpu::from_vec(vec![1.0, 2.0, 0.5]);
+        let dy = Vcpu::from_vec(vec![0.1, 0.2, 0.3]);
+        let init = InitCallable::new(&eqn, &y, &dy, 0.0);
+        assert_eq!(init.is_algebraic(), &[false, false, true]);
+        let x = init.pack(&y, &dy);
+        let check = check_jacobian(&init, &x, 0.0);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+        check_assembled_jacobian(&init, &x);
+    }
+
+    #[test]
+    fn test_init_callable_sparse_diagonal_mass() {
+        // the algebraic components of a sparse diagonal mass are found from its sparsity, and the jacobian is sparse
+        let (problem, _soln) = robertson::<Msparse>(true);
+        let y = problem.eqn.init(0.0);
+        let dy = Vcpu::zeros(3);
+        let init = InitCallable::new(&problem.eqn, &y, &dy, 0.0);
+        assert_eq!(init.is_algebraic(), &[false, false, true]);
+        assert!(init.mass_diagonal().is_some());
+        assert!(init.sparsity().is_some());
+        let x = init.pack(&y, &dy);
+        let jac = Mcpu::from(&init.jacobian(&x, 0.0));
+        let (problem_dense, _soln) = robertson::<Mcpu>(false);
+        let init_dense = InitCallable::new(&problem_dense.eqn, &y, &dy, 0.0);
+        assert!((jac - init_dense.jacobian(&x, 0.0)).amax() < 1e-12);
+    }
+
+    #[test]
+    fn test_new_consistent_without_algebraic_components() {
+        // y' is found directly from the rhs for an identity mass
+        let (problem, _soln) = exponential_decay_problem::<Mcpu>(false);
+        let mut root_solver = NewtonNonlinearSolver::new(NalgebraLU::default());
+        let state = OdeSolverState::new_consistent(&problem, &mut root_solver).unwrap();
+        state
+            .dy
+            .assert_eq_st(&problem.eqn.rhs().call(&state.y, 0.0), 1e-14);
+        assert_eq!(root_solver.niter(), 0);
+    }
+}
@@ -1,7 +1,7 @@
 use std::{cell::RefCell, rc::Rc};
 
 use crate::{
-    jacobian::{find_non_zeros_linear, JacobianColoring},
+    jacobian::{find_non_zeros_linear, ColoringAlgorithm, JacobianColoring},
     matrix::{MatrixCommon, MatrixSparsity},
     Matrix, Vector,
 };
@@ -60,7 +60,11 @@ where
             MatrixSparsity::try_from_indices(self.nout(), self.nstates(), non_zeros.clone())
                 .expect("invalid sparsity pattern"),
         );
-        self.coloring = Some(JacobianColoring::new_from_non_zeros(self, non_zeros));
+        self.coloring = Some(JacobianColoring::new_from_non_zeros(
+            self,
+            non_zeros,
+            ColoringAlgorithm::default(),
+        ));
     }
 }
 
@@ -5,15 +5,27 @@ use crate::{Matrix, Scalar, Vector};
 use num_traits::{One, Zero};
 use serde::Serialize;
 
+pub mod arclength;
 pub mod bdf;
+pub mod bvp;
 pub mod closure;
+pub mod closure_autodiff;
 pub mod closure_no_jac;
 pub mod constant_closure;
+pub mod delay_closure;
 pub mod filter;
+pub mod generalised_alpha;
+pub mod implicit_dae;
+pub mod init;
 pub mod linear_closure;
 pub mod linearise;
 pub mod matrix;
+pub mod multirate;
+pub mod residual_closure;
 pub mod sdirk;
+pub mod shooting;
+pub mod state_mass_closure;
+pub mod steady_state;
 pub mod unit;
 
 /// Op is a trait for operators that, given a paramter vector `p`, operates on an input vector `x` to produce an output vector `y`.
@@ -169,6 +181,105 @@ pub trait LinearOp: Op {
     }
 }
 
+/// StateMassOp is a trait for a mass matrix `M(t, y)` that depends on the state `y` as well as time. The operator is linear in the vector
+/// it is applied to, and the state at which it is evaluated is given to each method.
+pub trait StateMassOp: Op {
+    /// Compute the operator via a GEMV operation at a given state `x` and time (i.e. `y = M(t, x) v + beta * y`)
+    fn gemv_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, beta: Self::T, y: &mut Self::V);
+
+    /// Compute the product of the jacobian of `M(t, x) v` with respect to the state with the vector `w` (i.e. `y = d(M(t, x) v)/dx w`)
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, w: &Self::V, y: &mut Self::V);
+
+    /// Compute the matrix representation of the operator at a given state and time and store it in the matrix `y`.
+    fn matrix_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        let mut v = Self::V::zeros(self.nstates());
+        let mut col = Self::V::zeros(self.nout());
+        for j in 0..self.nstates() {
+            v[j] = Self::T::one();
+            self.gemv_inplace(x, t, &v, Self::T::zero(), &mut col);
+            y.set_column(j, &col);
+            v[j] = Self::T::zero();
+        }
+    }
+}
+
+/// ResidualOp is a trait for the residual `F(t, y, y')` of a fully implicit DAE `F(t, y, y') = 0`. It extends the Op trait with methods for
+/// computing the residual and the action of its jacobians with respect to `y` and `y'`.
+pub trait ResidualOp: Op {
+    /// Compute the residual at a given state `x`, time derivative `x_dot` and time.
+    fn call_inplace(&self, x: &Self::V, x_dot: &Self::V, t: Self::T, y: &mut Self::V);
+
+    /// Compute the product of the jacobian with respect to the state `dF/dy` with a given vector.
+    fn jac_mul_inplace(
+        &self,
+        x: &Self::V,
+        x_dot: &Self::V,
+        t: Self::T,
+        v: &Self::V,
+        y: &mut Self::V,
+    );
+
+    /// Compute the product of the jacobian with respect to the time derivative `dF/dy'` with a given vector.
+    fn jac_dot_mul_inplace(
+        &self,
+        x: &Self::V,
+        x_dot: &Self::V,
+        t: Self::T,
+        v: &Self::V,
+        y: &mut Self::V,
+    );
+
+    /// Compute the residual at a given state, time derivative and time, and return the result.
+    fn call(&self, x: &Self::V, x_dot: &Self::V, t: Self::T) -> Self::V {
+        let mut y = Self::V::zeros(self.nout());
+        self.call_inplace(x, x_dot, t, &mut y);
+        y
+    }
+
+    /// Compute the combined jacobian `dF/dy + c_j dF/dy'` (the form used by IDA) and store it in the matrix `y`.
+    /// `y` should have been previously initialised using the output of [`Op::sparsity`].
+    fn jacobian_inplace(
+        &self,
+        x: &Self::V,
+        x_dot: &Self::V,
+        t: Self::T,
+        c_j: Self::T,
+        y: &mut Self::M,
+    ) {
+        self._default_jacobian_inplace(x, x_dot, t, c_j, y);
+    }
+
+    /// Default implementation of the combined jacobian computation.
+    fn _default_jacobian_inplace(
+        &self,
+        x: &Self::V,
+        x_dot: &Self::V,
+        t: Self::T,
+        c_j: Self::T,
+        y: &mut Self::M,
+    ) {
+        let mut v = Self::V::zeros(self.nstates());
+        let mut col = Self::V::zeros(self.nout());
+        let mut col_dot = Self::V::zeros(self.nout());
+        for j in 0..self.nstates() {
+            v[j] = Self::T::one();
+            self.jac_mul_inplace(x, x_dot, t, &v, &mut col);
+            self.jac_dot_mul_inplace(x, x_dot, t, &v, &mut col_dot);
+            col.axpy(c_j, &col_dot, Self::T::one());
+            y.set_column(j, &col);
+            v[j] = Self::T::zero();
+        }
+    }
+
+    /// Compute the combined jacobian `dF/dy + c_j dF/dy'` and return it.
+    fn jacobian(&self, x: &Self::V, x_dot: &Self::V, t: Self::T, c_j: Self::T) -> Self::M {
+        let n = self.nstates();
+        let mut y = Self::M::new_from_sparsity(n, n, self.sparsity());
+        self.jacobian_inplace(x, x_dot, t, c_j, &mut y);
+        y
+    }
+}
+
 pub trait ConstantOp: Op {
     fn call_inplace(&self, t: Self::T, y: &mut Self::V);
     fn call(&self, t: Self::T) -> Self::V {
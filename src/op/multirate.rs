@@ -0,0 +1,134 @@
+// callables for the fast partition of a multirate method: the fast part of the right-hand side given by a subset of the states,
+// and the fast right-hand side plus the forcing from the slow part that is integrated by the inner solver
+
+use std::{cell::RefCell, rc::Rc};
+
+use num_traits::{One, Zero};
+
+use crate::{IndexType, Matrix, Vector, VectorIndex};
+
+use super::{NonLinearOp, Op, OpStatistics};
+
+/// The fast part of a right-hand side `F(t, y)` for a multirate method, given by the equations of a subset of the (fast) states,
+/// i.e. the components of `F(t, y)` for the fast states and zero for the remaining (slow) states.
+pub struct FastStatesCallable<C: NonLinearOp> {
+    callable: Rc<C>,
+    slow_indices: <C::V as Vector>::Index,
+}
+
+impl<C: NonLinearOp> FastStatesCallable<C> {
+    /// Create the fast part of `callable` from the indices of the fast states.
+    pub fn new(callable: Rc<C>, fast_indices: &[IndexType]) -> Self {
+        let n = callable.nout();
+        assert!(
+            fast_indices.iter().all(|&i| i < n),
+            "Fast state index out of bounds"
+        );
+        let slow: Vec<IndexType> = (0..n).filter(|i| !fast_indices.contains(i)).collect();
+        let slow_indices = <C::V as Vector>::Index::from_slice(&slow);
+        Self {
+            callable,
+            slow_indices,
+        }
+    }
+}
+
+impl<C: NonLinearOp> Op for FastStatesCallable<C> {
+    type V = C::V;
+    type T = C::T;
+    type M = C::M;
+    fn nstates(&self) -> usize {
+        self.callable.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.callable.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.callable.nparams()
+    }
+    // the parameters are shared with the wrapped callable
+    fn set_params(&mut self, _p: Rc<Self::V>) {}
+}
+
+impl<C: NonLinearOp> NonLinearOp for FastStatesCallable<C> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.callable.call_inplace(x, t, y);
+        y.assign_at_indices(&self.slow_indices, Self::T::zero());
+    }
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.callable.jac_mul_inplace(x, t, v, y);
+        y.assign_at_indices(&self.slow_indices, Self::T::zero());
+    }
+}
+
+// the forcing g(t) = g0 + g1 (t - t0) / dt from the slow part over a stage of a multirate method
+pub(crate) struct MriForcing<V: Vector> {
+    pub(crate) t0: V::T,
+    pub(crate) dt: V::T,
+    pub(crate) g0: V,
+    pub(crate) g1: V,
+}
+
+impl<V: Vector> MriForcing<V> {
+    pub(crate) fn new(n: usize) -> Self {
+        Self {
+            t0: V::T::zero(),
+            dt: V::T::one(),
+            g0: V::zeros(n),
+            g1: V::zeros(n),
+        }
+    }
+}
+
+/// The right-hand side `F_fast(t, v) + g(t)` of the modified fast equations that are integrated by the inner solver of a multirate method,
+/// where `g(t)` is the forcing from the slow part of the right-hand side over the current stage. The forcing does not depend on the state,
+/// so the jacobian is that of the fast part.
+pub struct MriFastCallable<F: NonLinearOp> {
+    fast: Rc<F>,
+    forcing: Rc<RefCell<MriForcing<F::V>>>,
+}
+
+impl<F: NonLinearOp> MriFastCallable<F> {
+    pub(crate) fn new(fast: Rc<F>, forcing: Rc<RefCell<MriForcing<F::V>>>) -> Self {
+        Self { fast, forcing }
+    }
+}
+
+impl<F: NonLinearOp> Op for MriFastCallable<F> {
+    type V = F::V;
+    type T = F::T;
+    type M = F::M;
+    fn nstates(&self) -> usize {
+        self.fast.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.fast.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.fast.nparams()
+    }
+    // the parameters are shared with the fast callable
+    fn set_params(&mut self, _p: Rc<Self::V>) {}
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        self.fast.sparsity()
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.fast.statistics()
+    }
+}
+
+impl<F: NonLinearOp> NonLinearOp for MriFastCallable<F> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.fast.call_inplace(x, t, y);
+        let forcing = self.forcing.borrow();
+        let theta = (t - forcing.t0) / forcing.dt;
+        y.axpy(Self::T::one(), &forcing.g0, Self::T::one());
+        y.axpy(theta, &forcing.g1, Self::T::one());
+    }
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.fast.jac_mul_inplace(x, t, v, y);
+    }
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.fast.jacobian_inplace(x, t, y);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{Matrix, Vector};
+
+use super::{Op, OpStatistics, ResidualOp};
+
+/// The residual `F(t, y, y')` of a fully implicit DAE, given as closures.
+///
+/// - `func`: computes the residual, called as `func(x, x_dot, p, t, y)`
+/// - `jac`: computes the action of the jacobian with respect to the state `dF/dy v`, called as `jac(x, x_dot, p, t, v, y)`
+/// - `jac_dot`: computes the action of the jacobian with respect to the time derivative `dF/dy' v`, called as `jac_dot(x, x_dot, p, t, v, y)`
+pub struct ResidualClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    func: F,
+    jac: G,
+    jac_dot: H,
+    nstates: usize,
+    nparams: usize,
+    p: Rc<M::V>,
+    statistics: RefCell<OpStatistics>,
+}
+
+impl<M, F, G, H> ResidualClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    pub fn new(func: F, jac: G, jac_dot: H, nstates: usize, p: Rc<M::V>) -> Self {
+        let nparams = p.len();
+        Self {
+            func,
+            jac,
+            jac_dot,
+            nstates,
+            nparams,
+            p,
+            statistics: RefCell::new(OpStatistics::default()),
+        }
+    }
+}
+
+impl<M, F, G, H> Op for ResidualClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    type V = M::V;
+    type T = M::T;
+    type M = M;
+    fn nstates(&self) -> usize {
+        self.nstates
+    }
+    fn nout(&self) -> usize {
+        self.nstates
+    }
+    fn nparams(&self) -> usize {
+        self.nparams
+    }
+    fn set_params(&mut self, p: Rc<M::V>) {
+        assert_eq!(p.len(), self.nparams);
+        self.p = p;
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.statistics.borrow().clone()
+    }
+}
+
+impl<M, F, G, H> ResidualOp for ResidualClosure<M, F, G, H>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+    H: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    fn call_inplace(&self, x: &M::V, x_dot: &M::V, t: M::T, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_call();
+        (self.func)(x, x_dot, self.p.as_ref(), t, y)
+    }
+    fn jac_mul_inplace(&self, x: &M::V, x_dot: &M::V, t: M::T, v: &M::V, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_jac_mul();
+        (self.jac)(x, x_dot, self.p.as_ref(), t, v, y)
+    }
+    fn jac_dot_mul_inplace(&self, x: &M::V, x_dot: &M::V, t: M::T, v: &M::V, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_jac_mul();
+        (self.jac_dot)(x, x_dot, self.p.as_ref(), t, v, y)
+    }
+    fn jacobian_inplace(&self, x: &M::V, x_dot: &M::V, t: M::T, c_j: M::T, y: &mut M) {
+        self.statistics.borrow_mut().increment_matrix();
+        self._default_jacobian_inplace(x, x_dot, t, c_j, y);
+    }
+}
@@ -1,7 +1,6 @@
 use crate::{
-    matrix::{MatrixRef, MatrixView},
-    ode_solver::equations::OdeEquations,
-    LinearOp, Matrix, MatrixSparsity, OdeSolverProblem, Vector, VectorRef,
+    matrix::MatrixView, ode_solver::equations::OdeEquations, IndexType, LinearOp, Matrix,
+    MatrixSparsity, OdeSolverProblem, Vector, VectorRef,
 };
 use num_traits::{One, Zero};
 use std::{
@@ -19,11 +18,13 @@ pub struct SdirkCallable<Eqn: OdeEquations> {
     h: RefCell<Eqn::T>,
     phi: RefCell<Eqn::V>,
     tmp: RefCell<Eqn::V>,
+    mass_state_jac_mul: RefCell<Eqn::V>,
     rhs_jac: RefCell<Eqn::M>,
     mass_jac: RefCell<Eqn::M>,
     jacobian_is_stale: RefCell<bool>,
     number_of_jac_evals: RefCell<usize>,
     sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+    jacobian_index: Option<(Vec<IndexType>, Vec<IndexType>)>,
 }
 
 impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
@@ -35,15 +36,23 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
         let jacobian_is_stale = RefCell::new(true);
         let number_of_jac_evals = RefCell::new(0);
         let tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
+        let mass_state_jac_mul = RefCell::new(<Eqn::V as Vector>::zeros(n));
 
         // create the mass and rhs jacobians according to the sparsity pattern
         let mass_sparsity = eqn.mass().sparsity();
         let rhs_jac_sparsity = eqn.rhs().sparsity();
         let rhs_jac = RefCell::new(Eqn::M::new_from_sparsity(n, n, rhs_jac_sparsity));
-        let sparsity = if let Some(rhs_jac_sparsity) = rhs_jac_sparsity {
-            mass_sparsity.map(|mass_sparsity| mass_sparsity.union(rhs_jac_sparsity).unwrap())
-        } else {
-            None
+        // the jacobian has the union of the mass and rhs jacobian sparsity, precompute where the
+        // non-zeros of each are in the union so the jacobian can be formed in a single pass
+        // (a state-dependent mass has no sparsity, so the jacobian is dense)
+        let (sparsity, jacobian_index) = match (mass_sparsity, rhs_jac_sparsity) {
+            _ if eqn.is_mass_state_dependent() => (None, None),
+            (Some(mass_sparsity), Some(rhs_jac_sparsity)) => {
+                let (sparsity, mass_index, rhs_index) =
+                    mass_sparsity.union_with_index(rhs_jac_sparsity).unwrap();
+                (Some(sparsity), Some((mass_index, rhs_index)))
+            }
+            _ => (None, None),
         };
 
         // if mass is constant then pre-compute it
@@ -61,26 +70,24 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
             rhs_jac,
             mass_jac,
             sparsity,
+            jacobian_index,
             jacobian_is_stale,
             number_of_jac_evals,
             tmp,
+            mass_state_jac_mul,
         }
     }
 
     pub fn number_of_jac_evals(&self) -> usize {
         *self.number_of_jac_evals.borrow()
     }
-    pub fn set_h(&self, h: Eqn::T)
-    where
-        for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
-    {
+    pub fn set_h(&self, h: Eqn::T) {
         self.h.replace(h);
     }
-    pub fn get_last_f_eval(&self) -> Ref<Eqn::V> {
+    pub fn get_last_f_eval(&self) -> Ref<'_, Eqn::V> {
         self.tmp.borrow()
     }
-    #[allow(dead_code)]
-    fn set_phi_direct(&self, phi: Eqn::V) {
+    pub fn set_phi_direct(&self, phi: Eqn::V) {
         let mut phi_ref = self.phi.borrow_mut();
         phi_ref.copy_from(&phi);
     }
@@ -108,6 +115,15 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
     pub fn set_jacobian_is_stale(&self) {
         self.jacobian_is_stale.replace(true);
     }
+
+    // y = mass_jac + beta * rhs_jac
+    fn assign_jacobian(&self, y: &mut Eqn::M, mass_jac: &Eqn::M, beta: Eqn::T, rhs_jac: &Eqn::M) {
+        if let Some((mass_index, rhs_index)) = self.jacobian_index.as_ref() {
+            y.scale_add_and_assign_mapped(mass_jac, mass_index, beta, rhs_jac, rhs_index);
+        } else {
+            y.scale_add_and_assign(mass_jac, beta, rhs_jac);
+        }
+    }
 }
 
 impl<Eqn: OdeEquations> Op for SdirkCallable<Eqn> {
@@ -131,7 +147,6 @@ impl<Eqn: OdeEquations> Op for SdirkCallable<Eqn> {
 impl<Eqn: OdeEquations> NonLinearOp for SdirkCallable<Eqn>
 where
     for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
-    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
 {
     // F(y) = M (y) - h f(phi + c * y) = 0
     fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
@@ -141,10 +156,14 @@ where
 
         self.eqn.rhs().call_inplace(&tmp, t, y);
 
-        // y = Mx - h y
-        self.eqn.mass().gemv_inplace(x, t, -h, y);
+        // y = M(phi + c * x) x - h y
+        if let Some(state_mass) = self.eqn.state_mass() {
+            state_mass.gemv_inplace(&tmp, t, x, -h, y);
+        } else {
+            self.eqn.mass().gemv_inplace(x, t, -h, y);
+        }
     }
-    // (M - c * h * f'(phi + c * y)) v
+    // (M - c * h * f'(phi + c * y)) v, plus c * d(M(phi + c * y) y)/dy v if the mass depends on the state
     fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
         self.set_tmp(x);
         let tmp = self.tmp.borrow();
@@ -154,14 +173,24 @@ where
         self.eqn.rhs().jac_mul_inplace(&tmp, t, v, y);
 
         // y = Mv - c h y
-        self.eqn.mass().gemv_inplace(v, t, -c * h, y);
+        if let Some(state_mass) = self.eqn.state_mass() {
+            state_mass.gemv_inplace(&tmp, t, v, -c * h, y);
+            let mut mass_state_jac_mul = self.mass_state_jac_mul.borrow_mut();
+            state_mass.jac_mul_inplace(&tmp, t, x, v, &mut mass_state_jac_mul);
+            y.axpy(c, &mass_state_jac_mul, Eqn::T::one());
+        } else {
+            self.eqn.mass().gemv_inplace(v, t, -c * h, y);
+        }
     }
 
     // M - c * h * f'(phi + c * y)
     fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
         let c = self.c;
         let h = *self.h.borrow().deref();
-        if *self.jacobian_is_stale.borrow() {
+        if self.eqn.is_mass_state_dependent() {
+            // the mass matrix and its derivative change with the state, so calculate the jacobian column by column
+            self._default_jacobian_inplace(x, t, y);
+        } else if *self.jacobian_is_stale.borrow() {
             // calculate the mass and rhs jacobians
             let mut rhs_jac = self.rhs_jac.borrow_mut();
             self.set_tmp(x);
@@ -170,18 +199,18 @@ where
 
             if self.eqn.is_mass_constant() {
                 let mass_jac = self.mass_jac.borrow();
-                y.scale_add_and_assign(mass_jac.deref(), -(c * h), rhs_jac.deref());
+                self.assign_jacobian(y, mass_jac.deref(), -(c * h), rhs_jac.deref());
             } else {
                 let mut mass_jac = self.mass_jac.borrow_mut();
                 self.eqn.mass().matrix_inplace(t, &mut mass_jac);
-                y.scale_add_and_assign(mass_jac.deref(), -(c * h), rhs_jac.deref());
+                self.assign_jacobian(y, mass_jac.deref(), -(c * h), rhs_jac.deref());
             }
             self.jacobian_is_stale.replace(false);
         } else {
             // only h has changed, so just do the addition
             let rhs_jac = self.rhs_jac.borrow();
             let mass_jac = self.mass_jac.borrow();
-            y.scale_add_and_assign(mass_jac.deref(), -(c * h), rhs_jac.deref());
+            self.assign_jacobian(y, mass_jac.deref(), -(c * h), rhs_jac.deref());
         }
         let number_of_jac_evals = *self.number_of_jac_evals.borrow() + 1;
         self.number_of_jac_evals.replace(number_of_jac_evals);
@@ -190,14 +219,17 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::jacobian::check::check_jacobian;
     use crate::ode_solver::test_models::exponential_decay::exponential_decay_problem;
     use crate::ode_solver::test_models::robertson::robertson;
-    use crate::op::NonLinearOp;
+    use crate::ode_solver::test_models::state_dependent_mass::state_dependent_mass_problem;
+    use crate::op::{NonLinearOp, Op};
     use crate::vector::Vector;
-    use crate::Matrix;
+    use crate::{Matrix, OdeEquations};
 
     use super::SdirkCallable;
     type Mcpu = nalgebra::DMatrix<f64>;
+    type Msparse = nalgebra_sparse::CscMatrix<f64>;
     type Vcpu = nalgebra::DVector<f64>;
 
     #[test]
@@ -222,6 +254,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sdirk_sparse_jacobian() {
+        let (problem, _soln) = robertson::<Msparse>(true);
+        let (problem_dense, _soln) = robertson::<Mcpu>(true);
+        let c = 0.1;
+        let phi = Vcpu::from_vec(vec![1.1, 1.2, 1.3]);
+        let y = Vcpu::from_vec(vec![1.1, 1.2, 1.3]);
+        let t = 0.9;
+        let sdirk_callable = SdirkCallable::new(&problem, c);
+        let sdirk_callable_dense = SdirkCallable::new(&problem_dense, c);
+        sdirk_callable.set_phi_direct(phi.clone());
+        sdirk_callable_dense.set_phi_direct(phi);
+        // mass and rhs jacobian have different sparsity
+        assert_eq!(problem.eqn.mass().sparsity().unwrap().nnz(), 2);
+        assert_eq!(sdirk_callable.sparsity().unwrap().nnz(), 9);
+        let mut jac = Msparse::new_from_sparsity(3, 3, sdirk_callable.sparsity());
+        // check a stale and a non-stale jacobian (only h has changed)
+        for h in [1.3, 0.7] {
+            sdirk_callable.set_h(h);
+            sdirk_callable_dense.set_h(h);
+            sdirk_callable.jacobian_inplace(&y, t, &mut jac);
+            let jac_dense = sdirk_callable_dense.jacobian(&y, t);
+            let mut v = Vcpu::zeros(3);
+            for j in 0..3 {
+                v[j] = 1.0;
+                let mut col = Vcpu::zeros(3);
+                let mut col_dense = Vcpu::zeros(3);
+                jac.gemv(1.0, &v, 0.0, &mut col);
+                jac_dense.gemv(1.0, &v, 0.0, &mut col_dense);
+                col.assert_eq_st(&col_dense, 1e-10);
+                v[j] = 0.0;
+            }
+        }
+    }
+
+    #[test]
+    fn test_sdirk_state_dependent_mass_jacobian() {
+        let (problem, _soln) = state_dependent_mass_problem::<Mcpu>();
+        let sdirk_callable = SdirkCallable::new(&problem, 0.3);
+        sdirk_callable.set_h(0.2);
+        sdirk_callable.set_phi_direct(Vcpu::from_vec(vec![1.1, 1.9, 1.0]));
+        let y = Vcpu::from_vec(vec![0.3, -0.2, 0.1]);
+        let check = check_jacobian(&sdirk_callable, &y, 0.5);
+        assert!(check.is_ok(1e-6), "{:?}", check.errors(1e-6));
+    }
+
     #[test]
     fn test_sdirk_callable() {
         let (problem, _soln) = exponential_decay_problem::<Mcpu>(false);
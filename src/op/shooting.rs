@@ -0,0 +1,232 @@
+// callable for finding a periodic orbit of an ODE using single shooting, the unknowns are x = (y0, T) and the residual is
+// H(x) = (y(T; y0) - y0, f(y_ref) . (y0 - y_ref))
+
+use std::cell::RefCell;
+
+use anyhow::Result;
+use num_traits::{One, Pow, Zero};
+
+use crate::{
+    ode_solver::equations::OdeEquations, NonLinearOp, OdeSolverMethod, OdeSolverProblem,
+    OdeSolverState, OdeSolverStopReason, Op, Scalar, Vector,
+};
+
+/// Solves the ODE for the shooting callables ([ShootingCallable] and [crate::op::bvp::MultipleShootingCallable]) using the given ODE solver,
+/// caching a value computed from the solution for the last value of the unknowns `x`. As a callable can only return a value to the nonlinear
+/// solver, if a solve fails the error is recorded (see [Self::take_error]) and the solution is replaced with `NaN`, so that the nonlinear
+/// solver fails.
+pub struct ShootingSolver<'a, Eqn: OdeEquations, S: OdeSolverMethod<Eqn>, C> {
+    solver: RefCell<&'a mut S>,
+    problem: &'a OdeSolverProblem<Eqn>,
+    last: RefCell<Option<(Eqn::V, C)>>,
+    number_of_solves: RefCell<usize>,
+    error: RefCell<Option<anyhow::Error>>,
+}
+
+impl<'a, Eqn: OdeEquations, S: OdeSolverMethod<Eqn>, C: Clone> ShootingSolver<'a, Eqn, S, C> {
+    pub fn new(solver: &'a mut S, problem: &'a OdeSolverProblem<Eqn>) -> Self {
+        Self {
+            solver: RefCell::new(solver),
+            problem,
+            last: RefCell::new(None),
+            number_of_solves: RefCell::new(0),
+            error: RefCell::new(None),
+        }
+    }
+
+    pub fn problem(&self) -> &'a OdeSolverProblem<Eqn> {
+        self.problem
+    }
+
+    /// The number of times the ODE has been solved.
+    pub fn number_of_solves(&self) -> usize {
+        *self.number_of_solves.borrow()
+    }
+
+    /// Returns the error from the first failed solve of the ODE (if any) since the last call to this function.
+    pub fn take_error(&self) -> Option<anyhow::Error> {
+        self.error.borrow_mut().take()
+    }
+
+    /// Solve the ODE from `y0` at time `t0` to `t1`, returning the final state and its time derivative.
+    pub fn solve(&self, t0: Eqn::T, y0: &Eqn::V, t1: Eqn::T) -> Result<(Eqn::V, Eqn::V)> {
+        let mut solver = self.solver.borrow_mut();
+        let mut state = OdeSolverState::new(self.problem);
+        state.t = t0;
+        state.y.copy_from(y0);
+        self.problem
+            .eqn
+            .rhs()
+            .call_inplace(&state.y, state.t, &mut state.dy);
+        solver.set_problem(state, self.problem)?;
+        solver.set_stop_time(t1)?;
+        while !matches!(solver.step()?, OdeSolverStopReason::TstopReached) {}
+        *self.number_of_solves.borrow_mut() += 1;
+        let state = solver.state().unwrap();
+        Ok((state.y.clone(), state.dy.clone()))
+    }
+
+    /// Solve the ODE as [Self::solve], recording the error and returning `NaN` if the solve fails.
+    pub fn solve_or_nan(&self, t0: Eqn::T, y0: &Eqn::V, t1: Eqn::T) -> (Eqn::V, Eqn::V) {
+        self.solve(t0, y0, t1).unwrap_or_else(|e| {
+            let mut error = self.error.borrow_mut();
+            if error.is_none() {
+                *error = Some(e);
+            }
+            let nan = Eqn::V::from_element(y0.len(), Eqn::T::NAN);
+            (nan.clone(), nan)
+        })
+    }
+
+    /// The value `f()` for the unknowns `x`, reusing the value of the last call if `x` has not changed.
+    pub fn cached(&self, x: &Eqn::V, f: impl FnOnce() -> C) -> C {
+        if let Some((last_x, last)) = self.last.borrow().as_ref() {
+            if last_x.binary_fold(x, true, |acc, a, b, _| acc && a == b) {
+                return last.clone();
+            }
+        }
+        let value = f();
+        self.last.replace(Some((x.clone(), value.clone())));
+        value
+    }
+}
+
+/// Callable used to find a periodic orbit of an ODE by single shooting (see [crate::periodic_orbit]).
+///
+/// The unknowns are the initial state and the period `x = (y0, T)` (so the callable has one more state than the equations), and the residual
+/// is `H(x) = (y(T; y0) - y0, f(y_ref) . (y0 - y_ref))`, where `y(T; y0)` is the solution at time `t0 + T` found using the given ODE solver.
+/// The last equation is the phase condition, which restricts `y0` to the hyperplane through the reference point `y_ref` orthogonal to the
+/// flow `f(y_ref)` at that point.
+///
+/// The jacobian of `y(T; y0)` with respect to `y0` (the monodromy matrix) is applied to a vector using a forward difference along that vector, so
+/// each jacobian-vector product requires one extra solve of the ODE. The derivative of `y(T; y0)` with respect to `T` is the time derivative of
+/// the state returned by the ODE solver at the final time.
+pub struct ShootingCallable<'a, Eqn: OdeEquations, S: OdeSolverMethod<Eqn>> {
+    // caches y(T; y0) and y'(T; y0) for the unknowns x = (y0, T)
+    solver: ShootingSolver<'a, Eqn, S, (Eqn::V, Eqn::V)>,
+    nstates: usize,
+    y_ref: Eqn::V,
+    f_ref: Eqn::V,
+}
+
+impl<'a, Eqn: OdeEquations, S: OdeSolverMethod<Eqn>> ShootingCallable<'a, Eqn, S> {
+    /// Create the callable with the phase condition through the reference point `y_ref`.
+    pub fn new(solver: &'a mut S, problem: &'a OdeSolverProblem<Eqn>, y_ref: &Eqn::V) -> Self {
+        let nstates = y_ref.len();
+        let f_ref = problem.eqn.rhs().call(y_ref, problem.t0);
+        Self {
+            solver: ShootingSolver::new(solver, problem),
+            nstates,
+            y_ref: y_ref.clone(),
+            f_ref,
+        }
+    }
+
+    /// The number of times the ODE has been solved.
+    pub fn number_of_solves(&self) -> usize {
+        self.solver.number_of_solves()
+    }
+
+    /// The error from the first failed solve of the ODE (if any) since the last call to this function (see [ShootingSolver]).
+    pub fn take_error(&self) -> Option<anyhow::Error> {
+        self.solver.take_error()
+    }
+
+    /// Pack the initial state `y0` and period into the unknowns `x`.
+    pub fn pack(&self, y0: &Eqn::V, period: Eqn::T) -> Eqn::V {
+        let mut x = Eqn::V::zeros(self.nstates + 1);
+        for i in 0..self.nstates {
+            x[i] = y0[i];
+        }
+        x[self.nstates] = period;
+        x
+    }
+
+    /// Unpack the unknowns `x` into the initial state `y0`, and return the period.
+    pub fn unpack(&self, x: &Eqn::V, y0: &mut Eqn::V) -> Eqn::T {
+        for i in 0..self.nstates {
+            y0[i] = x[i];
+        }
+        x[self.nstates]
+    }
+
+    /// Solve the ODE from `y0` at time `t0` to `t0 + period`, returning the final state and its time derivative.
+    pub fn solve(&self, y0: &Eqn::V, period: Eqn::T) -> Result<(Eqn::V, Eqn::V)> {
+        let t0 = self.solver.problem().t0;
+        self.solver.solve(t0, y0, t0 + period)
+    }
+
+    // the solution at x, reusing the last solve if x has not changed
+    fn solve_cached(&self, x: &Eqn::V) -> (Eqn::V, Eqn::V) {
+        self.solver.cached(x, || {
+            let mut y0 = Eqn::V::zeros(self.nstates);
+            let period = self.unpack(x, &mut y0);
+            let t0 = self.solver.problem().t0;
+            self.solver.solve_or_nan(t0, &y0, t0 + period)
+        })
+    }
+}
+
+impl<Eqn: OdeEquations, S: OdeSolverMethod<Eqn>> Op for ShootingCallable<'_, Eqn, S> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.nstates + 1
+    }
+    fn nout(&self) -> usize {
+        self.nstates + 1
+    }
+    fn nparams(&self) -> usize {
+        0
+    }
+}
+
+impl<Eqn: OdeEquations, S: OdeSolverMethod<Eqn>> NonLinearOp for ShootingCallable<'_, Eqn, S> {
+    // H(x) = (y(T; y0) - y0, f(y_ref) . (y0 - y_ref))
+    fn call_inplace(&self, x: &Eqn::V, _t: Eqn::T, out: &mut Eqn::V) {
+        let (y, _dy) = self.solve_cached(x);
+        for i in 0..self.nstates {
+            out[i] = y[i] - x[i];
+        }
+        out[self.nstates] =
+            self.f_ref
+                .binary_fold(&self.y_ref, Eqn::T::zero(), |acc, f_i, y_i, i| {
+                    acc + f_i * (x[i] - y_i)
+                });
+    }
+
+    // dH/dx v = (Phi v_y - v_y + y'(T) v_T, f(y_ref) . v_y), where Phi v_y is found using a forward difference
+    fn jac_mul_inplace(&self, x: &Eqn::V, _t: Eqn::T, v: &Eqn::V, out: &mut Eqn::V) {
+        let (y, dy) = self.solve_cached(x);
+        let mut y0 = Eqn::V::zeros(self.nstates);
+        let period = self.unpack(x, &mut y0);
+        let mut v_y = Eqn::V::zeros(self.nstates);
+        let v_t = self.unpack(v, &mut v_y);
+        let v_norm = v_y.norm();
+        let mut jv = Eqn::V::zeros(self.nstates);
+        if v_norm > Eqn::T::zero() {
+            let problem = self.solver.problem();
+            let rtol = if problem.rtol > Eqn::T::EPSILON {
+                problem.rtol
+            } else {
+                Eqn::T::EPSILON
+            };
+            let h = rtol.pow(Eqn::T::from(0.5)) * (Eqn::T::one() + y0.norm()) / v_norm;
+            y0.axpy(h, &v_y, Eqn::T::one());
+            let (y_h, _dy_h) = self
+                .solver
+                .solve_or_nan(problem.t0, &y0, problem.t0 + period);
+            jv.copy_from(&y_h);
+            jv.axpy(-Eqn::T::one() / h, &y, Eqn::T::one() / h);
+            jv.axpy(-Eqn::T::one(), &v_y, Eqn::T::one());
+        }
+        jv.axpy(v_t, &dy, Eqn::T::one());
+        for i in 0..self.nstates {
+            out[i] = jv[i];
+        }
+        out[self.nstates] = self
+            .f_ref
+            .binary_fold(&v_y, Eqn::T::zero(), |acc, f_i, v_i, _| acc + f_i * v_i);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{Matrix, Vector};
+
+use super::{Op, OpStatistics, StateMassOp};
+
+/// A mass matrix `M(t, y)` that depends on the state `y` as well as time, implementing [StateMassOp].
+///
+/// - `func`: computes the gemv `out = M(t, y) v + beta * out`, called as `func(v, y, p, t, beta, out)`
+/// - `jac_func`: computes the action of the jacobian of `M(t, y) v` with respect to the state `y` on `w`, i.e. `out = d(M(t, y) v)/dy w`,
+///   called as `jac_func(v, y, p, t, w, out)`
+///
+/// The matrix of a state-dependent mass is not sparse, and the jacobians of the BDF and SDIRK nonlinear systems are calculated column by column.
+pub struct StateMassClosure<M, F, G>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    func: F,
+    jac_func: G,
+    nstates: usize,
+    nparams: usize,
+    p: Rc<M::V>,
+    statistics: RefCell<OpStatistics>,
+}
+
+impl<M, F, G> StateMassClosure<M, F, G>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    pub fn new(func: F, jac_func: G, nstates: usize, p: Rc<M::V>) -> Self {
+        let nparams = p.len();
+        Self {
+            func,
+            jac_func,
+            nstates,
+            nparams,
+            p,
+            statistics: RefCell::new(OpStatistics::default()),
+        }
+    }
+}
+
+impl<M, F, G> Op for StateMassClosure<M, F, G>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    type V = M::V;
+    type T = M::T;
+    type M = M;
+    fn nstates(&self) -> usize {
+        self.nstates
+    }
+    fn nout(&self) -> usize {
+        self.nstates
+    }
+    fn nparams(&self) -> usize {
+        self.nparams
+    }
+    fn set_params(&mut self, p: Rc<M::V>) {
+        assert_eq!(p.len(), self.nparams);
+        self.p = p;
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.statistics.borrow().clone()
+    }
+}
+
+impl<M, F, G> StateMassOp for StateMassClosure<M, F, G>
+where
+    M: Matrix,
+    F: Fn(&M::V, &M::V, &M::V, M::T, M::T, &mut M::V),
+    G: Fn(&M::V, &M::V, &M::V, M::T, &M::V, &mut M::V),
+{
+    fn gemv_inplace(&self, x: &M::V, t: M::T, v: &M::V, beta: M::T, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_call();
+        (self.func)(v, x, self.p.as_ref(), t, beta, y)
+    }
+    fn jac_mul_inplace(&self, x: &M::V, t: M::T, v: &M::V, w: &M::V, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_jac_mul();
+        (self.jac_func)(v, x, self.p.as_ref(), t, w, y)
+    }
+}
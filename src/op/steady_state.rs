@@ -0,0 +1,68 @@
+// callable for the steady state of an ODE or DAE, F(y) = -f(t, y) = 0 for equations of the form M y' = f(t, y),
+// or F(y) = G(t, y, 0) = 0 for a fully implicit DAE
+
+use std::rc::Rc;
+
+use crate::{ode_solver::equations::OdeEquations, scale, Matrix, NonLinearOp, Op, Vector};
+
+use num_traits::One;
+
+/// Callable for the steady state of an ODE or DAE, i.e. the state `y` at which the time derivative `y'` is zero.
+///
+/// For equations of the form `M y' = f(t, y)` this computes `F(y) = -f(t, y)`, and for a fully implicit DAE `G(t, y, y') = 0` it computes
+/// `F(y) = G(t, y, 0)`. The sign is chosen so that `F` is the limit of the [crate::op::bdf::BdfCallable] residual (divided by the step size)
+/// as the step size tends to infinity. Note that all the equations (including the algebraic equations of a DAE) are included in `F`.
+pub struct SteadyStateCallable<Eqn: OdeEquations> {
+    eqn: Rc<Eqn>,
+    zeros: Eqn::V,
+}
+
+impl<Eqn: OdeEquations> SteadyStateCallable<Eqn> {
+    pub fn new(eqn: &Rc<Eqn>) -> Self {
+        let n = eqn.rhs().nstates();
+        Self {
+            eqn: eqn.clone(),
+            zeros: Eqn::V::zeros(n),
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> Op for SteadyStateCallable<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.zeros.len()
+    }
+    fn nout(&self) -> usize {
+        self.zeros.len()
+    }
+    fn nparams(&self) -> usize {
+        self.eqn.rhs().nparams()
+    }
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        match self.eqn.residual() {
+            Some(residual) => residual.sparsity(),
+            None => self.eqn.rhs().sparsity(),
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> NonLinearOp for SteadyStateCallable<Eqn> {
+    fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
+        if let Some(residual) = self.eqn.residual() {
+            residual.call_inplace(x, &self.zeros, t, y);
+        } else {
+            self.eqn.rhs().call_inplace(x, t, y);
+            *y *= scale(-Eqn::T::one());
+        }
+    }
+    fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        if let Some(residual) = self.eqn.residual() {
+            residual.jac_mul_inplace(x, &self.zeros, t, v, y);
+        } else {
+            self.eqn.rhs().jac_mul_inplace(x, t, v, y);
+            *y *= scale(-Eqn::T::one());
+        }
+    }
+}
@@ -1,14 +1,14 @@
 // unit is a callable that returns returns the input vector
 
-use crate::{Matrix, Vector};
-use num_traits::One;
+use crate::{Matrix, MatrixSparsity, Vector};
+use num_traits::{One, Zero};
 
-use super::{LinearOp, NonLinearOp, Op};
+use super::{LinearOp, NonLinearOp, Op, StateMassOp};
 
-/// A dummy operator that returns the input vector. Can be used either as a [NonLinearOp] or [LinearOp].
+/// A dummy operator that returns the input vector. Can be used as a [NonLinearOp], [LinearOp] or [StateMassOp].
 pub struct UnitCallable<M: Matrix> {
     n: usize,
-    _phantom: std::marker::PhantomData<M>,
+    sparsity: M::Sparsity,
 }
 
 impl<M: Matrix> Default for UnitCallable<M> {
@@ -19,10 +19,8 @@ impl<M: Matrix> Default for UnitCallable<M> {
 
 impl<M: Matrix> UnitCallable<M> {
     pub fn new(n: usize) -> Self {
-        Self {
-            n,
-            _phantom: std::marker::PhantomData,
-        }
+        let sparsity = M::Sparsity::new_diagonal(n);
+        Self { n, sparsity }
     }
 }
 
@@ -39,6 +37,11 @@ impl<M: Matrix> Op for UnitCallable<M> {
     fn nparams(&self) -> usize {
         0
     }
+    // the unit operator does not depend on the parameters, so it accepts (and ignores) the parameters of the equations it is part of
+    fn set_params(&mut self, _p: std::rc::Rc<Self::V>) {}
+    fn sparsity(&self) -> Option<&<Self::M as Matrix>::Sparsity> {
+        Some(&self.sparsity)
+    }
 }
 
 impl<M: Matrix> LinearOp for UnitCallable<M> {
@@ -47,6 +50,24 @@ impl<M: Matrix> LinearOp for UnitCallable<M> {
     }
 }
 
+impl<M: Matrix> StateMassOp for UnitCallable<M> {
+    fn gemv_inplace(&self, _x: &Self::V, _t: Self::T, v: &Self::V, beta: Self::T, y: &mut Self::V) {
+        y.axpy(Self::T::one(), v, beta);
+    }
+    fn jac_mul_inplace(
+        &self,
+        _x: &Self::V,
+        _t: Self::T,
+        _v: &Self::V,
+        _w: &Self::V,
+        y: &mut Self::V,
+    ) {
+        for i in 0..y.len() {
+            y[i] = Self::T::zero();
+        }
+    }
+}
+
 impl<M: Matrix> NonLinearOp for UnitCallable<M> {
     fn call_inplace(&self, x: &Self::V, _t: Self::T, y: &mut Self::V) {
         y.copy_from(x);
@@ -0,0 +1,282 @@
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+use nalgebra::ComplexField;
+use num_traits::{Pow, Signed};
+
+use super::Scalar;
+
+/// A scalar type that can be used to evaluate a function generic over the scalar type, either with plain
+/// values (i.e. `T`) or with dual numbers ([Dual]) to obtain the exact directional derivative of the function.
+///
+/// Only the arithmetic operators and a small set of elementary functions are required, so this is implemented for every
+/// [Scalar] type as well as for [Dual] (which does not implement the full [Scalar] trait).
+pub trait AutodiffScalar<T: Scalar>:
+    Copy
+    + Debug
+    + From<T>
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Add<T, Output = Self>
+    + Sub<T, Output = Self>
+    + Mul<T, Output = Self>
+    + Div<T, Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    /// The value of the scalar (i.e. without any derivative information).
+    fn value(self) -> T;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tanh(self) -> Self;
+    fn abs(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn powf(self, n: T) -> Self;
+}
+
+impl<T: Scalar> AutodiffScalar<T> for T {
+    fn value(self) -> T {
+        self
+    }
+    fn exp(self) -> Self {
+        ComplexField::exp(self)
+    }
+    fn ln(self) -> Self {
+        ComplexField::ln(self)
+    }
+    fn sqrt(self) -> Self {
+        ComplexField::sqrt(self)
+    }
+    fn sin(self) -> Self {
+        ComplexField::sin(self)
+    }
+    fn cos(self) -> Self {
+        ComplexField::cos(self)
+    }
+    fn tanh(self) -> Self {
+        ComplexField::tanh(self)
+    }
+    fn abs(self) -> Self {
+        Signed::abs(&self)
+    }
+    fn powi(self, n: i32) -> Self {
+        ComplexField::powi(self, n)
+    }
+    fn powf(self, n: T) -> Self {
+        Pow::pow(self, n)
+    }
+}
+
+/// A dual number `re + eps * ε`, where `ε² = 0`. Evaluating a function `f` with `x + v ε` gives `f(x) + (J(x) v) ε`,
+/// so the action of the jacobian of `f` on `v` is calculated exactly using a single function evaluation (forward-mode automatic differentiation).
+///
+/// Comparisons only use the real part.
+#[derive(Copy, Clone, Debug)]
+pub struct Dual<T: Scalar> {
+    pub re: T,
+    pub eps: T,
+}
+
+impl<T: Scalar> Dual<T> {
+    pub fn new(re: T, eps: T) -> Self {
+        Self { re, eps }
+    }
+    /// A dual number with no derivative part.
+    pub fn constant(re: T) -> Self {
+        Self::new(re, T::zero())
+    }
+}
+
+impl<T: Scalar> From<T> for Dual<T> {
+    fn from(re: T) -> Self {
+        Self::constant(re)
+    }
+}
+
+impl<T: Scalar> PartialEq for Dual<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.re == other.re
+    }
+}
+
+impl<T: Scalar> PartialOrd for Dual<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.re.partial_cmp(&other.re)
+    }
+}
+
+impl<T: Scalar> Neg for Dual<T> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.eps)
+    }
+}
+
+impl<T: Scalar> Add for Dual<T> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.eps + rhs.eps)
+    }
+}
+
+impl<T: Scalar> Sub for Dual<T> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.eps - rhs.eps)
+    }
+}
+
+impl<T: Scalar> Mul for Dual<T> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.re * rhs.re, self.re * rhs.eps + self.eps * rhs.re)
+    }
+}
+
+impl<T: Scalar> Div for Dual<T> {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        let re = self.re / rhs.re;
+        Self::new(re, (self.eps - re * rhs.eps) / rhs.re)
+    }
+}
+
+impl<T: Scalar> Add<T> for Dual<T> {
+    type Output = Self;
+    fn add(self, rhs: T) -> Self {
+        Self::new(self.re + rhs, self.eps)
+    }
+}
+
+impl<T: Scalar> Sub<T> for Dual<T> {
+    type Output = Self;
+    fn sub(self, rhs: T) -> Self {
+        Self::new(self.re - rhs, self.eps)
+    }
+}
+
+impl<T: Scalar> Mul<T> for Dual<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self {
+        Self::new(self.re * rhs, self.eps * rhs)
+    }
+}
+
+impl<T: Scalar> Div<T> for Dual<T> {
+    type Output = Self;
+    fn div(self, rhs: T) -> Self {
+        Self::new(self.re / rhs, self.eps / rhs)
+    }
+}
+
+macro_rules! impl_assign_op {
+    ($trait:ident, $method:ident, $operator:tt) => {
+        impl<T: Scalar> $trait for Dual<T> {
+            fn $method(&mut self, rhs: Self) {
+                *self = *self $operator rhs;
+            }
+        }
+    };
+}
+
+impl_assign_op!(AddAssign, add_assign, +);
+impl_assign_op!(SubAssign, sub_assign, -);
+impl_assign_op!(MulAssign, mul_assign, *);
+impl_assign_op!(DivAssign, div_assign, /);
+
+impl<T: Scalar> AutodiffScalar<T> for Dual<T> {
+    fn value(self) -> T {
+        self.re
+    }
+    fn exp(self) -> Self {
+        let re = <T as AutodiffScalar<T>>::exp(self.re);
+        Self::new(re, self.eps * re)
+    }
+    fn ln(self) -> Self {
+        Self::new(<T as AutodiffScalar<T>>::ln(self.re), self.eps / self.re)
+    }
+    fn sqrt(self) -> Self {
+        let re = <T as AutodiffScalar<T>>::sqrt(self.re);
+        Self::new(re, self.eps / (re + re))
+    }
+    fn sin(self) -> Self {
+        Self::new(
+            <T as AutodiffScalar<T>>::sin(self.re),
+            self.eps * <T as AutodiffScalar<T>>::cos(self.re),
+        )
+    }
+    fn cos(self) -> Self {
+        Self::new(
+            <T as AutodiffScalar<T>>::cos(self.re),
+            -self.eps * <T as AutodiffScalar<T>>::sin(self.re),
+        )
+    }
+    fn tanh(self) -> Self {
+        let re = <T as AutodiffScalar<T>>::tanh(self.re);
+        Self::new(re, self.eps * (T::one() - re * re))
+    }
+    fn abs(self) -> Self {
+        Self::new(
+            <T as AutodiffScalar<T>>::abs(self.re),
+            self.eps * Signed::signum(&self.re),
+        )
+    }
+    fn powi(self, n: i32) -> Self {
+        if n == 0 {
+            return Self::constant(T::one());
+        }
+        let d = T::from(f64::from(n)) * <T as AutodiffScalar<T>>::powi(self.re, n - 1);
+        Self::new(<T as AutodiffScalar<T>>::powi(self.re, n), self.eps * d)
+    }
+    fn powf(self, n: T) -> Self {
+        let d = n * <T as AutodiffScalar<T>>::powf(self.re, n - T::one());
+        Self::new(<T as AutodiffScalar<T>>::powf(self.re, n), self.eps * d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutodiffScalar, Dual};
+
+    fn f<S: AutodiffScalar<f64>>(x: S) -> S {
+        (x * x * 3.0 + x.sin()) / (x.exp() + 1.0) - x.sqrt().ln() + x.powi(3) - x.powf(1.5)
+    }
+
+    fn df(x: f64) -> f64 {
+        let num = 3.0 * x * x + x.sin();
+        let den = x.exp() + 1.0;
+        let dnum = 6.0 * x + x.cos();
+        let dden = x.exp();
+        (dnum * den - num * dden) / (den * den) - 0.5 / x + 3.0 * x * x - 1.5 * x.sqrt()
+    }
+
+    #[test]
+    fn dual_derivative() {
+        for x in [0.3, 1.0, 2.5] {
+            let y = f(Dual::new(x, 1.0));
+            assert!((y.re - f(x)).abs() < 1e-14);
+            assert!((y.eps - df(x)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn dual_tanh_abs() {
+        let x = Dual::new(-0.7, 2.0);
+        let y = x.tanh() * x.abs();
+        let t = (-0.7f64).tanh();
+        let expect = 2.0 * (1.0 - t * t) * 0.7 - 2.0 * t;
+        assert!((y.eps - expect).abs() < 1e-14);
+    }
+}
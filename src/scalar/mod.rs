@@ -8,6 +8,9 @@ use num_traits::{Pow, Signed};
 
 use crate::vector::VectorView;
 
+pub mod dual;
+pub mod tracer;
+
 pub trait Scalar:
     nalgebra::Scalar
     + faer::Entity
@@ -0,0 +1,112 @@
+use std::{
+    cmp::Ordering,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+use super::{dual::AutodiffScalar, Scalar};
+
+/// A scalar that tracks whether a value depends on a chosen input. Evaluating a function with a single input marked as
+/// a dependency gives the outputs that depend on that input, i.e. the structural non-zeros of a column of the jacobian.
+/// Unlike detection using NaN propagation, this does not depend on the numerical values of the states (e.g. multiplication by zero).
+///
+/// Comparisons only use the value, so branches in the function are still evaluated at the given state.
+#[derive(Copy, Clone, Debug)]
+pub struct Tracer<T: Scalar> {
+    pub value: T,
+    pub depends: bool,
+}
+
+impl<T: Scalar> Tracer<T> {
+    pub fn new(value: T, depends: bool) -> Self {
+        Self { value, depends }
+    }
+}
+
+impl<T: Scalar> From<T> for Tracer<T> {
+    fn from(value: T) -> Self {
+        Self::new(value, false)
+    }
+}
+
+impl<T: Scalar> PartialEq for Tracer<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Scalar> PartialOrd for Tracer<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Scalar> Neg for Tracer<T> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.value, self.depends)
+    }
+}
+
+macro_rules! impl_bin_op {
+    ($trait:ident, $method:ident, $operator:tt) => {
+        impl<T: Scalar> $trait for Tracer<T> {
+            type Output = Self;
+            fn $method(self, rhs: Self) -> Self {
+                Self::new(self.value $operator rhs.value, self.depends || rhs.depends)
+            }
+        }
+
+        impl<T: Scalar> $trait<T> for Tracer<T> {
+            type Output = Self;
+            fn $method(self, rhs: T) -> Self {
+                Self::new(self.value $operator rhs, self.depends)
+            }
+        }
+    };
+}
+
+macro_rules! impl_assign_op {
+    ($trait:ident, $method:ident, $operator:tt) => {
+        impl<T: Scalar> $trait for Tracer<T> {
+            fn $method(&mut self, rhs: Self) {
+                *self = *self $operator rhs;
+            }
+        }
+    };
+}
+
+impl_bin_op!(Add, add, +);
+impl_bin_op!(Sub, sub, -);
+impl_bin_op!(Mul, mul, *);
+impl_bin_op!(Div, div, /);
+
+impl_assign_op!(AddAssign, add_assign, +);
+impl_assign_op!(SubAssign, sub_assign, -);
+impl_assign_op!(MulAssign, mul_assign, *);
+impl_assign_op!(DivAssign, div_assign, /);
+
+macro_rules! impl_unary_fn {
+    ($($method:ident),*) => {
+        $(
+            fn $method(self) -> Self {
+                Self::new(<T as AutodiffScalar<T>>::$method(self.value), self.depends)
+            }
+        )*
+    };
+}
+
+impl<T: Scalar> AutodiffScalar<T> for Tracer<T> {
+    fn value(self) -> T {
+        self.value
+    }
+    impl_unary_fn!(exp, ln, sqrt, sin, cos, tanh, abs);
+    fn powi(self, n: i32) -> Self {
+        Self::new(
+            <T as AutodiffScalar<T>>::powi(self.value, n),
+            self.depends && n != 0,
+        )
+    }
+    fn powf(self, n: T) -> Self {
+        Self::new(<T as AutodiffScalar<T>>::powf(self.value, n), self.depends)
+    }
+}